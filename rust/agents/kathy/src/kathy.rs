@@ -11,7 +11,7 @@ use tracing::{info, Instrument};
 use ethers::core::types::H256;
 
 use nomad_base::{decl_agent, AgentCore, NomadAgent};
-use nomad_core::{Common, Home, Message, Replica};
+use nomad_core::{xapps::XAppMessage, Common, Home, Message, Replica};
 
 use crate::settings::KathySettings as Settings;
 
@@ -91,10 +91,11 @@ impl NomadAgent for Kathy {
                         };
                         info!(
                             target: "outgoing_messages",
-                            "Enqueuing message of length {} to {}::{}",
+                            "Enqueuing message of length {} to {}::{}: {}",
                             length = message.body.len(),
                             destination = message.destination,
-                            recipient = message.recipient
+                            recipient = message.recipient,
+                            body = XAppMessage::describe(&message.body)
                         );
 
                         let guard = home_lock.lock().await;
@@ -25,6 +25,7 @@ async fn _main() -> Result<()> {
         .tracing
         .start_tracing(agent.metrics().span_duration())?;
     let _ = agent.metrics().run_http_server();
+    let _ = agent.run_api_server();
 
     agent.run_all().await?
 }
@@ -6,7 +6,7 @@ use crate::kathy::ChatGenerator;
 
 use nomad_base::decl_settings;
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Deserialize)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum ChatGenConfig {
     Static {
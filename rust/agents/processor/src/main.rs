@@ -8,6 +8,7 @@
 #![warn(missing_docs)]
 #![warn(unused_extern_crates)]
 
+mod policy;
 mod processor;
 mod prover;
 mod prover_sync;
@@ -17,24 +18,15 @@ mod settings;
 use color_eyre::Result;
 
 use crate::{processor::Processor, settings::ProcessorSettings as Settings};
-use nomad_base::NomadAgent;
+use nomad_base::run_networks;
 
 async fn _main() -> Result<()> {
     color_eyre::install()?;
     let settings = Settings::new()?;
 
     // TODO: top-level root span customizations?
-    let agent = Processor::from_settings(settings).await?;
-    agent
-        .as_ref()
-        .settings
-        .tracing
-        .start_tracing(agent.metrics().span_duration())?;
-
-    let _ = agent.metrics().run_http_server();
-
-    agent.run_all().await??;
-    Ok(())
+    // One agent per configured network, or just one without `networks`
+    run_networks::<Processor>(settings.split_networks()).await
 }
 
 fn main() -> Result<()> {
@@ -0,0 +1,276 @@
+use ethers::prelude::H256;
+use std::collections::HashSet;
+
+use nomad_core::{
+    xapps::{MessageAction, XAppMessage},
+    NomadMessage,
+};
+
+use crate::settings::{PolicyAction, PolicyConfig, PolicyRule};
+
+/// A policy rule with its integers parsed
+#[derive(Debug)]
+struct Rule {
+    name: String,
+    action: PolicyAction,
+    senders: Option<HashSet<H256>>,
+    recipients: Option<HashSet<H256>>,
+    origins: Option<HashSet<u32>>,
+    destinations: Option<HashSet<u32>>,
+    min_body_size: Option<usize>,
+    max_body_size: Option<usize>,
+    body_prefix: Option<Vec<u8>>,
+    body_actions: Option<HashSet<MessageAction>>,
+}
+
+fn parse_all(values: &Option<Vec<String>>) -> Option<HashSet<u32>> {
+    values.as_ref().map(|values| {
+        values
+            .iter()
+            .map(|v| v.parse().expect("invalid integer"))
+            .collect()
+    })
+}
+
+impl From<&PolicyRule> for Rule {
+    fn from(rule: &PolicyRule) -> Self {
+        Self {
+            name: rule.name.clone(),
+            action: rule.action,
+            senders: rule.senders.clone(),
+            recipients: rule.recipients.clone(),
+            origins: parse_all(&rule.origins),
+            destinations: parse_all(&rule.destinations),
+            min_body_size: rule
+                .min_body_size
+                .as_ref()
+                .map(|s| s.parse().expect("invalid integer")),
+            max_body_size: rule
+                .max_body_size
+                .as_ref()
+                .map(|s| s.parse().expect("invalid integer")),
+            body_prefix: rule.body_prefix.as_ref().map(|p| p.as_ref().to_vec()),
+            body_actions: rule.body_actions.clone(),
+        }
+    }
+}
+
+impl Rule {
+    /// True if the message meets every condition of the rule
+    fn matches(&self, message: &NomadMessage) -> bool {
+        let contains = |set: &Option<HashSet<H256>>, value: H256| {
+            set.as_ref().map(|set| set.contains(&value)).unwrap_or(true)
+        };
+        let size = message.body.len();
+
+        contains(&self.senders, message.sender)
+            && contains(&self.recipients, message.recipient)
+            && self
+                .origins
+                .as_ref()
+                .map(|set| set.contains(&message.origin))
+                .unwrap_or(true)
+            && self
+                .destinations
+                .as_ref()
+                .map(|set| set.contains(&message.destination))
+                .unwrap_or(true)
+            && self.min_body_size.map(|min| size >= min).unwrap_or(true)
+            && self.max_body_size.map(|max| size <= max).unwrap_or(true)
+            && self
+                .body_prefix
+                .as_ref()
+                .map(|prefix| message.body.starts_with(prefix))
+                .unwrap_or(true)
+            && self
+                .body_actions
+                .as_ref()
+                .map(|set| set.contains(&XAppMessage::action_of(&message.body)))
+                .unwrap_or(true)
+    }
+}
+
+/// What the policy decided for a message, and the rule that decided it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Decision<'a> {
+    /// What to do with the message
+    pub(crate) action: PolicyAction,
+    /// Name of the rule that matched
+    pub(crate) rule: &'a str,
+}
+
+/// Decides which messages the processor processes. The allow and deny lists
+/// are checked first, then the policy rules in order.
+#[derive(Debug)]
+pub(crate) struct MessagePolicy {
+    allowed: Option<HashSet<H256>>,
+    denied: Option<HashSet<H256>>,
+    allowed_actions: Option<HashSet<MessageAction>>,
+    denied_actions: Option<HashSet<MessageAction>>,
+    rules: Vec<Rule>,
+    default: PolicyAction,
+}
+
+impl MessagePolicy {
+    /// Instantiate a policy from the processor's allow and deny lists and
+    /// policy rules
+    pub(crate) fn new(
+        allowed: Option<HashSet<H256>>,
+        denied: Option<HashSet<H256>>,
+        allowed_actions: Option<HashSet<MessageAction>>,
+        denied_actions: Option<HashSet<MessageAction>>,
+        policy: Option<&PolicyConfig>,
+    ) -> Self {
+        Self {
+            allowed,
+            denied,
+            allowed_actions,
+            denied_actions,
+            rules: policy
+                .map(|policy| policy.rules.iter().map(Rule::from).collect())
+                .unwrap_or_default(),
+            default: policy
+                .and_then(|policy| policy.default)
+                .unwrap_or(PolicyAction::Allow),
+        }
+    }
+
+    /// Decide what to do with `message`
+    pub(crate) fn decide(&self, message: &NomadMessage) -> Decision<'_> {
+        let deny = |rule: &'static str| Decision {
+            action: PolicyAction::Deny,
+            rule,
+        };
+
+        // if we have an allow list, deny senders not on it
+        if let Some(false) = self
+            .allowed
+            .as_ref()
+            .map(|set| set.contains(&message.sender))
+        {
+            return deny("allowed");
+        }
+
+        // if we have a deny list, deny senders on it
+        if let Some(true) = self
+            .denied
+            .as_ref()
+            .map(|set| set.contains(&message.sender))
+        {
+            return deny("denied");
+        }
+
+        if self.allowed_actions.is_some() || self.denied_actions.is_some() {
+            let action = XAppMessage::action_of(&message.body);
+            if let Some(false) = self
+                .allowed_actions
+                .as_ref()
+                .map(|set| set.contains(&action))
+            {
+                return deny("allowedActions");
+            }
+            if let Some(true) = self
+                .denied_actions
+                .as_ref()
+                .map(|set| set.contains(&action))
+            {
+                return deny("deniedActions");
+            }
+        }
+
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(message))
+            .map(|rule| Decision {
+                action: rule.action,
+                rule: &rule.name,
+            })
+            .unwrap_or(Decision {
+                action: self.default,
+                rule: "default",
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rule(name: &str, action: PolicyAction) -> PolicyRule {
+        PolicyRule {
+            name: name.to_owned(),
+            action,
+            senders: None,
+            recipients: None,
+            origins: None,
+            destinations: None,
+            min_body_size: None,
+            max_body_size: None,
+            body_prefix: None,
+            body_actions: None,
+        }
+    }
+
+    #[test]
+    fn it_applies_the_first_matching_rule() {
+        let bridge = H256::repeat_byte(1);
+        let ethereum: u32 = 6648936;
+        let config = PolicyConfig {
+            rules: vec![
+                PolicyRule {
+                    recipients: Some([bridge].into_iter().collect()),
+                    destinations: Some(vec![ethereum.to_string()]),
+                    ..rule("bridge to ethereum", PolicyAction::Allow)
+                },
+                PolicyRule {
+                    destinations: Some(vec![ethereum.to_string()]),
+                    max_body_size: Some("1024".to_owned()),
+                    ..rule("small messages to ethereum", PolicyAction::Defer)
+                },
+                PolicyRule {
+                    destinations: Some(vec![ethereum.to_string()]),
+                    ..rule("others to ethereum", PolicyAction::Deny)
+                },
+                PolicyRule {
+                    body_prefix: Some(vec![0xde, 0xad].into()),
+                    ..rule("dead prefix", PolicyAction::Deny)
+                },
+            ],
+            default: None,
+        };
+        let policy = MessagePolicy::new(None, None, None, None, Some(&config));
+        let message = |recipient, destination, body: Vec<u8>| NomadMessage {
+            origin: 1000,
+            sender: H256::repeat_byte(9),
+            nonce: 0,
+            destination,
+            recipient,
+            body,
+        };
+        let decide = |message: NomadMessage| {
+            let decision = policy.decide(&message);
+            (decision.action, decision.rule.to_owned())
+        };
+
+        assert_eq!(
+            decide(message(bridge, ethereum, vec![0xde, 0xad])),
+            (PolicyAction::Allow, "bridge to ethereum".to_owned())
+        );
+        assert_eq!(
+            decide(message(H256::zero(), ethereum, vec![1, 2])),
+            (PolicyAction::Defer, "small messages to ethereum".to_owned())
+        );
+        assert_eq!(
+            decide(message(H256::zero(), ethereum, vec![0; 2048])),
+            (PolicyAction::Deny, "others to ethereum".to_owned())
+        );
+        assert_eq!(
+            decide(message(H256::zero(), 2000, vec![0xde, 0xad, 0xbe, 0xef])),
+            (PolicyAction::Deny, "dead prefix".to_owned())
+        );
+        assert_eq!(
+            decide(message(H256::zero(), 2000, vec![])),
+            (PolicyAction::Allow, "default".to_owned())
+        );
+    }
+}
@@ -1,40 +1,121 @@
 use async_trait::async_trait;
-use color_eyre::{
-    eyre::{bail, eyre},
-    Result,
-};
-use ethers::prelude::H256;
+use color_eyre::{eyre::bail, Report, Result};
 use futures_util::future::select_all;
 use std::{
-    collections::{HashMap, HashSet},
+    convert::TryFrom,
     sync::Arc,
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{task::JoinHandle, time::sleep};
+use tracing::{
+    debug, error, info, info_span, instrument, instrument::Instrumented, warn, Instrument,
 };
-use tokio::{sync::RwLock, task::JoinHandle, time::sleep};
-use tracing::{debug, error, info, info_span, instrument, instrument::Instrumented, Instrument};
 
 use nomad_base::{
-    cancel_task, decl_agent, AgentCore, CachingHome, CachingReplica, ContractSyncMetrics,
-    IndexDataTypes, NomadAgent, NomadDB, ProcessorError,
+    abort_replica_tasks, cancel_task, decl_agent, AgentCore, Backoff, CachingHome, CachingReplica,
+    ContractSyncMetrics, GasBudget, GasOperation, IndexDataTypes, NomadAgent, NomadDB,
+    ProcessorError, ReplicaEnrollment, ReplicaTasks, ServedReplicas,
 };
 use nomad_core::{
-    accumulator::merkle::Proof, CommittedMessage, Common, Home, HomeEvents, MessageStatus,
+    accumulator::merkle::Proof, xapps::XAppMessage, CommittedMessage, Common, Home, MessageRetry,
+    MessageStatus,
 };
 
 use crate::{
+    policy::MessagePolicy,
     prover_sync::ProverSync,
     push::Pusher,
-    settings::{ProcessorSettings as Settings, S3Config},
+    settings::{
+        BatchConfig, MessageRetryConfig, PolicyAction, ProcessorSettings as Settings,
+        PublisherConfig,
+    },
 };
 
 const AGENT_NAME: &str = "processor";
-static CURRENT_NONCE: &str = "current_nonce_";
 
 enum Flow {
     Advance,
+    AdvanceTo(u32),
     Repeat,
 }
 
+/// Retry policy for messages whose processing failed
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RetryPolicy {
+    /// Backoff between retries
+    backoff: Backoff,
+    /// Number of failed attempts after which a message is dead-lettered
+    max_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            backoff: Backoff::new(30, 3600),
+            max_attempts: 10,
+        }
+    }
+}
+
+impl From<&MessageRetryConfig> for RetryPolicy {
+    fn from(config: &MessageRetryConfig) -> Self {
+        let default = Self::default();
+        Self {
+            backoff: default.backoff.configured(Some(&config.backoff)),
+            max_attempts: config
+                .max_attempts
+                .as_ref()
+                .map(|a| a.parse().expect("invalid integer"))
+                .unwrap_or(default.max_attempts),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay (in seconds) to wait after the `attempts`-th failure
+    fn delay(&self, attempts: u32) -> u64 {
+        self.backoff.delay(attempts).as_secs()
+    }
+}
+
+/// Limits on how many ready messages are submitted together
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BatchPolicy {
+    /// The most messages to submit in a single batch. Batching is disabled
+    /// when this is 1.
+    max_messages: usize,
+}
+
+impl Default for BatchPolicy {
+    fn default() -> Self {
+        Self { max_messages: 1 }
+    }
+}
+
+impl From<&BatchConfig> for BatchPolicy {
+    fn from(config: &BatchConfig) -> Self {
+        let size: usize = config.size.parse().expect("invalid integer");
+        let by_gas = match (&config.gas_limit, &config.message_gas) {
+            (Some(limit), Some(per_message)) => {
+                let limit: u64 = limit.parse().expect("invalid integer");
+                let per_message: u64 = per_message.parse().expect("invalid integer");
+                (limit / per_message.max(1)) as usize
+            }
+            _ => size,
+        };
+        Self {
+            max_messages: size.min(by_gas).max(1),
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock before unix epoch")
+        .as_secs()
+}
+
 /// The replica processor is responsible for polling messages and waiting until they validate
 /// before proving/processing them.
 #[derive(Debug)]
@@ -43,8 +124,10 @@ pub(crate) struct Replica {
     replica: Arc<CachingReplica>,
     home: Arc<CachingHome>,
     db: NomadDB,
-    allowed: Option<Arc<HashSet<H256>>>,
-    denied: Option<Arc<HashSet<H256>>>,
+    policy: Arc<MessagePolicy>,
+    retry_policy: RetryPolicy,
+    batch_policy: BatchPolicy,
+    budget: Arc<GasBudget>,
     next_message_nonce: Arc<prometheus::IntGaugeVec>,
 }
 
@@ -52,8 +135,8 @@ impl std::fmt::Display for Replica {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "ReplicaProcessor: {{ home: {:?}, replica: {:?}, allowed: {:?}, denied: {:?} }}",
-            self.home, self.replica, self.allowed, self.denied
+            "ReplicaProcessor: {{ home: {:?}, replica: {:?}, policy: {:?} }}",
+            self.home, self.replica, self.policy
         )
     }
 }
@@ -75,10 +158,17 @@ impl Replica {
                 //      - If not, wait and poll again
                 // 4. Check if the proof is valid under the replica
                 // 5. Submit the proof to the replica
+                //      - If submission fails, queue the message for retry and
+                //        move on to the next nonce
+                //
+                // Queued retries are attempted at the start of each iteration
+                // once their backoff has elapsed. They are handled in this
+                // task rather than a separate one so that submissions from
+                // this replica's signer never race each other.
                 let mut next_message_nonce: u32 = self
                     .db
-                    .retrieve_keyed_decodable(CURRENT_NONCE, &replica_domain)?
-                    .map(|n: u32| n + 1)
+                    .retrieve_processor_nonce(replica_domain)?
+                    .map(|n| n + 1)
                     .unwrap_or_default();
 
                 self.next_message_nonce
@@ -104,24 +194,35 @@ impl Replica {
                         home_domain = self.home.local_domain(),
                     );
 
-                    match self
-                        .try_msg_by_domain_and_nonce(replica_domain, next_message_nonce)
-                        .instrument(seq_span)
-                        .await
-                    {
-                        Ok(Flow::Advance) => {
-                            self.db
-                            .store_keyed_encodable(CURRENT_NONCE, &replica_domain, &next_message_nonce)?;
-
-                            next_message_nonce += 1;
-                            self.next_message_nonce
-                                .with_label_values(&[
-                                    self.home.name(),
-                                    self.replica.name(),
-                                    AGENT_NAME,
-                                ])
-                                .set(next_message_nonce as i64);
-                        }
+                    // stop subsidizing messages once the daily budget is spent
+                    if self.budget.is_exhausted(self.replica.name())? {
+                        warn!(
+                            replica = self.replica.name(),
+                            nonce = next_message_nonce,
+                            "Daily gas budget for replica {} exhausted. Processing resumes tomorrow (UTC).",
+                            self.replica.name(),
+                        );
+                        sleep(Duration::from_secs(self.interval)).await;
+                        continue;
+                    }
+
+                    self.process_due_retries(replica_domain)
+                        .instrument(seq_span.clone())
+                        .await?;
+
+                    let flow = if self.batch_policy.max_messages > 1 {
+                        self.try_batch_by_domain_and_nonce(replica_domain, next_message_nonce)
+                            .instrument(seq_span)
+                            .await
+                    } else {
+                        self.try_msg_by_domain_and_nonce(replica_domain, next_message_nonce)
+                            .instrument(seq_span)
+                            .await
+                    };
+
+                    let advance_to = match flow {
+                        Ok(Flow::Advance) => next_message_nonce + 1,
+                        Ok(Flow::AdvanceTo(nonce)) => nonce,
                         Ok(Flow::Repeat) => {
                             // there was some fault, let's wait and then try again later when state may have moved
                             debug!(
@@ -133,13 +234,22 @@ impl Replica {
                                 next_message_nonce,
                                 replica_domain,
                             );
-                            sleep(Duration::from_secs(self.interval)).await
+                            sleep(Duration::from_secs(self.interval)).await;
+                            continue;
                         }
                         Err(e) => {
                             error!("fatal error in processor::Replica: {}", e);
                             bail!(e)
                         }
-                    }
+                    };
+
+                    let last_nonce = advance_to - 1;
+                    self.db.store_processor_nonce(replica_domain, last_nonce)?;
+
+                    next_message_nonce = advance_to;
+                    self.next_message_nonce
+                        .with_label_values(&[self.home.name(), self.replica.name(), AGENT_NAME])
+                        .set(next_message_nonce as i64);
                 }
             }
             .in_current_span(),
@@ -149,7 +259,7 @@ impl Replica {
     /// Attempt to process a message.
     ///
     /// Postcondition: ```match retval? {
-    ///   Advance => message skipped ⊻ message was processed
+    ///   Advance => message skipped ⊻ message was processed ⊻ message queued for retry
     ///   Repeat => try again later
     /// }```
     ///
@@ -158,8 +268,8 @@ impl Replica {
     async fn try_msg_by_domain_and_nonce(&self, domain: u32, nonce: u32) -> Result<Flow> {
         use nomad_core::Replica;
 
-        let message = match self.home.message_by_nonce(domain, nonce).await {
-            Ok(Some(m)) => m,
+        let message = match self.db.message_by_nonce(domain, nonce) {
+            Ok(Some(m)) => CommittedMessage::try_from(m)?,
             Ok(None) => {
                 info!(
                     domain = domain,
@@ -174,31 +284,8 @@ impl Replica {
         };
 
         info!(target: "seen_committed_messages", leaf_index = message.leaf_index);
-        let sender = message.message.sender;
-
-        // if we have an allow list, filter senders not on it
-        if let Some(false) = self.allowed.as_ref().map(|set| set.contains(&sender)) {
-            info!(
-                sender = ?sender,
-                nonce = nonce,
-                "Skipping message because sender not on allow list. Sender: {}. Domain: {}. Nonce: {}",
-                sender,
-                domain,
-                nonce
-            );
-            return Ok(Flow::Advance);
-        }
 
-        // if we have a deny list, filter senders on it
-        if let Some(true) = self.denied.as_ref().map(|set| set.contains(&sender)) {
-            info!(
-                sender = ?sender,
-                nonce = nonce,
-                "Skipping message because sender on deny list. Sender: {}. Domain: {}. Nonce: {}",
-                sender,
-                domain,
-                nonce
-            );
+        if self.is_skipped(&message)? {
             return Ok(Flow::Advance);
         }
 
@@ -241,11 +328,317 @@ impl Replica {
             nonce
         );
 
-        self.process(message, proof).await?;
+        if let Err(e) = self.process(message.clone(), proof).await {
+            self.record_next_failure(&message, &e)?;
+        }
 
         Ok(Flow::Advance)
     }
 
+    /// Attempt to process a batch of consecutive messages starting at
+    /// `nonce`. Ready messages are collected until the batch is full, a
+    /// message or its proof is not yet available, or a proof is under a
+    /// different or not yet acceptable root. Messages in the batch that fail
+    /// are queued for retry individually.
+    ///
+    /// Postcondition: ```match retval? {
+    ///   AdvanceTo(n) => every message below n was skipped ⊻ processed ⊻ queued for retry
+    ///   Repeat => try again later
+    /// }```
+    #[instrument(err, skip(self), fields(self = %self))]
+    async fn try_batch_by_domain_and_nonce(&self, domain: u32, nonce: u32) -> Result<Flow> {
+        use nomad_core::Replica;
+
+        let mut next = nonce;
+        let mut root = None;
+        let mut proofs = vec![];
+        let mut messages: Vec<CommittedMessage> = vec![];
+
+        while messages.len() < self.batch_policy.max_messages {
+            let message = match self.db.message_by_nonce(domain, next)? {
+                Some(m) => CommittedMessage::try_from(m)?,
+                None => break,
+            };
+
+            info!(target: "seen_committed_messages", leaf_index = message.leaf_index);
+
+            if self.is_skipped(&message)? {
+                next += 1;
+                continue;
+            }
+
+            let proof = match self.db.proof_by_leaf_index(message.leaf_index)? {
+                Some(p) => p,
+                None => break,
+            };
+
+            if proof.leaf != message.to_leaf() {
+                bail!(ProcessorError::ProverConflictError {
+                    index: message.leaf_index,
+                    calculated_leaf: message.to_leaf(),
+                    proof_leaf: proof.leaf,
+                });
+            }
+
+            // all proofs in a batch must be under one acceptable root
+            let proof_root = proof.root();
+            match root {
+                Some(root) if root != proof_root => break,
+                Some(_) => {}
+                None => {
+                    if !self.replica.acceptable_root(proof_root).await? {
+                        break;
+                    }
+                }
+            }
+
+            match self.replica.message_status(message.to_leaf()).await? {
+                MessageStatus::Processed => {
+                    info!(
+                        domain,
+                        nonce = next,
+                        leaf_index = message.leaf_index,
+                        "Message {}:{} already processed",
+                        domain,
+                        next
+                    );
+                    next += 1;
+                    continue;
+                }
+                MessageStatus::Proven => {}
+                MessageStatus::None => proofs.push(proof),
+            }
+
+            root = Some(proof_root);
+            messages.push(message);
+            next += 1;
+        }
+
+        if messages.is_empty() {
+            return Ok(if next > nonce {
+                Flow::AdvanceTo(next)
+            } else {
+                Flow::Repeat
+            });
+        }
+
+        info!(
+            domain,
+            first_nonce = nonce,
+            messages = messages.len(),
+            proofs = proofs.len(),
+            "Dispatching a batch of {} messages for processing",
+            messages.len(),
+        );
+
+        let batch: Vec<_> = messages.iter().map(|m| m.message.clone()).collect();
+        let outcomes = match self.replica.prove_and_process_batch(&proofs, &batch).await {
+            Ok(outcomes) => outcomes,
+            Err(e) => {
+                let e = Report::from(e);
+                for message in messages.iter() {
+                    self.record_next_failure(message, &e)?;
+                }
+                return Ok(Flow::AdvanceTo(next));
+            }
+        };
+
+        for tx in outcomes.proofs.iter() {
+            self.budget
+                .record(self.replica.name(), GasOperation::Prove, tx)?;
+        }
+        for (message, outcome) in messages.iter().zip(outcomes.messages) {
+            if let Ok(tx) = &outcome {
+                self.budget
+                    .record(self.replica.name(), GasOperation::Process, tx)?;
+            }
+            let result = outcome.map_err(Report::from).and_then(|tx| {
+                if tx.executed {
+                    Ok(())
+                } else {
+                    Err(ProcessorError::ProcessTransactionReverted { tx: tx.txid }.into())
+                }
+            });
+
+            match result {
+                Ok(()) => info!(
+                    domain = message.message.destination,
+                    nonce = message.message.nonce,
+                    leaf_index = message.leaf_index,
+                    leaf = ?message.message.to_leaf(),
+                    body = %XAppMessage::describe(&message.message.body),
+                    "Processed message. Destination: {}. Nonce: {}. Leaf index: {}.",
+                    message.message.destination,
+                    message.message.nonce,
+                    message.leaf_index,
+                ),
+                Err(e) => self.record_next_failure(message, &e)?,
+            }
+        }
+
+        Ok(Flow::AdvanceTo(next))
+    }
+
+    /// Apply the message policy. Returns true if the message is denied or
+    /// deferred, in which case it is held in the manual queue.
+    fn is_skipped(&self, message: &CommittedMessage) -> Result<bool> {
+        let decision = self.policy.decide(&message.message);
+        let domain = message.message.destination;
+        let nonce = message.message.nonce;
+
+        match decision.action {
+            PolicyAction::Allow => {
+                info!(
+                    rule = decision.rule,
+                    nonce = nonce,
+                    "Message allowed by policy rule {}. Domain: {}. Nonce: {}",
+                    decision.rule,
+                    domain,
+                    nonce
+                );
+                Ok(false)
+            }
+            PolicyAction::Deny => {
+                info!(
+                    rule = decision.rule,
+                    sender = ?message.message.sender,
+                    nonce = nonce,
+                    "Skipping message denied by policy rule {}. Domain: {}. Nonce: {}",
+                    decision.rule,
+                    domain,
+                    nonce
+                );
+                Ok(true)
+            }
+            PolicyAction::Defer => {
+                info!(
+                    rule = decision.rule,
+                    nonce = nonce,
+                    "Deferring message to the manual queue by policy rule {}. Domain: {}. Nonce: {}",
+                    decision.rule,
+                    domain,
+                    nonce
+                );
+                self.db.store_deferred(&MessageRetry {
+                    destination: domain,
+                    nonce,
+                    leaf_index: message.leaf_index,
+                    attempts: 0,
+                    next_attempt_at: 0,
+                    last_error: format!("deferred by policy rule {}", decision.rule),
+                })?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Retry every queued message for `domain` whose backoff has elapsed.
+    /// Messages that are processed successfully leave the queue.
+    #[instrument(err, skip(self), fields(self = %self))]
+    async fn process_due_retries(&self, domain: u32) -> Result<()> {
+        let now = unix_now();
+        let due: Vec<MessageRetry> = self.db.retries(domain).filter(|r| r.is_due(now)).collect();
+
+        for retry in due {
+            let message = match self.db.message_by_nonce(domain, retry.nonce)? {
+                Some(m) => CommittedMessage::try_from(m)?,
+                None => {
+                    warn!(
+                        domain,
+                        nonce = retry.nonce,
+                        "Queued retry has no message in db. Dropping it from the retry queue."
+                    );
+                    self.db.remove_retry(domain, retry.nonce)?;
+                    continue;
+                }
+            };
+
+            let proof = match self.db.proof_by_leaf_index(message.leaf_index)? {
+                Some(p) => p,
+                None => continue,
+            };
+
+            info!(
+                domain,
+                nonce = retry.nonce,
+                leaf_index = message.leaf_index,
+                attempts = retry.attempts,
+                "Retrying message {}:{}",
+                domain,
+                retry.nonce
+            );
+
+            match self.process(message.clone(), proof).await {
+                Ok(()) => self.db.remove_retry(domain, retry.nonce)?,
+                Err(e) => self.record_failure(&message, retry.attempts + 1, &e)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record one more failure to process a message, on top of the failures
+    /// already in its retry queue entry
+    fn record_next_failure(&self, message: &CommittedMessage, error: &Report) -> Result<()> {
+        let failed = self
+            .db
+            .retry_by_nonce(message.message.destination, message.message.nonce)?
+            .map(|retry| retry.attempts)
+            .unwrap_or_default();
+        self.record_failure(message, failed + 1, error)
+    }
+
+    /// Record the `attempts`-th failure to process a message. The message is
+    /// rescheduled with exponential backoff, or moved to the dead-letter
+    /// store once it has used up its attempts.
+    fn record_failure(
+        &self,
+        message: &CommittedMessage,
+        attempts: u32,
+        error: &Report,
+    ) -> Result<()> {
+        let delay = self.retry_policy.delay(attempts);
+        let retry = MessageRetry {
+            destination: message.message.destination,
+            nonce: message.message.nonce,
+            leaf_index: message.leaf_index,
+            attempts,
+            next_attempt_at: unix_now() + delay,
+            last_error: error.to_string(),
+        };
+
+        if attempts >= self.retry_policy.max_attempts {
+            error!(
+                domain = retry.destination,
+                nonce = retry.nonce,
+                leaf_index = retry.leaf_index,
+                attempts,
+                error = %error,
+                "Message {}:{} failed {} times. Moving it to the dead-letter store.",
+                retry.destination,
+                retry.nonce,
+                attempts,
+            );
+            self.db.store_dead_letter(&retry)?;
+        } else {
+            warn!(
+                domain = retry.destination,
+                nonce = retry.nonce,
+                leaf_index = retry.leaf_index,
+                attempts,
+                delay,
+                error = %error,
+                "Failed to process message {}:{}. Retrying in {} seconds.",
+                retry.destination,
+                retry.nonce,
+                delay,
+            );
+            self.db.store_retry(&retry)?;
+        }
+
+        Ok(())
+    }
+
     #[instrument(err, level = "trace", skip(self), fields(self = %self))]
     /// Dispatch a message for processing. If the message is already proven, process only.
     async fn process(&self, message: CommittedMessage, proof: Proof) -> Result<()> {
@@ -274,6 +667,8 @@ impl Replica {
         };
 
         if let Some(tx_outcome) = opt_tx_outcome {
+            self.budget
+                .record(self.replica.name(), GasOperation::Process, &tx_outcome)?;
             if !tx_outcome.executed {
                 return Err(ProcessorError::ProcessTransactionReverted {
                     tx: tx_outcome.txid,
@@ -287,6 +682,7 @@ impl Replica {
             nonce = message.message.nonce,
             leaf_index = message.leaf_index,
             leaf = ?message.message.to_leaf(),
+            body = %XAppMessage::describe(&message.message.body),
             "Processed message. Destination: {}. Nonce: {}. Leaf index: {}.",
             message.message.destination,
             message.message.nonce,
@@ -301,12 +697,15 @@ decl_agent!(
     /// A processor agent
     Processor {
         interval: u64,
-        replica_tasks: RwLock<HashMap<String, JoinHandle<Result<()>>>>,
-        allowed: Option<Arc<HashSet<H256>>>,
-        denied: Option<Arc<HashSet<H256>>>,
+        replica_tasks: Arc<ReplicaTasks>,
+        served_replicas: Arc<ServedReplicas>,
+        policy: Arc<MessagePolicy>,
+        retry_policy: RetryPolicy,
+        batch_policy: BatchPolicy,
         index_only: bool,
+        budget: Arc<GasBudget>,
         next_message_nonce: Arc<prometheus::IntGaugeVec>,
-        config: Option<S3Config>,
+        publisher: Option<PublisherConfig>,
     }
 );
 
@@ -315,10 +714,11 @@ impl Processor {
     pub fn new(
         interval: u64,
         core: AgentCore,
-        allowed: Option<HashSet<H256>>,
-        denied: Option<HashSet<H256>>,
+        policy: MessagePolicy,
+        retry_policy: RetryPolicy,
+        batch_policy: BatchPolicy,
         index_only: bool,
-        config: Option<S3Config>,
+        publisher: Option<PublisherConfig>,
     ) -> Self {
         let next_message_nonce = Arc::new(
             core.metrics
@@ -327,20 +727,54 @@ impl Processor {
                     "Index of the next message to inspect",
                     &["home", "replica", "agent"],
                 )
-                .expect("processor metric already registered -- should have be a singleton"),
+                .expect("processor metric already registered -- should have been a singleton"),
         );
 
         Self {
             interval,
+            budget: Arc::new(GasBudget::new(AGENT_NAME, &core)),
+            served_replicas: Arc::new(std::sync::RwLock::new(core.replicas.clone())),
             core,
             replica_tasks: Default::default(),
-            allowed: allowed.map(Arc::new),
-            denied: denied.map(Arc::new),
+            policy: Arc::new(policy),
+            retry_policy,
+            batch_policy,
             next_message_nonce,
             index_only,
-            config,
+            publisher,
         }
     }
+
+    /// Process messages to `replica`, whether or not it is in the replicas
+    /// map
+    fn run_replica(&self, replica: Arc<CachingReplica>) -> Instrumented<JoinHandle<Result<()>>> {
+        let home = self.home();
+        let next_message_nonce = self.next_message_nonce.clone();
+        let interval = self.interval;
+        let db = NomadDB::new(home.name(), self.db());
+
+        let policy = self.policy.clone();
+        let retry_policy = self.retry_policy;
+        let batch_policy = self.batch_policy;
+        let budget = self.budget.clone();
+
+        tokio::spawn(async move {
+            Replica {
+                interval,
+                replica,
+                home,
+                db,
+                policy,
+                retry_policy,
+                batch_policy,
+                budget,
+                next_message_nonce,
+            }
+            .main()
+            .await?
+        })
+        .in_current_span()
+    }
 }
 
 #[async_trait]
@@ -357,41 +791,42 @@ impl NomadAgent for Processor {
         Ok(Self::new(
             settings.interval.parse().expect("invalid integer"),
             settings.as_ref().try_into_core(AGENT_NAME).await?,
-            settings.allowed,
-            settings.denied,
+            MessagePolicy::new(
+                settings.allowed,
+                settings.denied,
+                settings.allowed_actions,
+                settings.denied_actions,
+                settings.policy.as_ref(),
+            ),
+            settings
+                .retry
+                .as_ref()
+                .map(RetryPolicy::from)
+                .unwrap_or_default(),
+            settings
+                .batch
+                .as_ref()
+                .map(BatchPolicy::from)
+                .unwrap_or_default(),
             settings.indexon.is_some(),
-            settings.s3,
+            settings
+                .publisher
+                .or_else(|| settings.s3.map(PublisherConfig::S3)),
         ))
     }
 
-    fn run(&self, name: &str) -> Instrumented<JoinHandle<Result<()>>> {
-        let home = self.home();
-        let next_message_nonce = self.next_message_nonce.clone();
-        let interval = self.interval;
-        let db = NomadDB::new(home.name(), self.db());
-
-        let replica_opt = self.replica_by_name(name);
-        let name = name.to_owned();
-
-        let allowed = self.allowed.clone();
-        let denied = self.denied.clone();
-
-        tokio::spawn(async move {
-            let replica = replica_opt.ok_or_else(|| eyre!("No replica named {}", name))?;
+    fn served_replicas(&self) -> Arc<ServedReplicas> {
+        self.served_replicas.clone()
+    }
 
-            Replica {
-                interval,
-                replica,
-                home,
-                db,
-                allowed,
-                denied,
-                next_message_nonce,
+    fn run(&self, name: &str) -> Instrumented<JoinHandle<Result<()>>> {
+        match self.replica_by_name(name) {
+            Some(replica) => self.run_replica(replica),
+            None => {
+                let name = name.to_owned();
+                tokio::spawn(async move { bail!("No replica named {}", name) }).in_current_span()
             }
-            .main()
-            .await?
-        })
-        .in_current_span()
+        }
     }
 
     fn run_all(self) -> Instrumented<JoinHandle<Result<()>>>
@@ -428,24 +863,26 @@ impl NomadAgent for Processor {
             // instantiate task array here so we can optionally push run_task
             let mut tasks = vec![home_sync_task, prover_sync_task, home_fail_watch_task];
 
-            if !self.index_only {
-                // this is the unused must use
-                let names: Vec<&str> = self.replicas().keys().map(|k| k.as_str()).collect();
-                tasks.push(self.run_many(&names));
+            // if we have a publisher, add a task to push to it
+            if let Some(config) = &self.publisher {
+                let publisher = config.build();
+                info!(location = %publisher.location(), "Starting proof push tasks");
+                tasks.push(Pusher::new(self.core.home.name(), publisher, db.clone()).spawn())
             }
 
-            // if we have a bucket, add a task to push to it
-            if let Some(config) = &self.config {
-                info!(bucket = %config.bucket, "Starting S3 push tasks");
-                tasks.push(
-                    Pusher::new(
-                        self.core.home.name(),
-                        &config.bucket,
-                        config.region.parse().expect("invalid s3 region"),
-                        db.clone(),
-                    )
-                    .spawn(),
+            // serve replicas as they are configured and enrolled
+            let replica_tasks = self.replica_tasks.clone();
+            if !self.index_only {
+                let enrollment = ReplicaEnrollment::new(
+                    self.as_ref(),
+                    self.served_replicas.clone(),
+                    Box::new(|| Ok(Settings::new()?.base)),
                 )
+                .await?;
+                let processor = Arc::new(self);
+                tasks.push(enrollment.spawn(replica_tasks.clone(), move |replica| {
+                    processor.run_replica(replica)
+                }));
             }
 
             // find the first task to shut down. Then cancel all others
@@ -454,9 +891,374 @@ impl NomadAgent for Processor {
             for task in remaining.into_iter() {
                 cancel_task!(task);
             }
+            abort_replica_tasks(&replica_tasks).await;
 
             res?
         })
         .instrument(info_span!("Processor::run_all"))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use ethers::{core::types::H256, signers::LocalWallet};
+    use nomad_base::{
+        ArchiveRecord, ChainSetup, CommonIndexers, CoreMetrics, FileIndexer, HomeIndexers,
+        IndexSettings,
+    };
+    use nomad_core::{
+        BatchOutcomes, ChainCommunicationError, Encode, HomeIndexer, NomadMessage,
+        RawCommittedMessage, TxOutcome, Update,
+    };
+    use nomad_test::{
+        mocks::{MockHomeContract, MockIndexer, MockReplicaContract},
+        test_utils,
+    };
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::prover::Prover;
+
+    #[tokio::test]
+    async fn it_retries_each_message_of_a_failed_batch() {
+        test_utils::run_test_db(|db| async move {
+            let home_db = NomadDB::new("home_1", db.clone());
+
+            // three messages to the replica, proven under the same root
+            let mut prover = Prover::default();
+            let mut messages = vec![];
+            for nonce in 0..3 {
+                let message = NomadMessage {
+                    origin: 1000,
+                    sender: H256::repeat_byte(1),
+                    nonce,
+                    destination: 2000,
+                    recipient: H256::repeat_byte(2),
+                    body: vec![nonce as u8],
+                };
+                prover.ingest(message.to_leaf()).unwrap();
+                home_db
+                    .store_raw_committed_message(&RawCommittedMessage {
+                        leaf_index: nonce,
+                        committed_root: H256::zero(),
+                        message: message.to_vec(),
+                    })
+                    .unwrap();
+                messages.push(message);
+            }
+            for index in 0..3 {
+                home_db
+                    .store_proof(index, &prover.prove(index as usize).unwrap())
+                    .unwrap();
+            }
+
+            // the second message failed twice before
+            home_db
+                .store_retry(&MessageRetry {
+                    destination: 2000,
+                    nonce: 1,
+                    leaf_index: 1,
+                    attempts: 2,
+                    next_attempt_at: u64::MAX,
+                    last_error: "reverted".to_owned(),
+                })
+                .unwrap();
+
+            let mut mock_home = MockHomeContract::new();
+            mock_home.expect__name().return_const("home_1".to_owned());
+            let mut mock_replica = MockReplicaContract::new();
+            mock_replica
+                .expect__name()
+                .return_const("replica_1".to_owned());
+            mock_replica
+                .expect__message_status()
+                .returning(|_| Ok(MessageStatus::None));
+            mock_replica
+                .expect__acceptable_root()
+                .returning(|_| Ok(true));
+
+            // the first batch processes the first message only, the second
+            // can't be sent at all
+            let batches = Mutex::new(0);
+            mock_replica
+                .expect__prove_and_process_batch()
+                .times(2)
+                .returning(move |proofs: &[Proof], messages: &[NomadMessage]| {
+                    assert_eq!(proofs.len(), 3);
+                    assert_eq!(messages.len(), 3);
+                    let mut batches = batches.lock().unwrap();
+                    *batches += 1;
+                    match *batches {
+                        1 => Ok(BatchOutcomes {
+                            proofs: vec![],
+                            messages: vec![
+                                Ok(TxOutcome {
+                                    executed: true,
+                                    ..Default::default()
+                                }),
+                                Ok(TxOutcome::default()),
+                                Err(ChainCommunicationError::DroppedError(H256::zero())),
+                            ],
+                        }),
+                        _ => Err(ChainCommunicationError::DroppedError(H256::zero())),
+                    }
+                });
+
+            let mock_indexer: Arc<CommonIndexers> = Arc::new(MockIndexer::new().into());
+            let mock_home_indexer: Arc<HomeIndexers> = Arc::new(MockIndexer::new().into());
+            let home: Arc<CachingHome> =
+                CachingHome::new(mock_home.into(), home_db.clone(), mock_home_indexer).into();
+            let replica: Arc<CachingReplica> = CachingReplica::new(
+                mock_replica.into(),
+                NomadDB::new("replica_1", db.clone()),
+                mock_indexer,
+            )
+            .into();
+
+            let core = AgentCore {
+                home: home.clone(),
+                replicas: Default::default(),
+                db,
+                indexer: IndexSettings::default(),
+                settings: nomad_base::Settings::default(),
+                metrics: Arc::new(
+                    CoreMetrics::new(
+                        "processor_test",
+                        None,
+                        Arc::new(prometheus::Registry::new()),
+                    )
+                    .expect("could not make metrics"),
+                ),
+            };
+            let processor = Processor::new(
+                1,
+                core,
+                MessagePolicy::new(None, None, None, None, None),
+                RetryPolicy::default(),
+                BatchPolicy { max_messages: 3 },
+                false,
+                None,
+            );
+            let replica_processor = Replica {
+                interval: 1,
+                replica,
+                home,
+                db: home_db.clone(),
+                policy: processor.policy.clone(),
+                retry_policy: processor.retry_policy,
+                batch_policy: processor.batch_policy,
+                budget: processor.budget.clone(),
+                next_message_nonce: processor.next_message_nonce.clone(),
+            };
+            let attempts = |nonce| {
+                home_db
+                    .retry_by_nonce(2000, nonce)
+                    .unwrap()
+                    .map(|retry| retry.attempts)
+            };
+
+            let flow = replica_processor
+                .try_batch_by_domain_and_nonce(2000, 0)
+                .await
+                .unwrap();
+            assert!(matches!(flow, Flow::AdvanceTo(3)));
+            assert_eq!(attempts(0), None);
+            assert_eq!(attempts(1), Some(3));
+            assert_eq!(attempts(2), Some(1));
+
+            let flow = replica_processor
+                .try_batch_by_domain_and_nonce(2000, 0)
+                .await
+                .unwrap();
+            assert!(matches!(flow, Flow::AdvanceTo(3)));
+            assert_eq!(attempts(0), Some(1));
+            assert_eq!(attempts(1), Some(4));
+            assert_eq!(attempts(2), Some(2));
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn it_processes_messages_from_an_archive() {
+        test_utils::run_test_db(|db| async move {
+            let dir = std::env::temp_dir()
+                .join(format!("nomad-processor-archive-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = |name: &str| dir.join(name).to_str().unwrap().to_owned();
+            let _ = std::fs::remove_file(path("replica_1.writes.jsonl"));
+
+            // a message dispatched on the home, and the update committing it
+            // relayed to the replica
+            let signer: LocalWallet =
+                "1111111111111111111111111111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap();
+            let message = NomadMessage {
+                origin: 1000,
+                sender: H256::repeat_byte(1),
+                nonce: 0,
+                destination: 2000,
+                recipient: H256::repeat_byte(2),
+                body: vec![1, 2, 3],
+            };
+            let mut prover = Prover::default();
+            let root = prover.ingest(message.to_leaf()).unwrap();
+            let update = Update {
+                home_domain: 1000,
+                previous_root: H256::zero(),
+                new_root: root,
+            }
+            .sign_with(&signer)
+            .await
+            .unwrap();
+
+            let write_archive = |name: &str, records: &[ArchiveRecord]| {
+                let lines: Vec<_> = records
+                    .iter()
+                    .map(|record| serde_json::to_string(record).unwrap())
+                    .collect();
+                std::fs::write(path(name), lines.join("\n")).unwrap();
+            };
+            write_archive(
+                "home_1.jsonl",
+                &[
+                    ArchiveRecord::Dispatch {
+                        block_number: 10,
+                        leaf_index: 0,
+                        committed_root: H256::zero(),
+                        message: message.to_vec().into(),
+                    },
+                    ArchiveRecord::Update {
+                        block_number: 11,
+                        timestamp: None,
+                        update: update.clone(),
+                    },
+                ],
+            );
+            write_archive(
+                "replica_1.jsonl",
+                &[ArchiveRecord::Update {
+                    block_number: 20,
+                    timestamp: None,
+                    update,
+                }],
+            );
+
+            let setup = |name: &str, domain: &str, writes: Option<String>| -> ChainSetup {
+                serde_json::from_value(serde_json::json!({
+                    "name": name,
+                    "domain": domain,
+                    "address": "0x0000000000000000000000000000000000000000",
+                    "timelag": 0,
+                    "rpcStyle": "archive",
+                    "connection": {
+                        "path": path(&format!("{}.jsonl", name)),
+                        "writes": writes,
+                    },
+                }))
+                .unwrap()
+            };
+            let home_setup = setup("home_1", "1000", None);
+            let replica_setup = setup("replica_1", "2000", Some(path("replica_1.writes.jsonl")));
+            let tx_managers = nomad_base::Settings::default().tx_managers();
+
+            // index the home's messages and prove them, as the indexer and
+            // prover sync would
+            let home_indexer = FileIndexer::from_path(path("home_1.jsonl")).unwrap();
+            let home_db = NomadDB::new("home_1", db.clone());
+            for raw in home_indexer.fetch_sorted_messages(0, 11).await.unwrap() {
+                home_db.store_raw_committed_message(&raw).unwrap();
+                home_db
+                    .store_proof(
+                        raw.leaf_index,
+                        &prover.prove(raw.leaf_index as usize).unwrap(),
+                    )
+                    .unwrap();
+            }
+
+            let home: Arc<CachingHome> = CachingHome::new(
+                home_setup
+                    .try_into_home(None, None, &tx_managers)
+                    .await
+                    .unwrap(),
+                home_db.clone(),
+                Arc::new(HomeIndexers::Other(Box::new(home_indexer))),
+            )
+            .into();
+            let replica: Arc<CachingReplica> = CachingReplica::new(
+                replica_setup
+                    .try_into_replica(None, None, &tx_managers)
+                    .await
+                    .unwrap(),
+                NomadDB::new("replica_1", db.clone()),
+                Arc::new(CommonIndexers::Other(Box::new(
+                    FileIndexer::from_path(path("replica_1.jsonl")).unwrap(),
+                ))),
+            )
+            .into();
+
+            let core = AgentCore {
+                home: home.clone(),
+                replicas: Default::default(),
+                db,
+                indexer: IndexSettings::default(),
+                settings: nomad_base::Settings::default(),
+                metrics: Arc::new(
+                    CoreMetrics::new(
+                        "processor_test",
+                        None,
+                        Arc::new(prometheus::Registry::new()),
+                    )
+                    .expect("could not make metrics"),
+                ),
+            };
+            let processor = Processor::new(
+                1,
+                core,
+                MessagePolicy::new(None, None, None, None, None),
+                RetryPolicy::default(),
+                BatchPolicy::default(),
+                false,
+                None,
+            );
+            let replica_processor = Replica {
+                interval: 1,
+                replica: replica.clone(),
+                home,
+                db: home_db,
+                policy: processor.policy.clone(),
+                retry_policy: processor.retry_policy,
+                batch_policy: processor.batch_policy,
+                budget: processor.budget.clone(),
+                next_message_nonce: processor.next_message_nonce.clone(),
+            };
+
+            let flow = replica_processor
+                .try_msg_by_domain_and_nonce(2000, 0)
+                .await
+                .unwrap();
+            assert!(matches!(flow, Flow::Advance));
+            assert_eq!(
+                nomad_core::Replica::message_status(&*replica, message.to_leaf())
+                    .await
+                    .unwrap(),
+                MessageStatus::Processed
+            );
+
+            // the proof and the processing were recorded as transactions
+            let written = ArchiveRecord::read_all(path("replica_1.writes.jsonl")).unwrap();
+            assert_eq!(written.len(), 2);
+            assert!(matches!(
+                written[0],
+                ArchiveRecord::Prove { leaf, .. } if leaf == message.to_leaf()
+            ));
+            assert!(matches!(
+                written[1],
+                ArchiveRecord::Process { message_hash, .. } if message_hash == message.to_leaf()
+            ));
+
+            std::fs::remove_dir_all(&dir).unwrap();
+        })
+        .await
+    }
+}
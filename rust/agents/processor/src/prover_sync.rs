@@ -15,6 +15,7 @@ use tracing::{debug, error, info, info_span, instrument, instrument::Instrumente
 pub struct ProverSync {
     db: NomadDB,
     prover: Prover,
+    generation: u64,
 }
 
 impl Display for ProverSync {
@@ -103,6 +104,9 @@ impl ProverSync {
     /// instantiates new `ProverSync` and fills prover's merkle tree
     #[instrument(level = "debug", skip(db))]
     pub fn from_disk(db: NomadDB) -> Self {
+        // Read before the leaves, so a rollback in between triggers a rebuild
+        let generation = db.rollback_generation().expect("db error");
+
         // Ingest all leaves in db into prover tree
         let mut prover = Prover::default();
 
@@ -126,7 +130,11 @@ impl ProverSync {
             info!(target_latest_root = ?root, root = ?prover.root(), "Reloaded ProverSync from disk");
         }
 
-        let sync = Self { prover, db };
+        let sync = Self {
+            prover,
+            db,
+            generation,
+        };
 
         // Ensure proofs exist for all leaves
         for i in 0..sync.prover.count() as u32 {
@@ -143,6 +151,21 @@ impl ProverSync {
         sync
     }
 
+    /// Rebuild the prover tree from disk if updates or messages were rolled
+    /// back since it was built. Returns true if it was rebuilt.
+    fn rebuild_if_rolled_back(&mut self) -> Result<bool, ProverSyncError> {
+        if self.db.rollback_generation()? == self.generation {
+            return Ok(false);
+        }
+        info!(
+            root = ?self.prover.root(),
+            size = self.prover.count(),
+            "Rebuilding prover tree after rollback"
+        );
+        *self = Self::from_disk(self.db.clone());
+        Ok(true)
+    }
+
     /// Given new root, update prover tree with leaves until prover tree root
     /// matches new_root
     #[instrument(level = "debug", skip(self))]
@@ -183,6 +206,8 @@ impl ProverSync {
         let span = info_span!("ProverSync", self = %self);
         tokio::spawn(async move {
             loop {
+                self.rebuild_if_rolled_back()?;
+
                 // Try to retrieve new signed update
                 let local_root = self.local_root();
                 let signed_update_opt = self.db.update_by_previous_root(local_root)?;
@@ -235,3 +260,84 @@ impl ProverSync {
         .instrument(span)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::signers::LocalWallet;
+    use nomad_core::{
+        Encode, NomadMessage, RawCommittedMessage, SignedUpdateWithMeta, Update, UpdateMeta,
+    };
+    use nomad_test::test_utils;
+
+    #[tokio::test]
+    async fn it_rebuilds_the_tree_after_a_rollback() {
+        test_utils::run_test_db(|db| async move {
+            let db = NomadDB::new("home_1", db);
+            let signer: LocalWallet =
+                "1111111111111111111111111111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap();
+
+            let mut prover = Prover::default();
+            let mut roots = vec![];
+            for nonce in 0..3 {
+                let message = RawCommittedMessage {
+                    leaf_index: nonce,
+                    committed_root: H256::zero(),
+                    message: NomadMessage {
+                        origin: 1,
+                        sender: H256::repeat_byte(10),
+                        nonce,
+                        destination: 2,
+                        recipient: H256::repeat_byte(11),
+                        body: vec![],
+                    }
+                    .to_vec(),
+                };
+                db.store_latest_message(&message).unwrap();
+                prover.ingest(message.leaf()).unwrap();
+                roots.push(prover.root());
+            }
+
+            // One update committing leaf 0, then one committing leaves 1 and 2
+            let mut updates = vec![];
+            for (block_number, (previous_root, new_root)) in
+                [(H256::zero(), roots[0]), (roots[0], roots[2])]
+                    .into_iter()
+                    .enumerate()
+            {
+                let signed_update = Update {
+                    home_domain: 1,
+                    previous_root,
+                    new_root,
+                }
+                .sign_with(&signer)
+                .await
+                .unwrap();
+                updates.push(SignedUpdateWithMeta {
+                    signed_update,
+                    metadata: UpdateMeta {
+                        block_number: block_number as u64,
+                        timestamp: None,
+                    },
+                });
+            }
+            db.store_updates_and_meta(&updates).unwrap();
+            db.store_prover_latest_committed(roots[2]).unwrap();
+
+            let mut sync = ProverSync::from_disk(db.clone());
+            assert_eq!(sync.prover.count(), 3);
+            assert!(!sync.rebuild_if_rolled_back().unwrap());
+
+            // The second update and its leaves get reorged out
+            db.rollback_updates(Some(0)).unwrap();
+            db.rollback_messages(Some(0)).unwrap();
+
+            assert!(sync.rebuild_if_rolled_back().unwrap());
+            assert_eq!(sync.prover.count(), 1);
+            assert_eq!(sync.prover.root(), roots[0]);
+        })
+        .await
+    }
+}
@@ -0,0 +1,48 @@
+use async_trait::async_trait;
+use color_eyre::eyre::Result;
+
+use super::ProofPublisher;
+
+/// Publishes proofs by POSTing them to `<url>/<key>`.
+///
+/// The endpoint can't be asked whether a proof was already published, so
+/// the pusher relies on its stored progress to avoid publishing twice. The
+/// endpoint should still accept repeated keys, as proofs published right
+/// before a restart may be sent again.
+#[derive(Debug)]
+pub struct HttpPublisher {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl HttpPublisher {
+    /// Instantiate a new publisher POSTing to `url`
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_owned(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl ProofPublisher for HttpPublisher {
+    fn location(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn exists(&self, _key: &str) -> Result<bool> {
+        Ok(false)
+    }
+
+    async fn publish(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        self.client
+            .post(format!("{}/{}", self.url, key))
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
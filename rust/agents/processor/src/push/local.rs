@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use color_eyre::eyre::{Result, WrapErr};
+
+use super::ProofPublisher;
+
+/// Publishes proofs as files in a local directory, one file per key
+#[derive(Debug)]
+pub struct LocalPublisher {
+    dir: PathBuf,
+}
+
+impl LocalPublisher {
+    /// Instantiate a new publisher writing to `dir`. The directory is created
+    /// on first publish if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl ProofPublisher for LocalPublisher {
+    fn location(&self) -> String {
+        format!("file://{}", self.dir.display())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        Ok(tokio::fs::metadata(self.dir.join(key)).await.is_ok())
+    }
+
+    async fn publish(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .wrap_err_with(|| format!("unable to create {}", self.dir.display()))?;
+
+        // Write to a temporary file first so readers never see partial proofs
+        let tmp = self.dir.join(format!(".{}.tmp", key));
+        tokio::fs::write(&tmp, body).await?;
+        tokio::fs::rename(&tmp, self.dir.join(key)).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_publishes_to_a_directory() {
+        let dir = std::env::temp_dir().join(format!("nomad-proofs-{}", std::process::id()));
+        let publisher = LocalPublisher::new(&dir);
+
+        assert!(!publisher.exists("home_0").await.unwrap());
+        publisher.publish("home_0", b"{}".to_vec()).await.unwrap();
+        assert!(publisher.exists("home_0").await.unwrap());
+        assert_eq!(std::fs::read(dir.join("home_0")).unwrap(), b"{}");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,269 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use ethers::utils::keccak256;
+
+use color_eyre::eyre::{eyre, Result};
+
+use nomad_base::NomadDB;
+
+use nomad_core::accumulator::merkle::Proof;
+use tokio::{task::JoinHandle, time::sleep};
+use tracing::{debug, info, info_span, instrument::Instrumented, Instrument};
+
+use crate::settings::PublisherConfig;
+
+mod http;
+mod local;
+mod s3;
+
+pub use http::HttpPublisher;
+pub use local::LocalPublisher;
+pub use s3::S3Publisher;
+
+static PUSHER_NEXT_INDEX: &str = "proof_pusher_next_index_";
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ProvenMessage {
+    message: Vec<u8>,
+    proof: Proof,
+}
+
+/// A destination proofs are published to
+#[async_trait]
+pub trait ProofPublisher: std::fmt::Debug + Send + Sync {
+    /// Where proofs are published, used in logs and to track progress
+    fn location(&self) -> String;
+
+    /// Whether a proof was already published under `key`
+    async fn exists(&self, key: &str) -> Result<bool>;
+
+    /// Publish a JSON encoded proof under `key`
+    async fn publish(&self, key: &str, body: Vec<u8>) -> Result<()>;
+}
+
+impl PublisherConfig {
+    /// Instantiate the configured publisher
+    pub fn build(&self) -> Box<dyn ProofPublisher> {
+        match self {
+            PublisherConfig::S3(config) => Box::new(S3Publisher::new(
+                &config.bucket,
+                config.region.parse().expect("invalid s3 region"),
+            )),
+            PublisherConfig::Local { path } => Box::new(LocalPublisher::new(path)),
+            PublisherConfig::Http { url } => Box::new(HttpPublisher::new(url)),
+        }
+    }
+}
+
+/// Pushes proofs to a `ProofPublisher`
+#[derive(Debug)]
+pub struct Pusher {
+    name: String,
+    db: NomadDB,
+    publisher: Box<dyn ProofPublisher>,
+    index: u32,
+    generation: u64,
+    republish_below: u32,
+}
+
+impl Pusher {
+    /// Instantiate a new pusher publishing to `publisher`
+    pub fn new(name: &str, publisher: Box<dyn ProofPublisher>, db: NomadDB) -> Self {
+        Self {
+            name: name.to_owned(),
+            db,
+            publisher,
+            index: 0,
+            generation: 0,
+            republish_below: 0,
+        }
+    }
+
+    async fn upload_proof(&self, proven: &ProvenMessage) -> Result<()> {
+        let key = self.key(proven);
+        let proof_json = Vec::from(serde_json::to_string_pretty(proven)?);
+        info!(
+            leaf = ?proven.proof.leaf,
+            leaf_index = proven.proof.index,
+            key = %key,
+            "Publishing proof",
+        );
+        self.publisher.publish(&key, proof_json).await
+    }
+
+    async fn already_uploaded(&self, proven: &ProvenMessage) -> Result<bool> {
+        let key = self.key(proven);
+        let exists = self.publisher.exists(&key).await?;
+        if exists {
+            debug!(
+                leaf = ?proven.proof.leaf,
+                leaf_index = proven.proof.index,
+                key = %key,
+                "Proof already published"
+            );
+        }
+        Ok(exists)
+    }
+
+    fn key(&self, proven: &ProvenMessage) -> String {
+        format!("{}_{}", self.name, proven.proof.index)
+    }
+
+    /// The progress key is scoped to the publisher location so that pointing
+    /// the processor to a new destination publishes all proofs again
+    fn progress_key(&self) -> String {
+        format!("{}{}", PUSHER_NEXT_INDEX, self.publisher.location())
+    }
+
+    /// Load the progress persisted by a previous run
+    fn load_progress(&mut self) -> Result<()> {
+        self.generation = self.db.rollback_generation()?;
+        self.index = self
+            .db
+            .retrieve_decodable("", self.progress_key())?
+            .unwrap_or_default();
+        Ok(())
+    }
+
+    /// Rewind to the first leaf still indexed if messages were rolled back.
+    /// Proofs published past it may belong to rolled back messages, so they
+    /// are published again even if present.
+    fn rewind_if_rolled_back(&mut self) -> Result<()> {
+        let generation = self.db.rollback_generation()?;
+        if generation == self.generation {
+            return Ok(());
+        }
+        self.generation = generation;
+
+        let indexed = self
+            .db
+            .retrieve_latest_leaf_index()?
+            .map_or(0, |index| index + 1);
+        if indexed < self.index {
+            info!(
+                from = self.index,
+                to = indexed,
+                "Rewinding proof pusher after rollback"
+            );
+            self.republish_below = self.republish_below.max(self.index);
+            self.index = indexed;
+            self.db
+                .store_encodable("", self.progress_key(), &self.index)?;
+        }
+        Ok(())
+    }
+
+    /// Publish the proof of the next leaf if there is one. Returns false if
+    /// there is no proof for it yet.
+    async fn push_next(&mut self) -> Result<bool> {
+        self.rewind_if_rolled_back()?;
+
+        let proof = match self.db.proof_by_leaf_index(self.index)? {
+            Some(proof) => proof,
+            None => return Ok(false),
+        };
+        let message = self
+            .db
+            .message_by_leaf_index(self.index)?
+            .map(|message| message.message)
+            .ok_or_else(|| eyre!("Missing message for known proof"))?;
+        debug_assert_eq!(keccak256(&message), *proof.leaf.as_fixed_bytes());
+        let proven = ProvenMessage { proof, message };
+        // upload if not already present
+        if self.index < self.republish_below || !self.already_uploaded(&proven).await? {
+            self.upload_proof(&proven).await?;
+        }
+
+        self.index += 1;
+        self.db
+            .store_encodable("", self.progress_key(), &self.index)?;
+        Ok(true)
+    }
+
+    /// Spawn the pusher task and return a joinhandle
+    ///
+    /// The pusher task polls the DB for new proofs and attempts to push them
+    /// to the publisher
+    pub fn spawn(mut self) -> Instrumented<JoinHandle<Result<()>>> {
+        let span = info_span!(
+            "ProofPusher",
+            location = %self.publisher.location(),
+            home = %self.name,
+        );
+        tokio::spawn(async move {
+            self.load_progress()?;
+            loop {
+                if !self.push_next().await? {
+                    sleep(Duration::from_millis(500)).await;
+                }
+            }
+        })
+        .instrument(span)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::core::types::H256;
+    use nomad_core::{
+        accumulator::incremental::IncrementalMerkle, Encode, NomadMessage, RawCommittedMessage,
+    };
+    use nomad_test::test_utils;
+
+    /// Store the message with `body` at `leaf_index` and a proof for it
+    fn store(db: &NomadDB, leaf_index: u32, body: Vec<u8>) {
+        let message = RawCommittedMessage {
+            leaf_index,
+            committed_root: H256::zero(),
+            message: NomadMessage {
+                origin: 1,
+                sender: H256::repeat_byte(10),
+                nonce: leaf_index,
+                destination: 2,
+                recipient: H256::repeat_byte(11),
+                body,
+            }
+            .to_vec(),
+        };
+        db.store_latest_message(&message).unwrap();
+        let proof = Proof {
+            leaf: message.leaf(),
+            index: leaf_index as usize,
+            path: *IncrementalMerkle::default().branch(),
+        };
+        db.store_proof(leaf_index, &proof).unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_republishes_proofs_after_a_rollback() {
+        test_utils::run_test_db(|db| async move {
+            let db = NomadDB::new("home_1", db);
+            let dir = std::env::temp_dir().join(format!("nomad-pusher-{}", std::process::id()));
+            let mut pusher = Pusher::new("home_1", Box::new(LocalPublisher::new(&dir)), db.clone());
+            pusher.load_progress().unwrap();
+
+            store(&db, 0, vec![]);
+            store(&db, 1, vec![]);
+            assert!(pusher.push_next().await.unwrap());
+            assert!(pusher.push_next().await.unwrap());
+            assert!(!pusher.push_next().await.unwrap());
+
+            // Leaf 1 gets reorged out and replaced by a different message
+            db.rollback_messages(Some(0)).unwrap();
+            assert!(!pusher.push_next().await.unwrap());
+            assert_eq!(pusher.index, 1);
+
+            store(&db, 1, vec![1]);
+            assert!(pusher.push_next().await.unwrap());
+            let published: serde_json::Value =
+                serde_json::from_slice(&std::fs::read(dir.join("home_1_1")).unwrap()).unwrap();
+            let message = db.message_by_leaf_index(1).unwrap().unwrap().message;
+            assert_eq!(published["message"], serde_json::json!(message));
+
+            std::fs::remove_dir_all(dir).unwrap();
+        })
+        .await
+    }
+}
@@ -0,0 +1,72 @@
+use async_trait::async_trait;
+use color_eyre::eyre::{bail, Result};
+use rusoto_core::{credential::EnvironmentProvider, HttpClient, Region, RusotoError};
+use rusoto_s3::{GetObjectError, GetObjectRequest, PutObjectRequest, S3Client, S3};
+
+use super::ProofPublisher;
+
+static AWS_S3_PREFIX: &str = "OPT_PROCESSOR_S3";
+
+/// Publishes proofs to an S3 bucket
+pub struct S3Publisher {
+    bucket: String,
+    region: Region,
+    client: S3Client,
+}
+
+impl std::fmt::Debug for S3Publisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("S3Publisher")
+            .field("region", &self.region)
+            .field("bucket", &self.bucket)
+            .finish()
+    }
+}
+
+impl S3Publisher {
+    /// Instantiate a new S3 publisher with a region
+    pub fn new(bucket: &str, region: Region) -> Self {
+        let client = S3Client::new_with(
+            HttpClient::new().unwrap(),
+            EnvironmentProvider::with_prefix(AWS_S3_PREFIX),
+            region.clone(),
+        );
+        Self {
+            bucket: bucket.to_owned(),
+            region,
+            client,
+        }
+    }
+}
+
+#[async_trait]
+impl ProofPublisher for S3Publisher {
+    fn location(&self) -> String {
+        format!("s3://{}/{}", self.region.name(), self.bucket)
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        let req = GetObjectRequest {
+            key: key.to_owned(),
+            bucket: self.bucket.clone(),
+            ..Default::default()
+        };
+        match self.client.get_object(req).await {
+            Ok(_) => Ok(true),
+            Err(RusotoError::Service(GetObjectError::NoSuchKey(_))) => Ok(false),
+            Err(e) => bail!(e),
+        }
+    }
+
+    async fn publish(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        let req = PutObjectRequest {
+            key: key.to_owned(),
+            bucket: self.bucket.clone(),
+            body: Some(body.into()),
+            content_type: Some("application/json".to_owned()),
+            ..Default::default()
+        };
+        self.client.put_object(req).await?;
+        Ok(())
+    }
+}
@@ -1,9 +1,10 @@
 //! Configuration
-use ethers::prelude::H256;
+use ethers::prelude::{Bytes, H256};
 use serde::Deserialize;
 use std::collections::HashSet;
 
-use nomad_base::decl_settings;
+use nomad_base::{decl_settings, RetryConfig};
+use nomad_core::xapps::MessageAction;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct S3Config {
@@ -11,6 +12,100 @@ pub struct S3Config {
     pub region: String,
 }
 
+/// Destination to publish proofs to
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PublisherConfig {
+    /// An amazon aws s3 bucket
+    S3(S3Config),
+    /// A local directory
+    Local {
+        /// Path of the directory
+        path: String,
+    },
+    /// An HTTP endpoint. Proofs are POSTed to `<url>/<key>`
+    Http {
+        /// Base url of the endpoint
+        url: String,
+    },
+}
+
+/// Retry and dead-letter settings for messages whose processing failed
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageRetryConfig {
+    /// Backoff between retries
+    #[serde(flatten)]
+    pub backoff: RetryConfig,
+    /// Number of failed attempts after which a message is dead-lettered
+    pub max_attempts: Option<String>,
+}
+
+/// Settings for submitting several ready messages in one transaction.
+/// Batches go through the chain's multicall contract, which becomes the
+/// `msg.sender` of the replica's `prove` and `process` calls instead of the
+/// processor's signer.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchConfig {
+    /// The most messages to submit in a single batch
+    pub size: String,
+    /// Gas limit for a single batch. Requires `messageGas`.
+    pub gas_limit: Option<String>,
+    /// Gas budgeted for proving and processing one message
+    pub message_gas: Option<String>,
+}
+
+/// What to do with the messages a policy rule matches
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum PolicyAction {
+    /// Process the message
+    Allow,
+    /// Skip the message
+    Deny,
+    /// Hold the message in the manual queue until an operator releases it
+    Defer,
+}
+
+/// A rule of the processor's message policy. A message matches the rule if it
+/// meets every condition set.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyRule {
+    /// Name of the rule in logs
+    pub name: String,
+    /// What to do with matching messages
+    pub action: PolicyAction,
+    /// Message senders to match
+    pub senders: Option<HashSet<H256>>,
+    /// Message recipients to match
+    pub recipients: Option<HashSet<H256>>,
+    /// Origin domains to match
+    pub origins: Option<Vec<String>>,
+    /// Destination domains to match
+    pub destinations: Option<Vec<String>>,
+    /// Smallest body size (in bytes) to match
+    pub min_body_size: Option<String>,
+    /// Largest body size (in bytes) to match
+    pub max_body_size: Option<String>,
+    /// 0x-prefixed hex the body must start with
+    pub body_prefix: Option<Bytes>,
+    /// Actions of decoded bodies to match, e.g. `transfer` or `unknown`
+    pub body_actions: Option<HashSet<MessageAction>>,
+}
+
+/// Ordered rules deciding which messages the processor processes. The first
+/// rule a message matches decides what happens to it.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyConfig {
+    /// The rules, in order
+    pub rules: Vec<PolicyRule>,
+    /// What to do with messages no rule matches. Defaults to `allow`
+    pub default: Option<PolicyAction>,
+}
+
 decl_settings!(Processor {
     /// The polling interval (in seconds)
     interval: String,
@@ -18,8 +113,23 @@ decl_settings!(Processor {
     allowed: Option<HashSet<H256>>,
     /// A deny list of message senders
     denied: Option<HashSet<H256>>,
+    /// An allow list of the actions message bodies carry, e.g. `transfer`
+    /// or `batch`. Bodies of unknown xApps carry the `unknown` action
+    allowed_actions: Option<HashSet<MessageAction>>,
+    /// A deny list of the actions message bodies carry
+    denied_actions: Option<HashSet<MessageAction>>,
+    /// Rules applied to messages not excluded by the allow and deny lists
+    policy: Option<PolicyConfig>,
     /// Only index transactions if this key is set
     indexon: Option<String>,
-    /// An amazon aws s3 bucket to push proofs to
+    /// An amazon aws s3 bucket to push proofs to. Superseded by `publisher`
     s3: Option<S3Config>,
+    /// Where to publish proofs to. Proofs are not published if neither this
+    /// nor `s3` is set.
+    publisher: Option<PublisherConfig>,
+    /// Retry and dead-letter settings for failed messages
+    retry: Option<MessageRetryConfig>,
+    /// Batch submission settings. Messages are submitted one at a time if
+    /// this key is not set.
+    batch: Option<BatchConfig>,
 });
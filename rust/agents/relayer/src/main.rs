@@ -2,6 +2,10 @@
 //!
 //! At a regular interval, the relayer polls Home for signed updates and
 //! submits them as updates with a pending timelock on the replica.
+//!
+//! The relay of each update is tracked in the replica's DB until the replica
+//! accepts it. Relays that revert or are dropped are resubmitted with
+//! exponential backoff, configured under `retry`.
 
 #![forbid(unsafe_code)]
 #![warn(missing_docs)]
@@ -12,7 +16,7 @@ mod settings;
 
 use color_eyre::Result;
 
-use nomad_base::NomadAgent;
+use nomad_base::run_networks;
 
 use crate::{relayer::Relayer, settings::RelayerSettings as Settings};
 
@@ -20,18 +24,8 @@ async fn _main() -> Result<()> {
     color_eyre::install()?;
     let settings = Settings::new()?;
 
-    let agent = Relayer::from_settings(settings).await?;
-
-    agent
-        .as_ref()
-        .settings
-        .tracing
-        .start_tracing(agent.metrics().span_duration())?;
-
-    let _ = agent.metrics().run_http_server();
-
-    agent.run_all().await??;
-    Ok(())
+    // One agent per configured network, or just one without `networks`
+    run_networks::<Relayer>(settings.split_networks()).await
 }
 
 fn main() -> Result<()> {
@@ -1,21 +1,53 @@
 use async_trait::async_trait;
 use color_eyre::{eyre::bail, Result};
-use std::{sync::Arc, time::Duration};
-use tokio::{sync::Mutex, task::JoinHandle, time::sleep};
-use tracing::{info, instrument::Instrumented, Instrument};
+use ethers::core::types::H256;
+use futures_util::future::select_all;
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{task::JoinHandle, time::sleep};
+use tracing::{info, info_span, instrument::Instrumented, warn, Instrument};
 
-use nomad_base::{AgentCore, CachingHome, CachingReplica, NomadAgent};
-use nomad_core::{Common, CommonEvents};
+use nomad_base::{
+    abort_replica_tasks, cancel_task, AgentCore, Backoff, CachingHome, CachingReplica,
+    ContractSyncMetrics, GasBudget, GasOperation, IndexDataTypes, NomadAgent, ReplicaEnrollment,
+    ReplicaTasks, ServedReplicas,
+};
+use nomad_core::{Common, RelayStatus, SignedUpdate, UpdateRelay};
 
 use crate::settings::RelayerSettings as Settings;
 
+/// Backoff between resubmissions of relays that reverted or were dropped,
+/// unless configured otherwise
+const DEFAULT_BACKOFF: Backoff = Backoff::new(30, 600);
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock before unix epoch")
+        .as_secs()
+}
+
+/// Metrics of the relays from the home to one replica
+#[derive(Debug, Clone)]
+struct RelayMetrics {
+    updates_relayed_count: prometheus::IntCounter,
+    relay_failures_count: prometheus::IntCounter,
+    relay_latency: prometheus::Histogram,
+}
+
+/// Relays the home's updates to one replica. The relay of every update is
+/// tracked in the replica's DB as pending, confirmed, reverted or dropped, so
+/// that failed relays are resubmitted with backoff, across restarts too.
 #[derive(Debug)]
 struct UpdatePoller {
     duration: Duration,
     home: Arc<CachingHome>,
     replica: Arc<CachingReplica>,
-    semaphore: Mutex<()>,
-    updates_relayed_count: prometheus::IntCounter,
+    backoff: Backoff,
+    budget: Arc<GasBudget>,
+    metrics: RelayMetrics,
 }
 
 impl std::fmt::Display for UpdatePoller {
@@ -33,17 +65,166 @@ impl UpdatePoller {
         home: Arc<CachingHome>,
         replica: Arc<CachingReplica>,
         duration: u64,
-        updates_relayed_count: prometheus::IntCounter,
+        backoff: Backoff,
+        budget: Arc<GasBudget>,
+        metrics: RelayMetrics,
     ) -> Self {
         Self {
             home,
             replica,
             duration: Duration::from_secs(duration),
-            semaphore: Mutex::new(()),
-            updates_relayed_count,
+            backoff,
+            budget,
+            metrics,
         }
     }
 
+    /// Follow the chain of updates the home has stored from `root` to the
+    /// latest one
+    fn updates_from(&self, root: H256) -> Result<Vec<SignedUpdate>> {
+        let db = self.home.db();
+        let mut updates = vec![];
+        let mut next = db.update_by_previous_root(root)?;
+        while let Some(signed_update) = next {
+            next = db.update_by_previous_root(signed_update.update.new_root)?;
+            updates.push(signed_update);
+        }
+        Ok(updates)
+    }
+
+    /// The relay state of `signed_update`, new if it was never relayed
+    fn relay_state(&self, signed_update: &SignedUpdate) -> Result<UpdateRelay> {
+        let update = &signed_update.update;
+        if let Some(relay) = self
+            .replica
+            .db()
+            .relay_by_roots(update.previous_root, update.new_root)?
+        {
+            return Ok(relay);
+        }
+
+        // when the update appeared on the home, or now if the home's block
+        // timestamp is unknown
+        let seen_at = self
+            .home
+            .db()
+            .retrieve_update_metadata(update.new_root)?
+            .and_then(|meta| meta.timestamp)
+            .unwrap_or_else(unix_now);
+        Ok(UpdateRelay::new(signed_update, seen_at))
+    }
+
+    /// Record that the replica accepted the update of `relay`
+    fn confirm(&self, relay: UpdateRelay, txid: Option<H256>) -> Result<()> {
+        let now = unix_now();
+        let relay = UpdateRelay {
+            status: RelayStatus::Confirmed,
+            txid: txid.unwrap_or(relay.txid),
+            confirmed_at: now,
+            ..relay
+        };
+        self.metrics
+            .relay_latency
+            .observe(now.saturating_sub(relay.seen_at) as f64);
+        info!(
+            previous_root = ?relay.previous_root,
+            new_root = ?relay.new_root,
+            attempts = relay.attempts,
+            "Replica {} accepted update {} to {}",
+            self.replica.name(),
+            relay.previous_root,
+            relay.new_root,
+        );
+        Ok(self.replica.db().store_relay(&relay)?)
+    }
+
+    /// Record that the relay of `relay`'s update failed, and schedule its
+    /// resubmission
+    fn fail(&self, relay: UpdateRelay, status: RelayStatus, txid: Option<H256>) -> Result<()> {
+        let attempts = relay.attempts + 1;
+        let delay = self.backoff.delay(attempts).as_secs();
+        let relay = UpdateRelay {
+            status,
+            txid: txid.unwrap_or(relay.txid),
+            attempts,
+            next_attempt_at: unix_now() + delay,
+            ..relay
+        };
+        self.metrics.relay_failures_count.inc();
+        warn!(
+            previous_root = ?relay.previous_root,
+            new_root = ?relay.new_root,
+            tx_hash = ?relay.txid,
+            status = status.as_str(),
+            attempts,
+            delay,
+            "Relay of update {} to {} to replica {} {}. Retrying in {} seconds.",
+            relay.previous_root,
+            relay.new_root,
+            self.replica.name(),
+            status.as_str(),
+            delay,
+        );
+        Ok(self.replica.db().store_relay(&relay)?)
+    }
+
+    /// Settle a relay left pending by a previous run, which stopped while
+    /// the relay was in flight. Its last transaction may have landed since,
+    /// so its receipt is checked before the update is resubmitted. Without a
+    /// receipt, the relay is treated as dropped and backs off as usual.
+    async fn resolve_pending(&self, relay: UpdateRelay) -> Result<()> {
+        if relay.txid.is_zero() {
+            return self.fail(relay, RelayStatus::Dropped, None);
+        }
+        match self.replica.status(relay.txid).await? {
+            Some(outcome) if outcome.executed => self.confirm(relay, Some(outcome.txid)),
+            Some(outcome) => self.fail(relay, RelayStatus::Reverted, Some(outcome.txid)),
+            None => self.fail(relay, RelayStatus::Dropped, None),
+        }
+    }
+
+    /// Confirm the relays of the updates the replica accepted since the last
+    /// poll, whoever submitted them
+    fn settle(&self, committed_root: H256) -> Result<()> {
+        let db = self.replica.db();
+        let home_db = self.home.db();
+        let settled_root = db.retrieve_settled_root()?.unwrap_or(committed_root);
+
+        let mut accepted = vec![];
+        let mut root = settled_root;
+        while root != committed_root {
+            match home_db.update_by_previous_root(root)? {
+                Some(signed_update) => {
+                    root = signed_update.update.new_root;
+                    accepted.push(signed_update);
+                }
+                None => break,
+            }
+        }
+
+        // the home does not know how the replica got to its root, so the
+        // relays in between can't be settled
+        if root != committed_root {
+            warn!(
+                settled_root = ?settled_root,
+                committed_root = ?committed_root,
+                "No chain of updates from root {} to replica {} root {}",
+                settled_root,
+                self.replica.name(),
+                committed_root,
+            );
+            accepted.clear();
+        }
+
+        for signed_update in accepted.iter() {
+            let relay = self.relay_state(signed_update)?;
+            if relay.status != RelayStatus::Confirmed {
+                self.confirm(relay, None)?;
+            }
+        }
+        Ok(db.store_settled_root(committed_root)?)
+    }
+
     #[tracing::instrument(err, skip(self), fields(self = %self))]
     async fn poll_and_relay_update(&self) -> Result<()> {
         // Get replica's current root.
@@ -53,38 +234,105 @@ impl UpdatePoller {
             self.replica.name(),
             old_root
         );
+        self.settle(old_root)?;
 
-        // Check for first signed update building off of the replica's current root
-        let signed_update_opt = self.home.signed_update_by_old_root(old_root).await?;
+        // Collect every signed update between the replica's current root and
+        // the latest root
+        let updates = self.updates_from(old_root)?;
+        let new_root = match updates.last() {
+            Some(signed_update) => signed_update.update.new_root,
+            None => {
+                info!(
+                    "No update. Current root for replica {} is {}",
+                    self.replica.name(),
+                    old_root
+                );
+                return Ok(());
+            }
+        };
+        info!(
+            "{} updates for replica {}. Root {} to {}",
+            updates.len(),
+            self.replica.name(),
+            old_root,
+            new_root,
+        );
+
+        // Relays still stored as pending were in flight when a previous run
+        // stopped
+        let mut relays = updates
+            .iter()
+            .map(|signed_update| self.relay_state(signed_update))
+            .collect::<Result<Vec<_>>>()?;
+        for (signed_update, relay) in updates.iter().zip(relays.iter_mut()) {
+            let update = &signed_update.update;
+            let stored = self
+                .replica
+                .db()
+                .relay_by_roots(update.previous_root, update.new_root)?;
+            if let Some(stored) = stored.filter(|stored| stored.status == RelayStatus::Pending) {
+                self.resolve_pending(stored).await?;
+                *relay = self.relay_state(signed_update)?;
+            }
+        }
 
-        // If signed update exists for replica's committed root, try to
-        // relay
-        if let Some(signed_update) = signed_update_opt {
+        // Updates build off each other, so the chain waits for its first
+        // update's backoff
+        let now = unix_now();
+        if !relays[0].is_due(now) {
             info!(
-                "Update for replica {}. Root {} to {}",
+                next_attempt_at = relays[0].next_attempt_at,
+                "Relay to replica {} backing off for {} seconds",
                 self.replica.name(),
-                &signed_update.update.previous_root,
-                &signed_update.update.new_root,
+                relays[0].next_attempt_at.saturating_sub(now),
             );
+            return Ok(());
+        }
 
-            // Attempt to acquire lock for submitting tx
-            let lock = self.semaphore.try_lock();
-            if lock.is_err() {
-                return Ok(()); // tx in flight. just do nothing
-            }
+        // Hold off until tomorrow (UTC) once the daily budget is spent
+        if self.budget.is_exhausted(self.replica.name())? {
+            warn!(
+                replica = self.replica.name(),
+                "Daily gas budget for replica {} exhausted, not relaying",
+                self.replica.name()
+            );
+            return Ok(());
+        }
 
-            // Relay update and increment counters if tx successful
-            if self.replica.update(&signed_update).await.is_ok() {
-                self.updates_relayed_count.inc();
+        // Relay the whole chain, in as few transactions as the replica
+        // allows. Relays stay pending until the outcome is known.
+        let db = self.replica.db();
+        for relay in relays.iter() {
+            db.store_relay(&UpdateRelay {
+                status: RelayStatus::Pending,
+                ..relay.clone()
+            })?;
+        }
+        let outcomes = match self.replica.update_chain(&updates).await {
+            Ok(outcomes) => outcomes,
+            Err(e) => {
+                warn!(error = %e, "Failed to relay updates to replica {}", self.replica.name());
+                vec![]
             }
+        };
 
-            // lock dropped here
-        } else {
-            info!(
-                "No update. Current root for replica {} is {}",
-                self.replica.name(),
-                old_root
-            );
+        // Record what our transactions spent and count only the updates they
+        // got accepted. Updates without an outcome never made it on chain.
+        for (i, relay) in relays.into_iter().enumerate() {
+            match outcomes.get(i) {
+                Some(outcome) if outcome.executed => {
+                    self.budget
+                        .record(self.replica.name(), GasOperation::Relay, outcome)?;
+                    self.metrics.updates_relayed_count.inc();
+                    self.confirm(relay, Some(outcome.txid))?;
+                }
+                Some(outcome) => {
+                    self.budget
+                        .record(self.replica.name(), GasOperation::Relay, outcome)?;
+                    self.fail(relay, RelayStatus::Reverted, Some(outcome.txid))?;
+                }
+                None => self.fail(relay, RelayStatus::Dropped, None)?,
+            }
         }
 
         Ok(())
@@ -105,7 +353,13 @@ impl UpdatePoller {
 pub struct Relayer {
     duration: u64,
     core: AgentCore,
+    replica_tasks: Arc<ReplicaTasks>,
+    served_replicas: Arc<ServedReplicas>,
+    backoff: Backoff,
+    budget: Arc<GasBudget>,
     updates_relayed_count: prometheus::IntCounterVec,
+    relay_failures_count: prometheus::IntCounterVec,
+    relay_latency: prometheus::HistogramVec,
 }
 
 impl AsRef<AgentCore> for Relayer {
@@ -117,7 +371,7 @@ impl AsRef<AgentCore> for Relayer {
 #[allow(clippy::unit_arg)]
 impl Relayer {
     /// Instantiate a new relayer
-    pub fn new(duration: u64, core: AgentCore) -> Self {
+    pub(crate) fn new(duration: u64, backoff: Backoff, core: AgentCore) -> Self {
         let updates_relayed_count = core
             .metrics
             .new_int_counter(
@@ -125,14 +379,62 @@ impl Relayer {
                 "Number of updates relayed from given home to replica",
                 &["home", "replica", "agent"],
             )
-            .expect("processor metric already registered -- should have be a singleton");
+            .expect("relayer metric already registered -- should have been a singleton");
+
+        let relay_failures_count = core
+            .metrics
+            .new_int_counter(
+                "relay_failures_count",
+                "Number of update relays from given home to replica that reverted or were dropped",
+                &["home", "replica", "agent"],
+            )
+            .expect("relayer metric already registered -- should have been a singleton");
+
+        let relay_latency = core
+            .metrics
+            .new_histogram(
+                "relay_latency_seconds",
+                "Seconds from an update appearing on given home to its acceptance by replica",
+                &["home", "replica", "agent"],
+                &[
+                    30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 1800.0, 3600.0, 7200.0, 21600.0,
+                ],
+            )
+            .expect("relayer metric already registered -- should have been a singleton");
 
         Self {
             duration,
+            budget: Arc::new(GasBudget::new(Self::AGENT_NAME, &core)),
+            served_replicas: Arc::new(std::sync::RwLock::new(core.replicas.clone())),
             core,
+            replica_tasks: Default::default(),
+            backoff,
             updates_relayed_count,
+            relay_failures_count,
+            relay_latency,
         }
     }
+
+    /// Relay updates to `replica`, whether or not it is in the replicas map
+    fn run_replica(&self, replica: Arc<CachingReplica>) -> Instrumented<JoinHandle<Result<()>>> {
+        let home = self.home();
+        let labels = [home.name(), replica.name(), Self::AGENT_NAME];
+        let metrics = RelayMetrics {
+            updates_relayed_count: self.updates_relayed_count.with_label_values(&labels),
+            relay_failures_count: self.relay_failures_count.with_label_values(&labels),
+            relay_latency: self.relay_latency.with_label_values(&labels),
+        };
+        let update_poller = UpdatePoller::new(
+            home,
+            replica,
+            self.duration,
+            self.backoff,
+            self.budget.clone(),
+            metrics,
+        );
+
+        tokio::spawn(async move { update_poller.spawn().await? }).in_current_span()
+    }
 }
 
 #[async_trait]
@@ -148,36 +450,305 @@ impl NomadAgent for Relayer {
     {
         Ok(Self::new(
             settings.interval.parse().expect("invalid uint"),
+            DEFAULT_BACKOFF.configured(settings.retry.as_ref()),
             settings.as_ref().try_into_core("relayer").await?,
         ))
     }
 
+    fn served_replicas(&self) -> Arc<ServedReplicas> {
+        self.served_replicas.clone()
+    }
+
     #[tracing::instrument]
     fn run(&self, name: &str) -> Instrumented<JoinHandle<Result<()>>> {
-        let replica_opt = self.replica_by_name(name);
-        let home = self.home();
-        let updates_relayed_count = self.updates_relayed_count.clone();
-
-        let name = name.to_owned();
-        let duration = self.duration;
+        match self.replica_by_name(name) {
+            Some(replica) => self.run_replica(replica),
+            None => {
+                let name = name.to_owned();
+                tokio::spawn(async move { bail!("No replica named {}", name) }).in_current_span()
+            }
+        }
+    }
 
+    fn run_all(self) -> Instrumented<JoinHandle<Result<()>>>
+    where
+        Self: Sized + 'static,
+    {
         tokio::spawn(async move {
-            if replica_opt.is_none() {
-                bail!("No replica named {}", name);
+            let sync_metrics = ContractSyncMetrics::new(self.metrics());
+            let indexer = &self.as_ref().indexer;
+            let sync_task = self.home().sync(
+                Self::AGENT_NAME.to_owned(),
+                indexer.from(),
+                indexer.chunk_size(),
+                sync_metrics,
+                IndexDataTypes::Updates,
+            );
+
+            // serve replicas as they are configured and enrolled
+            let replica_tasks = self.replica_tasks.clone();
+            let enrollment = ReplicaEnrollment::new(
+                self.as_ref(),
+                self.served_replicas.clone(),
+                Box::new(|| Ok(Settings::new()?.base)),
+            )
+            .await?;
+            let relayer = Arc::new(self);
+            let enrollment_task = enrollment.spawn(replica_tasks.clone(), move |replica| {
+                relayer.run_replica(replica)
+            });
+
+            let (res, _, remaining) = select_all(vec![sync_task, enrollment_task]).await;
+            for task in remaining.into_iter() {
+                cancel_task!(task);
             }
-            let replica = replica_opt.unwrap();
+            abort_replica_tasks(&replica_tasks).await;
 
-            let update_poller = UpdatePoller::new(
-                home.clone(),
-                replica.clone(),
-                duration,
-                updates_relayed_count.with_label_values(&[home.name(), &name, Self::AGENT_NAME]),
-            );
-            update_poller.spawn().await?
+            res?
         })
-        .in_current_span()
+        .instrument(info_span!("Relayer::run_all"))
     }
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use ethers::signers::LocalWallet;
+    use nomad_base::{CommonIndexers, CoreMetrics, HomeIndexers, IndexSettings, NomadDB};
+    use nomad_core::{db::DB, ChainCommunicationError, TxOutcome, Update};
+    use nomad_test::{
+        mocks::{MockHomeContract, MockIndexer, MockReplicaContract},
+        test_utils,
+    };
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// A poller relaying from `mock_home` to `mock_replica`, and its metrics
+    fn poller(
+        db: DB,
+        home_db: NomadDB,
+        replica_db: NomadDB,
+        mock_home: MockHomeContract,
+        mock_replica: MockReplicaContract,
+        backoff: Backoff,
+    ) -> (UpdatePoller, RelayMetrics) {
+        let mock_indexer: Arc<CommonIndexers> = Arc::new(MockIndexer::new().into());
+        let mock_home_indexer: Arc<HomeIndexers> = Arc::new(MockIndexer::new().into());
+        let home: Arc<CachingHome> =
+            CachingHome::new(mock_home.into(), home_db, mock_home_indexer).into();
+        let replica: Arc<CachingReplica> =
+            CachingReplica::new(mock_replica.into(), replica_db, mock_indexer).into();
+
+        let core = AgentCore {
+            home: home.clone(),
+            replicas: Default::default(),
+            db,
+            indexer: IndexSettings::default(),
+            settings: nomad_base::Settings::default(),
+            metrics: Arc::new(
+                CoreMetrics::new("relayer_test", None, Arc::new(prometheus::Registry::new()))
+                    .expect("could not make metrics"),
+            ),
+        };
+        let relayer = Relayer::new(1, backoff, core);
+        let labels = ["home_1", "replica_1", Relayer::AGENT_NAME];
+        let metrics = RelayMetrics {
+            updates_relayed_count: relayer.updates_relayed_count.with_label_values(&labels),
+            relay_failures_count: relayer.relay_failures_count.with_label_values(&labels),
+            relay_latency: relayer.relay_latency.with_label_values(&labels),
+        };
+        let poller = UpdatePoller::new(
+            home,
+            replica,
+            1,
+            backoff,
+            relayer.budget.clone(),
+            metrics.clone(),
+        );
+        (poller, metrics)
+    }
+
+    #[tokio::test]
+    async fn it_tracks_relays_until_the_replica_accepts_them() {
+        test_utils::run_test_db(|db| async move {
+            let signer: LocalWallet =
+                "1111111111111111111111111111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap();
+
+            // the home has three updates the replica has not seen
+            let home_db = NomadDB::new("home_1", db.clone());
+            let roots: Vec<H256> = (0u8..4).map(H256::repeat_byte).collect();
+            let mut updates = vec![];
+            for window in roots.windows(2) {
+                let signed_update = Update {
+                    home_domain: 1,
+                    previous_root: window[0],
+                    new_root: window[1],
+                }
+                .sign_with(&signer)
+                .await
+                .expect("!sign");
+                home_db.store_latest_update(&signed_update).unwrap();
+                updates.push(signed_update);
+            }
+
+            let mut mock_home = MockHomeContract::new();
+            mock_home.expect__name().return_const("home_1".to_owned());
+
+            let mut mock_replica = MockReplicaContract::new();
+            mock_replica
+                .expect__name()
+                .return_const("replica_1".to_owned());
+
+            // the replica moves to the second root, then someone else relays
+            // the third update
+            let committed_roots = Mutex::new(vec![roots[0], roots[2], roots[3]].into_iter());
+            mock_replica
+                .expect__committed_root()
+                .returning(move || Ok(committed_roots.lock().unwrap().next().unwrap()));
+
+            // the transaction carrying the third update reverts, and its
+            // resubmission is dropped
+            mock_replica
+                .expect__update_chain()
+                .times(2)
+                .returning(|updates: &[SignedUpdate]| match updates.len() {
+                    3 => {
+                        let executed = TxOutcome {
+                            executed: true,
+                            ..Default::default()
+                        };
+                        Ok(vec![executed, executed, Default::default()])
+                    }
+                    _ => Err(ChainCommunicationError::DroppedError(H256::zero())),
+                });
+
+            let replica_db = NomadDB::new("replica_1", db.clone());
+            let (poller, metrics) = poller(
+                db,
+                home_db,
+                replica_db.clone(),
+                mock_home,
+                mock_replica,
+                Backoff::new(0, 0),
+            );
+            let relay = |i: usize| {
+                replica_db
+                    .relay_by_roots(roots[i], roots[i + 1])
+                    .unwrap()
+                    .unwrap()
+            };
+
+            poller.poll_and_relay_update().await.unwrap();
+            assert_eq!(relay(0).status, RelayStatus::Confirmed);
+            assert_eq!(relay(1).status, RelayStatus::Confirmed);
+            assert_eq!(relay(2).status, RelayStatus::Reverted);
+            assert_eq!(relay(2).attempts, 1);
+
+            // the second poll resubmits the third update alone
+            poller.poll_and_relay_update().await.unwrap();
+            assert_eq!(relay(2).status, RelayStatus::Dropped);
+            assert_eq!(relay(2).attempts, 2);
+
+            // the third poll sees it landed and does not submit anything
+            poller.poll_and_relay_update().await.unwrap();
+            assert_eq!(relay(2).status, RelayStatus::Confirmed);
+            assert!(relay(2).confirmed_at > 0);
+
+            assert_eq!(metrics.updates_relayed_count.get(), 2);
+            assert_eq!(metrics.relay_failures_count.get(), 2);
+            assert_eq!(metrics.relay_latency.get_sample_count(), 3);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn it_checks_pending_relays_before_resubmitting() {
+        test_utils::run_test_db(|db| async move {
+            let signer: LocalWallet =
+                "1111111111111111111111111111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap();
+
+            let home_db = NomadDB::new("home_1", db.clone());
+            let roots: Vec<H256> = (0u8..3).map(H256::repeat_byte).collect();
+            let mut updates = vec![];
+            for window in roots.windows(2) {
+                let signed_update = Update {
+                    home_domain: 1,
+                    previous_root: window[0],
+                    new_root: window[1],
+                }
+                .sign_with(&signer)
+                .await
+                .expect("!sign");
+                home_db.store_latest_update(&signed_update).unwrap();
+                updates.push(signed_update);
+            }
+
+            // a previous run stopped while relaying both updates, the first
+            // one's last transaction having reverted
+            let replica_db = NomadDB::new("replica_1", db.clone());
+            let txid = H256::repeat_byte(0xaa);
+            for signed_update in updates.iter() {
+                replica_db
+                    .store_relay(&UpdateRelay {
+                        status: RelayStatus::Pending,
+                        txid,
+                        ..UpdateRelay::new(signed_update, unix_now())
+                    })
+                    .unwrap();
+            }
+
+            let mut mock_home = MockHomeContract::new();
+            mock_home.expect__name().return_const("home_1".to_owned());
+
+            let mut mock_replica = MockReplicaContract::new();
+            mock_replica
+                .expect__name()
+                .return_const("replica_1".to_owned());
+            mock_replica
+                .expect__committed_root()
+                .returning(move || Ok(roots[0]));
+            mock_replica
+                .expect__status()
+                .withf(move |id: &H256| *id == txid)
+                .times(2)
+                .returning(|txid| {
+                    Ok(Some(TxOutcome {
+                        txid,
+                        executed: false,
+                        ..Default::default()
+                    }))
+                });
+            // the relays back off instead of being resubmitted right away
+            mock_replica.expect__update_chain().times(0);
+
+            let (poller, metrics) = poller(
+                db,
+                home_db,
+                replica_db.clone(),
+                mock_home,
+                mock_replica,
+                Backoff::new(60, 60),
+            );
+
+            poller.poll_and_relay_update().await.unwrap();
+            for signed_update in updates.iter() {
+                let relay = replica_db
+                    .relay_by_roots(
+                        signed_update.update.previous_root,
+                        signed_update.update.new_root,
+                    )
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(relay.status, RelayStatus::Reverted);
+                assert_eq!(relay.attempts, 1);
+                assert!(relay.next_attempt_at > unix_now());
+            }
+            assert_eq!(metrics.relay_failures_count.get(), 2);
+        })
+        .await
+    }
+}
@@ -1,8 +1,10 @@
 //! Configuration
 
-use nomad_base::decl_settings;
+use nomad_base::{decl_settings, RetryConfig};
 
 decl_settings!(Relayer {
     /// The polling interval (in seconds)
     interval: String,
+    /// Backoff between resubmissions of relays that reverted or were dropped
+    retry: Option<RetryConfig>,
 });
@@ -0,0 +1,334 @@
+//! Exchange of update signatures between the members of an updater committee.
+//!
+//! Each member signs updates with its own key and serves them over HTTP. The
+//! member holding the home's updater key collects the signatures of its peers
+//! and only submits an update once enough members signed it.
+//!
+//! Routes:
+//! - `GET /attestations/<previous_root>`: this member's signed update building
+//!   off `previous_root`
+//! - `GET /attestations/<previous_root>/committee`: the committee attestation
+//!   collected for it, for watchers to verify
+
+use std::{convert::TryFrom, sync::Arc, time::Duration};
+
+use color_eyre::{
+    eyre::{ensure, WrapErr},
+    Report, Result,
+};
+use ethers::core::types::{Address, H256};
+use nomad_base::NomadDB;
+use nomad_core::{db::DbError, SignedUpdate, UpdateAttestation};
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info_span, instrument::Instrumented, warn, Instrument};
+use warp::{http::StatusCode, reply, Filter, Rejection, Reply};
+
+use crate::settings::CommitteeConfig;
+
+/// How long to wait for a peer to serve its signed update
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A committee of updaters that must attest updates before they are
+/// submitted
+#[derive(Debug, Clone)]
+pub struct Committee {
+    threshold: usize,
+    members: Vec<Address>,
+    peers: Vec<String>,
+    port: u16,
+    client: reqwest::Client,
+}
+
+impl TryFrom<&CommitteeConfig> for Committee {
+    type Error = Report;
+
+    fn try_from(config: &CommitteeConfig) -> Result<Self> {
+        Ok(Self {
+            threshold: config
+                .threshold
+                .parse()
+                .wrap_err("Invalid committee threshold")?,
+            members: config.members.clone(),
+            peers: config
+                .peers
+                .iter()
+                .map(|peer| peer.trim_end_matches('/').to_owned())
+                .collect(),
+            port: config.port.parse().wrap_err("Invalid committee port")?,
+            client: peer_client()?,
+        })
+    }
+}
+
+/// A client for requests to peers, timing out on unresponsive ones
+fn peer_client() -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder().timeout(PEER_TIMEOUT).build()?)
+}
+
+impl Committee {
+    /// True if `address` is a committee member
+    pub(crate) fn contains(&self, address: Address) -> bool {
+        self.members.contains(&address)
+    }
+
+    /// Check that the number of members and the threshold make sense
+    pub(crate) fn validate(&self) -> Result<()> {
+        ensure!(
+            self.threshold > 0 && self.threshold <= self.members.len(),
+            "Committee threshold {} out of range for {} members",
+            self.threshold,
+            self.members.len()
+        );
+        Ok(())
+    }
+
+    /// Serve the signed updates in `db` to the other members
+    pub(crate) fn serve(&self, db: NomadDB) -> Instrumented<JoinHandle<Result<()>>> {
+        let span = info_span!("AttestationServer", port = self.port);
+        let port = self.port;
+        tokio::spawn(async move {
+            warp::serve(routes(db)).run(([0, 0, 0, 0], port)).await;
+            Ok(())
+        })
+        .instrument(span)
+    }
+}
+
+fn routes(db: NomadDB) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let db = warp::any().map(move || db.clone());
+
+    let signed = warp::path!("attestations" / H256)
+        .and(db.clone())
+        .map(|root, db: NomadDB| respond(db.retrieve_produced_update(root)));
+    let attestation = warp::path!("attestations" / H256 / "committee")
+        .and(db)
+        .map(|root, db: NomadDB| respond(db.retrieve_attestation(root)));
+
+    warp::get().and(signed.or(attestation))
+}
+
+/// Reply with the JSON encoded value, a 404 if there is none or a 500 on DB
+/// errors
+fn respond<T: Serialize>(res: Result<Option<T>, DbError>) -> reply::Response {
+    match res {
+        Ok(Some(value)) => reply::json(&value).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            error!(error = %e, "attestation db error");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Collects the signatures of the other committee members
+#[derive(Debug)]
+pub(crate) struct AttestationCollector {
+    db: NomadDB,
+    committee: Arc<Committee>,
+    client: reqwest::Client,
+}
+
+impl AttestationCollector {
+    pub(crate) fn new(db: NomadDB, committee: Arc<Committee>) -> Self {
+        Self {
+            db,
+            client: committee.client.clone(),
+            committee,
+        }
+    }
+
+    /// Fetch the update `peer` signed building off `previous_root`
+    async fn fetch(&self, peer: &str, previous_root: H256) -> Result<Option<SignedUpdate>> {
+        let response = self
+            .client
+            .get(format!("{}/attestations/{:?}", peer, previous_root))
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body = response.error_for_status()?.bytes().await?;
+        Ok(Some(serde_json::from_slice(&body)?))
+    }
+
+    /// Add the signatures of `signed` and of the peers that signed the same
+    /// update to its attestation. Unreachable peers, invalid signatures and
+    /// signatures that don't belong to the committee are skipped. A stored
+    /// attestation of a different update building off the same root is
+    /// replaced.
+    pub(crate) async fn collect(&self, signed: &SignedUpdate) -> Result<UpdateAttestation> {
+        let previous_root = signed.update.previous_root;
+        let mut attestation = match self.db.retrieve_attestation(previous_root)? {
+            Some(attestation) if attestation.update == signed.update => attestation,
+            Some(stale) => {
+                warn!(
+                    previous_root = ?previous_root,
+                    stale = ?stale.update.new_root,
+                    new_root = ?signed.update.new_root,
+                    "Replacing attestation of a different update building off {}",
+                    previous_root
+                );
+                UpdateAttestation::new(signed.update)
+            }
+            None => UpdateAttestation::new(signed.update),
+        };
+        attestation.add(signed)?;
+
+        for peer in self.committee.peers.iter() {
+            let peer_signed = match self.fetch(peer, previous_root).await {
+                Ok(Some(peer_signed)) => peer_signed,
+                Ok(None) => {
+                    debug!(peer = %peer, previous_root = ?previous_root, "Peer has not signed yet");
+                    continue;
+                }
+                Err(e) => {
+                    warn!(peer = %peer, error = %e, "Failed to fetch attestation from peer");
+                    continue;
+                }
+            };
+
+            let signer = match peer_signed.recover() {
+                Ok(signer) => signer,
+                Err(e) => {
+                    warn!(peer = %peer, error = %e, "Invalid signature from peer");
+                    continue;
+                }
+            };
+            if !self.committee.contains(signer) {
+                warn!(peer = %peer, signer = ?signer, "Peer signer is not a committee member");
+                continue;
+            }
+            if let Err(e) = attestation.add(&peer_signed) {
+                error!(peer = %peer, error = %e, "Peer signed a conflicting update");
+            }
+        }
+
+        self.db.store_attestation(&attestation)?;
+        Ok(attestation)
+    }
+
+    /// Check that enough committee members attested the update
+    pub(crate) fn verify(&self, attestation: &UpdateAttestation) -> Result<()> {
+        Ok(attestation.verify(&self.committee.members, self.committee.threshold)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::signers::{LocalWallet, Signer};
+    use nomad_core::Update;
+    use nomad_test::test_utils;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_collects_signatures_until_the_threshold() {
+        test_utils::run_test_db(|db| async move {
+            let wallets: Vec<LocalWallet> = (1u8..=3)
+                .map(|i| hex::encode([i; 32]).parse().unwrap())
+                .collect();
+            let lead_db = NomadDB::new("lead", db.clone());
+            let peer_db = NomadDB::new("peer", db);
+
+            let (address, server) =
+                warp::serve(routes(peer_db.clone())).bind_ephemeral(([127, 0, 0, 1], 0));
+            tokio::spawn(server);
+
+            let committee = Committee {
+                threshold: 2,
+                members: wallets.iter().map(Signer::address).collect(),
+                peers: vec![
+                    format!("http://{}", address),
+                    // the third member is down
+                    "http://127.0.0.1:1".to_owned(),
+                ],
+                port: 0,
+                client: peer_client().unwrap(),
+            };
+            let collector = AttestationCollector::new(lead_db.clone(), Arc::new(committee));
+
+            let update = Update {
+                home_domain: 1000,
+                previous_root: H256::repeat_byte(1),
+                new_root: H256::repeat_byte(2),
+            };
+            let signed = update.sign_with(&wallets[0]).await.unwrap();
+
+            let attestation = collector.collect(&signed).await.unwrap();
+            assert_eq!(attestation.signatures.len(), 1);
+            assert!(collector.verify(&attestation).is_err());
+
+            let peer_signed = update.sign_with(&wallets[1]).await.unwrap();
+            peer_db
+                .store_produced_update(update.previous_root, &peer_signed)
+                .unwrap();
+
+            let attestation = collector.collect(&signed).await.unwrap();
+            collector.verify(&attestation).unwrap();
+            assert_eq!(
+                attestation.signers().unwrap(),
+                vec![wallets[0].address(), wallets[1].address()]
+            );
+            assert_eq!(
+                lead_db.retrieve_attestation(update.previous_root).unwrap(),
+                Some(attestation)
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn it_replaces_attestations_of_other_updates() {
+        test_utils::run_test_db(|db| async move {
+            let wallets: Vec<LocalWallet> = (1u8..=2)
+                .map(|i| hex::encode([i; 32]).parse().unwrap())
+                .collect();
+            let lead_db = NomadDB::new("lead", db.clone());
+            let peer_db = NomadDB::new("peer", db);
+
+            let (address, server) =
+                warp::serve(routes(peer_db.clone())).bind_ephemeral(([127, 0, 0, 1], 0));
+            tokio::spawn(server);
+
+            let committee = Committee {
+                threshold: 2,
+                members: wallets.iter().map(Signer::address).collect(),
+                peers: vec![format!("http://{}", address)],
+                port: 0,
+                client: peer_client().unwrap(),
+            };
+            let collector = AttestationCollector::new(lead_db.clone(), Arc::new(committee));
+
+            let stale = Update {
+                home_domain: 1000,
+                previous_root: H256::repeat_byte(1),
+                new_root: H256::repeat_byte(2),
+            };
+            let update = Update {
+                new_root: H256::repeat_byte(3),
+                ..stale
+            };
+
+            // an attestation of an update the lead signed before restarting
+            let mut attestation = UpdateAttestation::new(stale);
+            attestation
+                .add(&stale.sign_with(&wallets[0]).await.unwrap())
+                .unwrap();
+            lead_db.store_attestation(&attestation).unwrap();
+
+            // the peer serves a signature that recovers to no address
+            let mut invalid = update.sign_with(&wallets[1]).await.unwrap();
+            invalid.signature.v = 5;
+            peer_db
+                .store_produced_update(update.previous_root, &invalid)
+                .unwrap();
+
+            let signed = update.sign_with(&wallets[0]).await.unwrap();
+            let attestation = collector.collect(&signed).await.unwrap();
+            assert_eq!(attestation.update, update);
+            assert_eq!(attestation.signers().unwrap(), vec![wallets[0].address()]);
+        })
+        .await
+    }
+}
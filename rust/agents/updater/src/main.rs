@@ -2,11 +2,16 @@
 //!
 //! This updater polls the Home for queued updates at a regular interval.
 //! It signs them and submits them back to the home chain.
+//!
+//! Optionally, a committee of updaters each sign updates with their own key
+//! and exchange signatures over HTTP. The updater holding the home's updater
+//! key only submits an update once enough members signed it.
 
 #![forbid(unsafe_code)]
 #![warn(missing_docs)]
 #![warn(unused_extern_crates)]
 
+mod attest;
 mod produce;
 mod settings;
 mod submit;
@@ -32,6 +37,7 @@ async fn _main() -> Result<()> {
         .start_tracing(agent.metrics().span_duration())?;
 
     let _ = agent.metrics().run_http_server();
+    let _ = agent.run_api_server();
 
     agent.run("").await?
 }
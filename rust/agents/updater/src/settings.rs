@@ -1,9 +1,29 @@
 //! Configuration
+use ethers::types::Address;
 use nomad_base::*;
+use serde::Deserialize;
+
+/// Settings for attesting updates with a committee of updaters
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitteeConfig {
+    /// Number of committee members that must sign an update before it is
+    /// submitted
+    pub threshold: String,
+    /// Addresses of the committee members, this updater included
+    pub members: Vec<Address>,
+    /// Base URLs of the other members' attestation servers
+    pub peers: Vec<String>,
+    /// Port to serve this updater's attestations on
+    pub port: String,
+}
 
 decl_settings!(Updater {
     /// The updater attestation signer
     updater: nomad_base::SignerConf,
     /// The polling interval (in seconds)
     interval: String,
+    /// Committee settings. Updates are submitted as soon as they are signed
+    /// if omitted
+    committee: Option<CommitteeConfig>,
 });
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use nomad_base::{CachingHome, NomadDB};
+use nomad_base::{CachingHome, GasBudget, GasOperation, NomadDB};
 use nomad_core::Common;
 use prometheus::IntCounter;
 use std::time::Duration;
@@ -9,10 +9,14 @@ use color_eyre::Result;
 use tokio::{task::JoinHandle, time::sleep};
 use tracing::{info, info_span, instrument::Instrumented, Instrument};
 
+use crate::attest::AttestationCollector;
+
 pub(crate) struct UpdateSubmitter {
     home: Arc<CachingHome>,
     db: NomadDB,
     interval_seconds: u64,
+    budget: Arc<GasBudget>,
+    collector: Option<AttestationCollector>,
     submitted_update_count: IntCounter,
 }
 
@@ -21,12 +25,16 @@ impl UpdateSubmitter {
         home: Arc<CachingHome>,
         db: NomadDB,
         interval_seconds: u64,
+        budget: Arc<GasBudget>,
+        collector: Option<AttestationCollector>,
         submitted_update_count: IntCounter,
     ) -> Self {
         Self {
             home,
             db,
             interval_seconds,
+            budget,
+            collector,
             submitted_update_count,
         }
     }
@@ -44,6 +52,21 @@ impl UpdateSubmitter {
                 // if we have produced an update building off the committed root
                 // submit it
                 if let Some(signed) = self.db.retrieve_produced_update(committed_root)? {
+                    // in committee mode, hold the update back until enough
+                    // members signed it
+                    if let Some(collector) = &self.collector {
+                        let attestation = collector.collect(&signed).await?;
+                        if let Err(e) = collector.verify(&attestation) {
+                            info!(
+                                previous_root = ?signed.update.previous_root,
+                                new_root = ?signed.update.new_root,
+                                "Waiting for committee attestations: {}",
+                                e,
+                            );
+                            continue;
+                        }
+                    }
+
                     let hex_signature = format!("0x{}", hex::encode(signed.signature.to_vec()));
                     info!(
                         previous_root = ?signed.update.previous_root,
@@ -54,7 +77,9 @@ impl UpdateSubmitter {
 
                     // Submit update and let the home indexer pick up the
                     // update once it is confirmed state in the chain
-                    self.home.update(&signed).await?;
+                    let outcome = self.home.update(&signed).await?;
+                    self.budget
+                        .record(self.home.name(), GasOperation::Update, &outcome)?;
 
                     self.submitted_update_count.inc();
 
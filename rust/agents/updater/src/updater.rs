@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{convert::TryFrom, sync::Arc};
 
 use async_trait::async_trait;
 use color_eyre::{eyre::ensure, Result};
@@ -9,9 +9,12 @@ use tokio::task::JoinHandle;
 use tracing::{info, instrument::Instrumented, Instrument};
 
 use crate::{
-    produce::UpdateProducer, settings::UpdaterSettings as Settings, submit::UpdateSubmitter,
+    attest::{AttestationCollector, Committee},
+    produce::UpdateProducer,
+    settings::UpdaterSettings as Settings,
+    submit::UpdateSubmitter,
 };
-use nomad_base::{AgentCore, ContractSyncMetrics, IndexDataTypes, NomadAgent, NomadDB};
+use nomad_base::{AgentCore, ContractSyncMetrics, GasBudget, IndexDataTypes, NomadAgent, NomadDB};
 use nomad_core::{Common, Signers};
 
 /// An updater agent
@@ -19,6 +22,7 @@ use nomad_core::{Common, Signers};
 pub struct Updater {
     signer: Arc<Signers>,
     interval_seconds: u64,
+    committee: Option<Arc<Committee>>,
     pub(crate) core: AgentCore,
     signed_attestation_count: IntCounter,
     submitted_update_count: IntCounter,
@@ -31,8 +35,14 @@ impl AsRef<AgentCore> for Updater {
 }
 
 impl Updater {
-    /// Instantiate a new updater
-    pub fn new(signer: Signers, interval_seconds: u64, core: AgentCore) -> Self {
+    /// Instantiate a new updater. With a `committee`, updates are only
+    /// submitted once enough of its members signed them.
+    pub fn new(
+        signer: Signers,
+        interval_seconds: u64,
+        committee: Option<Committee>,
+        core: AgentCore,
+    ) -> Self {
         let home_name = core.home.name();
         let signed_attestation_count = core
             .metrics
@@ -57,6 +67,7 @@ impl Updater {
         Self {
             signer: Arc::new(signer),
             interval_seconds,
+            committee: committee.map(Arc::new),
             core,
             signed_attestation_count,
             submitted_update_count,
@@ -79,8 +90,21 @@ impl NomadAgent for Updater {
     {
         let signer = settings.updater.try_into_signer().await?;
         let interval_seconds = settings.interval.parse().expect("invalid uint");
+        let committee = settings
+            .committee
+            .as_ref()
+            .map(Committee::try_from)
+            .transpose()?;
+        if let Some(committee) = &committee {
+            committee.validate()?;
+            ensure!(
+                committee.contains(signer.address()),
+                "Updater {} is not a committee member",
+                signer.address()
+            );
+        }
         let core = settings.as_ref().try_into_core(Self::AGENT_NAME).await?;
-        Ok(Self::new(signer, interval_seconds, core))
+        Ok(Self::new(signer, interval_seconds, committee, core))
     }
 
     fn run(&self, _replica: &str) -> Instrumented<JoinHandle<Result<()>>> {
@@ -100,10 +124,15 @@ impl NomadAgent for Updater {
             self.signed_attestation_count.clone(),
         );
 
+        let committee = self.committee.clone();
         let submit = UpdateSubmitter::new(
             self.home(),
-            db,
+            db.clone(),
             self.interval_seconds,
+            Arc::new(GasBudget::new(Self::AGENT_NAME, self.as_ref())),
+            committee
+                .clone()
+                .map(|committee| AttestationCollector::new(db.clone(), committee)),
             self.submitted_update_count.clone(),
         );
 
@@ -113,13 +142,30 @@ impl NomadAgent for Updater {
         tokio::spawn(async move {
             fail_check.await??;
 
+            // Committee members without the updater key only sign and serve
+            // their attestations, the holder of the key submits
             let expected: Address = home.updater().await?.into();
+            let submits = expected == address;
             ensure!(
-                expected == address,
+                submits || committee.is_some(),
                 "Contract updater does not match keys. On-chain: {}. Local: {}",
                 expected,
                 address
             );
+            if let Some(committee) = &committee {
+                ensure!(
+                    committee.contains(expected),
+                    "Contract updater {} is not a committee member",
+                    expected
+                );
+            }
+            if !submits {
+                info!(
+                    on_chain = ?expected,
+                    local = ?address,
+                    "Signing as a committee member. Updates are submitted by the on-chain updater."
+                );
+            }
 
             info!("Spawning sync task for updater...");
             let sync_task = home.sync(
@@ -132,16 +178,15 @@ impl NomadAgent for Updater {
 
             // Only spawn updater tasks once syncing has finished
             info!("Spawning produce and submit tasks...");
-            let produce_task = produce.spawn();
-            let submit_task = submit.spawn();
-
-            let (res, _, rem) = select_all(vec![
-                sync_task,
-                produce_task,
-                submit_task,
-                home_fail_watch_task,
-            ])
-            .await;
+            let mut tasks = vec![sync_task, produce.spawn(), home_fail_watch_task];
+            if submits {
+                tasks.push(submit.spawn());
+            }
+            if let Some(committee) = committee {
+                tasks.push(committee.serve(db));
+            }
+
+            let (res, _, rem) = select_all(tasks).await;
 
             for task in rem.into_iter() {
                 task.into_inner().abort();
@@ -0,0 +1,252 @@
+//! Sinks notifying operators of fraud, independently of chain submissions
+
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use nomad_core::SignedUpdate;
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tracing::{error, info};
+
+use crate::{
+    evidence::DoubleUpdateEvidence,
+    settings::AlertConfig,
+    submissions::{Fraud, SubmissionRecord},
+};
+
+/// An event operators are alerted of
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum Alert {
+    /// A double update was detected. Sent before submitting anything to
+    /// chain.
+    DoubleUpdateDetected {
+        /// Evidence of the double update
+        evidence: DoubleUpdateEvidence,
+    },
+    /// Double update and unenrollment submissions settled
+    DoubleUpdateSubmitted {
+        /// Evidence of the double update, including submission outcomes
+        evidence: DoubleUpdateEvidence,
+    },
+    /// Some submissions in response to fraud failed and are being retried
+    SubmissionsPending {
+        /// The fraud submitted
+        fraud: Fraud,
+        /// Progress of all submissions, including the pending ones
+        submissions: Vec<SubmissionRecord>,
+    },
+    /// Some submissions in response to fraud were still not confirmed after
+    /// the maximum number of rounds and were given up on until restart
+    SubmissionsAbandoned {
+        /// The fraud submitted
+        fraud: Fraud,
+        /// Progress of all submissions, including the abandoned ones
+        submissions: Vec<SubmissionRecord>,
+    },
+    /// An update on the home is not attested by enough members of the
+    /// updater committee
+    UnattestedUpdate {
+        /// The update
+        update: SignedUpdate,
+        /// Why its attestation was rejected
+        reason: String,
+    },
+}
+
+/// A destination for alerts
+#[async_trait]
+pub trait AlertSink: std::fmt::Debug + Send + Sync {
+    /// Deliver an alert
+    async fn alert(&self, alert: &Alert) -> Result<()>;
+}
+
+impl AlertConfig {
+    /// Instantiate the configured sink
+    pub fn build(&self) -> Box<dyn AlertSink> {
+        match self {
+            AlertConfig::Webhook { url } => Box::new(WebhookSink::new(url)),
+            AlertConfig::Command { program, args } => {
+                Box::new(CommandSink::new(program, args.clone().unwrap_or_default()))
+            }
+            AlertConfig::File { path } => Box::new(FileSink::new(path)),
+        }
+    }
+}
+
+/// Deliver `alert` to every sink. Failures are logged, not returned, so that
+/// one broken sink doesn't prevent delivery to the others.
+pub async fn send_alerts(sinks: &[Box<dyn AlertSink>], alert: &Alert) {
+    for sink in sinks {
+        match sink.alert(alert).await {
+            Ok(()) => info!(sink = ?sink, "Delivered alert"),
+            Err(e) => error!(sink = ?sink, error = ?e, "Failed to deliver alert"),
+        }
+    }
+}
+
+/// POSTs alerts as JSON to a URL
+#[derive(Debug)]
+pub struct WebhookSink {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl WebhookSink {
+    /// Instantiate a sink POSTing to `url`
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl AlertSink for WebhookSink {
+    async fn alert(&self, alert: &Alert) -> Result<()> {
+        self.client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(alert)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Runs a command with the JSON alert on its stdin
+#[derive(Debug)]
+pub struct CommandSink {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandSink {
+    /// Instantiate a sink running `program` with `args`
+    pub fn new(program: &str, args: Vec<String>) -> Self {
+        Self {
+            program: program.to_owned(),
+            args,
+        }
+    }
+}
+
+#[async_trait]
+impl AlertSink for CommandSink {
+    async fn alert(&self, alert: &Alert) -> Result<()> {
+        let mut child = tokio::process::Command::new(&self.program)
+            .args(&self.args)
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+            .wrap_err_with(|| format!("unable to run {}", self.program))?;
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin.write_all(&serde_json::to_vec(alert)?).await?;
+        // Close stdin so the command sees EOF
+        drop(stdin);
+
+        let status = child.wait().await?;
+        if !status.success() {
+            bail!("{} exited with {}", self.program, status);
+        }
+        Ok(())
+    }
+}
+
+/// Appends alerts to a file, one JSON object per line
+#[derive(Debug)]
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    /// Instantiate a sink appending to `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl AlertSink for FileSink {
+    async fn alert(&self, alert: &Alert) -> Result<()> {
+        let mut line = serde_json::to_vec(alert)?;
+        line.push(b'\n');
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .wrap_err_with(|| format!("unable to open {}", self.path.display()))?;
+        file.write_all(&line).await?;
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn alert() -> Alert {
+        Alert::SubmissionsPending {
+            fraud: Fraud::FailedHome,
+            submissions: vec![],
+        }
+    }
+
+    /// A fresh file path for `name` in the temp dir
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("nomad-alerts-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[tokio::test]
+    async fn file_sink_appends_one_line_per_alert() {
+        let path = temp_path("file_sink.jsonl");
+        let sink = FileSink::new(&path);
+        sink.alert(&alert()).await.unwrap();
+        sink.alert(&alert()).await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "submissionsPending");
+        assert_eq!(lines[0]["fraud"]["type"], "failedHome");
+        assert_eq!(lines[0], lines[1]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn command_sink_writes_alert_to_stdin() {
+        let path = temp_path("command_sink.json");
+        let sink = CommandSink::new(
+            "sh",
+            vec!["-c".to_owned(), format!("cat > {}", path.display())],
+        );
+        sink.alert(&alert()).await.unwrap();
+
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written, serde_json::to_value(alert()).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        // Failing commands are reported
+        assert!(CommandSink::new("false", vec![])
+            .alert(&alert())
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,279 @@
+//! Checks that the home's updates were attested by the updater committee.
+//!
+//! In committee mode, the updater submitting to the home serves the
+//! attestation it collected for each update at
+//! `GET /attestations/<previous_root>/committee`. The watcher fetches it for
+//! every new update on the home and alerts operators if it is missing or not
+//! signed by enough committee members.
+
+use std::{convert::TryFrom, sync::Arc, time::Duration};
+
+use color_eyre::{
+    eyre::{bail, ensure, WrapErr},
+    Report, Result,
+};
+use ethers::core::types::{Address, H256};
+use nomad_base::CachingHome;
+use nomad_core::{Common, SignedUpdate, UpdateAttestation};
+use reqwest::StatusCode;
+use tokio::{sync::mpsc, task::JoinHandle, time::sleep};
+use tracing::{debug, error, info, info_span, instrument::Instrumented, warn, Instrument};
+
+use crate::{
+    alerts::{send_alerts, Alert, AlertSink},
+    settings::CommitteeConfig,
+};
+
+/// How long to wait for an attestation server to respond
+const SERVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Times the attestation servers are asked for an update's attestation
+/// before alerting that it is missing
+const FETCH_ATTEMPTS: u32 = 3;
+
+/// The updater committee and the servers its attestations are fetched from
+#[derive(Debug, Clone)]
+pub(crate) struct CommitteeVerifier {
+    threshold: usize,
+    members: Vec<Address>,
+    servers: Vec<String>,
+    client: reqwest::Client,
+}
+
+impl TryFrom<&CommitteeConfig> for CommitteeVerifier {
+    type Error = Report;
+
+    fn try_from(config: &CommitteeConfig) -> Result<Self> {
+        let threshold: usize = config
+            .threshold
+            .parse()
+            .wrap_err("Invalid committee threshold")?;
+        ensure!(
+            threshold > 0 && threshold <= config.members.len(),
+            "Committee threshold {} out of range for {} members",
+            threshold,
+            config.members.len()
+        );
+        Ok(Self {
+            threshold,
+            members: config.members.clone(),
+            servers: config
+                .servers
+                .iter()
+                .map(|server| server.trim_end_matches('/').to_owned())
+                .collect(),
+            client: reqwest::Client::builder().timeout(SERVER_TIMEOUT).build()?,
+        })
+    }
+}
+
+impl CommitteeVerifier {
+    /// Fetch the attestation `server` collected for the update building off
+    /// `previous_root`
+    async fn fetch(&self, server: &str, previous_root: H256) -> Result<Option<UpdateAttestation>> {
+        let response = self
+            .client
+            .get(format!(
+                "{}/attestations/{:?}/committee",
+                server, previous_root
+            ))
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body = response.error_for_status()?.bytes().await?;
+        Ok(Some(serde_json::from_slice(&body)?))
+    }
+
+    /// Check that `attestation` attests `signed` with enough committee
+    /// signatures
+    fn check(&self, attestation: &UpdateAttestation, signed: &SignedUpdate) -> Result<()> {
+        ensure!(
+            attestation.update == signed.update,
+            "Committee attested update {:?} instead of {:?}",
+            attestation.update,
+            signed.update
+        );
+        Ok(attestation.verify(&self.members, self.threshold)?)
+    }
+
+    /// Check that `signed` was attested by enough committee members. Passes
+    /// if any server serves a valid attestation.
+    pub(crate) async fn verify(&self, signed: &SignedUpdate) -> Result<()> {
+        let previous_root = signed.update.previous_root;
+        let mut rejection = None;
+        for server in self.servers.iter() {
+            match self.fetch(server, previous_root).await {
+                Ok(Some(attestation)) => match self.check(&attestation, signed) {
+                    Ok(()) => return Ok(()),
+                    Err(e) => {
+                        warn!(server = %server, error = %e, "Invalid committee attestation");
+                        rejection = Some(e);
+                    }
+                },
+                Ok(None) => {
+                    debug!(server = %server, previous_root = ?previous_root, "Server has no attestation")
+                }
+                Err(e) => {
+                    warn!(server = %server, error = %e, "Failed to fetch committee attestation")
+                }
+            }
+        }
+
+        match rejection {
+            Some(e) => Err(e),
+            None => bail!(
+                "No server serves an attestation of the update building off {:?}",
+                previous_root
+            ),
+        }
+    }
+}
+
+/// Verifies the committee attestation of each new update on the home,
+/// received through `rx`, and alerts operators of unattested ones
+#[derive(Debug)]
+pub(crate) struct AttestationChecker {
+    verifier: CommitteeVerifier,
+    rx: mpsc::UnboundedReceiver<SignedUpdate>,
+    home: Arc<CachingHome>,
+    from: H256,
+    alerts: Arc<Vec<Box<dyn AlertSink>>>,
+    interval: u64,
+}
+
+impl AttestationChecker {
+    pub(crate) fn new(
+        verifier: CommitteeVerifier,
+        rx: mpsc::UnboundedReceiver<SignedUpdate>,
+        home: Arc<CachingHome>,
+        from: H256,
+        alerts: Arc<Vec<Box<dyn AlertSink>>>,
+        interval: u64,
+    ) -> Self {
+        Self {
+            verifier,
+            rx,
+            home,
+            from,
+            alerts,
+            interval,
+        }
+    }
+
+    /// Verify `signed`, asking the servers again after an interval while
+    /// none serves a valid attestation
+    async fn verify(&self, signed: &SignedUpdate) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            match self.verifier.verify(signed).await {
+                Err(e) if attempt < FETCH_ATTEMPTS => {
+                    debug!(attempt, error = %e, "Committee attestation not verified yet");
+                    attempt += 1;
+                    sleep(Duration::from_secs(self.interval)).await;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Check the updates extending the home's chain from root `from`, the
+    /// one the home is watched from. Updates that don't extend it, e.g. older
+    /// ones synced from history or ones already checked, are skipped.
+    pub(crate) fn spawn(mut self) -> Instrumented<JoinHandle<Result<()>>> {
+        tokio::spawn(async move {
+            let mut next_root = self.from;
+            while let Some(signed) = self.rx.recv().await {
+                if signed.update.previous_root != next_root {
+                    continue;
+                }
+                next_root = signed.update.new_root;
+
+                match self.verify(&signed).await {
+                    Ok(()) => info!(
+                        previous_root = ?signed.update.previous_root,
+                        new_root = ?signed.update.new_root,
+                        "Verified committee attestation of update"
+                    ),
+                    Err(e) => {
+                        error!(
+                            update = ?signed,
+                            error = %e,
+                            "Update on home {} is not attested by the committee",
+                            self.home.name()
+                        );
+                        send_alerts(
+                            &self.alerts,
+                            &Alert::UnattestedUpdate {
+                                update: signed,
+                                reason: e.to_string(),
+                            },
+                        )
+                        .await;
+                    }
+                }
+            }
+            Ok(())
+        })
+        .instrument(info_span!("AttestationChecker"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::signers::{LocalWallet, Signer};
+    use nomad_core::Update;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_checks_committee_attestations() {
+        let wallets: Vec<LocalWallet> = (1u8..=3)
+            .map(|i| format!("{:064x}", i).parse().unwrap())
+            .collect();
+        let verifier = CommitteeVerifier::try_from(&CommitteeConfig {
+            threshold: "2".to_owned(),
+            members: wallets.iter().map(Signer::address).collect(),
+            // nothing listens there
+            servers: vec!["http://127.0.0.1:1/".to_owned()],
+        })
+        .unwrap();
+
+        let update = Update {
+            home_domain: 1000,
+            previous_root: H256::repeat_byte(1),
+            new_root: H256::repeat_byte(2),
+        };
+        let signed = update.sign_with(&wallets[0]).await.unwrap();
+
+        let mut attestation = UpdateAttestation::new(update);
+        attestation.add(&signed).unwrap();
+        assert!(verifier.check(&attestation, &signed).is_err());
+
+        attestation
+            .add(&update.sign_with(&wallets[2]).await.unwrap())
+            .unwrap();
+        verifier.check(&attestation, &signed).unwrap();
+
+        // an attestation of another update building off the same root
+        let other = Update {
+            new_root: H256::repeat_byte(3),
+            ..update
+        }
+        .sign_with(&wallets[0])
+        .await
+        .unwrap();
+        assert!(verifier.check(&attestation, &other).is_err());
+
+        // no server serves the attestation
+        assert!(verifier.verify(&signed).await.is_err());
+
+        assert!(CommitteeVerifier::try_from(&CommitteeConfig {
+            threshold: "4".to_owned(),
+            members: wallets.iter().map(Signer::address).collect(),
+            servers: vec![],
+        })
+        .is_err());
+    }
+}
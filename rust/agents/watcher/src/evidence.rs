@@ -0,0 +1,229 @@
+//! Durable record of fraud observed by the watcher
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ethers::core::types::Address;
+use serde::{Deserialize, Serialize};
+
+use nomad_base::NomadDB;
+use nomad_core::{db::DbError, Decode, DoubleUpdate, Encode, NomadError, SignedUpdate};
+
+use crate::submissions::{Fraud, SubmissionRecord};
+
+static DOUBLE_UPDATE_EVIDENCE: &str = "double_update_evidence_";
+
+/// A contract an update was observed on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObservedOn {
+    /// Name of the contract
+    pub contract: String,
+    /// Block the update was included in
+    pub block_number: u64,
+}
+
+/// A signed update along with where it was observed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateEvidence {
+    /// The signed update
+    pub signed_update: SignedUpdate,
+    /// The recovered signer, if the signature is valid
+    pub signer: Option<Address>,
+    /// Contracts whose indexed events include the update
+    pub observed_on: Vec<ObservedOn>,
+}
+
+impl UpdateEvidence {
+    /// Look up where `signed_update` was observed in the contracts' DBs
+    pub fn new(signed_update: SignedUpdate, contract_dbs: &[(String, NomadDB)]) -> Self {
+        let new_root = signed_update.update.new_root;
+        let observed_on = contract_dbs
+            .iter()
+            .filter_map(|(contract, db)| {
+                let meta = db.retrieve_update_metadata(new_root).ok()??;
+                // Metadata is keyed by new root only, make sure it belongs to
+                // this update and not the conflicting one
+                let stored = db.update_by_new_root(new_root).ok()??;
+                (stored == signed_update).then(|| ObservedOn {
+                    contract: contract.to_owned(),
+                    block_number: meta.block_number,
+                })
+            })
+            .collect();
+
+        Self {
+            signer: signed_update.recover().ok(),
+            signed_update,
+            observed_on,
+        }
+    }
+}
+
+/// Evidence bundle of a double update
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DoubleUpdateEvidence {
+    /// Unix timestamp (in seconds) of the detection
+    pub detected_at: u64,
+    /// The update seen first
+    pub first: UpdateEvidence,
+    /// The conflicting update
+    pub second: UpdateEvidence,
+    /// Outcomes of the double update and unenrollment submissions. Empty
+    /// until submissions settle, or if running in dry run mode.
+    pub submissions: Vec<SubmissionRecord>,
+    /// Whether the watcher ran in dry run mode and submitted nothing
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl DoubleUpdateEvidence {
+    /// Gather evidence of `double` from the contracts' DBs
+    pub fn new(double: &DoubleUpdate, contract_dbs: &[(String, NomadDB)]) -> Self {
+        Self {
+            detected_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            first: UpdateEvidence::new(double.0.clone(), contract_dbs),
+            second: UpdateEvidence::new(double.1.clone(), contract_dbs),
+            submissions: vec![],
+            dry_run: false,
+        }
+    }
+
+    /// The double update the evidence is of
+    pub fn fraud(&self) -> Fraud {
+        Fraud::DoubleUpdate {
+            first: self.first.signed_update.clone(),
+            second: self.second.signed_update.clone(),
+        }
+    }
+
+    /// Record the outcomes of submissions
+    pub fn record_submissions(&mut self, submissions: Vec<SubmissionRecord>) {
+        self.submissions = submissions;
+    }
+
+    /// Store (or overwrite) the evidence in the watcher DB
+    ///
+    /// Keys --> Values:
+    /// - `fraud_id` --> `double_update_evidence`
+    pub fn store(&self, db: &NomadDB) -> Result<(), DbError> {
+        db.store_keyed_encodable(DOUBLE_UPDATE_EVIDENCE, &self.fraud().id(), self)
+    }
+
+    /// Retrieve the evidence of the double update `fraud`
+    pub fn retrieve(db: &NomadDB, fraud: &Fraud) -> Result<Option<Self>, DbError> {
+        db.retrieve_keyed_decodable(DOUBLE_UPDATE_EVIDENCE, &fraud.id())
+    }
+}
+
+impl Encode for DoubleUpdateEvidence {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let buf = serde_json::to_vec(self)?;
+        writer.write_all(&buf)?;
+        Ok(buf.len())
+    }
+}
+
+impl Decode for DoubleUpdateEvidence {
+    fn read_from<R>(reader: &mut R) -> Result<Self, NomadError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        let mut buf = vec![];
+        reader.read_to_end(&mut buf)?;
+        Ok(serde_json::from_slice(&buf).map_err(std::io::Error::from)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::{
+        core::types::H256,
+        signers::{LocalWallet, Signer},
+    };
+    use nomad_core::{SignedUpdateWithMeta, TxOutcome, Update, UpdateMeta};
+
+    use crate::submissions::SubmissionAction;
+    use nomad_test::test_utils;
+
+    #[tokio::test]
+    async fn it_stores_double_update_evidence() {
+        test_utils::run_test_db(|db| async move {
+            let signer: LocalWallet =
+                "1111111111111111111111111111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap();
+            let sign = |new_root| {
+                Update {
+                    home_domain: 1,
+                    previous_root: H256::repeat_byte(1),
+                    new_root,
+                }
+                .sign_with(&signer)
+            };
+            let first = sign(H256::repeat_byte(2)).await.unwrap();
+            let second = sign(H256::repeat_byte(3)).await.unwrap();
+
+            let home_db = NomadDB::new("home_1", db.clone());
+            home_db
+                .store_updates_and_meta(&[SignedUpdateWithMeta {
+                    signed_update: first.clone(),
+                    metadata: UpdateMeta {
+                        block_number: 10,
+                        timestamp: None,
+                    },
+                }])
+                .unwrap();
+
+            let mut evidence = DoubleUpdateEvidence::new(
+                &DoubleUpdate(first.clone(), second),
+                &[("home_1".to_owned(), home_db)],
+            );
+            assert_eq!(evidence.first.signer, Some(signer.address()));
+            assert_eq!(
+                evidence.first.observed_on,
+                vec![ObservedOn {
+                    contract: "home_1".to_owned(),
+                    block_number: 10
+                }]
+            );
+            assert!(evidence.second.observed_on.is_empty());
+
+            let mut submission = SubmissionRecord::new("replica_1", SubmissionAction::DoubleUpdate);
+            submission.record_attempt(&Ok(TxOutcome {
+                txid: H256::repeat_byte(9),
+                executed: true,
+                ..Default::default()
+            }));
+            evidence.record_submissions(vec![submission]);
+
+            let watcher_db = NomadDB::new("home_1_watcher", db);
+            evidence.store(&watcher_db).unwrap();
+
+            // Another conflicting update on the same root doesn't overwrite
+            // the evidence
+            let third = sign(H256::repeat_byte(4)).await.unwrap();
+            let other = DoubleUpdateEvidence::new(&DoubleUpdate(first, third), &[]);
+            other.store(&watcher_db).unwrap();
+
+            assert_eq!(
+                DoubleUpdateEvidence::retrieve(&watcher_db, &evidence.fraud()).unwrap(),
+                Some(evidence)
+            );
+            assert_eq!(
+                DoubleUpdateEvidence::retrieve(&watcher_db, &other.fraud()).unwrap(),
+                Some(other)
+            );
+        })
+        .await
+    }
+}
@@ -0,0 +1,361 @@
+//! Detection of improper updates against a locally rebuilt home tree
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use color_eyre::{eyre::bail, Result};
+use ethers::core::types::H256;
+use tokio::{sync::mpsc, task::JoinHandle, time::sleep};
+use tracing::{debug, error, info};
+
+use nomad_base::CachingHome;
+use nomad_core::{accumulator::incremental::IncrementalMerkle, Common, Home, SignedUpdate};
+
+/// Number of polls in a row without new leaves after which an update that
+/// can't be checked yet is given up on
+const MAX_IDLE_POLLS: u32 = 60;
+
+/// Rebuilds the home's merkle tree from the leaves indexed in the home DB and
+/// checks that updates only ever commit to roots the tree actually had.
+#[derive(Debug)]
+pub struct ImproperUpdateChecker {
+    rx: mpsc::UnboundedReceiver<SignedUpdate>,
+    home: Arc<CachingHome>,
+    tree: IncrementalMerkle,
+    /// Roots the tree had, with the number of leaves under them
+    produced: HashMap<H256, usize>,
+    latest_root: Option<H256>,
+    generation: u64,
+    interval: u64,
+}
+
+impl ImproperUpdateChecker {
+    /// Instantiate a checker receiving updates over `rx`. The tree is rebuilt
+    /// from the home DB as updates come in.
+    pub fn new(
+        rx: mpsc::UnboundedReceiver<SignedUpdate>,
+        home: Arc<CachingHome>,
+        interval: u64,
+    ) -> Self {
+        Self {
+            rx,
+            home,
+            tree: Default::default(),
+            produced: Default::default(),
+            latest_root: None,
+            generation: 0,
+            interval,
+        }
+    }
+
+    /// True if the indexed home data went backwards since the tree was built:
+    /// a rollback happened, fewer leaves are indexed than ingested or the
+    /// latest indexed root is older than the previous one
+    fn went_backwards(&self, generation: u64, indexed: usize, latest_root: Option<H256>) -> bool {
+        let root_count = |root: Option<H256>| root.and_then(|root| self.produced.get(&root));
+        generation != self.generation
+            || indexed < self.tree.count()
+            || matches!(
+                (root_count(latest_root), root_count(self.latest_root)),
+                (Some(latest), Some(previous)) if latest < previous
+            )
+    }
+
+    /// Ingest the leaves indexed since the last call, recording the root
+    /// after each of them. The tree is rebuilt from scratch if the indexed
+    /// data went backwards in the meantime.
+    fn ingest_indexed_leaves(&mut self) -> Result<()> {
+        let db = self.home.db();
+        let generation = db.rollback_generation()?;
+        let indexed = db
+            .retrieve_latest_leaf_index()?
+            .map_or(0, |index| index as usize + 1);
+        let latest_root = db.retrieve_latest_root()?;
+
+        if self.went_backwards(generation, indexed, latest_root) {
+            if self.tree.count() > 0 {
+                info!(
+                    leaves = self.tree.count(),
+                    indexed, "Rebuilding local home tree after rollback"
+                );
+            }
+            self.tree = Default::default();
+            self.produced.clear();
+        }
+        self.generation = generation;
+        self.latest_root = latest_root;
+
+        while let Some(leaf) = db.leaf_by_leaf_index(self.tree.count() as u32)? {
+            self.tree.ingest(leaf);
+            self.produced.insert(self.tree.root(), self.tree.count());
+        }
+        if self.tree.count() == 0 && indexed > 0 {
+            bail!(
+                "Home messages are indexed from after leaf 0 (up to leaf {}). The home tree can't be rebuilt, index from the home's deployment block.",
+                indexed - 1
+            );
+        }
+        Ok(())
+    }
+
+    /// The home's latest root, whether or not it was committed yet
+    async fn home_latest_root(&self) -> Result<H256> {
+        Ok(match self.home.produce_update().await? {
+            Some(update) => update.new_root,
+            None => self.home.committed_root().await?,
+        })
+    }
+
+    /// Check whether `update` commits to a root the home tree never had.
+    ///
+    /// Message indexing may lag behind updates, so a root not produced
+    /// locally is only deemed improper once the local tree has caught up with
+    /// the home's latest root. Updates can't commit to roots the home doesn't
+    /// have yet, so by then any proper root has been produced. Errors if
+    /// indexing makes no progress for `MAX_IDLE_POLLS` polls in a row.
+    pub async fn is_improper(&mut self, update: &SignedUpdate) -> Result<bool> {
+        let new_root = update.update.new_root;
+        self.ingest_indexed_leaves()?;
+        if self.produced.contains_key(&new_root) {
+            return Ok(false);
+        }
+
+        let latest_root = self.home_latest_root().await?;
+        let mut idle_polls = 0;
+        loop {
+            if self.produced.contains_key(&new_root) {
+                return Ok(false);
+            }
+            // A zero root means nothing was ever dispatched on the home
+            if latest_root.is_zero() || self.produced.contains_key(&latest_root) {
+                return Ok(true);
+            }
+            if idle_polls >= MAX_IDLE_POLLS {
+                bail!(
+                    "Message indexing stalled at {} leaves before reaching the home's latest root {:?}",
+                    self.tree.count(),
+                    latest_root
+                );
+            }
+
+            debug!(
+                new_root = ?new_root,
+                latest_root = ?latest_root,
+                leaves = self.tree.count(),
+                "Waiting for message indexing to catch up with the home before checking update"
+            );
+            sleep(Duration::from_secs(self.interval)).await;
+
+            let leaves = self.tree.count();
+            self.ingest_indexed_leaves()?;
+            if self.tree.count() > leaves {
+                idle_polls = 0;
+            } else {
+                idle_polls += 1;
+            }
+        }
+    }
+
+    /// Receive updates and check them against the local tree. Return the
+    /// first improper update found. Updates that can't be checked are logged
+    /// as errors and skipped. This loop should never exit naturally unless
+    /// the channel for sending updates was closed, in which case we return an
+    /// error.
+    #[tracing::instrument]
+    pub fn spawn(mut self) -> JoinHandle<Result<SignedUpdate>> {
+        tokio::spawn(async move {
+            loop {
+                let update = match self.rx.recv().await {
+                    Some(update) => update,
+                    // channel is closed
+                    None => bail!("Channel closed."),
+                };
+
+                match self.is_improper(&update).await {
+                    Ok(true) => {
+                        error!(
+                            update = ?update,
+                            "ImproperUpdateChecker detected improper update! Root {} was never produced by home {}.",
+                            update.update.new_root,
+                            self.home.name(),
+                        );
+                        return Ok(update);
+                    }
+                    Ok(false) => {}
+                    Err(e) => error!(
+                        update = ?update,
+                        error = %e,
+                        "ImproperUpdateChecker could not check update"
+                    ),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::signers::LocalWallet;
+    use nomad_base::NomadDB;
+    use nomad_core::{Encode, NomadMessage, RawCommittedMessage, Update};
+    use nomad_test::{
+        mocks::{MockHomeContract, MockIndexer},
+        test_utils,
+    };
+
+    fn message(nonce: u32, body: Vec<u8>) -> RawCommittedMessage {
+        let mut message = vec![];
+        NomadMessage {
+            origin: 1,
+            destination: 2,
+            sender: H256::repeat_byte(10),
+            nonce,
+            recipient: H256::repeat_byte(11),
+            body,
+        }
+        .write_to(&mut message)
+        .expect("!write_to");
+        RawCommittedMessage {
+            leaf_index: nonce,
+            committed_root: H256::zero(),
+            message,
+        }
+    }
+
+    /// Root of the tree containing `messages`
+    fn root(messages: &[&RawCommittedMessage]) -> H256 {
+        let mut tree = IncrementalMerkle::default();
+        for message in messages {
+            tree.ingest(message.leaf());
+        }
+        tree.root()
+    }
+
+    /// A caching home whose latest root is `latest_root`
+    fn home(home_db: NomadDB, latest_root: H256) -> Arc<CachingHome> {
+        let mut mock_home = MockHomeContract::new();
+        mock_home.expect__name().return_const("home_1".to_owned());
+        mock_home.expect__produce_update().returning(|| Ok(None));
+        mock_home
+            .expect__committed_root()
+            .returning(move || Ok(latest_root));
+        CachingHome::new(
+            mock_home.into(),
+            home_db,
+            Arc::new(MockIndexer::new().into()),
+        )
+        .into()
+    }
+
+    async fn update(previous_root: H256, new_root: H256) -> SignedUpdate {
+        let signer: LocalWallet =
+            "1111111111111111111111111111111111111111111111111111111111111111"
+                .parse()
+                .unwrap();
+        Update {
+            home_domain: 1,
+            previous_root,
+            new_root,
+        }
+        .sign_with(&signer)
+        .await
+        .expect("!sign")
+    }
+
+    #[tokio::test]
+    async fn it_flags_roots_the_home_never_produced() {
+        test_utils::run_test_db(|db| async move {
+            let home_db = NomadDB::new("home_1", db);
+            let messages = [message(0, vec![]), message(1, vec![])];
+            for message in messages.iter() {
+                home_db.store_latest_message(message).unwrap();
+            }
+            let roots = [root(&[&messages[0]]), root(&[&messages[0], &messages[1]])];
+
+            let (_tx, rx) = mpsc::unbounded_channel();
+            let mut checker = ImproperUpdateChecker::new(rx, home(home_db, roots[1]), 1);
+
+            let proper = update(roots[0], roots[1]).await;
+            assert!(!checker.is_improper(&proper).await.unwrap());
+
+            let improper = update(roots[0], H256::repeat_byte(7)).await;
+            assert!(checker.is_improper(&improper).await.unwrap());
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn it_rebuilds_the_tree_after_a_rollback() {
+        test_utils::run_test_db(|db| async move {
+            let home_db = NomadDB::new("home_1", db);
+            let messages = [message(0, vec![]), message(1, vec![])];
+            for message in messages.iter() {
+                home_db.store_latest_message(message).unwrap();
+            }
+            let first_root = root(&[&messages[0]]);
+            let reorged_root = root(&[&messages[0], &messages[1]]);
+
+            // Leaf 1 gets reorged out and replaced by a different message
+            let replacement = message(1, vec![1]);
+            let latest_root = root(&[&messages[0], &replacement]);
+
+            let (_tx, rx) = mpsc::unbounded_channel();
+            let mut checker = ImproperUpdateChecker::new(rx, home(home_db.clone(), latest_root), 1);
+            assert!(!checker
+                .is_improper(&update(first_root, reorged_root).await)
+                .await
+                .unwrap());
+
+            home_db.rollback_messages(Some(0)).unwrap();
+            home_db.store_latest_message(&replacement).unwrap();
+
+            assert!(!checker
+                .is_improper(&update(first_root, latest_root).await)
+                .await
+                .unwrap());
+            assert!(checker
+                .is_improper(&update(first_root, reorged_root).await)
+                .await
+                .unwrap());
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn it_gives_up_when_indexing_stalls() {
+        test_utils::run_test_db(|db| async move {
+            let home_db = NomadDB::new("home_1", db);
+            let messages = [message(0, vec![]), message(1, vec![])];
+            home_db.store_latest_message(&messages[0]).unwrap();
+            let first_root = root(&[&messages[0]]);
+            let latest_root = root(&[&messages[0], &messages[1]]);
+
+            // Leaf 1 never gets indexed
+            let (_tx, rx) = mpsc::unbounded_channel();
+            let mut checker = ImproperUpdateChecker::new(rx, home(home_db, latest_root), 0);
+            assert!(checker
+                .is_improper(&update(first_root, H256::repeat_byte(7)).await)
+                .await
+                .is_err());
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn it_errors_when_indexing_starts_after_leaf_0() {
+        test_utils::run_test_db(|db| async move {
+            let home_db = NomadDB::new("home_1", db);
+            let messages = [message(0, vec![]), message(1, vec![])];
+            home_db.store_latest_message(&messages[1]).unwrap();
+            let latest_root = root(&[&messages[0], &messages[1]]);
+
+            let (_tx, rx) = mpsc::unbounded_channel();
+            let mut checker = ImproperUpdateChecker::new(rx, home(home_db, latest_root), 0);
+            assert!(checker
+                .is_improper(&update(H256::zero(), latest_root).await)
+                .await
+                .is_err());
+        })
+        .await
+    }
+}
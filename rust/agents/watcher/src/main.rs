@@ -4,17 +4,26 @@
 //! updates and checks them against its local DB of updates for fraud. It
 //! checks for double updates on both the Home and Replicas and fraudulent
 //! updates on just the Replicas by verifying Replica updates on the Home.
+//! Improper updates are detected by checking every updated root against the
+//! Home's merkle tree, rebuilt locally from indexed messages. With an updater
+//! committee configured, the committee attestation of every new update on the
+//! Home is verified too.
 
 #![forbid(unsafe_code)]
 #![warn(missing_docs)]
 #![warn(unused_extern_crates)]
 
+mod alerts;
+mod attestations;
+mod evidence;
+mod improper;
 mod settings;
+mod submissions;
 mod watcher;
 
 use color_eyre::Result;
 
-use nomad_base::NomadAgent;
+use nomad_base::run_networks;
 
 use crate::{settings::WatcherSettings as Settings, watcher::Watcher};
 
@@ -22,17 +31,8 @@ async fn _main() -> Result<()> {
     color_eyre::install()?;
     let settings = Settings::new()?;
 
-    let agent = Watcher::from_settings(settings).await?;
-
-    agent
-        .as_ref()
-        .settings
-        .tracing
-        .start_tracing(agent.metrics().span_duration())?;
-    let _ = agent.metrics().run_http_server();
-
-    agent.run_all().await??;
-    Ok(())
+    // One agent per configured network, or just one without `networks`
+    run_networks::<Watcher>(settings.split_networks()).await
 }
 
 fn main() -> Result<()> {
@@ -1,8 +1,45 @@
 //! Configuration
 
-use nomad_base::{decl_settings, ChainSetup, SignerConf};
+use ethers::types::Address;
+use nomad_base::{decl_settings, ChainSetup, RetryConfig, SignerConf};
+use serde::Deserialize;
 use std::collections::HashMap;
 
+/// Destination to alert operators at when fraud is detected
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum AlertConfig {
+    /// POST the alert as JSON to a URL
+    Webhook {
+        /// URL to POST to
+        url: String,
+    },
+    /// Run a command with the JSON alert on its stdin
+    Command {
+        /// Program to run
+        program: String,
+        /// Arguments to pass to the program
+        args: Option<Vec<String>>,
+    },
+    /// Append the alert as a JSON line to a file
+    File {
+        /// Path of the file
+        path: String,
+    },
+}
+
+/// An updater committee whose attestations of the home's updates are checked
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitteeConfig {
+    /// Number of committee members that must attest each update
+    pub threshold: String,
+    /// Addresses of the committee members
+    pub members: Vec<Address>,
+    /// Base URLs of the members' attestation servers
+    pub servers: Vec<String>,
+}
+
 decl_settings!(Watcher {
     /// The watcher's attestation signer
     watcher: SignerConf,
@@ -10,4 +47,16 @@ decl_settings!(Watcher {
     managers: HashMap<String, ChainSetup>,
     /// The polling interval (in seconds)
     interval: String,
+    /// Sinks to alert when fraud is detected
+    alerts: Option<Vec<AlertConfig>>,
+    /// Backoff between retries of fraud submissions
+    retry: Option<RetryConfig>,
+    /// Updater committee whose attestations of the home's updates are
+    /// checked. Not checked if omitted
+    committee: Option<CommitteeConfig>,
+    /// Detect fraud and collect evidence, but only log the transactions that
+    /// would have been sent. The watcher keeps running after fraud, serving
+    /// the `would_act` metric
+    #[serde(default)]
+    dry_run: bool,
 });
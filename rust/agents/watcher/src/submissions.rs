@@ -0,0 +1,280 @@
+//! Durable progress of the transactions submitted in response to fraud
+
+use ethers::{core::types::H256, utils::keccak256};
+use serde::{Deserialize, Serialize};
+
+use nomad_base::{GasOperation, NomadDB};
+use nomad_core::{db::DbError, Decode, DoubleUpdate, Encode, NomadError, SignedUpdate, TxOutcome};
+
+static PENDING_SUBMISSIONS: &str = "pending_submissions_";
+
+/// Fraud the watcher responds to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Fraud {
+    /// Two conflicting updates building on the same root
+    DoubleUpdate {
+        /// The update seen first
+        first: SignedUpdate,
+        /// The conflicting update
+        second: SignedUpdate,
+    },
+    /// An update to a root the home never produced
+    ImproperUpdate {
+        /// The improper update
+        update: SignedUpdate,
+    },
+    /// The home was failed, by the watcher or somebody else
+    FailedHome,
+}
+
+impl Fraud {
+    /// Identifier of the fraud, under which its submissions are stored
+    pub fn id(&self) -> H256 {
+        keccak256(serde_json::to_vec(self).expect("!serialize")).into()
+    }
+}
+
+impl From<&DoubleUpdate> for Fraud {
+    fn from(double: &DoubleUpdate) -> Self {
+        Fraud::DoubleUpdate {
+            first: double.0.clone(),
+            second: double.1.clone(),
+        }
+    }
+}
+
+/// Transaction submitted in response to fraud
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SubmissionAction {
+    /// Submit a double update to the home or a replica
+    DoubleUpdate,
+    /// Submit an improper update to the home
+    ImproperUpdate,
+    /// Unenroll the replica of the failed home from a connection manager
+    UnenrollReplica,
+}
+
+impl SubmissionAction {
+    /// Name of the action in logs and metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubmissionAction::DoubleUpdate => "double_update",
+            SubmissionAction::ImproperUpdate => "improper_update",
+            SubmissionAction::UnenrollReplica => "unenroll_replica",
+        }
+    }
+
+    /// What the submission's gas is accounted under
+    pub fn gas_operation(&self) -> GasOperation {
+        match self {
+            SubmissionAction::DoubleUpdate => GasOperation::DoubleUpdate,
+            SubmissionAction::ImproperUpdate => GasOperation::ImproperUpdate,
+            SubmissionAction::UnenrollReplica => GasOperation::Unenroll,
+        }
+    }
+}
+
+/// Where a submission stands
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SubmissionStatus {
+    /// Not confirmed yet, will be retried
+    Pending,
+    /// Included and executed successfully
+    Confirmed,
+    /// No longer needed, e.g. the contract is already failed or the replica
+    /// already unenrolled
+    Moot,
+}
+
+/// Progress of a transaction submitted in response to fraud
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmissionRecord {
+    /// Contract the transaction is submitted to
+    pub target: String,
+    /// Transaction submitted
+    pub action: SubmissionAction,
+    /// Where the submission stands
+    pub status: SubmissionStatus,
+    /// Number of submission attempts so far
+    pub attempts: u32,
+    /// Transaction hash of the last attempt, if the transaction was included
+    pub txid: Option<H256>,
+    /// Whether the last attempt executed successfully
+    pub executed: bool,
+    /// Error returned by the last attempt, if any
+    pub error: Option<String>,
+}
+
+impl SubmissionRecord {
+    /// A submission of `action` to `target`, not attempted yet
+    pub fn new(target: &str, action: SubmissionAction) -> Self {
+        Self {
+            target: target.to_owned(),
+            action,
+            status: SubmissionStatus::Pending,
+            attempts: 0,
+            txid: None,
+            executed: false,
+            error: None,
+        }
+    }
+
+    /// Whether the submission still needs to be attempted
+    pub fn is_pending(&self) -> bool {
+        self.status == SubmissionStatus::Pending
+    }
+
+    /// Record the result of an attempt. Confirms the submission if the
+    /// transaction executed successfully.
+    pub fn record_attempt<E: std::fmt::Display>(&mut self, result: &Result<TxOutcome, E>) {
+        self.attempts += 1;
+        match result {
+            Ok(outcome) => {
+                self.txid = Some(outcome.txid);
+                self.executed = outcome.executed;
+                self.error = None;
+                if outcome.executed {
+                    self.status = SubmissionStatus::Confirmed;
+                }
+            }
+            Err(e) => {
+                self.txid = None;
+                self.executed = false;
+                self.error = Some(e.to_string());
+            }
+        }
+    }
+}
+
+/// Submissions in response to a fraud. Stored in the watcher DB, keyed by
+/// fraud, after every round of attempts so a restarted watcher resumes where
+/// it left off.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingSubmissions {
+    /// The fraud submitted
+    pub fraud: Fraud,
+    /// One record per contract submitted to
+    pub submissions: Vec<SubmissionRecord>,
+}
+
+impl PendingSubmissions {
+    /// Submissions in response to `fraud`, none attempted yet
+    pub fn new(fraud: Fraud, submissions: Vec<SubmissionRecord>) -> Self {
+        Self { fraud, submissions }
+    }
+
+    /// True if every submission is confirmed or moot
+    pub fn is_settled(&self) -> bool {
+        !self.submissions.iter().any(SubmissionRecord::is_pending)
+    }
+
+    /// Targets of the submissions still pending
+    pub fn pending_targets(&self) -> Vec<&str> {
+        self.submissions
+            .iter()
+            .filter(|record| record.is_pending())
+            .map(|record| record.target.as_str())
+            .collect()
+    }
+
+    /// Store (or overwrite) the submissions in the watcher DB
+    ///
+    /// Keys --> Values:
+    /// - `fraud_id` --> `pending_submissions`
+    pub fn store(&self, db: &NomadDB) -> Result<(), DbError> {
+        db.store_keyed_encodable(PENDING_SUBMISSIONS, &self.fraud.id(), self)
+    }
+
+    /// Retrieve the stored submissions in response to `fraud`
+    pub fn retrieve(db: &NomadDB, fraud: &Fraud) -> Result<Option<Self>, DbError> {
+        db.retrieve_keyed_decodable(PENDING_SUBMISSIONS, &fraud.id())
+    }
+
+    /// Iterate over the stored submissions of every fraud
+    pub fn all(db: &NomadDB) -> impl Iterator<Item = Self> + '_ {
+        db.prefix_values(PENDING_SUBMISSIONS)
+    }
+}
+
+impl Encode for PendingSubmissions {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let buf = serde_json::to_vec(self)?;
+        writer.write_all(&buf)?;
+        Ok(buf.len())
+    }
+}
+
+impl Decode for PendingSubmissions {
+    fn read_from<R>(reader: &mut R) -> Result<Self, NomadError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        let mut buf = vec![];
+        reader.read_to_end(&mut buf)?;
+        Ok(serde_json::from_slice(&buf).map_err(std::io::Error::from)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::signers::LocalWallet;
+    use nomad_core::Update;
+    use nomad_test::test_utils;
+
+    #[tokio::test]
+    async fn it_stores_submissions_per_fraud() {
+        test_utils::run_test_db(|db| async move {
+            let db = NomadDB::new("home_1_watcher", db);
+            let signer: LocalWallet =
+                "1111111111111111111111111111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap();
+            let update = Update {
+                home_domain: 1,
+                previous_root: H256::repeat_byte(1),
+                new_root: H256::repeat_byte(2),
+            }
+            .sign_with(&signer)
+            .await
+            .expect("!sign");
+
+            let improper = PendingSubmissions::new(
+                Fraud::ImproperUpdate { update },
+                vec![SubmissionRecord::new(
+                    "home_1",
+                    SubmissionAction::ImproperUpdate,
+                )],
+            );
+            let failed = PendingSubmissions::new(
+                Fraud::FailedHome,
+                vec![SubmissionRecord::new(
+                    "connection_manager_2",
+                    SubmissionAction::UnenrollReplica,
+                )],
+            );
+            improper.store(&db).unwrap();
+            failed.store(&db).unwrap();
+
+            assert_eq!(
+                PendingSubmissions::retrieve(&db, &improper.fraud).unwrap(),
+                Some(improper.clone())
+            );
+            assert_eq!(
+                PendingSubmissions::retrieve(&db, &failed.fraud).unwrap(),
+                Some(failed.clone())
+            );
+            assert_eq!(PendingSubmissions::all(&db).count(), 2);
+        })
+        .await
+    }
+}
@@ -3,7 +3,8 @@ use color_eyre::{eyre::bail, Report, Result};
 use thiserror::Error;
 
 use ethers::core::types::H256;
-use futures_util::future::{join, join_all, select_all};
+use futures_util::future::{join_all, select_all};
+use prometheus::IntGaugeVec;
 use std::{collections::HashMap, fmt::Display, sync::Arc, time::Duration};
 use tokio::{
     select,
@@ -11,21 +12,38 @@ use tokio::{
     task::JoinHandle,
     time::sleep,
 };
-use tracing::{error, info, info_span, instrument::Instrumented, Instrument};
+use tracing::{error, info, info_span, instrument::Instrumented, warn, Instrument};
 
 use nomad_base::{
-    cancel_task, AgentCore, BaseError, CachingHome, ConnectionManagers, ContractSyncMetrics,
-    IndexDataTypes, NomadAgent, NomadDB,
+    cancel_task, AgentCore, Backoff, BaseError, CachingHome, CachingReplica, ConnectionManagers,
+    ContractSyncMetrics, GasBudget, GasOperation, IndexDataTypes, NomadAgent, NomadDB,
 };
 use nomad_core::{
-    ChainCommunicationError, Common, CommonEvents, ConnectionManager, DoubleUpdate,
-    FailureNotification, Home, SignedFailureNotification, SignedUpdate, Signers, TxOutcome,
+    Common, CommonEvents, ConnectionManager, DoubleUpdate, FailureNotification, Home,
+    SignedFailureNotification, SignedUpdate, Signers, State, TxOutcome,
 };
 
-use crate::settings::WatcherSettings as Settings;
+use crate::{
+    alerts::{send_alerts, Alert, AlertSink},
+    attestations::{AttestationChecker, CommitteeVerifier},
+    evidence::DoubleUpdateEvidence,
+    improper::ImproperUpdateChecker,
+    settings::WatcherSettings as Settings,
+    submissions::{
+        Fraud, PendingSubmissions, SubmissionAction, SubmissionRecord, SubmissionStatus,
+    },
+};
 
 const AGENT_NAME: &str = "watcher";
 
+/// Rounds of fraud submission attempts after which unconfirmed submissions
+/// are given up on. They stay stored and resume on the next restart.
+const MAX_SUBMISSION_ROUNDS: u32 = 20;
+
+/// Backoff between rounds of fraud submission attempts, unless configured
+/// otherwise
+const DEFAULT_BACKOFF: Backoff = Backoff::new(10, 300);
+
 #[derive(Debug, Error)]
 enum WatcherError {
     #[error("Syncing finished")]
@@ -194,20 +212,32 @@ where
 #[derive(Debug)]
 pub struct UpdateHandler {
     rx: mpsc::Receiver<SignedUpdate>,
+    checker_tx: mpsc::UnboundedSender<SignedUpdate>,
+    attestation_tx: Option<mpsc::UnboundedSender<SignedUpdate>>,
     watcher_db: NomadDB,
     home: Arc<CachingHome>,
+    budget: Arc<GasBudget>,
+    dry_run: bool,
 }
 
 impl UpdateHandler {
     pub fn new(
         rx: mpsc::Receiver<SignedUpdate>,
+        checker_tx: mpsc::UnboundedSender<SignedUpdate>,
+        attestation_tx: Option<mpsc::UnboundedSender<SignedUpdate>>,
         watcher_db: NomadDB,
         home: Arc<CachingHome>,
+        budget: Arc<GasBudget>,
+        dry_run: bool,
     ) -> Self {
         Self {
             rx,
+            checker_tx,
+            attestation_tx,
             watcher_db,
             home,
+            budget,
+            dry_run,
         }
     }
 
@@ -259,14 +289,99 @@ impl UpdateHandler {
                 let old_root = update.update.previous_root;
 
                 if old_root == self.home.committed_root().await? {
-                    // It is okay if tx reverts
-                    let _ = self.home.update(&update).await;
+                    if self.dry_run {
+                        info!(
+                            update = ?update,
+                            "Dry run: not relaying update to home {}",
+                            self.home.name()
+                        );
+                    } else {
+                        // It is okay if tx reverts
+                        if let Ok(outcome) = self.home.update(&update).await {
+                            if let Err(e) =
+                                self.budget
+                                    .record(self.home.name(), GasOperation::Update, &outcome)
+                            {
+                                warn!(error = %e, "Could not account update cost");
+                            }
+                        }
+                    }
                 }
 
                 if let Err(double_update) = self.check_double_update(&update) {
                     return Ok(double_update);
                 }
+
+                // Hand the update over for attestation and improper update
+                // checks. If a checker stopped, the watcher is shutting down
+                // anyway.
+                if let Some(attestation_tx) = &self.attestation_tx {
+                    let _ = attestation_tx.send(update.clone());
+                }
+                let _ = self.checker_tx.send(update);
+            }
+        })
+    }
+}
+
+/// A contract fraud is submitted to: the home, a replica or a connection
+/// manager
+enum SubmissionTarget<'a> {
+    Home(&'a Arc<CachingHome>),
+    Replica(&'a Arc<CachingReplica>),
+    ConnectionManager(&'a Arc<ConnectionManagers>),
+}
+
+impl SubmissionTarget<'_> {
+    /// True if submitting `fraud` to the contract is no longer needed
+    async fn is_moot(
+        &self,
+        fraud: &Fraud,
+        signed_failure: &SignedFailureNotification,
+    ) -> Result<bool> {
+        Ok(match self {
+            SubmissionTarget::Home(home) => {
+                home.state().await? == State::Failed
+                    || match fraud {
+                        Fraud::ImproperUpdate { update } => {
+                            update.update.previous_root != home.committed_root().await?
+                        }
+                        _ => false,
+                    }
+            }
+            SubmissionTarget::Replica(replica) => replica.state().await? == State::Failed,
+            SubmissionTarget::ConnectionManager(connection_manager) => {
+                let replica = connection_manager
+                    .domain_to_replica(signed_failure.notification.home_domain)
+                    .await?;
+                H256::from(replica).is_zero()
+            }
+        })
+    }
+
+    /// Submit `fraud` to the contract
+    async fn submit(
+        &self,
+        fraud: &Fraud,
+        signed_failure: &SignedFailureNotification,
+    ) -> Result<TxOutcome> {
+        Ok(match (self, fraud) {
+            (SubmissionTarget::Home(home), Fraud::DoubleUpdate { first, second }) => {
+                home.double_update(&DoubleUpdate(first.clone(), second.clone()))
+                    .await?
+            }
+            (SubmissionTarget::Home(home), Fraud::ImproperUpdate { update }) => {
+                home.improper_update(update).await?
+            }
+            (SubmissionTarget::Replica(replica), Fraud::DoubleUpdate { first, second }) => {
+                replica
+                    .double_update(&DoubleUpdate(first.clone(), second.clone()))
+                    .await?
+            }
+            (SubmissionTarget::ConnectionManager(connection_manager), _) => {
+                connection_manager.unenroll_replica(signed_failure).await?
             }
+            _ => bail!("Nothing to submit for this fraud"),
         })
     }
 }
@@ -280,6 +395,13 @@ pub struct Watcher {
     sync_tasks: TaskMap,
     watch_tasks: TaskMap,
     connection_managers: Vec<Arc<ConnectionManagers>>,
+    connection_manager_chains: HashMap<u32, String>,
+    alerts: Arc<Vec<Box<dyn AlertSink>>>,
+    committee: Option<CommitteeVerifier>,
+    dry_run: bool,
+    backoff: Backoff,
+    budget: Arc<GasBudget>,
+    would_act: IntGaugeVec,
     core: AgentCore,
 }
 
@@ -296,34 +418,137 @@ impl Watcher {
         signer: Signers,
         interval_seconds: u64,
         connection_managers: Vec<Arc<ConnectionManagers>>,
+        alerts: Vec<Box<dyn AlertSink>>,
+        dry_run: bool,
+        backoff: Backoff,
         core: AgentCore,
     ) -> Self {
+        let would_act = core
+            .metrics
+            .new_int_gauge(
+                "would_act",
+                "Whether the watcher would have acted on a contract in dry run mode",
+                &["contract", "action", "agent"],
+            )
+            .expect("watcher metric already registered -- should have been a singleton");
+
         Self {
             signer: Arc::new(signer),
             interval_seconds,
             sync_tasks: Default::default(),
             watch_tasks: Default::default(),
             connection_managers,
+            connection_manager_chains: Default::default(),
+            alerts: Arc::new(alerts),
+            committee: None,
+            dry_run,
+            backoff,
+            budget: Arc::new(GasBudget::new(AGENT_NAME, &core)),
+            would_act,
             core,
         }
     }
 
+    /// Verify the committee attestation of every new update on the home
+    pub(crate) fn with_committee(mut self, committee: Option<CommitteeVerifier>) -> Self {
+        self.committee = committee;
+        self
+    }
+
+    /// Account the gas spent on connection managers under the name of their
+    /// chain, by domain
+    pub(crate) fn with_connection_manager_chains(mut self, chains: HashMap<u32, String>) -> Self {
+        self.connection_manager_chains = chains;
+        self
+    }
+
+    /// In dry run mode, flag that the watcher would act on `contract` and log
+    /// the transaction instead of sending it
+    fn flag_would_act(&self, contract: &str, action: &str) {
+        if self.dry_run {
+            self.would_act
+                .with_label_values(&[contract, action, AGENT_NAME])
+                .set(1);
+            info!(
+                contract,
+                action, "Dry run: not submitting {} to {}", action, contract
+            );
+        }
+    }
+
+    /// Name identifying a connection manager in logs and metrics
+    fn connection_manager_name(connection_manager: &ConnectionManagers) -> String {
+        format!("connection_manager_{}", connection_manager.local_domain())
+    }
+
+    /// Name of the chain the gas spent on `target`, named `name`, is
+    /// accounted under
+    fn gas_chain(&self, target: &SubmissionTarget<'_>, name: &str) -> String {
+        match target {
+            SubmissionTarget::ConnectionManager(connection_manager) => self
+                .connection_manager_chains
+                .get(&connection_manager.local_domain())
+                .cloned()
+                .unwrap_or_else(|| name.to_owned()),
+            _ => name.to_owned(),
+        }
+    }
+
+    /// DB handle for the watcher's own records
+    fn watcher_db(&self) -> NomadDB {
+        NomadDB::new(format!("{}_{}", self.home().name(), AGENT_NAME), self.db())
+    }
+
+    /// DB handles of the home and replicas, by contract name
+    fn contract_dbs(&self) -> Vec<(String, NomadDB)> {
+        std::iter::once((self.home().name().to_owned(), self.home().db()))
+            .chain(
+                self.replicas()
+                    .iter()
+                    .map(|(name, replica)| (name.to_owned(), replica.db())),
+            )
+            .collect()
+    }
+
     /// Spawn UpdateHandler and sync tasks. Have sync tasks send UpdateHandler
-    /// signed updates through mpsc. Return Some(double_update) if any
-    /// conflicting updates are found.
-    fn watch_double_update(&self) -> Instrumented<JoinHandle<Result<Option<DoubleUpdate>>>> {
+    /// signed updates through mpsc. Updates without conflicts are passed on
+    /// through `checker_tx`. Return Some(double_update) if any conflicting
+    /// updates are found.
+    fn watch_double_update(
+        &self,
+        checker_tx: mpsc::UnboundedSender<SignedUpdate>,
+    ) -> Instrumented<JoinHandle<Result<Option<DoubleUpdate>>>> {
         let home = self.home();
         let replicas = self.replicas().clone();
-        let watcher_db_name = format!("{}_{}", home.name(), AGENT_NAME);
-        let watcher_db = NomadDB::new(watcher_db_name, self.db());
+        let watcher_db = self.watcher_db();
+        let dry_run = self.dry_run;
+        let budget = self.budget.clone();
         let interval_seconds = self.interval_seconds;
         let sync_tasks = self.sync_tasks.clone();
         let watch_tasks = self.watch_tasks.clone();
+        let committee = self.committee.clone();
+        let alerts = self.alerts.clone();
 
         tokio::spawn(async move {
             // Spawn update handler
             let (tx, rx) = mpsc::channel(200);
-            let handler = UpdateHandler::new(rx, watcher_db, home.clone()).spawn();
+            let (attestation_tx, attestation_rx) = match committee {
+                Some(committee) => {
+                    let (attestation_tx, attestation_rx) = mpsc::unbounded_channel();
+                    (Some(attestation_tx), Some((committee, attestation_rx)))
+                }
+                None => (None, None),
+            };
+            let handler = UpdateHandler::new(
+                rx,
+                checker_tx,
+                attestation_tx,
+                watcher_db,
+                home.clone(),
+                budget,
+                dry_run,
+            )
+            .spawn();
 
             // For each replica, spawn polling and history syncing tasks
             info!("Spawning replica watch and sync tasks...");
@@ -352,10 +577,23 @@ impl Watcher {
                 ContractWatcher::new(interval_seconds, from, tx.clone(), home.clone())
                     .spawn()
                     .in_current_span();
-            let home_sync = HistorySync::new(interval_seconds, from, tx.clone(), home)
+            let home_sync = HistorySync::new(interval_seconds, from, tx.clone(), home.clone())
                 .spawn()
                 .in_current_span();
 
+            // Check the committee attested the home's updates from there on
+            let attestation_checker = attestation_rx.map(|(committee, attestation_rx)| {
+                AttestationChecker::new(
+                    committee,
+                    attestation_rx,
+                    home,
+                    from,
+                    alerts,
+                    interval_seconds,
+                )
+                .spawn()
+            });
+
             // Wait for update handler to finish (should only happen watcher is
             // manually shut down)
             let double_update_res = handler.await?;
@@ -364,6 +602,9 @@ impl Watcher {
             tracing::info!("Update handler has resolved. Cancelling all other tasks");
             cancel_task!(home_watcher);
             cancel_task!(home_sync);
+            if let Some(attestation_checker) = attestation_checker {
+                cancel_task!(attestation_checker);
+            }
 
             // Map Result<DoubleUpdate> into Option. If handler returned error
             // no double update. If handler returned Ok(double_update), map into
@@ -373,6 +614,18 @@ impl Watcher {
         .in_current_span()
     }
 
+    /// Spawn ImproperUpdateChecker, checking updates received through
+    /// `checker_rx` against the home tree rebuilt from indexed messages.
+    /// Resolves with the first improper update found.
+    fn watch_improper_update(
+        &self,
+        checker_rx: mpsc::UnboundedReceiver<SignedUpdate>,
+    ) -> Instrumented<JoinHandle<Result<SignedUpdate>>> {
+        ImproperUpdateChecker::new(checker_rx, self.home(), self.interval_seconds)
+            .spawn()
+            .in_current_span()
+    }
+
     async fn create_signed_failure(&self) -> SignedFailureNotification {
         FailureNotification {
             home_domain: self.home().local_domain(),
@@ -383,55 +636,297 @@ impl Watcher {
         .expect("!sign")
     }
 
+    /// Record evidence of a double update, alert operators and submit it to
+    /// all contracts. Evidence is stored and alerts are sent both before and
+    /// after submitting, so that operators hear of the double update even if
+    /// submissions fail or hang.
+    #[tracing::instrument]
+    async fn handle_double_update(&self, double: &DoubleUpdate) -> Result<()> {
+        let watcher_db = self.watcher_db();
+        let mut evidence = DoubleUpdateEvidence::new(double, &self.contract_dbs());
+        evidence.dry_run = self.dry_run;
+        // Keep the original detection time if the watcher restarted since
+        if let Some(prior) = DoubleUpdateEvidence::retrieve(&watcher_db, &evidence.fraud())? {
+            evidence.detected_at = prior.detected_at;
+        }
+        evidence.store(&watcher_db)?;
+        send_alerts(
+            &self.alerts,
+            &Alert::DoubleUpdateDetected {
+                evidence: evidence.clone(),
+            },
+        )
+        .await;
+
+        let submissions = self.handle_double_update_failure(double).await?;
+        Self::log_submissions(&submissions);
+
+        evidence.record_submissions(submissions);
+        evidence.store(&watcher_db)?;
+        send_alerts(&self.alerts, &Alert::DoubleUpdateSubmitted { evidence }).await;
+
+        Ok(())
+    }
+
     /// Handle a double-update once it has been detected. Submit double updates
-    /// and failure notifications to all homes/replicas.
+    /// to the home and replicas and failure notifications to connection
+    /// managers, retrying until every submission settles.
     #[tracing::instrument]
     async fn handle_double_update_failure(
         &self,
         double: &DoubleUpdate,
-    ) -> Vec<Result<TxOutcome, ChainCommunicationError>> {
-        // Create vector of double update futures
-        let mut double_update_futs: Vec<_> = self
-            .core
-            .replicas
-            .values()
-            .map(|replica| replica.double_update(double))
-            .collect();
-        double_update_futs.push(self.core.home.double_update(double));
+    ) -> Result<Vec<SubmissionRecord>> {
+        self.submit_fraud(double.into()).await
+    }
 
-        // Created signed failure notification
-        let signed_failure = self.create_signed_failure().await;
+    /// Handle an improper update detected against the local tree. Submit it
+    /// to the home, failing the home, and failure notifications to connection
+    /// managers, retrying until every submission settles.
+    #[tracing::instrument]
+    async fn handle_improper_update(
+        &self,
+        improper: &SignedUpdate,
+    ) -> Result<Vec<SubmissionRecord>> {
+        self.submit_fraud(Fraud::ImproperUpdate {
+            update: improper.clone(),
+        })
+        .await
+    }
+
+    /// Handle a failed home once it has been detected. Submit failure
+    /// notifications to connection managers, retrying until every submission
+    /// settles.
+    #[tracing::instrument]
+    async fn handle_improper_update_failure(&self) -> Result<Vec<SubmissionRecord>> {
+        self.submit_fraud(Fraud::FailedHome).await
+    }
 
-        // Create vector of futures for unenrolling replicas (one per
-        // connection manager)
-        let mut unenroll_futs = Vec::new();
-        for connection_manager in self.connection_managers.iter() {
-            unenroll_futs.push(connection_manager.unenroll_replica(&signed_failure));
+    /// In dry run mode nothing is submitted in response to `fraud`. Instead
+    /// of shutting down, stop watching and stay up so that the `would_act`
+    /// flags can still be scraped from the metrics server.
+    async fn idle_after_dry_run(&self, fraud: &str) {
+        self.shutdown().await;
+        warn!(
+            "Dry run: {} detected. Evidence stored and contracts flagged, nothing submitted. Watcher stays up until manually shut down.",
+            fraud
+        );
+        std::future::pending::<()>().await
+    }
+
+    fn log_submissions(submissions: &[SubmissionRecord]) {
+        submissions.iter().for_each(|submission| {
+            info!(
+                contract = %submission.target,
+                action = submission.action.as_str(),
+                status = ?submission.status,
+                attempts = submission.attempts,
+                txid = ?submission.txid,
+                "Fraud submission settled"
+            )
+        });
+    }
+
+    /// Submissions due in response to `fraud`
+    async fn submissions_for(&self, fraud: &Fraud) -> Result<Vec<SubmissionRecord>> {
+        let home = self.home();
+        let mut submissions = vec![];
+        match fraud {
+            Fraud::DoubleUpdate { .. } => {
+                submissions.extend(
+                    self.replicas()
+                        .keys()
+                        .map(|name| SubmissionRecord::new(name, SubmissionAction::DoubleUpdate)),
+                );
+                submissions.push(SubmissionRecord::new(
+                    home.name(),
+                    SubmissionAction::DoubleUpdate,
+                ));
+            }
+            Fraud::ImproperUpdate { update } => {
+                // The home only accepts improper updates building on its
+                // committed root
+                if update.update.previous_root == home.committed_root().await? {
+                    submissions.push(SubmissionRecord::new(
+                        home.name(),
+                        SubmissionAction::ImproperUpdate,
+                    ));
+                } else {
+                    info!(
+                        "Improper update does not build on committed root of home {}. Not submitting it.",
+                        home.name()
+                    );
+                }
+            }
+            Fraud::FailedHome => {}
         }
+        submissions.extend(self.connection_managers.iter().map(|connection_manager| {
+            SubmissionRecord::new(
+                &Self::connection_manager_name(connection_manager),
+                SubmissionAction::UnenrollReplica,
+            )
+        }));
+        Ok(submissions)
+    }
 
-        // Join both vectors of double update and unenroll futures and
-        // return vector containing all results
-        let (double_update_res, unenroll_res) =
-            join(join_all(double_update_futs), join_all(unenroll_futs)).await;
-        double_update_res
-            .into_iter()
-            .chain(unenroll_res.into_iter())
-            .collect()
+    /// Submit `fraud` to every contract concerned. If the same fraud was being
+    /// submitted before a restart, its stored progress is resumed. Returns
+    /// the settled submissions, or nothing in dry run mode.
+    async fn submit_fraud(&self, fraud: Fraud) -> Result<Vec<SubmissionRecord>> {
+        let submissions = self.submissions_for(&fraud).await?;
+        for submission in submissions.iter() {
+            self.flag_would_act(&submission.target, submission.action.as_str());
+        }
+        if self.dry_run {
+            return Ok(vec![]);
+        }
+
+        let pending = match PendingSubmissions::retrieve(&self.watcher_db(), &fraud)? {
+            Some(pending) => {
+                info!(
+                    pending = ?pending.pending_targets(),
+                    "Resuming stored fraud submissions"
+                );
+                pending
+            }
+            _ => PendingSubmissions::new(fraud, submissions),
+        };
+
+        Ok(self.settle_submissions(pending).await?.submissions)
     }
 
-    /// Handle a double-update once it has been detected. Submit double updates
-    /// and failure notifications to all homes/replicas.
-    #[tracing::instrument]
-    async fn handle_improper_update_failure(
+    /// Attempt pending submissions in rounds, with backoff in between, until
+    /// each is confirmed or moot. Progress is stored after every round.
+    /// Submissions still pending after `MAX_SUBMISSION_ROUNDS` rounds are
+    /// given up on and alerted of.
+    async fn settle_submissions(
         &self,
-    ) -> Vec<Result<TxOutcome, ChainCommunicationError>> {
+        mut pending: PendingSubmissions,
+    ) -> Result<PendingSubmissions> {
+        let watcher_db = self.watcher_db();
+        pending.store(&watcher_db)?;
+
         let signed_failure = self.create_signed_failure().await;
-        let mut unenroll_futs = Vec::new();
-        for connection_manager in self.connection_managers.iter() {
-            unenroll_futs.push(connection_manager.unenroll_replica(&signed_failure));
+
+        for round in 0..MAX_SUBMISSION_ROUNDS {
+            let attempts = pending.submissions.iter().cloned().map(|submission| {
+                self.attempt_submission(&pending.fraud, &signed_failure, submission)
+            });
+            let submissions = join_all(attempts).await;
+            pending.submissions = submissions;
+            pending.store(&watcher_db)?;
+
+            if pending.is_settled() {
+                return Ok(pending);
+            }
+            if round + 1 == MAX_SUBMISSION_ROUNDS {
+                break;
+            }
+
+            let delay = self.backoff.delay(round + 1);
+            error!(
+                round,
+                pending = ?pending.pending_targets(),
+                "Fraud submissions not confirmed. Retrying in {:?}",
+                delay
+            );
+            if round == 0 {
+                send_alerts(
+                    &self.alerts,
+                    &Alert::SubmissionsPending {
+                        fraud: pending.fraud.clone(),
+                        submissions: pending.submissions.clone(),
+                    },
+                )
+                .await;
+            }
+            sleep(delay).await;
+        }
+
+        error!(
+            rounds = MAX_SUBMISSION_ROUNDS,
+            pending = ?pending.pending_targets(),
+            "Giving up on unconfirmed fraud submissions. They resume on restart."
+        );
+        send_alerts(
+            &self.alerts,
+            &Alert::SubmissionsAbandoned {
+                fraud: pending.fraud.clone(),
+                submissions: pending.submissions.clone(),
+            },
+        )
+        .await;
+        Ok(pending)
+    }
+
+    /// Contract a submission is addressed to, by name
+    fn submission_target(&self, name: &str) -> Option<SubmissionTarget<'_>> {
+        if name == self.core.home.name() {
+            return Some(SubmissionTarget::Home(&self.core.home));
+        }
+        if let Some(replica) = self.core.replicas.get(name) {
+            return Some(SubmissionTarget::Replica(replica));
+        }
+        self.connection_managers
+            .iter()
+            .find(|connection_manager| Self::connection_manager_name(connection_manager) == name)
+            .map(SubmissionTarget::ConnectionManager)
+    }
+
+    /// Make one attempt at a pending submission. Submissions are first checked
+    /// for mootness, so nothing is sent to contracts that are already failed
+    /// or no longer enroll the replica.
+    async fn attempt_submission(
+        &self,
+        fraud: &Fraud,
+        signed_failure: &SignedFailureNotification,
+        mut submission: SubmissionRecord,
+    ) -> SubmissionRecord {
+        if !submission.is_pending() {
+            return submission;
+        }
+
+        let target = match self.submission_target(&submission.target) {
+            Some(target) => target,
+            None => {
+                // The contract was removed from the config since
+                error!(
+                    contract = %submission.target,
+                    "Unknown contract. Not retrying submission."
+                );
+                submission.status = SubmissionStatus::Moot;
+                submission.error = Some("unknown contract".to_owned());
+                return submission;
+            }
+        };
+
+        match target.is_moot(fraud, signed_failure).await {
+            Ok(true) => {
+                info!(
+                    contract = %submission.target,
+                    action = submission.action.as_str(),
+                    "Submission is moot"
+                );
+                submission.status = SubmissionStatus::Moot;
+                return submission;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                submission.error = Some(e.to_string());
+                return submission;
+            }
         }
 
-        join_all(unenroll_futs).await
+        let result = target.submit(fraud, signed_failure).await;
+        if let Ok(outcome) = &result {
+            // Fraud submissions are never held back by the gas budget
+            let operation = submission.action.gas_operation();
+            let chain = self.gas_chain(&target, &submission.target);
+            if let Err(e) = self.budget.record(&chain, operation, outcome) {
+                warn!(error = %e, "Could not account fraud submission cost");
+            }
+        }
+        submission.record_attempt(&result);
+        submission
     }
 
     async fn shutdown(&self) {
@@ -456,18 +951,28 @@ impl NomadAgent for Watcher {
     where
         Self: Sized,
     {
+        let core = settings.as_ref().try_into_core("watcher").await?;
+
         let mut connection_managers = vec![];
         for chain_setup in settings.managers.values() {
-            let signer = settings.base.get_signer(&chain_setup.name).await;
             let xapp_timelag = if settings.base.use_timelag {
                 Some(chain_setup.timelag)
             } else {
                 None
             };
 
-            let manager = chain_setup
-                .try_into_connection_manager(signer, xapp_timelag)
-                .await;
+            let manager = match settings.base.get_signer(&chain_setup.name).await {
+                Ok(signer) => {
+                    chain_setup
+                        .try_into_connection_manager(
+                            signer,
+                            xapp_timelag,
+                            &core.settings.tx_managers(),
+                        )
+                        .await
+                }
+                Err(e) => Err(e),
+            };
             connection_managers.push(manager);
         }
 
@@ -485,15 +990,41 @@ impl NomadAgent for Watcher {
             .map(Result::unwrap)
             .map(Arc::new)
             .collect();
+        let connection_manager_chains = settings
+            .managers
+            .values()
+            .map(|setup| {
+                (
+                    setup.domain.parse().expect("invalid uint"),
+                    setup.name.clone(),
+                )
+            })
+            .collect();
 
-        let core = settings.as_ref().try_into_core("watcher").await?;
+        let alerts = settings
+            .alerts
+            .iter()
+            .flatten()
+            .map(|config| config.build())
+            .collect();
 
         Ok(Self::new(
             settings.watcher.try_into_signer().await?,
             settings.interval.parse().expect("invalid uint"),
             connection_managers,
+            alerts,
+            settings.dry_run,
+            DEFAULT_BACKOFF.configured(settings.retry.as_ref()),
             core,
-        ))
+        )
+        .with_committee(
+            settings
+                .committee
+                .as_ref()
+                .map(CommitteeVerifier::try_from)
+                .transpose()?,
+        )
+        .with_connection_manager_chains(connection_manager_chains))
     }
 
     #[tracing::instrument]
@@ -510,6 +1041,30 @@ impl NomadAgent for Watcher {
         Self: Sized + 'static,
     {
         tokio::spawn(async move {
+            // Finish submissions interrupted by a restart before anything else
+            let watcher_db = self.watcher_db();
+            let interrupted: Vec<_> = PendingSubmissions::all(&watcher_db)
+                .filter(|pending| !pending.is_settled())
+                .collect();
+            if !interrupted.is_empty() && !self.dry_run {
+                for pending in interrupted {
+                    error!(
+                        fraud = ?pending.fraud,
+                        pending = ?pending.pending_targets(),
+                        "Resuming fraud submissions interrupted by a restart"
+                    );
+                    let settled = self.settle_submissions(pending).await?;
+                    Self::log_submissions(&settled.submissions);
+                }
+
+                bail!(
+                    r#"
+                    Fraud submissions resumed!
+                    Watcher has been shut down!
+                "#
+                )
+            }
+
             info!("Starting Watcher tasks");
 
             let indexer = &self.as_ref().indexer;
@@ -517,7 +1072,7 @@ impl NomadAgent for Watcher {
 
             let home_sync_task = self
                 .home()
-                .sync(Self::AGENT_NAME.to_owned(), indexer.from(), indexer.chunk_size(), sync_metrics.clone(), IndexDataTypes::Updates);
+                .sync(Self::AGENT_NAME.to_owned(), indexer.from(), indexer.chunk_size(), sync_metrics.clone(), IndexDataTypes::Both);
 
             let replica_sync_tasks: Vec<Instrumented<JoinHandle<Result<()>>>> = self.replicas().values().map(|replica| {
                 replica.sync(Self::AGENT_NAME.to_owned(), indexer.from(), indexer.chunk_size(), sync_metrics.clone())
@@ -527,7 +1082,9 @@ impl NomadAgent for Watcher {
             sync_tasks.extend(replica_sync_tasks);
             let sync_task_unified = select_all(sync_tasks);
 
-            let double_update_watch_task = self.watch_double_update();
+            let (checker_tx, checker_rx) = mpsc::unbounded_channel();
+            let double_update_watch_task = self.watch_double_update(checker_tx);
+            let improper_update_check_task = self.watch_improper_update(checker_rx);
             let improper_update_watch_task = self.watch_home_fail(self.interval_seconds);
 
             // Race index and run tasks
@@ -546,10 +1103,10 @@ impl NomadAgent for Watcher {
                             double
                         );
 
-                        self.handle_double_update_failure(&double)
-                            .await
-                            .iter()
-                            .for_each(|res| tracing::info!("{:#?}", res));
+                        self.handle_double_update(&double).await?;
+                        if self.dry_run {
+                            self.idle_after_dry_run("Double update").await;
+                        }
 
                         bail!(
                             r#"
@@ -563,6 +1120,29 @@ impl NomadAgent for Watcher {
 
                     self.shutdown().await;
                 },
+                improper_res = improper_update_check_task => {
+                    let improper = improper_res??;
+                    tracing::error!(
+                        improper_update = ?improper,
+                        "Improper update detected! Notifying home and unenrolling replicas! Improper update: {:?}",
+                        improper
+                    );
+
+                    let submissions = self.handle_improper_update(&improper).await?;
+                    Self::log_submissions(&submissions);
+                    if self.dry_run {
+                        self.idle_after_dry_run("Improper update").await;
+                    }
+
+                    bail!(
+                        r#"
+                        Improper update detected!
+                        Home notified!
+                        Replicas unenrolled!
+                        Watcher has been shut down!
+                    "#
+                    )
+                },
                 improper_res = improper_update_watch_task => {
 
                     if let Err(e) = improper_res? {
@@ -572,10 +1152,11 @@ impl NomadAgent for Watcher {
                                 "Improper update detected! Notifying all contracts and unenrolling replicas!",
                             );
 
-                            self.handle_improper_update_failure()
-                                .await
-                                .iter()
-                                .for_each(|res| tracing::info!("{:#?}", res));
+                            let submissions = self.handle_improper_update_failure().await?;
+                            Self::log_submissions(&submissions);
+                            if self.dry_run {
+                                self.idle_after_dry_run("Failed home").await;
+                            }
 
                             bail!(
                                 r#"
@@ -604,7 +1185,10 @@ impl NomadAgent for Watcher {
 mod test {
     use nomad_base::IndexSettings;
     use nomad_test::mocks::MockIndexer;
-    use std::sync::Arc;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
     use tokio::sync::mpsc;
 
     use ethers::core::types::H256;
@@ -781,16 +1365,37 @@ mod test {
             let mut mock_home = MockHomeContract::new();
             mock_home.expect__name().return_const("home_1".to_owned());
 
-            let nomad_db = NomadDB::new("home_1_watcher", db);
+            let nomad_db = NomadDB::new("home_1_watcher", db.clone());
             let mock_home_indexer = Arc::new(MockIndexer::new().into());
             let home: Arc<CachingHome> =
                 CachingHome::new(mock_home.into(), nomad_db.clone(), mock_home_indexer).into();
 
+            let core = AgentCore {
+                home: home.clone(),
+                replicas: Default::default(),
+                db,
+                indexer: IndexSettings::default(),
+                settings: nomad_base::Settings::default(),
+                metrics: Arc::new(
+                    nomad_base::CoreMetrics::new(
+                        "watcher_test",
+                        None,
+                        Arc::new(prometheus::Registry::new()),
+                    )
+                    .expect("could not make metrics"),
+                ),
+            };
+
             let (_tx, rx) = mpsc::channel(200);
+            let (checker_tx, _checker_rx) = mpsc::unbounded_channel();
             let mut handler = UpdateHandler {
                 rx,
+                checker_tx,
+                attestation_tx: None,
                 watcher_db: nomad_db.clone(),
                 home,
+                budget: Arc::new(GasBudget::new(AGENT_NAME, &core)),
+                dry_run: false,
             };
 
             let _first_update_ret = handler
@@ -877,6 +1482,12 @@ mod test {
                     .times(1)
                     .return_once(move || Ok(updater.address().into()));
 
+                // Home is still active when submitting
+                mock_home
+                    .expect__state()
+                    .times(1)
+                    .return_once(move || Ok(State::Active));
+
                 // home.double_update called once
                 let double = double.clone();
                 mock_home
@@ -887,6 +1498,7 @@ mod test {
                         Ok(TxOutcome {
                             txid: H256::default(),
                             executed: true,
+                            ..Default::default()
                         })
                     });
             }
@@ -895,6 +1507,11 @@ mod test {
                     .expect__name()
                     .return_const("replica_1".to_owned());
 
+                mock_replica_1
+                    .expect__state()
+                    .times(1)
+                    .return_once(move || Ok(State::Active));
+
                 // replica_1.double_update called once
                 let double = double.clone();
                 mock_replica_1
@@ -905,6 +1522,7 @@ mod test {
                         Ok(TxOutcome {
                             txid: H256::default(),
                             executed: true,
+                            ..Default::default()
                         })
                     });
             }
@@ -913,6 +1531,11 @@ mod test {
                     .expect__name()
                     .return_const("replica_2".to_owned());
 
+                mock_replica_2
+                    .expect__state()
+                    .times(1)
+                    .return_once(move || Ok(State::Active));
+
                 // replica_2.double_update called once
                 let double = double.clone();
                 mock_replica_2
@@ -923,12 +1546,24 @@ mod test {
                         Ok(TxOutcome {
                             txid: H256::default(),
                             executed: true,
+                            ..Default::default()
                         })
                     });
             }
 
             // Connection manager expectations
             {
+                mock_connection_manager_1
+                    .expect__local_domain()
+                    .return_const(2u32);
+
+                // Replica of the home is still enrolled when submitting
+                mock_connection_manager_1
+                    .expect__domain_to_replica()
+                    .withf(move |domain: &u32| *domain == home_domain)
+                    .times(1)
+                    .return_once(move |_| Ok(H256::repeat_byte(1).into()));
+
                 // connection_manager_1.unenroll_replica called once
                 let signed_failure = signed_failure.clone();
                 mock_connection_manager_1
@@ -939,10 +1574,22 @@ mod test {
                         Ok(TxOutcome {
                             txid: H256::default(),
                             executed: true,
+                            ..Default::default()
                         })
                     });
             }
             {
+                mock_connection_manager_2
+                    .expect__local_domain()
+                    .return_const(3u32);
+
+                // Replica of the home is still enrolled when submitting
+                mock_connection_manager_2
+                    .expect__domain_to_replica()
+                    .withf(move |domain: &u32| *domain == home_domain)
+                    .times(1)
+                    .return_once(move |_| Ok(H256::repeat_byte(1).into()));
+
                 // connection_manager_2.unenroll_replica called once
                 let signed_failure = signed_failure.clone();
                 mock_connection_manager_2
@@ -953,6 +1600,8 @@ mod test {
                         Ok(TxOutcome {
                             txid: H256::default(),
                             executed: true,
+                            gas_used: 21_000.into(),
+                            gas_price: 1.into(),
                         })
                     });
             }
@@ -1014,9 +1663,25 @@ mod test {
                 };
 
                 {
-                    let watcher =
-                        Watcher::new(updater.into(), 1, connection_managers.clone(), core);
-                    watcher.handle_double_update_failure(&double).await;
+                    let watcher = Watcher::new(
+                        updater.into(),
+                        1,
+                        connection_managers.clone(),
+                        vec![],
+                        false,
+                        Default::default(),
+                        core,
+                    )
+                    .with_connection_manager_chains(
+                        [(3, "chain_3".to_owned())].into_iter().collect(),
+                    );
+                    watcher.handle_double_update_failure(&double).await.unwrap();
+
+                    // Unenrollment gas is accounted under the chain's name
+                    assert_eq!(
+                        watcher.budget.spent_today("chain_3").unwrap(),
+                        21_000.into()
+                    );
                 }
 
                 // Checkpoint connection managers
@@ -1083,6 +1748,17 @@ mod test {
 
             // Connection manager expectations
             {
+                mock_connection_manager_1
+                    .expect__local_domain()
+                    .return_const(2u32);
+
+                // Replica of the home is still enrolled when submitting
+                mock_connection_manager_1
+                    .expect__domain_to_replica()
+                    .withf(move |domain: &u32| *domain == home_domain)
+                    .times(1)
+                    .return_once(move |_| Ok(H256::repeat_byte(1).into()));
+
                 // connection_manager_1.unenroll_replica called once
                 let signed_failure = signed_failure.clone();
                 mock_connection_manager_1
@@ -1093,10 +1769,22 @@ mod test {
                         Ok(TxOutcome {
                             txid: H256::default(),
                             executed: true,
+                            ..Default::default()
                         })
                     });
             }
             {
+                mock_connection_manager_2
+                    .expect__local_domain()
+                    .return_const(3u32);
+
+                // Replica of the home is still enrolled when submitting
+                mock_connection_manager_2
+                    .expect__domain_to_replica()
+                    .withf(move |domain: &u32| *domain == home_domain)
+                    .times(1)
+                    .return_once(move |_| Ok(H256::repeat_byte(1).into()));
+
                 // connection_manager_2.unenroll_replica called once
                 let signed_failure = signed_failure.clone();
                 mock_connection_manager_2
@@ -1107,6 +1795,7 @@ mod test {
                         Ok(TxOutcome {
                             txid: H256::default(),
                             executed: true,
+                            ..Default::default()
                         })
                     });
             }
@@ -1167,7 +1856,15 @@ mod test {
                     ),
                 };
 
-                let watcher = Watcher::new(updater.into(), 1, connection_managers.clone(), core);
+                let watcher = Watcher::new(
+                    updater.into(),
+                    1,
+                    connection_managers.clone(),
+                    vec![],
+                    false,
+                    Default::default(),
+                    core,
+                );
                 let state = watcher
                     .watch_home_fail(1)
                     .await
@@ -1183,7 +1880,7 @@ mod test {
                     false
                 });
 
-                watcher.handle_improper_update_failure().await;
+                watcher.handle_improper_update_failure().await.unwrap();
             }
 
             // Checkpoint connection managers
@@ -1198,4 +1895,203 @@ mod test {
         })
         .await
     }
+
+    #[tokio::test]
+    async fn it_only_flags_contracts_in_dry_run() {
+        test_utils::run_test_db(|db| async move {
+            let updater: LocalWallet =
+                "1111111111111111111111111111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap();
+
+            let double = DoubleUpdate(
+                Update {
+                    home_domain: 1,
+                    previous_root: H256::from([1; 32]),
+                    new_root: H256::from([2; 32]),
+                }
+                .sign_with(&updater)
+                .await
+                .expect("!sign"),
+                Update {
+                    home_domain: 1,
+                    previous_root: H256::from([1; 32]),
+                    new_root: H256::from([3; 32]),
+                }
+                .sign_with(&updater)
+                .await
+                .expect("!sign"),
+            );
+
+            // No submission expectations: any transaction fails the test
+            let mut mock_home = MockHomeContract::new();
+            mock_home.expect__name().return_const("home_1".to_owned());
+            let mut mock_replica = MockReplicaContract::new();
+            mock_replica
+                .expect__name()
+                .return_const("replica_1".to_owned());
+            let mut mock_connection_manager = MockConnectionManagerContract::new();
+            mock_connection_manager
+                .expect__local_domain()
+                .return_const(2u32);
+
+            let home: Arc<CachingHome> = CachingHome::new(
+                mock_home.into(),
+                NomadDB::new("home_1", db.clone()),
+                Arc::new(MockIndexer::new().into()),
+            )
+            .into();
+            let replica: Arc<CachingReplica> = CachingReplica::new(
+                mock_replica.into(),
+                NomadDB::new("replica_1", db.clone()),
+                Arc::new(MockIndexer::new().into()),
+            )
+            .into();
+
+            let mut replica_map: HashMap<String, Arc<CachingReplica>> = HashMap::new();
+            replica_map.insert("replica_1".into(), replica);
+
+            let core = AgentCore {
+                home,
+                replicas: replica_map,
+                db,
+                indexer: IndexSettings::default(),
+                settings: nomad_base::Settings::default(),
+                metrics: Arc::new(
+                    nomad_base::CoreMetrics::new(
+                        "watcher_test",
+                        None,
+                        Arc::new(prometheus::Registry::new()),
+                    )
+                    .expect("could not make metrics"),
+                ),
+            };
+
+            let watcher = Watcher::new(
+                updater.into(),
+                1,
+                vec![Arc::new(mock_connection_manager.into())],
+                vec![],
+                true,
+                Default::default(),
+                core,
+            );
+
+            assert!(watcher
+                .handle_double_update_failure(&double)
+                .await
+                .unwrap()
+                .is_empty());
+            assert!(watcher
+                .handle_improper_update_failure()
+                .await
+                .unwrap()
+                .is_empty());
+
+            for (contract, action) in [
+                ("home_1", "double_update"),
+                ("replica_1", "double_update"),
+                ("connection_manager_2", "unenroll_replica"),
+            ] {
+                let flag = watcher
+                    .would_act
+                    .with_label_values(&[contract, action, AGENT_NAME])
+                    .get();
+                assert_eq!(flag, 1);
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn it_retries_unconfirmed_submissions_until_moot() {
+        test_utils::run_test_db(|db| async move {
+            let updater: LocalWallet =
+                "1111111111111111111111111111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap();
+
+            let mut mock_home = MockHomeContract::new();
+            mock_home.expect__name().return_const("home_1".to_owned());
+            mock_home.expect__local_domain().return_const(1u32);
+            let address = updater.address();
+            mock_home
+                .expect__updater()
+                .returning(move || Ok(address.into()));
+
+            // First unenrollment reverts, then the replica turns out to be
+            // unenrolled already
+            let mut mock_connection_manager = MockConnectionManagerContract::new();
+            mock_connection_manager
+                .expect__local_domain()
+                .return_const(2u32);
+            mock_connection_manager
+                .expect__unenroll_replica()
+                .times(1)
+                .return_once(move |_| {
+                    Ok(TxOutcome {
+                        txid: H256::repeat_byte(1),
+                        executed: false,
+                        ..Default::default()
+                    })
+                });
+            let checks = AtomicUsize::new(0);
+            mock_connection_manager
+                .expect__domain_to_replica()
+                .withf(|domain: &u32| *domain == 1)
+                .times(2)
+                .returning(move |_| {
+                    Ok(match checks.fetch_add(1, Ordering::SeqCst) {
+                        0 => H256::repeat_byte(1).into(),
+                        _ => Default::default(),
+                    })
+                });
+
+            let home: Arc<CachingHome> = CachingHome::new(
+                mock_home.into(),
+                NomadDB::new("home_1", db.clone()),
+                Arc::new(MockIndexer::new().into()),
+            )
+            .into();
+
+            let core = AgentCore {
+                home,
+                replicas: HashMap::new(),
+                db,
+                indexer: IndexSettings::default(),
+                settings: nomad_base::Settings::default(),
+                metrics: Arc::new(
+                    nomad_base::CoreMetrics::new(
+                        "watcher_test",
+                        None,
+                        Arc::new(prometheus::Registry::new()),
+                    )
+                    .expect("could not make metrics"),
+                ),
+            };
+
+            let watcher = Watcher::new(
+                updater.into(),
+                1,
+                vec![Arc::new(mock_connection_manager.into())],
+                vec![],
+                false,
+                Backoff::new(0, 0),
+                core,
+            );
+
+            let submissions = watcher.handle_improper_update_failure().await.unwrap();
+            assert_eq!(submissions.len(), 1);
+            assert_eq!(submissions[0].target, "connection_manager_2");
+            assert_eq!(submissions[0].status, SubmissionStatus::Moot);
+            assert_eq!(submissions[0].attempts, 1);
+
+            let stored = PendingSubmissions::retrieve(&watcher.watcher_db(), &Fraud::FailedHome)
+                .unwrap()
+                .unwrap();
+            assert_eq!(stored.fraud, Fraud::FailedHome);
+            assert!(stored.is_settled());
+        })
+        .await
+    }
 }
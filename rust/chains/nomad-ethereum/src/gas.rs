@@ -1,16 +1,188 @@
 use ethers::providers::{FromErr, Middleware};
-use ethers::types::{transaction::eip2718::TypedTransaction, BlockId, U256};
+use ethers::types::{
+    transaction::eip2718::TypedTransaction, BlockId, BlockNumber, Eip1559TransactionRequest,
+    TransactionReceipt, U256,
+};
 use std::fmt;
 use thiserror::Error;
 
 /// Closure that will be used for gas calculation. Takes existing gas
 type GasPolicy = Box<dyn Fn(U256) -> U256 + Send + Sync>;
 
+/// Type of transactions sent on a chain
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TxType {
+    /// Legacy transactions paying a single gas price
+    Legacy,
+    /// EIP-1559 (type 2) transactions paying a base fee and a priority fee
+    Eip1559,
+}
+
+impl Default for TxType {
+    fn default() -> Self {
+        Self::Legacy
+    }
+}
+
+/// Per-chain fee policy configuration. Unset fields keep the historical
+/// behavior: legacy transactions, tripled gas estimates and a gas price
+/// multiplied by 1.5 on Ethereum mainnet and by 2 elsewhere.
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GasConf {
+    /// Type of transactions to send
+    #[serde(default)]
+    tx_type: TxType,
+    /// Multiplier applied to every gas estimate
+    gas_estimate_multiplier: Option<String>,
+    /// Multiplier applied to the node's gas price. Legacy only
+    gas_price_multiplier: Option<String>,
+    /// Reward percentile of recent blocks used as priority fee. EIP-1559 only
+    priority_fee_percentile: Option<String>,
+    /// Number of recent blocks sampled through `eth_feeHistory`. EIP-1559 only
+    fee_history_blocks: Option<String>,
+    /// Multiplier applied to the next block's base fee when computing the max
+    /// fee, to survive consecutive full blocks. EIP-1559 only
+    base_fee_multiplier: Option<String>,
+    /// Upper bound in wei on the max fee (EIP-1559) or gas price (legacy)
+    max_fee_per_gas: Option<String>,
+    /// Upper bound in wei on the priority fee. EIP-1559 only
+    max_priority_fee_per_gas: Option<String>,
+}
+
+fn parse_multiplier(value: &Option<String>, default: f64) -> f64 {
+    value
+        .as_ref()
+        .map(|s| s.parse::<f64>().expect("invalid multiplier"))
+        .unwrap_or(default)
+}
+
+fn parse_wei(value: &Option<String>) -> Option<U256> {
+    value
+        .as_ref()
+        .map(|s| U256::from_dec_str(s).expect("invalid wei amount"))
+}
+
+/// Multiply by a float with a precision of 1/1000
+fn multiply(value: U256, multiplier: f64) -> U256 {
+    value * U256::from((multiplier * 1000.0).round() as u64) / 1000
+}
+
+impl GasConf {
+    /// Get the `gas_estimate_multiplier` setting
+    pub fn gas_estimate_multiplier(&self) -> f64 {
+        parse_multiplier(&self.gas_estimate_multiplier, 3.0)
+    }
+
+    /// Get the `gas_price_multiplier` setting
+    pub fn gas_price_multiplier(&self, chain_id: u64) -> f64 {
+        let default = if chain_id == 1 { 1.5 } else { 2.0 };
+        parse_multiplier(&self.gas_price_multiplier, default)
+    }
+
+    /// Get the `max_fee_per_gas` setting
+    pub fn max_fee_per_gas(&self) -> Option<U256> {
+        parse_wei(&self.max_fee_per_gas)
+    }
+
+    /// The configured upper bounds on fees
+    pub fn fee_caps(&self) -> FeeCaps {
+        FeeCaps {
+            max_fee_per_gas: parse_wei(&self.max_fee_per_gas),
+            max_priority_fee_per_gas: parse_wei(&self.max_priority_fee_per_gas),
+        }
+    }
+
+    /// The EIP-1559 policy, if the chain is configured for type 2
+    /// transactions
+    pub fn eip1559_policy(&self) -> Option<Eip1559Policy> {
+        if self.tx_type != TxType::Eip1559 {
+            return None;
+        }
+
+        Some(Eip1559Policy {
+            priority_fee_percentile: parse_multiplier(&self.priority_fee_percentile, 50.0),
+            fee_history_blocks: self
+                .fee_history_blocks
+                .as_ref()
+                .map(|s| s.parse::<u64>().expect("invalid integer"))
+                .unwrap_or(10),
+            base_fee_multiplier: parse_multiplier(&self.base_fee_multiplier, 2.0),
+            max_fee_per_gas: parse_wei(&self.max_fee_per_gas),
+            max_priority_fee_per_gas: parse_wei(&self.max_priority_fee_per_gas),
+        })
+    }
+}
+
+/// Upper bounds on the fees paid by a transaction
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FeeCaps {
+    /// Upper bound on the max fee (EIP-1559) or gas price (legacy)
+    pub max_fee_per_gas: Option<U256>,
+    /// Upper bound on the priority fee. EIP-1559 only
+    pub max_priority_fee_per_gas: Option<U256>,
+}
+
+/// Fee policy for EIP-1559 transactions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Eip1559Policy {
+    /// Reward percentile of recent blocks used as priority fee
+    pub priority_fee_percentile: f64,
+    /// Number of recent blocks sampled
+    pub fee_history_blocks: u64,
+    /// Multiplier applied to the next block's base fee
+    pub base_fee_multiplier: f64,
+    /// Upper bound on the max fee
+    pub max_fee_per_gas: Option<U256>,
+    /// Upper bound on the priority fee
+    pub max_priority_fee_per_gas: Option<U256>,
+}
+
+impl Eip1559Policy {
+    /// Compute `(max_fee_per_gas, max_priority_fee_per_gas)` from the next
+    /// block's base fee and the sampled priority fee rewards
+    pub fn fees(&self, next_base_fee: U256, mut rewards: Vec<U256>) -> (U256, U256) {
+        rewards.sort();
+        let mut priority_fee = rewards.get(rewards.len() / 2).cloned().unwrap_or_default();
+        if let Some(cap) = self.max_priority_fee_per_gas {
+            priority_fee = priority_fee.min(cap);
+        }
+
+        let mut max_fee = multiply(next_base_fee, self.base_fee_multiplier) + priority_fee;
+        if let Some(cap) = self.max_fee_per_gas {
+            max_fee = max_fee.min(cap);
+        }
+
+        (max_fee, priority_fee.min(max_fee))
+    }
+}
+
+/// Fill in the effective gas price of `receipt` from its transaction's gas
+/// price, on chains whose receipts don't report it
+pub(crate) async fn fill_effective_gas_price<M: Middleware>(
+    provider: &M,
+    receipt: &mut TransactionReceipt,
+) {
+    if receipt.effective_gas_price.is_some() {
+        return;
+    }
+    match provider.get_transaction(receipt.transaction_hash).await {
+        Ok(tx) => receipt.effective_gas_price = tx.and_then(|tx| tx.gas_price),
+        Err(e) => tracing::warn!(
+            tx_hash = ?receipt.transaction_hash,
+            error = %e,
+            "Could not fetch gas price of transaction"
+        ),
+    }
+}
+
 /// Middleware used for adjusting gas using predefined policy
 pub struct GasAdjusterMiddleware<M> {
     inner: M,
     gas_estimate_policy: GasPolicy,
     gas_price_policy: GasPolicy,
+    eip1559_policy: Option<Eip1559Policy>,
 }
 
 impl<M> fmt::Debug for GasAdjusterMiddleware<M>
@@ -20,6 +192,7 @@ where
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("GasAdjusterMiddleware")
             .field("inner", &self.inner)
+            .field("eip1559_policy", &self.eip1559_policy)
             .finish()
     }
 }
@@ -35,28 +208,79 @@ where
             inner,
             gas_estimate_policy,
             gas_price_policy,
+            eip1559_policy: None,
         }
     }
 
+    /// Instantiates the middleware with the default fee policy for the chain
     pub fn with_default_policy(inner: M, chain_id: u64) -> Self {
-        // triple gas estimate
-        let gas_estimate_policy = move |gas| gas * 3;
+        Self::with_conf(inner, chain_id, &Default::default())
+    }
 
-        // 1.5x gas price for ethereum, 2x elsewhere
-        let is_ethereum = chain_id == 1;
+    /// Instantiates the middleware with the fee policy configured for the
+    /// chain
+    pub fn with_conf(inner: M, chain_id: u64, conf: &GasConf) -> Self {
+        let estimate_multiplier = conf.gas_estimate_multiplier();
+        let gas_estimate_policy = move |gas| multiply(gas, estimate_multiplier);
+
+        let price_multiplier = conf.gas_price_multiplier(chain_id);
+        let max_price = conf.max_fee_per_gas();
         let gas_price_policy = move |price| {
-            if is_ethereum {
-                price + price / 2
-            } else {
-                price * 2
-            }
+            let price = multiply(price, price_multiplier);
+            max_price.map_or(price, |max| price.min(max))
         };
 
-        Self::new(
-            inner,
-            Box::new(gas_estimate_policy),
-            Box::new(gas_price_policy),
-        )
+        Self {
+            eip1559_policy: conf.eip1559_policy(),
+            ..Self::new(
+                inner,
+                Box::new(gas_estimate_policy),
+                Box::new(gas_price_policy),
+            )
+        }
+    }
+
+    /// Compute EIP-1559 fees from the fee history of recent blocks
+    async fn eip1559_fees(
+        &self,
+        policy: &Eip1559Policy,
+    ) -> Result<(U256, U256), GasAdjusterMiddlewareError<M>> {
+        let history = self
+            .inner
+            .fee_history(
+                policy.fee_history_blocks,
+                BlockNumber::Latest,
+                &[policy.priority_fee_percentile],
+            )
+            .await
+            .map_err(FromErr::from)?;
+
+        // The last entry is the base fee of the next block
+        let next_base_fee = history.base_fee_per_gas.last().cloned().unwrap_or_default();
+        let rewards = history
+            .reward
+            .iter()
+            .filter_map(|block| block.first().cloned())
+            .collect();
+
+        Ok(policy.fees(next_base_fee, rewards))
+    }
+}
+
+/// Copy the common fields of any transaction into a type 2 request
+fn to_eip1559(tx: &TypedTransaction) -> Eip1559TransactionRequest {
+    match tx {
+        TypedTransaction::Eip1559(inner) => inner.clone(),
+        _ => {
+            let mut request = Eip1559TransactionRequest::new();
+            request.from = tx.from().cloned();
+            request.to = tx.to().cloned();
+            request.gas = tx.gas().cloned();
+            request.value = tx.value().cloned();
+            request.data = tx.data().cloned();
+            request.nonce = tx.nonce().cloned();
+            request
+        }
     }
 }
 
@@ -93,16 +317,32 @@ where
         tx: &mut TypedTransaction,
         block: Option<BlockId>,
     ) -> Result<(), Self::Error> {
+        // Keep prices set further up the stack, e.g. by a replacement. Fees
+        // are set before filling so the inner provider doesn't estimate its
+        // own
+        if tx.gas_price().is_none() {
+            match &self.eip1559_policy {
+                Some(policy) => {
+                    let (max_fee, priority_fee) = self.eip1559_fees(policy).await?;
+                    let mut request = to_eip1559(tx);
+                    request.max_fee_per_gas = Some(max_fee);
+                    request.max_priority_fee_per_gas = Some(priority_fee);
+                    *tx = request.into();
+                }
+                None => {
+                    let adjusted_price = self.get_gas_price().await?;
+                    tx.set_gas_price(adjusted_price);
+                }
+            }
+        }
+
         self.inner
             .fill_transaction(tx, block)
             .await
             .map_err(FromErr::from)?;
 
         let adjusted_gas = self.estimate_gas(tx).await?;
-        let adjusted_price = self.get_gas_price().await?;
-
         tx.set_gas(adjusted_gas);
-        tx.set_gas_price(adjusted_price);
 
         Ok(())
     }
@@ -123,3 +363,38 @@ where
             .map_err(FromErr::from)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_multiplies() {
+        assert_eq!(multiply(100.into(), 1.5), 150.into());
+        assert_eq!(multiply(100.into(), 3.0), 300.into());
+        assert_eq!(multiply(1000.into(), 0.001), 1.into());
+    }
+
+    #[test]
+    fn it_computes_eip1559_fees() {
+        let conf: GasConf = serde_json::from_str(
+            r#"{"txType": "eip1559", "maxFeePerGas": "500", "maxPriorityFeePerGas": "20"}"#,
+        )
+        .unwrap();
+        let policy = conf.eip1559_policy().expect("!eip1559");
+
+        // median reward, twice the base fee
+        let rewards = vec![5.into(), 1.into(), 9.into()];
+        assert_eq!(policy.fees(100.into(), rewards), (205.into(), 5.into()));
+
+        // priority fee capped
+        let rewards = vec![50.into()];
+        assert_eq!(policy.fees(100.into(), rewards), (220.into(), 20.into()));
+
+        // max fee capped
+        let rewards = vec![5.into()];
+        assert_eq!(policy.fees(1000.into(), rewards), (500.into(), 5.into()));
+
+        assert!(GasConf::default().eip1559_policy().is_none());
+    }
+}
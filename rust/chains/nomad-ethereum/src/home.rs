@@ -70,6 +70,15 @@ where
         Ok(self.provider.get_block_number().await?.as_u32())
     }
 
+    #[instrument(err, skip(self))]
+    async fn get_block_hash(&self, height: u32) -> Result<Option<H256>> {
+        Ok(self
+            .provider
+            .get_block(height as u64)
+            .await?
+            .and_then(|block| block.hash))
+    }
+
     #[instrument(err, skip(self))]
     async fn fetch_sorted_updates(&self, from: u32, to: u32) -> Result<Vec<SignedUpdateWithMeta>> {
         let mut events = self
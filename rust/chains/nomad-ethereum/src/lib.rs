@@ -17,6 +17,10 @@ mod macros;
 mod retrying;
 pub use retrying::{RetryingProvider, RetryingProviderError};
 
+/// Quorum and failover Provider
+mod quorum;
+pub use quorum::{QuorumProvider, QuorumProviderError};
+
 /// Contract binding
 #[cfg(not(doctest))]
 pub(crate) mod bindings;
@@ -35,9 +39,18 @@ mod xapp;
 
 /// Gas increasing Middleware
 mod gas;
+pub use gas::{
+    Eip1559Policy, FeeCaps, GasAdjusterMiddleware, GasAdjusterMiddlewareError, GasConf, TxType,
+};
+
+/// Nonce management and stuck transaction replacement Middleware
+mod tx_manager;
+pub use tx_manager::{
+    InFlightTx, TxManager, TxManagerConf, TxManagerMiddleware, TxManagerMiddlewareError, TxManagers,
+};
 
 /// Ethereum connection configuration
-#[derive(Debug, serde::Deserialize, Clone)]
+#[derive(Debug, serde::Deserialize, Clone, PartialEq)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum Connection {
     /// HTTP connection details
@@ -50,6 +63,32 @@ pub enum Connection {
         /// Fully qualified string to connect to
         url: String,
     },
+    /// Several HTTP endpoints with failover and optional quorum reads
+    Quorum {
+        /// Endpoints to connect to
+        endpoints: Vec<Endpoint>,
+        /// Combined weight of endpoints that must agree on contract reads and
+        /// logs. Reads are not checked for agreement if unset
+        quorum: Option<String>,
+        /// Seconds before failing over to the next endpoint. Defaults to 10
+        timeout: Option<String>,
+    },
+}
+
+/// A weighted endpoint of a multi-endpoint connection
+#[derive(Debug, serde::Deserialize, Clone, PartialEq)]
+pub struct Endpoint {
+    /// Fully qualified string to connect to
+    pub url: String,
+    /// Weight of the endpoint. Defaults to 1
+    pub weight: Option<String>,
+}
+
+impl Endpoint {
+    /// Get the weight of the endpoint
+    pub fn weight(&self) -> Result<u64, std::num::ParseIntError> {
+        self.weight.as_deref().map_or(Ok(1), str::parse)
+    }
 }
 
 impl Default for Connection {
@@ -63,11 +102,41 @@ impl Default for Connection {
 #[cfg(not(doctest))]
 pub use crate::{home::*, replica::*, xapp::*};
 
-#[allow(dead_code)]
 /// A live connection to an ethereum-compatible chain.
-pub struct Chain {
-    creation_metadata: Connection,
-    ethers: ethers::providers::Provider<ethers::providers::Http>,
+#[derive(Debug)]
+pub struct Chain<M> {
+    provider: Arc<M>,
+}
+
+impl<M> Chain<M> {
+    /// Wrap a provider
+    pub fn new(provider: Arc<M>) -> Self {
+        Self { provider }
+    }
+}
+
+/// Connect to the chain at `conn`
+pub async fn make_chain(conn: Connection) -> Result<Box<dyn nomad_core::Chain + Send + Sync>> {
+    Ok(match conn {
+        Connection::Http { url } => {
+            let provider: RetryingProvider<Http> = url.parse()?;
+            Box::new(Chain::new(Arc::new(Provider::new(provider))))
+        }
+        Connection::Ws { url } => {
+            let ws = Ws::connect(url).await?;
+            Box::new(Chain::new(Arc::new(Provider::new(ws))))
+        }
+        Connection::Quorum {
+            endpoints,
+            quorum,
+            timeout,
+        } => {
+            let provider: QuorumProvider<Http> =
+                QuorumProvider::from_endpoints(&endpoints, quorum.as_deref(), timeout.as_deref())?;
+            let provider = RetryingProvider::new(provider, 6);
+            Box::new(Chain::new(Arc::new(Provider::new(provider))))
+        }
+    })
 }
 
 boxed_trait!(
@@ -84,7 +153,12 @@ boxed_trait!(
     from_height: u32,
     chunk_size: u32
 );
-boxed_trait!(make_replica, EthereumReplica, Replica,);
+boxed_trait!(
+    make_replica,
+    EthereumReplica,
+    Replica,
+    multicall: Option<ethers::types::Address>
+);
 boxed_trait!(make_home, EthereumHome, Home,);
 boxed_trait!(
     make_conn_manager,
@@ -93,11 +167,14 @@ boxed_trait!(
 );
 
 #[async_trait::async_trait]
-impl nomad_core::Chain for Chain {
+impl<M> nomad_core::Chain for Chain<M>
+where
+    M: Middleware + 'static,
+{
     async fn query_balance(&self, addr: nomad_core::Address) -> Result<nomad_core::Balance> {
         let balance = format!(
             "{:x}",
-            self.ethers
+            self.provider
                 .get_balance(
                     NameOrAddress::Address(H160::from_slice(&addr.0[..])),
                     Some(BlockId::Number(BlockNumber::Latest))
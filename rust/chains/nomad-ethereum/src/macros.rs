@@ -36,16 +36,17 @@ macro_rules! report_tx {
     }};
 
     // Legacy way of sending transactions.
-    (@legacy $tx:expr, $($tail:tt)*) => {{
+    (@legacy $tx:expr, $provider:expr) => {{
         log_tx_details!($tx);
 
         let dispatch_fut = $tx.send();
         let dispatched = dispatch_fut.await?;
 
         let tx_hash: ethers::core::types::H256 = *dispatched;
-        let result = dispatched
+        let mut result = dispatched
             .await?
             .ok_or_else(|| nomad_core::ChainCommunicationError::DroppedError(tx_hash))?;
+        crate::gas::fill_effective_gas_price(&**$provider, &mut result).await;
 
         tracing::info!(
             "confirmed transaction with tx_hash {:?}",
@@ -72,7 +73,7 @@ macro_rules! log_tx_details {
 }
 
 macro_rules! boxed_trait {
-    (@finish $provider:expr, $abi:ident, $signer:ident, $($tail:tt)*) => {{
+    (@finish $provider:expr, $abi:ident, $signer:ident, $gas_conf:ident, $tx_conf:ident, $tx_managers:ident, $($tail:tt)*) => {{
         if let Some(signer) = $signer {
             // If there's a provided signer, we want to manage every aspect
             // locally
@@ -81,19 +82,26 @@ macro_rules! boxed_trait {
             let provider_chain_id = $provider.get_chainid().await?;
             let signer = ethers::signers::Signer::with_chain_id(signer, provider_chain_id.as_u64());
 
-            // Manage the nonce locally
             let address = ethers::prelude::Signer::address(&signer);
-            let provider =
-                ethers::middleware::nonce_manager::NonceManagerMiddleware::new($provider, address);
 
-            // Kludge. Increase the gas by multiplication of every estimated gas by 2
-            // except the gas for chain id 1 (Ethereum Mainnet)
-            let provider = crate::gas::GasAdjusterMiddleware::with_default_policy(provider, provider_chain_id.as_u64());
+            // Adjust gas estimates and fees using the chain's fee policy
+            let provider = crate::gas::GasAdjusterMiddleware::with_conf($provider, provider_chain_id.as_u64(), &$gas_conf);
 
             // Manage signing locally
             let signing_provider = ethers::middleware::SignerMiddleware::new(provider, signer);
 
-            Box::new(crate::$abi::new(signing_provider.into(), $($tail)*))
+            // Manage the nonce locally and replace stuck transactions. This
+            // sits above the signer so replacements are signed anew. Every
+            // contract of the signer on this chain shares the same manager
+            let manager = $tx_managers.get(provider_chain_id.as_u64(), address, &$tx_conf);
+            let managed_provider = Arc::new(crate::tx_manager::TxManagerMiddleware::new(
+                signing_provider,
+                manager,
+                $gas_conf.fee_caps(),
+            ));
+            managed_provider.spawn_resume();
+
+            Box::new(crate::$abi::new(managed_provider, $($tail)*))
         } else {
             Box::new(crate::$abi::new($provider, $($tail)*))
         }
@@ -121,15 +129,29 @@ macro_rules! boxed_trait {
             boxed_trait!(@finish provider, $($tail)*)
         }
     }};
+    (@quorum $endpoints:expr, $quorum:expr, $timeout:expr, $timelag:ident, $($tail:tt)*) => {{
+        let provider: crate::quorum::QuorumProvider<ethers::providers::Http> =
+            crate::quorum::QuorumProvider::from_endpoints(&$endpoints, $quorum.as_deref(), $timeout.as_deref())?;
+        let provider = crate::retrying::RetryingProvider::new(provider, 6);
+        let provider = Arc::new(ethers::providers::Provider::new(provider));
+        if let Some(lag) = $timelag {
+            boxed_trait!(@timelag provider, lag, $($tail)*)
+        } else {
+            boxed_trait!(@finish provider, $($tail)*)
+        }
+    }};
     ($name:ident, $abi:ident, $trait:ident, $($n:ident:$t:ty),*)  => {
         #[doc = "Cast a contract locator to a live contract handle"]
-        pub async fn $name(conn: Connection, locator: &ContractLocator, signer: Option<Signers>, timelag: Option<u8>, $($n:$t),*) -> color_eyre::Result<Box<dyn $trait>> {
+        pub async fn $name(conn: Connection, locator: &ContractLocator, signer: Option<Signers>, timelag: Option<u8>, gas_conf: GasConf, tx_conf: TxManagerConf, tx_managers: &TxManagers, $($n:$t),*) -> color_eyre::Result<Box<dyn $trait>> {
             let b: Box<dyn $trait> = match conn {
                 Connection::Http { url } => {
-                    boxed_trait!(@http url, timelag, $abi, signer, locator, $($n),*)
+                    boxed_trait!(@http url, timelag, $abi, signer, gas_conf, tx_conf, tx_managers, locator, $($n),*)
                 }
                 Connection::Ws { url } => {
-                    boxed_trait!(@ws url, timelag, $abi, signer, locator, $($n),*)
+                    boxed_trait!(@ws url, timelag, $abi, signer, gas_conf, tx_conf, tx_managers, locator, $($n),*)
+                }
+                Connection::Quorum { endpoints, quorum, timeout } => {
+                    boxed_trait!(@quorum endpoints, quorum, timeout, timelag, $abi, signer, gas_conf, tx_conf, tx_managers, locator, $($n),*)
                 }
             };
             Ok(b)
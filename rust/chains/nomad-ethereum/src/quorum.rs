@@ -0,0 +1,449 @@
+use std::{
+    fmt::Debug,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use color_eyre::eyre::{ensure, Result as EyreResult, WrapErr};
+use ethers::providers::{JsonRpcClient, ProviderError};
+use ethers::types::U64;
+use futures_util::future::join_all;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tokio::time::timeout;
+use tracing::{debug, instrument, warn};
+
+use crate::Endpoint;
+
+/// Methods whose results must be agreed upon by a quorum of providers, if a
+/// quorum is configured. These back contract reads (`committed_root`,
+/// `queue_contains`, `message_status`, ...) and the logs indexed by
+/// `ContractSync`.
+const QUORUM_METHODS: &[&str] = &["eth_call", "eth_getLogs"];
+
+#[derive(Debug)]
+struct WeightedProvider<P> {
+    inner: P,
+    weight: u64,
+}
+
+/// A provider dispatching requests over several weighted JSON-RPC clients.
+///
+/// Requests are sent to the preferred client and fail over to the next one on
+/// error or timeout. Reads listed in `QUORUM_METHODS` are sent to all clients
+/// concurrently and only succeed if clients totalling at least `quorum`
+/// weight return the same result. Reads of the `latest` block are pinned to
+/// the lowest block number reported by the clients, so that clients at
+/// different heights answer for the same block.
+#[derive(Debug)]
+pub struct QuorumProvider<P> {
+    providers: Vec<WeightedProvider<P>>,
+    quorum: Option<u64>,
+    timeout: Duration,
+    preferred: AtomicUsize,
+}
+
+impl<P> QuorumProvider<P> {
+    /// Instantiate a QuorumProvider from `(client, weight)` pairs. Clients
+    /// with a higher weight are preferred. Errors if there are no clients or
+    /// the quorum exceeds their total weight.
+    pub fn new(
+        providers: Vec<(P, u64)>,
+        quorum: Option<u64>,
+        timeout: Duration,
+    ) -> EyreResult<Self> {
+        ensure!(!providers.is_empty(), "no providers");
+
+        let mut providers: Vec<_> = providers
+            .into_iter()
+            .map(|(inner, weight)| WeightedProvider { inner, weight })
+            .collect();
+        providers.sort_by(|a, b| b.weight.cmp(&a.weight));
+
+        if let Some(quorum) = quorum {
+            let total_weight: u64 = providers.iter().map(|p| p.weight).sum();
+            ensure!(
+                quorum <= total_weight,
+                "quorum of {} can never be reached with a total weight of {}",
+                quorum,
+                total_weight
+            );
+        }
+
+        Ok(Self {
+            providers,
+            quorum,
+            timeout,
+            preferred: AtomicUsize::new(0),
+        })
+    }
+
+    /// Get the quorum weight, if any
+    pub fn quorum(&self) -> Option<u64> {
+        self.quorum
+    }
+}
+
+impl<P> QuorumProvider<P>
+where
+    P: FromStr,
+    <P as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+{
+    /// Instantiate a QuorumProvider from connection settings
+    pub fn from_endpoints(
+        endpoints: &[Endpoint],
+        quorum: Option<&str>,
+        timeout: Option<&str>,
+    ) -> EyreResult<Self> {
+        let providers = endpoints
+            .iter()
+            .map(|endpoint| {
+                let provider = endpoint
+                    .url
+                    .parse()
+                    .wrap_err_with(|| format!("invalid endpoint url {}", endpoint.url))?;
+                let weight = endpoint
+                    .weight()
+                    .wrap_err_with(|| format!("invalid weight for endpoint {}", endpoint.url))?;
+                Ok((provider, weight))
+            })
+            .collect::<EyreResult<Vec<_>>>()?;
+        let quorum = quorum
+            .map(|q| q.parse().wrap_err_with(|| format!("invalid quorum {}", q)))
+            .transpose()?;
+        let timeout = timeout
+            .map(|t| t.parse().wrap_err_with(|| format!("invalid timeout {}", t)))
+            .transpose()?
+            .unwrap_or(10);
+
+        Self::new(providers, quorum, Duration::from_secs(timeout))
+    }
+}
+
+/// True if `params` read from the `latest` block
+fn reads_latest(params: &Value) -> bool {
+    match params {
+        Value::String(tag) => tag == "latest",
+        Value::Array(values) => values.iter().any(reads_latest),
+        Value::Object(map) => map.values().any(reads_latest),
+        _ => false,
+    }
+}
+
+/// Replace every `latest` block tag in `params` with `block`
+fn pin_latest(params: &mut Value, block: U64) {
+    match params {
+        Value::String(tag) if tag == "latest" => {
+            *params = serde_json::to_value(block).expect("valid");
+        }
+        Value::Array(values) => values.iter_mut().for_each(|v| pin_latest(v, block)),
+        Value::Object(map) => map.values_mut().for_each(|v| pin_latest(v, block)),
+        _ => {}
+    }
+}
+
+/// Error type for the QuorumProvider
+#[derive(Error, Debug)]
+pub enum QuorumProviderError<P>
+where
+    P: JsonRpcClient,
+{
+    /// Every provider errored or timed out
+    #[error("All providers failed ({} errors, {timeouts} timeouts)", .errors.len())]
+    AllProvidersFailed {
+        /// Errors returned by the providers
+        errors: Vec<P::Error>,
+        /// Number of providers that timed out
+        timeouts: usize,
+    },
+    /// Responding providers didn't agree
+    #[error("No quorum reached. Weights of distinct responses: {weights:?}")]
+    NoQuorum {
+        /// Total weight behind each distinct response
+        weights: Vec<u64>,
+        /// Errors returned by the providers
+        errors: Vec<P::Error>,
+    },
+    /// The agreed upon response could not be deserialized
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+}
+
+impl<P> From<QuorumProviderError<P>> for ProviderError
+where
+    P: JsonRpcClient + 'static,
+    <P as JsonRpcClient>::Error: Send + Sync,
+{
+    fn from(src: QuorumProviderError<P>) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(src))
+    }
+}
+
+impl<P> QuorumProvider<P>
+where
+    P: JsonRpcClient + 'static,
+    <P as JsonRpcClient>::Error: Send + Sync,
+{
+    async fn dispatch(
+        &self,
+        provider: &WeightedProvider<P>,
+        method: &str,
+        params: &Value,
+    ) -> Option<Result<Value, P::Error>> {
+        let fut = match params {
+            Value::Null => provider.inner.request(method, ()),
+            _ => provider.inner.request(method, params),
+        };
+        timeout(self.timeout, fut).await.ok()
+    }
+
+    async fn request_with_failover(
+        &self,
+        method: &str,
+        params: &Value,
+    ) -> Result<Value, QuorumProviderError<P>> {
+        let mut errors = vec![];
+        let mut timeouts = 0;
+
+        let preferred = self.preferred.load(Ordering::Relaxed);
+        for i in 0..self.providers.len() {
+            let index = (preferred + i) % self.providers.len();
+            debug!(provider = index, "Dispatching request");
+
+            match self.dispatch(&self.providers[index], method, params).await {
+                Some(Ok(res)) => {
+                    if index != preferred {
+                        warn!(from = preferred, to = index, "Failed over to provider");
+                        self.preferred.store(index, Ordering::Relaxed);
+                    }
+                    return Ok(res);
+                }
+                Some(Err(e)) => {
+                    warn!(provider = index, error = %e, method = %method, "Error in quorum provider");
+                    errors.push(e);
+                }
+                None => {
+                    warn!(provider = index, method = %method, "Timeout in quorum provider");
+                    timeouts += 1;
+                }
+            }
+        }
+
+        Err(QuorumProviderError::AllProvidersFailed { errors, timeouts })
+    }
+
+    /// The lowest block number reported by the clients, which all clients
+    /// responding can serve
+    async fn common_block_number(&self) -> Result<U64, QuorumProviderError<P>> {
+        let responses = join_all(
+            self.providers
+                .iter()
+                .map(|provider| self.dispatch(provider, "eth_blockNumber", &Value::Null)),
+        )
+        .await;
+
+        let mut errors = vec![];
+        let mut timeouts = 0;
+        let mut lowest: Option<U64> = None;
+        for response in responses {
+            match response {
+                Some(Ok(value)) => {
+                    let block: U64 = serde_json::from_value(value)?;
+                    lowest = Some(lowest.map_or(block, |lowest| lowest.min(block)));
+                }
+                Some(Err(e)) => errors.push(e),
+                None => timeouts += 1,
+            }
+        }
+        lowest.ok_or(QuorumProviderError::AllProvidersFailed { errors, timeouts })
+    }
+
+    async fn request_with_quorum(
+        &self,
+        method: &str,
+        params: &Value,
+        quorum: u64,
+    ) -> Result<Value, QuorumProviderError<P>> {
+        let pinned = if reads_latest(params) {
+            let block = self.common_block_number().await?;
+            debug!(block = %block, method = %method, "Pinned latest block");
+            let mut pinned = params.clone();
+            pin_latest(&mut pinned, block);
+            Some(pinned)
+        } else {
+            None
+        };
+        let params = pinned.as_ref().unwrap_or(params);
+
+        let responses = join_all(
+            self.providers
+                .iter()
+                .map(|provider| self.dispatch(provider, method, params)),
+        )
+        .await;
+
+        let mut errors = vec![];
+        let mut timeouts = 0;
+        let mut tallies: Vec<(Value, u64)> = vec![];
+        for (provider, response) in self.providers.iter().zip(responses) {
+            match response {
+                Some(Ok(value)) => match tallies.iter_mut().find(|(v, _)| *v == value) {
+                    Some((_, weight)) => *weight += provider.weight,
+                    None => tallies.push((value, provider.weight)),
+                },
+                Some(Err(e)) => errors.push(e),
+                None => timeouts += 1,
+            }
+        }
+
+        if tallies.is_empty() {
+            return Err(QuorumProviderError::AllProvidersFailed { errors, timeouts });
+        }
+
+        match tallies.iter().position(|(_, weight)| *weight >= quorum) {
+            Some(index) => Ok(tallies.swap_remove(index).0),
+            None => {
+                let weights = tallies.iter().map(|(_, weight)| *weight).collect();
+                warn!(method = %method, weights = ?weights, "No quorum reached");
+                Err(QuorumProviderError::NoQuorum { weights, errors })
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<P> JsonRpcClient for QuorumProvider<P>
+where
+    P: JsonRpcClient + 'static,
+    <P as JsonRpcClient>::Error: Send + Sync,
+{
+    type Error = QuorumProviderError<P>;
+
+    #[instrument(
+        level = "debug",
+        err,
+        skip(self, params),
+        fields(params = %serde_json::to_string(&params).unwrap()))
+    ]
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let params = serde_json::to_value(params).expect("valid");
+
+        let value = match self.quorum {
+            Some(quorum) if QUORUM_METHODS.contains(&method) => {
+                self.request_with_quorum(method, &params, quorum).await?
+            }
+            _ => self.request_with_failover(method, &params).await?,
+        };
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::providers::MockProvider;
+    use ethers::types::U256;
+
+    fn mock(response: Option<u64>) -> MockProvider {
+        let provider = MockProvider::new();
+        if let Some(response) = response {
+            provider.push(U256::from(response)).unwrap();
+        }
+        provider
+    }
+
+    #[tokio::test]
+    async fn it_fails_over() {
+        let provider = QuorumProvider::new(
+            vec![(mock(None), 2), (mock(Some(1)), 1)],
+            None,
+            Duration::from_secs(1),
+        )
+        .unwrap();
+
+        let res: U256 = provider.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(res, 1.into());
+        assert_eq!(provider.preferred.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn it_requires_quorum() {
+        let provider = QuorumProvider::new(
+            vec![(mock(Some(1)), 1), (mock(Some(2)), 1), (mock(Some(1)), 1)],
+            Some(2),
+            Duration::from_secs(1),
+        )
+        .unwrap();
+        let res: U256 = provider.request("eth_call", ()).await.unwrap();
+        assert_eq!(res, 1.into());
+
+        let provider = QuorumProvider::new(
+            vec![(mock(Some(1)), 1), (mock(Some(2)), 1), (mock(None), 1)],
+            Some(2),
+            Duration::from_secs(1),
+        )
+        .unwrap();
+        let res: Result<U256, _> = provider.request("eth_call", ()).await;
+        assert!(matches!(res, Err(QuorumProviderError::NoQuorum { .. })));
+    }
+
+    #[tokio::test]
+    async fn it_pins_latest_reads() {
+        let mock = |block: u64| {
+            let provider = MockProvider::new();
+            provider.push(U64::from(block)).unwrap();
+            provider.push(U256::from(1)).unwrap();
+            provider
+        };
+        let provider = QuorumProvider::new(
+            vec![(mock(12), 1), (mock(10), 1), (mock(11), 1)],
+            Some(2),
+            Duration::from_secs(1),
+        )
+        .unwrap();
+
+        let res: U256 = provider
+            .request("eth_call", (serde_json::json!({}), "latest"))
+            .await
+            .unwrap();
+        assert_eq!(res, 1.into());
+        for provider in provider.providers.iter() {
+            provider
+                .inner
+                .assert_request("eth_blockNumber", ())
+                .unwrap();
+            provider
+                .inner
+                .assert_request("eth_call", (serde_json::json!({}), U64::from(10)))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn it_rejects_invalid_config() {
+        assert!(QuorumProvider::<MockProvider>::new(vec![], None, Duration::from_secs(1)).is_err());
+        assert!(QuorumProvider::new(
+            vec![(mock(None), 1), (mock(None), 1)],
+            Some(3),
+            Duration::from_secs(1)
+        )
+        .is_err());
+
+        let endpoints = [Endpoint {
+            url: "http://localhost:8545".to_owned(),
+            weight: Some("heavy".to_owned()),
+        }];
+        assert!(
+            QuorumProvider::<ethers::providers::Http>::from_endpoints(&endpoints, None, None)
+                .is_err()
+        );
+    }
+}
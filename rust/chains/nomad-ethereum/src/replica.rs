@@ -3,18 +3,40 @@
 
 use async_trait::async_trait;
 use color_eyre::Result;
-use ethers::core::types::{Signature, H256};
+use ethers::contract::Multicall;
+use ethers::core::types::{Address, Signature, H256, U256};
+use ethers::providers::PendingTransaction;
 use futures_util::future::join_all;
 use nomad_core::{
-    accumulator::merkle::Proof, ChainCommunicationError, Common, CommonIndexer, ContractLocator,
-    DoubleUpdate, Encode, MessageStatus, NomadMessage, Replica, SignedUpdate, SignedUpdateWithMeta,
-    State, TxOutcome, Update, UpdateMeta,
+    accumulator::merkle::Proof, BatchOutcomes, ChainCommunicationError, Common, CommonIndexer,
+    ContractLocator, DoubleUpdate, Encode, MessageStatus, NomadMessage, Replica, SignedUpdate,
+    SignedUpdateWithMeta, State, TxOutcome, Update, UpdateMeta,
 };
 use std::{convert::TryFrom, error::Error as StdError, sync::Arc};
 use tracing::instrument;
 
 use crate::{bindings::replica::Replica as EthereumReplicaInternal, report_tx};
 
+/// The most calls the multicall contract accepts in a single aggregate
+const MAX_MULTICALL_CALLS: usize = 16;
+
+/// Encode a proof path as the fixed-size array expected by the contract
+fn sol_proof(proof: &Proof) -> [[u8; 32]; 32] {
+    let mut sol_proof: [[u8; 32]; 32] = Default::default();
+    sol_proof
+        .iter_mut()
+        .enumerate()
+        .for_each(|(i, elem)| *elem = proof.path[i].to_fixed_bytes());
+    sol_proof
+}
+
+/// A single call within a batched submission
+enum BatchCall<'a> {
+    Prove(&'a Proof),
+    Process(usize, &'a NomadMessage),
+    Update(&'a SignedUpdate),
+}
+
 #[derive(Debug)]
 /// Struct that retrieves indexes event data for Ethereum replica
 pub struct EthereumReplicaIndexer<M>
@@ -61,6 +83,15 @@ where
         Ok(self.provider.get_block_number().await?.as_u32())
     }
 
+    #[instrument(err, skip(self))]
+    async fn get_block_hash(&self, height: u32) -> Result<Option<H256>> {
+        Ok(self
+            .provider
+            .get_block(height as u64)
+            .await?
+            .and_then(|block| block.hash))
+    }
+
     #[instrument(err, skip(self))]
     async fn fetch_sorted_updates(&self, from: u32, to: u32) -> Result<Vec<SignedUpdateWithMeta>> {
         let mut events = self
@@ -125,6 +156,7 @@ where
     domain: u32,
     name: String,
     provider: Arc<M>,
+    multicall: Option<Address>,
 }
 
 impl<M> EthereumReplica<M>
@@ -132,7 +164,8 @@ where
     M: ethers::providers::Middleware,
 {
     /// Create a reference to a Replica at a specific Ethereum address on some
-    /// chain
+    /// chain. Batched submissions go through the multicall contract at
+    /// `multicall`, or the well-known deployment for the chain if `None`.
     pub fn new(
         provider: Arc<M>,
         ContractLocator {
@@ -140,13 +173,72 @@ where
             domain,
             address,
         }: &ContractLocator,
+        multicall: Option<Address>,
     ) -> Self {
         Self {
             contract: Arc::new(EthereumReplicaInternal::new(address, provider.clone())),
             domain: *domain,
             name: name.to_owned(),
             provider,
+            multicall,
+        }
+    }
+}
+
+impl<M> EthereumReplica<M>
+where
+    M: ethers::providers::Middleware + 'static,
+{
+    /// Timestamp at which `root` becomes acceptable, or `None` if the
+    /// replica has not received an update to `root`
+    pub async fn confirm_at(&self, root: H256) -> Result<Option<u64>, ChainCommunicationError> {
+        let confirm_at = self.contract.confirm_at(root.into()).call().await?;
+        Ok(Some(confirm_at.as_u64()).filter(|at| *at != 0))
+    }
+
+    /// Aggregate `calls` into a single multicall transaction and wait for
+    /// its receipt
+    async fn send_multicall(
+        &self,
+        calls: &[BatchCall<'_>],
+    ) -> Result<TxOutcome, ChainCommunicationError> {
+        let mut multicall = Multicall::new(self.provider.clone(), self.multicall).await?;
+        for call in calls {
+            match call {
+                BatchCall::Prove(proof) => multicall.add_call(self.contract.prove(
+                    proof.leaf.into(),
+                    sol_proof(proof),
+                    proof.index.into(),
+                )),
+                BatchCall::Process(_, message) => {
+                    multicall.add_call(self.contract.process(message.to_vec().into()))
+                }
+                BatchCall::Update(update) => multicall.add_call(self.contract.update(
+                    update.update.previous_root.to_fixed_bytes(),
+                    update.update.new_root.to_fixed_bytes(),
+                    update.signature.to_vec().into(),
+                )),
+            };
         }
+
+        let tx_hash = multicall.send().await?;
+        tracing::info!(
+            tx_hash = ?tx_hash,
+            calls = calls.len(),
+            "Dispatched multicall transaction"
+        );
+
+        let mut receipt = PendingTransaction::new(tx_hash, self.provider.provider())
+            .await?
+            .ok_or(ChainCommunicationError::DroppedError(tx_hash))?;
+        crate::gas::fill_effective_gas_price(&*self.provider, &mut receipt).await;
+
+        tracing::info!(
+            "confirmed transaction with tx_hash {:?}",
+            receipt.transaction_hash
+        );
+
+        Ok(receipt.into())
     }
 }
 
@@ -237,15 +329,9 @@ where
 
     #[tracing::instrument(err)]
     async fn prove(&self, proof: &Proof) -> Result<TxOutcome, ChainCommunicationError> {
-        let mut sol_proof: [[u8; 32]; 32] = Default::default();
-        sol_proof
-            .iter_mut()
-            .enumerate()
-            .for_each(|(i, elem)| *elem = proof.path[i].to_fixed_bytes());
-
         let tx = self
             .contract
-            .prove(proof.leaf.into(), sol_proof, proof.index.into());
+            .prove(proof.leaf.into(), sol_proof(proof), proof.index.into());
 
         Ok(report_tx!(tx, &self.provider).into())
     }
@@ -262,18 +348,150 @@ where
         message: &NomadMessage,
         proof: &Proof,
     ) -> Result<TxOutcome, ChainCommunicationError> {
-        let mut sol_proof: [[u8; 32]; 32] = Default::default();
-        sol_proof
-            .iter_mut()
-            .enumerate()
-            .for_each(|(i, elem)| *elem = proof.path[i].to_fixed_bytes());
-
-        let tx =
-            self.contract
-                .prove_and_process(message.to_vec().into(), sol_proof, proof.index.into());
+        let tx = self.contract.prove_and_process(
+            message.to_vec().into(),
+            sol_proof(proof),
+            proof.index.into(),
+        );
         Ok(report_tx!(tx, &self.provider).into())
     }
 
+    /// Proofs and messages are aggregated through the multicall contract, in
+    /// chunks of at most `MAX_MULTICALL_CALLS` calls. The aggregate reverts
+    /// as a whole if any call fails, so messages left unprocessed by a
+    /// failed chunk are resubmitted individually to learn their own outcome.
+    ///
+    /// Batched calls reach the replica through the multicall contract, so it
+    /// is `msg.sender` of `prove` and `process` rather than the processor's
+    /// signer. The replica doesn't restrict who proves or processes, and
+    /// recipients' `handle` is called by the replica either way.
+    #[tracing::instrument(err, skip(proofs, messages))]
+    async fn prove_and_process_batch(
+        &self,
+        proofs: &[Proof],
+        messages: &[NomadMessage],
+    ) -> Result<BatchOutcomes, ChainCommunicationError> {
+        let calls: Vec<_> = proofs
+            .iter()
+            .map(BatchCall::Prove)
+            .chain(
+                messages
+                    .iter()
+                    .enumerate()
+                    .map(|(i, message)| BatchCall::Process(i, message)),
+            )
+            .collect();
+
+        // the outcome of the chunk that carried each message's process call
+        let mut batch_outcomes: Vec<Option<TxOutcome>> = vec![None; messages.len()];
+        let mut proof_outcomes = vec![];
+        for chunk in calls.chunks(MAX_MULTICALL_CALLS) {
+            let outcome = match self.send_multicall(chunk).await {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    tracing::warn!(error = %e, "Batched prove and process submission failed");
+                    continue;
+                }
+            };
+
+            // the chunk's gas is accounted to its first message only, or to
+            // its proofs if it processes none
+            let mut outcome = outcome;
+            let mut processes = false;
+            for call in chunk {
+                if let BatchCall::Process(i, _) = call {
+                    batch_outcomes[*i] = Some(outcome);
+                    outcome.gas_used = U256::zero();
+                    processes = true;
+                }
+            }
+            if !processes {
+                proof_outcomes.push(outcome);
+            }
+        }
+
+        let mut outcomes = Vec::with_capacity(messages.len());
+        for (message, batch_outcome) in messages.iter().zip(batch_outcomes) {
+            let leaf = message.to_leaf();
+            let outcome = match (batch_outcome, self.message_status(leaf).await) {
+                (Some(tx), Ok(MessageStatus::Processed)) => Ok(TxOutcome {
+                    executed: true,
+                    ..tx
+                }),
+                (_, Err(e)) => Err(e),
+                (_, Ok(MessageStatus::Processed)) => Ok(TxOutcome {
+                    executed: true,
+                    ..Default::default()
+                }),
+                (_, Ok(MessageStatus::Proven)) => self.process(message).await,
+                (_, Ok(MessageStatus::None)) => match proofs.iter().find(|p| p.leaf == leaf) {
+                    Some(proof) => self.prove_and_process(message, proof).await,
+                    None => self.process(message).await,
+                },
+            };
+            outcomes.push(outcome);
+        }
+
+        Ok(BatchOutcomes {
+            proofs: proof_outcomes,
+            messages: outcomes,
+        })
+    }
+
+    /// The replica accepts updates from any sender, so consecutive updates
+    /// are aggregated through the multicall contract, in chunks of at most
+    /// `MAX_MULTICALL_CALLS` updates. Every update in a chunk is confirmed
+    /// within the same block, so they share a single optimistic window. If
+    /// the multicall can't be built or sent, the chunk's updates are
+    /// submitted in a transaction each instead.
+    #[tracing::instrument(err, skip(updates), fields(updates = updates.len()))]
+    async fn update_chain(
+        &self,
+        updates: &[SignedUpdate],
+    ) -> Result<Vec<TxOutcome>, ChainCommunicationError> {
+        let mut outcomes = Vec::with_capacity(updates.len());
+        'chunks: for chunk in updates.chunks(MAX_MULTICALL_CALLS) {
+            if chunk.len() > 1 {
+                let calls: Vec<_> = chunk.iter().map(BatchCall::Update).collect();
+                match self.send_multicall(&calls).await {
+                    Ok(mut outcome) => {
+                        // the chunk's gas is accounted to its first update only
+                        for _ in chunk {
+                            outcomes.push(outcome);
+                            outcome.gas_used = U256::zero();
+                        }
+                        if !outcome.executed {
+                            break;
+                        }
+                        continue;
+                    }
+                    Err(e) => tracing::warn!(
+                        error = %e,
+                        "Batched update submission failed, submitting updates individually"
+                    ),
+                }
+            }
+
+            for update in chunk {
+                match self.update(update).await {
+                    Ok(outcome) => {
+                        outcomes.push(outcome);
+                        if !outcome.executed {
+                            break 'chunks;
+                        }
+                    }
+                    Err(e) if outcomes.is_empty() => return Err(e),
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Update submission failed, stopping early");
+                        break 'chunks;
+                    }
+                }
+            }
+        }
+
+        Ok(outcomes)
+    }
+
     #[tracing::instrument(err)]
     async fn message_status(&self, leaf: H256) -> Result<MessageStatus, ChainCommunicationError> {
         let status = self.contract.messages(leaf.into()).call().await?;
@@ -289,3 +507,115 @@ where
         Ok(self.contract.acceptable_root(root.into()).call().await?)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::providers::{JsonRpcClient, MockError, Provider};
+    use ethers::types::{Transaction, TransactionReceipt};
+    use serde::{de::DeserializeOwned, Serialize};
+    use serde_json::{json, Value};
+    use std::{fmt, time::Duration};
+
+    /// A chain at block 1 that includes every transaction it is sent, except
+    /// those to the multicall contract, which fail gas estimation. Records
+    /// the recipient of every transaction sent.
+    #[derive(Debug)]
+    struct MockChain {
+        multicall: Address,
+        sent: Arc<std::sync::Mutex<Vec<Address>>>,
+    }
+
+    #[async_trait]
+    impl JsonRpcClient for MockChain {
+        type Error = MockError;
+
+        async fn request<T, R>(&self, method: &str, params: T) -> Result<R, MockError>
+        where
+            T: fmt::Debug + Serialize + Send + Sync,
+            R: DeserializeOwned,
+        {
+            let params = serde_json::to_value(params)?;
+            let to = || -> Result<Address, MockError> {
+                Ok(serde_json::from_value(params[0]["to"].clone())?)
+            };
+            let response = match method {
+                "eth_blockNumber" | "eth_gasPrice" | "eth_chainId" => json!(U256::one()),
+                "eth_estimateGas" if to()? == self.multicall => {
+                    return Err(MockError::EmptyResponses)
+                }
+                "eth_estimateGas" => json!(U256::from(100_000)),
+                "eth_sendTransaction" => {
+                    let mut sent = self.sent.lock().unwrap();
+                    sent.push(to()?);
+                    json!(H256::from_low_u64_be(sent.len() as u64))
+                }
+                "eth_getTransactionByHash" => json!(Transaction {
+                    hash: serde_json::from_value(params[0].clone())?,
+                    block_number: Some(1.into()),
+                    gas_price: Some(U256::one()),
+                    ..Default::default()
+                }),
+                "eth_getTransactionReceipt" => json!(TransactionReceipt {
+                    transaction_hash: serde_json::from_value(params[0].clone())?,
+                    block_number: Some(1.into()),
+                    status: Some(1.into()),
+                    gas_used: Some(U256::from(100_000)),
+                    effective_gas_price: Some(U256::one()),
+                    ..Default::default()
+                }),
+                _ => Value::Null,
+            };
+            Ok(serde_json::from_value(response)?)
+        }
+    }
+
+    fn signed_update(previous_root: H256, new_root: H256) -> SignedUpdate {
+        SignedUpdate {
+            update: Update {
+                home_domain: 1000,
+                previous_root,
+                new_root,
+            },
+            signature: Signature {
+                r: U256::one(),
+                s: U256::one(),
+                v: 27,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn it_submits_updates_individually_when_multicall_fails() {
+        let address = Address::repeat_byte(1);
+        let multicall = Address::repeat_byte(2);
+        let sent: Arc<std::sync::Mutex<Vec<Address>>> = Default::default();
+
+        let provider = Provider::new(MockChain {
+            multicall,
+            sent: sent.clone(),
+        })
+        .interval(Duration::from_millis(1));
+        let replica = EthereumReplica::new(
+            Arc::new(provider),
+            &ContractLocator {
+                name: "replica".into(),
+                domain: 2000,
+                address: address.into(),
+            },
+            Some(multicall),
+        );
+
+        let roots: Vec<_> = (0..3).map(H256::from_low_u64_be).collect();
+        let updates = vec![
+            signed_update(roots[0], roots[1]),
+            signed_update(roots[1], roots[2]),
+        ];
+
+        let outcomes = replica.update_chain(&updates).await.unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(|outcome| outcome.executed));
+        // each update was sent to the replica in its own transaction
+        assert_eq!(*sent.lock().unwrap(), vec![address, address]);
+    }
+}
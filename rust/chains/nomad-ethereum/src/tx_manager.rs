@@ -0,0 +1,842 @@
+use ethers::providers::{FromErr, Middleware, PendingTransaction};
+use ethers::types::{
+    transaction::eip2718::TypedTransaction, Address, BlockId, BlockNumber, Bytes,
+    Eip1559TransactionRequest, NameOrAddress, TransactionRequest, H256, U256,
+};
+use nomad_core::db::{DbError, TypedDB, DB};
+use nomad_core::{Decode, Encode, NomadError};
+
+use crate::FeeCaps;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tracing::{info, instrument, warn, Instrument};
+
+static IN_FLIGHT: &str = "in_flight_";
+
+/// Minimum fee bump, in percent, nodes accept for a replacement
+const MIN_GAS_BUMP_PERCENT: u64 = 10;
+
+/// Transaction manager configuration. Shared by every agent that submits
+/// transactions through a locally managed signer.
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TxManagerConf {
+    /// Number of blocks a transaction may stay pending before it is
+    /// considered stuck and replaced
+    stuck_after_blocks: Option<String>,
+    /// Percentage by which fees are bumped on every replacement
+    gas_bump_percent: Option<String>,
+    /// Maximum number of replacements sent for a single nonce
+    max_replacements: Option<String>,
+    /// Seconds between inclusion checks of a pending transaction
+    polling_interval: Option<String>,
+}
+
+impl TxManagerConf {
+    /// Get the `stuck_after_blocks` setting
+    pub fn stuck_after_blocks(&self) -> u64 {
+        self.stuck_after_blocks
+            .as_ref()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(10)
+    }
+
+    /// Get the `gas_bump_percent` setting. Nodes reject replacements that
+    /// bump fees by less than 10%.
+    pub fn gas_bump_percent(&self) -> u64 {
+        self.gas_bump_percent
+            .as_ref()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(15)
+            .max(MIN_GAS_BUMP_PERCENT)
+    }
+
+    /// Get the `max_replacements` setting
+    pub fn max_replacements(&self) -> u32 {
+        self.max_replacements
+            .as_ref()
+            .and_then(|s| s.parse::<u32>().ok())
+            .unwrap_or(5)
+    }
+
+    /// Get the `polling_interval` setting
+    pub fn polling_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.polling_interval
+                .as_ref()
+                .and_then(|s| s.parse::<u64>().ok())
+                .unwrap_or(15),
+        )
+    }
+}
+
+/// A submitted transaction which has not been included yet
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InFlightTx {
+    /// Nonce of the transaction
+    pub nonce: U256,
+    /// Hashes of the original transaction and all of its replacements
+    pub hashes: Vec<H256>,
+    /// Block number at which the latest replacement was broadcast
+    pub sent_at_block: u64,
+    /// Number of replacements broadcast so far
+    pub replacements: u32,
+    /// Recipient
+    pub to: Option<Address>,
+    /// Calldata
+    pub data: Option<Bytes>,
+    /// Value transferred
+    pub value: Option<U256>,
+    /// Gas limit
+    pub gas: Option<U256>,
+    /// Legacy gas price
+    pub gas_price: Option<U256>,
+    /// EIP-1559 max fee per gas
+    pub max_fee_per_gas: Option<U256>,
+    /// EIP-1559 max priority fee per gas
+    pub max_priority_fee_per_gas: Option<U256>,
+}
+
+impl InFlightTx {
+    fn new(tx: &TypedTransaction, tx_hash: H256, sent_at_block: u64) -> Self {
+        let (gas_price, max_fee_per_gas, max_priority_fee_per_gas) = match tx {
+            TypedTransaction::Eip1559(inner) => {
+                (None, inner.max_fee_per_gas, inner.max_priority_fee_per_gas)
+            }
+            _ => (tx.gas_price(), None, None),
+        };
+
+        Self {
+            nonce: tx.nonce().cloned().unwrap_or_default(),
+            hashes: vec![tx_hash],
+            sent_at_block,
+            replacements: 0,
+            to: match tx.to() {
+                Some(NameOrAddress::Address(address)) => Some(*address),
+                _ => None,
+            },
+            data: tx.data().cloned(),
+            value: tx.value().cloned(),
+            gas: tx.gas().cloned(),
+            gas_price,
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        }
+    }
+
+    /// True if the transaction was submitted as an EIP-1559 transaction
+    pub fn is_eip1559(&self) -> bool {
+        self.max_fee_per_gas.is_some()
+    }
+
+    /// The hash of the most recent broadcast
+    pub fn latest_hash(&self) -> H256 {
+        *self.hashes.last().expect("!hashes")
+    }
+
+    /// Rebuild the transaction request sent by `from`
+    pub fn to_tx(&self, from: Address) -> TypedTransaction {
+        if self.is_eip1559() {
+            let mut tx = Eip1559TransactionRequest::new()
+                .from(from)
+                .nonce(self.nonce);
+            tx.to = self.to.map(Into::into);
+            tx.data = self.data.clone();
+            tx.value = self.value;
+            tx.gas = self.gas;
+            tx.max_fee_per_gas = self.max_fee_per_gas;
+            tx.max_priority_fee_per_gas = self.max_priority_fee_per_gas;
+            tx.into()
+        } else {
+            let mut tx = TransactionRequest::new().from(from).nonce(self.nonce);
+            tx.to = self.to.map(Into::into);
+            tx.data = self.data.clone();
+            tx.value = self.value;
+            tx.gas = self.gas;
+            tx.gas_price = self.gas_price;
+            tx.into()
+        }
+    }
+
+    /// Bump all fees by `percent`, never going below `floor_price` for
+    /// legacy transactions nor above `caps`. Returns false, leaving the fees
+    /// untouched, if the caps keep the bump below what nodes accept for a
+    /// replacement.
+    fn bump_fees(&mut self, percent: u64, floor_price: U256, caps: &FeeCaps) -> bool {
+        let bump = |fee: U256| fee * (100 + percent) / 100 + 1;
+        let clamp = |fee: U256, cap: Option<U256>| cap.map_or(fee, |cap| fee.min(cap));
+        let replaces =
+            |old: U256, new: U256| new > old && new >= old * (100 + MIN_GAS_BUMP_PERCENT) / 100;
+
+        if self.is_eip1559() {
+            let old_max_fee = self.max_fee_per_gas.unwrap_or_default();
+            let old_priority_fee = self.max_priority_fee_per_gas.unwrap_or_default();
+            let max_fee = clamp(bump(old_max_fee), caps.max_fee_per_gas);
+            let priority_fee =
+                clamp(bump(old_priority_fee), caps.max_priority_fee_per_gas).min(max_fee);
+            if !replaces(old_max_fee, max_fee) || !replaces(old_priority_fee, priority_fee) {
+                return false;
+            }
+            self.max_fee_per_gas = Some(max_fee);
+            self.max_priority_fee_per_gas = Some(priority_fee);
+        } else {
+            let old_price = self.gas_price.unwrap_or_default();
+            let price = clamp(bump(old_price).max(floor_price), caps.max_fee_per_gas);
+            if !replaces(old_price, price) {
+                return false;
+            }
+            self.gas_price = Some(price);
+        }
+        true
+    }
+}
+
+impl Encode for InFlightTx {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let buf = serde_json::to_vec(self)?;
+        writer.write_all(&buf)?;
+        Ok(buf.len())
+    }
+}
+
+impl Decode for InFlightTx {
+    fn read_from<R>(reader: &mut R) -> Result<Self, NomadError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        let mut buf = vec![];
+        reader.read_to_end(&mut buf)?;
+        Ok(serde_json::from_slice(&buf).map_err(std::io::Error::from)?)
+    }
+}
+
+/// Nonce and in-flight transaction state of one signer on one chain. Shared
+/// by the middlewares of every contract the signer sends transactions to, so
+/// that they all draw from the same nonce.
+#[derive(Debug)]
+pub struct TxManager {
+    address: Address,
+    conf: TxManagerConf,
+    db: Option<TypedDB>,
+    nonce: Mutex<Option<U256>>,
+    resumed: AtomicBool,
+}
+
+impl TxManager {
+    /// Instantiate the manager of transactions sent by `address`. If a `db`
+    /// is provided, in-flight transactions are persisted and picked up again
+    /// after a restart.
+    pub fn new(address: Address, chain_id: u64, conf: TxManagerConf, db: Option<DB>) -> Self {
+        Self {
+            address,
+            conf,
+            db: db.map(|db| TypedDB::new(format!("tx_manager_{}_{:x}", chain_id, address), db)),
+            nonce: Mutex::new(None),
+            resumed: AtomicBool::new(false),
+        }
+    }
+
+    /// All persisted in-flight transactions, by ascending nonce
+    pub fn in_flight(&self) -> Vec<InFlightTx> {
+        self.db
+            .as_ref()
+            .map(|db| db.prefix_values(IN_FLIGHT).collect())
+            .unwrap_or_default()
+    }
+
+    fn store_in_flight(&self, tx: &InFlightTx) -> Result<(), DbError> {
+        match &self.db {
+            Some(db) => db.store_keyed_encodable(IN_FLIGHT, &tx.nonce.as_u64(), tx),
+            None => Ok(()),
+        }
+    }
+
+    fn remove_in_flight(&self, tx: &InFlightTx) -> Result<(), DbError> {
+        match &self.db {
+            Some(db) => db.delete_keyed_value(IN_FLIGHT, &tx.nonce.as_u64()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The tx managers of an agent, one per chain and signer
+#[derive(Debug, Clone, Default)]
+pub struct TxManagers {
+    db: Option<DB>,
+    managers: Arc<std::sync::Mutex<HashMap<(u64, Address), Arc<TxManager>>>>,
+}
+
+impl TxManagers {
+    /// Instantiate an empty set of managers persisting in-flight transactions
+    /// to `db`, if provided
+    pub fn new(db: Option<DB>) -> Self {
+        Self {
+            db,
+            managers: Default::default(),
+        }
+    }
+
+    /// The manager of transactions sent by `address` on `chain_id`. Created
+    /// with `conf` the first time it is requested.
+    pub fn get(&self, chain_id: u64, address: Address, conf: &TxManagerConf) -> Arc<TxManager> {
+        self.managers
+            .lock()
+            .expect("tx managers lock poisoned")
+            .entry((chain_id, address))
+            .or_insert_with(|| {
+                Arc::new(TxManager::new(
+                    address,
+                    chain_id,
+                    conf.clone(),
+                    self.db.clone(),
+                ))
+            })
+            .clone()
+    }
+}
+
+/// Middleware that assigns nonces locally, persists every in-flight
+/// transaction and replaces transactions that stay pending for too long with
+/// copies paying higher fees.
+///
+/// `send_transaction` only returns once one of the broadcasts for the nonce
+/// has been included, so the returned `PendingTransaction` always tracks the
+/// hash that actually landed on chain.
+pub struct TxManagerMiddleware<M> {
+    inner: M,
+    manager: Arc<TxManager>,
+    fee_caps: FeeCaps,
+}
+
+impl<M> fmt::Debug for TxManagerMiddleware<M>
+where
+    M: Middleware,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TxManagerMiddleware")
+            .field("inner", &self.inner)
+            .field("address", &self.manager.address)
+            .field("conf", &self.manager.conf)
+            .field("fee_caps", &self.fee_caps)
+            .finish()
+    }
+}
+
+#[derive(Error, Debug)]
+/// Thrown when an error happens at the Tx Manager Middleware
+pub enum TxManagerMiddlewareError<M: Middleware> {
+    /// Thrown when the internal middleware errors
+    #[error("{0}")]
+    MiddlewareError(M::Error),
+    /// Thrown when in-flight transactions can't be persisted
+    #[error("{0}")]
+    DbError(#[from] DbError),
+    /// Thrown when a transaction is still pending after the maximum number of
+    /// replacements, or once the fee caps prevent replacing it. It stays
+    /// persisted and is resumed on restart.
+    #[error("Transaction with nonce {nonce} stuck after maximum number of replacements or fee cap reached. Latest hash: {tx_hash:?}")]
+    Stuck {
+        /// Nonce of the transaction
+        nonce: U256,
+        /// Hash of the latest replacement
+        tx_hash: H256,
+    },
+}
+
+/// Convert inner Middleware error into TxManagerMiddlewareError
+impl<M: Middleware> FromErr<M::Error> for TxManagerMiddlewareError<M> {
+    fn from(src: M::Error) -> Self {
+        TxManagerMiddlewareError::MiddlewareError(src)
+    }
+}
+
+impl<M> TxManagerMiddleware<M>
+where
+    M: Middleware,
+{
+    /// Instantiate the middleware sending transactions through `manager`.
+    /// Replacements never pay more than `fee_caps`.
+    pub fn new(inner: M, manager: Arc<TxManager>, fee_caps: FeeCaps) -> Self {
+        Self {
+            inner,
+            manager,
+            fee_caps,
+        }
+    }
+
+    /// All persisted in-flight transactions, by ascending nonce
+    pub fn in_flight(&self) -> Vec<InFlightTx> {
+        self.manager.in_flight()
+    }
+
+    /// Wait for in-flight transactions persisted by a previous run, replacing
+    /// them if stuck
+    #[instrument(skip(self), fields(address = ?self.manager.address))]
+    async fn resume_in_flight(&self) -> Result<(), TxManagerMiddlewareError<M>> {
+        let confirmed = self
+            .inner
+            .get_transaction_count(self.manager.address, Some(BlockNumber::Latest.into()))
+            .await
+            .map_err(FromErr::from)?;
+
+        for tx in self.in_flight() {
+            if tx.nonce < confirmed {
+                self.manager.remove_in_flight(&tx)?;
+                continue;
+            }
+            info!(nonce = ?tx.nonce, tx_hash = ?tx.latest_hash(), "Resuming in-flight transaction");
+            self.wait_for_inclusion(tx).await?;
+        }
+        Ok(())
+    }
+
+    /// The nonce to use when none was assigned yet: the account's pending
+    /// nonce, skipping past in-flight transactions of a previous run the node
+    /// may have dropped
+    async fn initial_nonce(&self) -> Result<U256, TxManagerMiddlewareError<M>> {
+        let pending = self
+            .inner
+            .get_transaction_count(self.manager.address, Some(BlockNumber::Pending.into()))
+            .await
+            .map_err(FromErr::from)?;
+        Ok(self
+            .in_flight()
+            .last()
+            .map(|tx| tx.nonce + 1)
+            .unwrap_or_default()
+            .max(pending))
+    }
+
+    /// Poll until one of the broadcasts for the nonce is included, replacing
+    /// the transaction whenever it has been pending for `stuck_after_blocks`.
+    /// Returns the hash of the included broadcast, or the latest hash if the
+    /// nonce was consumed by a transaction we don't know about. Errors once
+    /// the transaction is stuck after `max_replacements` replacements or at
+    /// the fee caps.
+    #[instrument(skip(self, tx), fields(nonce = ?tx.nonce))]
+    async fn wait_for_inclusion(
+        &self,
+        mut tx: InFlightTx,
+    ) -> Result<H256, TxManagerMiddlewareError<M>> {
+        let conf = &self.manager.conf;
+        loop {
+            tokio::time::sleep(conf.polling_interval()).await;
+
+            // Read the account nonce before looking for receipts so that a
+            // broadcast included in between is still found below
+            let confirmed = self
+                .inner
+                .get_transaction_count(self.manager.address, Some(BlockNumber::Latest.into()))
+                .await
+                .map_err(FromErr::from)?;
+
+            for hash in tx.hashes.iter().rev() {
+                let receipt = self
+                    .inner
+                    .get_transaction_receipt(*hash)
+                    .await
+                    .map_err(FromErr::from)?;
+                if receipt.is_some() {
+                    self.manager.remove_in_flight(&tx)?;
+                    return Ok(*hash);
+                }
+            }
+
+            if confirmed > tx.nonce {
+                warn!(
+                    tx_hash = ?tx.latest_hash(),
+                    "Nonce consumed by an unknown transaction"
+                );
+                self.manager.remove_in_flight(&tx)?;
+                return Ok(tx.latest_hash());
+            }
+
+            let current_block = self
+                .inner
+                .get_block_number()
+                .await
+                .map_err(FromErr::from)?
+                .as_u64();
+            if current_block < tx.sent_at_block + conf.stuck_after_blocks() {
+                continue;
+            }
+
+            if tx.replacements >= conf.max_replacements() {
+                return Err(TxManagerMiddlewareError::Stuck {
+                    nonce: tx.nonce,
+                    tx_hash: tx.latest_hash(),
+                });
+            }
+
+            self.replace(&mut tx, current_block).await?;
+        }
+    }
+
+    /// Broadcast a copy of `tx` paying higher fees. Errors if the fee caps
+    /// don't leave room for a replacement.
+    async fn replace(
+        &self,
+        tx: &mut InFlightTx,
+        current_block: u64,
+    ) -> Result<(), TxManagerMiddlewareError<M>> {
+        let floor_price = self.inner.get_gas_price().await.map_err(FromErr::from)?;
+
+        let mut replacement = tx.clone();
+        if !replacement.bump_fees(
+            self.manager.conf.gas_bump_percent(),
+            floor_price,
+            &self.fee_caps,
+        ) {
+            warn!(
+                tx_hash = ?tx.latest_hash(),
+                gas_price = ?tx.gas_price,
+                max_fee_per_gas = ?tx.max_fee_per_gas,
+                "Fee cap reached, not replacing stuck transaction"
+            );
+            return Err(TxManagerMiddlewareError::Stuck {
+                nonce: tx.nonce,
+                tx_hash: tx.latest_hash(),
+            });
+        }
+
+        match self
+            .inner
+            .send_transaction(replacement.to_tx(self.manager.address), None)
+            .await
+        {
+            Ok(pending) => {
+                let tx_hash = *pending;
+                info!(
+                    replaced = ?tx.latest_hash(),
+                    tx_hash = ?tx_hash,
+                    gas_price = ?replacement.gas_price,
+                    max_fee_per_gas = ?replacement.max_fee_per_gas,
+                    "Replaced stuck transaction"
+                );
+                replacement.hashes.push(tx_hash);
+                replacement.sent_at_block = current_block;
+                replacement.replacements += 1;
+                *tx = replacement;
+                self.manager.store_in_flight(tx)?;
+            }
+            // Most likely an earlier broadcast got included in the meantime.
+            // The next poll will find out.
+            Err(e) => warn!(
+                tx_hash = ?tx.latest_hash(),
+                error = %e,
+                "Failed to replace stuck transaction"
+            ),
+        }
+        Ok(())
+    }
+}
+
+impl<M> TxManagerMiddleware<M>
+where
+    M: Middleware + 'static,
+{
+    /// Drive the in-flight transactions persisted by a previous run to
+    /// completion in the background. Only done once per manager, by the
+    /// first middleware calling it.
+    pub fn spawn_resume(self: &Arc<Self>) {
+        if self.manager.resumed.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let middleware = self.clone();
+        tokio::spawn(
+            async move {
+                if let Err(e) = middleware.resume_in_flight().await {
+                    warn!(error = %e, "Failed to resume in-flight transactions");
+                }
+            }
+            .in_current_span(),
+        );
+    }
+}
+
+#[async_trait::async_trait]
+impl<M> Middleware for TxManagerMiddleware<M>
+where
+    M: Middleware,
+{
+    type Error = TxManagerMiddlewareError<M>;
+    type Provider = M::Provider;
+    type Inner = M;
+
+    fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    async fn send_transaction<T: Into<TypedTransaction> + Send + Sync>(
+        &self,
+        tx: T,
+        block: Option<BlockId>,
+    ) -> Result<PendingTransaction<'_, Self::Provider>, Self::Error> {
+        let mut tx: TypedTransaction = tx.into();
+        tx.set_from(self.manager.address);
+
+        let mut nonce = self.manager.nonce.lock().await;
+        let next = match *nonce {
+            Some(next) => next,
+            None => self.initial_nonce().await?,
+        };
+        tx.set_nonce(next);
+
+        // Fill gas and fees up front so the exact values are persisted
+        self.inner
+            .fill_transaction(&mut tx, block)
+            .await
+            .map_err(FromErr::from)?;
+
+        let tx_hash = match self.inner.send_transaction(tx.clone(), block).await {
+            Ok(pending) => *pending,
+            Err(e) => {
+                // Resync with the chain on the next send
+                *nonce = None;
+                return Err(FromErr::from(e));
+            }
+        };
+        *nonce = Some(next + 1);
+
+        let sent_at_block = self
+            .inner
+            .get_block_number()
+            .await
+            .map_err(FromErr::from)?
+            .as_u64();
+        let in_flight = InFlightTx::new(&tx, tx_hash, sent_at_block);
+        self.manager.store_in_flight(&in_flight)?;
+
+        // Let other transactions go out while we watch this one
+        drop(nonce);
+
+        let tx_hash = self.wait_for_inclusion(in_flight).await?;
+        Ok(PendingTransaction::new(tx_hash, self.provider()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::providers::{JsonRpcClient, MockError, Provider};
+    use ethers::types::TransactionReceipt;
+    use futures_util::future::join_all;
+    use serde::{de::DeserializeOwned, Serialize};
+    use serde_json::{json, Value};
+
+    /// A chain at block 1 on which the account has sent 7 transactions.
+    /// Records the nonce of every transaction sent and only reports receipts
+    /// if `include` is set.
+    #[derive(Debug, Default)]
+    struct MockChain {
+        include: bool,
+        sent: Arc<std::sync::Mutex<Vec<U256>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl JsonRpcClient for MockChain {
+        type Error = MockError;
+
+        async fn request<T, R>(&self, method: &str, params: T) -> Result<R, MockError>
+        where
+            T: fmt::Debug + Serialize + Send + Sync,
+            R: DeserializeOwned,
+        {
+            let params = serde_json::to_value(params)?;
+            let response = match method {
+                "eth_getTransactionCount" => json!(U256::from(7)),
+                "eth_blockNumber" | "eth_gasPrice" | "eth_chainId" => json!(U256::one()),
+                "eth_estimateGas" => json!(U256::from(21000)),
+                "eth_sendTransaction" => {
+                    let nonce: U256 = serde_json::from_value(params[0]["nonce"].clone())?;
+                    let mut sent = self.sent.lock().unwrap();
+                    sent.push(nonce);
+                    json!(H256::from_low_u64_be(sent.len() as u64))
+                }
+                "eth_getTransactionReceipt" if self.include => json!(TransactionReceipt {
+                    transaction_hash: serde_json::from_value(params[0].clone())?,
+                    ..Default::default()
+                }),
+                "eth_getTransactionReceipt" => Value::Null,
+                _ => return Err(MockError::EmptyResponses),
+            };
+            Ok(serde_json::from_value(response)?)
+        }
+    }
+
+    fn conf(stuck_after_blocks: &str, max_replacements: &str) -> TxManagerConf {
+        TxManagerConf {
+            stuck_after_blocks: Some(stuck_after_blocks.to_owned()),
+            max_replacements: Some(max_replacements.to_owned()),
+            polling_interval: Some("0".to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn tx() -> TransactionRequest {
+        TransactionRequest::new()
+            .to(Address::repeat_byte(2))
+            .gas(21000)
+            .gas_price(1)
+    }
+
+    #[tokio::test]
+    async fn it_shares_nonces_between_middlewares() {
+        let sent: Arc<std::sync::Mutex<Vec<U256>>> = Default::default();
+        let managers = TxManagers::new(None);
+        let address = Address::repeat_byte(1);
+
+        // Two contracts sending from the same signer on the same chain
+        let middlewares: Vec<_> = (0..2)
+            .map(|_| {
+                let chain = Provider::new(MockChain {
+                    include: true,
+                    sent: sent.clone(),
+                });
+                TxManagerMiddleware::new(
+                    chain,
+                    managers.get(1, address, &conf("10", "5")),
+                    Default::default(),
+                )
+            })
+            .collect();
+
+        let results =
+            join_all(middlewares.iter().flat_map(|middleware| {
+                (0..3).map(move |_| middleware.send_transaction(tx(), None))
+            }))
+            .await;
+        assert!(results.iter().all(Result::is_ok));
+
+        let mut sent = sent.lock().unwrap().clone();
+        sent.sort();
+        assert_eq!(sent, (7..13).map(U256::from).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn it_fails_stuck_transactions() {
+        let sent: Arc<std::sync::Mutex<Vec<U256>>> = Default::default();
+        let chain = Provider::new(MockChain {
+            include: false,
+            sent: sent.clone(),
+        });
+        let manager = TxManagers::new(None).get(1, Address::repeat_byte(1), &conf("0", "1"));
+        let middleware = TxManagerMiddleware::new(chain, manager, Default::default());
+
+        let res = middleware.send_transaction(tx(), None).await;
+        assert!(matches!(
+            res,
+            Err(TxManagerMiddlewareError::Stuck { nonce, .. }) if nonce == U256::from(7)
+        ));
+        // the original and one replacement were broadcast
+        assert_eq!(*sent.lock().unwrap(), vec![U256::from(7), U256::from(7)]);
+    }
+
+    #[tokio::test]
+    async fn it_stops_replacing_at_the_fee_cap() {
+        let sent: Arc<std::sync::Mutex<Vec<U256>>> = Default::default();
+        let chain = Provider::new(MockChain {
+            include: false,
+            sent: sent.clone(),
+        });
+        let manager = TxManagers::new(None).get(1, Address::repeat_byte(1), &conf("0", "5"));
+        let caps = FeeCaps {
+            max_fee_per_gas: Some(U256::one()),
+            ..Default::default()
+        };
+        let middleware = TxManagerMiddleware::new(chain, manager, caps);
+
+        let res = middleware.send_transaction(tx(), None).await;
+        assert!(matches!(
+            res,
+            Err(TxManagerMiddlewareError::Stuck { nonce, .. }) if nonce == U256::from(7)
+        ));
+        // the gas price already is at the cap: no replacement was broadcast
+        assert_eq!(*sent.lock().unwrap(), vec![U256::from(7)]);
+    }
+
+    #[test]
+    fn it_bumps_fees() {
+        let mut legacy = InFlightTx::new(
+            &TransactionRequest::new()
+                .nonce(3)
+                .gas_price(100)
+                .gas(21000)
+                .into(),
+            H256::repeat_byte(1),
+            10,
+        );
+        let no_caps = FeeCaps::default();
+        assert!(legacy.bump_fees(15, U256::zero(), &no_caps));
+        assert_eq!(legacy.gas_price, Some(116.into()));
+        assert!(legacy.bump_fees(15, 1000.into(), &no_caps));
+        assert_eq!(legacy.gas_price, Some(1000.into()));
+
+        // clamped to the cap, as long as nodes still accept the replacement
+        let caps = FeeCaps {
+            max_fee_per_gas: Some(1120.into()),
+            ..Default::default()
+        };
+        assert!(legacy.bump_fees(15, U256::zero(), &caps));
+        assert_eq!(legacy.gas_price, Some(1120.into()));
+        assert!(!legacy.bump_fees(15, U256::zero(), &caps));
+        assert_eq!(legacy.gas_price, Some(1120.into()));
+
+        let mut eip1559 = InFlightTx::new(
+            &Eip1559TransactionRequest::new()
+                .nonce(3)
+                .max_fee_per_gas(200)
+                .max_priority_fee_per_gas(10)
+                .into(),
+            H256::repeat_byte(1),
+            10,
+        );
+        assert!(eip1559.bump_fees(10, 1000.into(), &no_caps));
+        assert_eq!(eip1559.gas_price, None);
+        assert_eq!(eip1559.max_fee_per_gas, Some(221.into()));
+        assert_eq!(eip1559.max_priority_fee_per_gas, Some(12.into()));
+
+        // the priority fee can't be bumped past its cap
+        let caps = FeeCaps {
+            max_priority_fee_per_gas: Some(12.into()),
+            ..Default::default()
+        };
+        assert!(!eip1559.bump_fees(10, 1000.into(), &caps));
+        assert_eq!(eip1559.max_fee_per_gas, Some(221.into()));
+        assert!(matches!(
+            eip1559.to_tx(Address::zero()),
+            TypedTransaction::Eip1559(_)
+        ));
+    }
+
+    #[test]
+    fn it_roundtrips_in_flight_txs() {
+        let tx = InFlightTx::new(
+            &TransactionRequest::new()
+                .to(Address::repeat_byte(2))
+                .data(vec![1, 2, 3])
+                .nonce(7)
+                .gas_price(100)
+                .into(),
+            H256::repeat_byte(1),
+            10,
+        );
+        let decoded = InFlightTx::read_from(&mut tx.to_vec().as_slice()).unwrap();
+        assert_eq!(tx, decoded);
+        assert_eq!(decoded.to_tx(Address::zero()).nonce(), Some(&7.into()));
+    }
+}
@@ -63,6 +63,14 @@ where
             .await?)
     }
 
+    #[tracing::instrument(err)]
+    async fn domain_to_replica(
+        &self,
+        domain: u32,
+    ) -> Result<NomadIdentifier, ChainCommunicationError> {
+        Ok(self.contract.domain_to_replica(domain).call().await?.into())
+    }
+
     #[tracing::instrument(err)]
     async fn watcher_permission(
         &self,
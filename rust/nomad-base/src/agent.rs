@@ -2,16 +2,21 @@ use crate::{
     cancel_task,
     metrics::CoreMetrics,
     settings::{IndexSettings, Settings},
-    BaseError, CachingHome, CachingReplica, ContractSyncMetrics, IndexDataTypes,
+    ApiState, BaseError, CachingHome, CachingReplica, ContractSyncMetrics, IndexDataTypes,
+    ServedReplicas, SpendStore,
 };
 use async_trait::async_trait;
-use color_eyre::{eyre::WrapErr, Result};
-use futures_util::future::select_all;
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    Result,
+};
+use futures_util::{future::select_all, stream::FuturesUnordered, StreamExt};
 use nomad_core::db::DB;
+use nomad_ethereum::TxManagers;
 use tracing::instrument::Instrumented;
-use tracing::{info_span, Instrument};
+use tracing::{error, info, info_span, Instrument};
 
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
 use tokio::{task::JoinHandle, time::sleep};
 
 /// Properties shared across all agents
@@ -53,6 +58,29 @@ pub trait NomadAgent: Send + Sync + std::fmt::Debug + AsRef<AgentCore> {
         self.as_ref().metrics.clone()
     }
 
+    /// Run the read-only HTTP API server, if an API port is configured
+    fn run_api_server(&self) -> JoinHandle<()> {
+        let core = self.as_ref();
+        let port = core
+            .settings
+            .api
+            .as_ref()
+            .map(|v| v.parse::<u16>().expect("api port must be u16"));
+        let host: std::net::IpAddr = core
+            .settings
+            .api_host
+            .as_ref()
+            .map_or(Ok([127, 0, 0, 1].into()), |v| v.parse())
+            .expect("api host must be an IP address");
+        ApiState::new(core.home.clone(), self.served_replicas()).run_http_server(host, port)
+    }
+
+    /// The replicas served, as reported by the API. The configured replicas,
+    /// unless the agent serves replicas as they are enrolled.
+    fn served_replicas(&self) -> Arc<ServedReplicas> {
+        Arc::new(std::sync::RwLock::new(self.replicas().clone()))
+    }
+
     /// Return a handle to the DB
     fn db(&self) -> DB {
         self.as_ref().db.clone()
@@ -191,3 +219,77 @@ pub trait NomadAgent: Send + Sync + std::fmt::Debug + AsRef<AgentCore> {
         .instrument(span)
     }
 }
+
+/// Run one agent per network in `settings`, as returned by `split_networks`.
+/// The agents share tracing and the metrics server, which are started once,
+/// as well as signers, tx managers and gas spend totals, so transactions sent
+/// from the same signer on the same chain share nonces and daily caps
+/// whichever network they are for.
+/// An agent stopping, e.g. after an RPC error on its home, leaves the others
+/// running. Resolves once every agent stopped, with the first error if any.
+pub async fn run_networks<A>(mut settings: Vec<A::Settings>) -> Result<()>
+where
+    A: NomadAgent + 'static,
+    A::Settings: AsMut<Settings>,
+{
+    if settings.len() > 1 {
+        // In-flight transactions and gas spend are persisted next to the
+        // networks' DBs
+        let network_db = Path::new(&settings[0].as_ref().db);
+        let shared_dir = network_db.parent().unwrap_or(network_db);
+        let open = |name: &str| DB::from_path(&shared_dir.join(name).to_string_lossy());
+        let tx_managers = TxManagers::new(Some(open("tx_managers")?));
+        let spend_store = SpendStore::new(open("gas_budget")?);
+        for settings in settings.iter_mut() {
+            settings.as_mut().tx_managers = Some(tx_managers.clone());
+            settings.as_mut().spend_store = Some(spend_store.clone());
+        }
+    }
+
+    let mut agents = Vec::with_capacity(settings.len());
+    for settings in settings {
+        agents.push(A::from_settings(settings).await?);
+    }
+
+    let first = agents
+        .first()
+        .ok_or_else(|| eyre!("No network to run {} for", A::AGENT_NAME))?;
+    first
+        .as_ref()
+        .settings
+        .tracing
+        .start_tracing(first.metrics().span_duration())?;
+    let _ = first.metrics().run_http_server();
+
+    let mut tasks: FuturesUnordered<_> = agents
+        .into_iter()
+        .map(|agent| {
+            let _ = agent.run_api_server();
+            let network = agent.as_ref().settings.network.clone();
+            let task = agent.run_all();
+            async move { (network, task.await) }
+        })
+        .collect();
+
+    let mut first_error = None;
+    while let Some((network, res)) = tasks.next().await {
+        let network = network.unwrap_or_default();
+        match res.map_err(color_eyre::Report::from).and_then(|res| res) {
+            Ok(()) => info!(network = %network, "Agent stopped"),
+            Err(e) => {
+                error!(
+                    network = %network,
+                    error = ?e,
+                    remaining = tasks.len(),
+                    "Agent stopped with an error. Agents of other networks keep running."
+                );
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,343 @@
+//! Read-only HTTP/JSON API serving agent state from the DB.
+//!
+//! Routes:
+//! - `GET /messages/<destination>/<nonce>`: message by destination and nonce
+//! - `GET /messages/leaf/<leaf>`: message by leaf hash
+//! - `GET /proofs/<leaf_index>`: proof by leaf index
+//! - `GET /updates/<root>`: signed update by new root
+//! - `GET /latest_blocks`: latest block indexed per contract
+//! - `GET /processor/nonces`: last nonce handled by the processor per replica
+
+use ethers::core::types::{Bytes, H256};
+use nomad_core::{
+    accumulator::merkle::Proof, db::DbError, CommittedMessage, Common, RawCommittedMessage,
+    Replica, SignedUpdate, UpdateMeta,
+};
+use serde::Serialize;
+use std::{collections::HashMap, convert::TryFrom, net::IpAddr, sync::Arc};
+use tokio::task::JoinHandle;
+use warp::{http::StatusCode, reply, Filter, Rejection, Reply};
+
+use crate::{
+    contract_sync::{CommonContractSyncDB, HomeContractSyncDB},
+    CachingHome, ServedReplicas,
+};
+
+/// A committed message along with its leaf
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageResponse {
+    leaf: H256,
+    leaf_index: u32,
+    committed_root: H256,
+    origin: u32,
+    sender: H256,
+    nonce: u32,
+    destination: u32,
+    recipient: H256,
+    body: Bytes,
+}
+
+impl TryFrom<RawCommittedMessage> for MessageResponse {
+    type Error = nomad_core::NomadError;
+
+    fn try_from(raw: RawCommittedMessage) -> Result<Self, Self::Error> {
+        let committed = CommittedMessage::try_from(raw)?;
+        Ok(Self {
+            leaf: committed.to_leaf(),
+            leaf_index: committed.leaf_index,
+            committed_root: committed.committed_root,
+            origin: committed.message.origin,
+            sender: committed.message.sender,
+            nonce: committed.message.nonce,
+            destination: committed.message.destination,
+            recipient: committed.message.recipient,
+            body: committed.message.body.into(),
+        })
+    }
+}
+
+/// A signed update along with its metadata, if known
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateResponse {
+    signed_update: SignedUpdate,
+    metadata: Option<UpdateMeta>,
+}
+
+/// Latest block indexed for each data type of a contract
+#[derive(Debug, Serialize)]
+struct LatestBlocks {
+    updates: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    messages: Option<u32>,
+}
+
+/// Last nonce the processor handled for a replica
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProcessorNonce {
+    domain: u32,
+    last_processed: Option<u32>,
+}
+
+/// Contracts whose DB state the API serves
+#[derive(Debug, Clone)]
+pub struct ApiState {
+    home: Arc<CachingHome>,
+    replicas: Arc<ServedReplicas>,
+}
+
+impl ApiState {
+    /// Instantiate the API state. Reports on the replicas in `replicas` at
+    /// the time of each request.
+    pub fn new(home: Arc<CachingHome>, replicas: Arc<ServedReplicas>) -> Self {
+        Self { home, replicas }
+    }
+
+    fn message_by_nonce(
+        &self,
+        destination: u32,
+        nonce: u32,
+    ) -> Result<Option<MessageResponse>, DbError> {
+        self.home
+            .db()
+            .message_by_nonce(destination, nonce)?
+            .map(MessageResponse::try_from)
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    fn message_by_leaf(&self, leaf: H256) -> Result<Option<MessageResponse>, DbError> {
+        self.home
+            .db()
+            .message_by_leaf(leaf)?
+            .map(MessageResponse::try_from)
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    fn proof_by_leaf_index(&self, leaf_index: u32) -> Result<Option<Proof>, DbError> {
+        self.home.db().proof_by_leaf_index(leaf_index)
+    }
+
+    fn update_by_root(&self, root: H256) -> Result<Option<UpdateResponse>, DbError> {
+        let db = self.home.db();
+        match db.update_by_new_root(root)? {
+            Some(signed_update) => Ok(Some(UpdateResponse {
+                signed_update,
+                metadata: db.retrieve_update_metadata(root)?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    fn latest_blocks(&self) -> Result<Option<HashMap<String, LatestBlocks>>, DbError> {
+        let home_db = self.home.db();
+        let mut latest = HashMap::new();
+        latest.insert(
+            self.home.name().to_owned(),
+            LatestBlocks {
+                updates: home_db.retrieve_update_latest_block_end(),
+                messages: home_db.retrieve_message_latest_block_end(),
+            },
+        );
+        let replicas = self.replicas.read().expect("served replicas lock poisoned");
+        for (name, replica) in replicas.iter() {
+            latest.insert(
+                name.to_owned(),
+                LatestBlocks {
+                    updates: replica.db().retrieve_update_latest_block_end(),
+                    messages: None,
+                },
+            );
+        }
+        Ok(Some(latest))
+    }
+
+    fn processor_nonces(&self) -> Result<Option<HashMap<String, ProcessorNonce>>, DbError> {
+        let db = self.home.db();
+        let mut nonces = HashMap::new();
+        let replicas = self.replicas.read().expect("served replicas lock poisoned");
+        for (name, replica) in replicas.iter() {
+            let domain = replica.local_domain();
+            nonces.insert(
+                name.to_owned(),
+                ProcessorNonce {
+                    domain,
+                    last_processed: db.retrieve_processor_nonce(domain)?,
+                },
+            );
+        }
+        Ok(Some(nonces))
+    }
+
+    /// Run an HTTP server serving the API on `host` and `port`
+    pub fn run_http_server(self, host: IpAddr, port: Option<u16>) -> JoinHandle<()> {
+        let port = match port {
+            Some(port) => port,
+            None => {
+                tracing::info!("not starting api server");
+                return tokio::spawn(std::future::ready(()));
+            }
+        };
+        tracing::info!(%host, port, "starting api server on {}:{}", host, port);
+
+        let routes = self.routes();
+        tokio::spawn(async move {
+            warp::serve(routes).run((host, port)).await;
+        })
+    }
+
+    fn routes(self) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+        let state = warp::any().map(move || self.clone());
+
+        let message_by_nonce = warp::path!("messages" / u32 / u32).and(state.clone()).map(
+            |destination, nonce, state: ApiState| {
+                respond(state.message_by_nonce(destination, nonce))
+            },
+        );
+        let message_by_leaf = warp::path!("messages" / "leaf" / H256)
+            .and(state.clone())
+            .map(|leaf, state: ApiState| respond(state.message_by_leaf(leaf)));
+        let proof = warp::path!("proofs" / u32)
+            .and(state.clone())
+            .map(|leaf_index, state: ApiState| respond(state.proof_by_leaf_index(leaf_index)));
+        let update = warp::path!("updates" / H256)
+            .and(state.clone())
+            .map(|root, state: ApiState| respond(state.update_by_root(root)));
+        let latest_blocks = warp::path!("latest_blocks")
+            .and(state.clone())
+            .map(|state: ApiState| respond(state.latest_blocks()));
+        let processor_nonces = warp::path!("processor" / "nonces")
+            .and(state)
+            .map(|state: ApiState| respond(state.processor_nonces()));
+
+        warp::get().and(
+            message_by_nonce
+                .or(message_by_leaf)
+                .or(proof)
+                .or(update)
+                .or(latest_blocks)
+                .or(processor_nonces),
+        )
+    }
+}
+
+/// Reply with the JSON encoded value, a 404 if there is none or a 500 on DB
+/// errors
+fn respond<T: Serialize>(res: Result<Option<T>, DbError>) -> reply::Response {
+    match res {
+        Ok(Some(value)) => reply::json(&value).into_response(),
+        Ok(None) => reply::with_status(
+            reply::json(&serde_json::json!({ "error": "not found" })),
+            StatusCode::NOT_FOUND,
+        )
+        .into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "api db error");
+            reply::with_status(
+                reply::json(&serde_json::json!({ "error": e.to_string() })),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{CachingReplica, NomadDB};
+    use nomad_core::{Encode, NomadMessage};
+    use nomad_test::{
+        mocks::{MockHomeContract, MockIndexer, MockReplicaContract},
+        test_utils,
+    };
+
+    #[tokio::test]
+    async fn it_serves_db_state() {
+        test_utils::run_test_db(|db| async move {
+            let home_db = NomadDB::new("home_1", db.clone());
+            let message = NomadMessage {
+                origin: 1,
+                sender: H256::repeat_byte(2),
+                nonce: 0,
+                destination: 2000,
+                recipient: H256::repeat_byte(3),
+                body: vec![1, 2, 3],
+            };
+            let raw = RawCommittedMessage {
+                leaf_index: 0,
+                committed_root: H256::zero(),
+                message: message.to_vec(),
+            };
+            home_db.store_latest_message(&raw).unwrap();
+            home_db.store_processor_nonce(2000, 0).unwrap();
+
+            let mut mock_home = MockHomeContract::new();
+            mock_home.expect__name().return_const("home_1".to_owned());
+            let home = CachingHome::new(
+                mock_home.into(),
+                home_db,
+                Arc::new(MockIndexer::new().into()),
+            );
+            let mut mock_replica = MockReplicaContract::new();
+            mock_replica.expect__local_domain().return_const(2000u32);
+            let replica = CachingReplica::new(
+                mock_replica.into(),
+                NomadDB::new("replica_1", db),
+                Arc::new(MockIndexer::new().into()),
+            );
+            let routes = ApiState::new(
+                Arc::new(home),
+                Arc::new(std::sync::RwLock::new(
+                    [("replica_1".to_owned(), Arc::new(replica))]
+                        .into_iter()
+                        .collect(),
+                )),
+            )
+            .routes();
+
+            let res = warp::test::request()
+                .path("/messages/2000/0")
+                .reply(&routes)
+                .await;
+            assert_eq!(res.status(), StatusCode::OK);
+            let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+            assert_eq!(body["leaf"], serde_json::json!(raw.leaf()));
+            assert_eq!(body["destination"], 2000);
+
+            let res = warp::test::request()
+                .path(&format!("/messages/leaf/{:?}", raw.leaf()))
+                .reply(&routes)
+                .await;
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let res = warp::test::request()
+                .path("/messages/2000/1")
+                .reply(&routes)
+                .await;
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+            let res = warp::test::request()
+                .path("/processor/nonces")
+                .reply(&routes)
+                .await;
+            assert_eq!(res.status(), StatusCode::OK);
+            let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+            assert_eq!(
+                body["replica_1"],
+                serde_json::json!({ "domain": 2000, "lastProcessed": 0 })
+            );
+
+            let res = warp::test::request()
+                .method("POST")
+                .path("/processor/nonces")
+                .reply(&routes)
+                .await;
+            assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        })
+        .await
+    }
+}
@@ -0,0 +1,721 @@
+use async_trait::async_trait;
+use color_eyre::Result;
+use ethers::{core::types::H256, utils::keccak256};
+use nomad_core::{
+    accumulator::{incremental::IncrementalMerkle, merkle::Proof},
+    ChainCommunicationError, Common, ConnectionManager, Decode, DoubleUpdate, Encode, Home,
+    Message, MessageStatus, NomadError, NomadIdentifier, NomadMessage, Replica,
+    SignedFailureNotification, SignedUpdate, State, TxOutcome, Update,
+};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use crate::ArchiveRecord;
+
+/// Connection details of a chain replayed from a recorded log archive
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveConf {
+    /// Path of the contract's JSON lines archive
+    pub path: String,
+    /// Path of a JSON lines file the contract's transactions are appended to.
+    /// Transactions already in the file are replayed on startup. Kept in
+    /// memory only if unset.
+    #[serde(default)]
+    pub writes: Option<String>,
+}
+
+fn txid(line: &str) -> H256 {
+    keccak256(line.as_bytes()).into()
+}
+
+/// Contract state rebuilt from archive records
+#[derive(Debug, Default)]
+struct ArchiveState {
+    tip: u32,
+    updater: Option<H256>,
+    remote_domain: Option<u32>,
+    committed_root: H256,
+    accepted_roots: HashSet<H256>,
+    update_by_previous_root: HashMap<H256, H256>,
+    failed: bool,
+    tree: IncrementalMerkle,
+    queue: VecDeque<H256>,
+    nonces: HashMap<u32, u32>,
+    statuses: HashMap<H256, MessageStatus>,
+    replicas: HashMap<u32, H256>,
+    permissions: HashSet<(H256, u32)>,
+    txids: HashSet<H256>,
+}
+
+impl ArchiveState {
+    fn apply_update(&mut self, update: &SignedUpdate) {
+        let Update {
+            home_domain,
+            previous_root,
+            new_root,
+        } = update.update;
+
+        if self.updater.is_none() {
+            self.updater = update.recover().ok().map(Into::into);
+        }
+        self.remote_domain.get_or_insert(home_domain);
+
+        // a second update building off the same root is a double update
+        match self.update_by_previous_root.get(&previous_root) {
+            Some(root) if *root != new_root => self.failed = true,
+            Some(_) => return,
+            None => {
+                self.update_by_previous_root.insert(previous_root, new_root);
+            }
+        }
+
+        // the first update builds off the root the contract was initialized
+        // with
+        if self.accepted_roots.is_empty() || previous_root == self.committed_root {
+            self.committed_root = new_root;
+            self.accepted_roots.insert(previous_root);
+            self.accepted_roots.insert(new_root);
+            if self.queue.contains(&new_root) {
+                while self.queue.pop_front() != Some(new_root) {}
+            }
+        }
+    }
+
+    fn apply(&mut self, record: &ArchiveRecord) -> Result<(), NomadError> {
+        self.tip = self.tip.max(record.block_number());
+
+        match record {
+            ArchiveRecord::Block { .. } => {}
+            ArchiveRecord::Update { update, .. } => self.apply_update(update),
+            ArchiveRecord::Dispatch { message, .. } => {
+                let message = NomadMessage::read_from(&mut &message.to_vec()[..])?;
+                *self.nonces.entry(message.destination).or_default() += 1;
+                self.tree.ingest(message.to_leaf());
+                self.queue.push_back(self.tree.root());
+            }
+            ArchiveRecord::NewUpdater { updater, .. } => self.updater = Some(*updater),
+            ArchiveRecord::DoubleUpdate { first, second, .. } => {
+                self.apply_update(first);
+                self.apply_update(second);
+                self.failed = true;
+            }
+            ArchiveRecord::ImproperUpdate { .. } => self.failed = true,
+            ArchiveRecord::Prove { leaf, .. } => {
+                self.statuses.entry(*leaf).or_insert(MessageStatus::Proven);
+            }
+            ArchiveRecord::Process { message_hash, .. } => {
+                self.statuses
+                    .insert(*message_hash, MessageStatus::Processed);
+            }
+            ArchiveRecord::ReplicaEnrolled {
+                domain, replica, ..
+            } => {
+                self.replicas.insert(*domain, (*replica).into());
+            }
+            ArchiveRecord::ReplicaUnenrolled { domain, .. } => {
+                self.replicas.remove(domain);
+            }
+            ArchiveRecord::WatcherPermissionSet {
+                domain,
+                watcher,
+                access,
+                ..
+            } => {
+                let key: (H256, u32) = ((*watcher).into(), *domain);
+                if *access {
+                    self.permissions.insert(key);
+                } else {
+                    self.permissions.remove(&key);
+                }
+            }
+            ArchiveRecord::NewHome { .. } => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Home, replica or connection manager replayed from a recorded log archive
+/// instead of a chain.
+///
+/// Reads are answered from the state the archive's records build up.
+/// Transactions are not validated: they always succeed, cost no gas and are
+/// applied to that state and recorded in the `writes` file as archive records
+/// at the archive's last block. Their txid is the hash of the record. The
+/// contract's indexer only replays the archive itself, not these writes.
+///
+/// Replicas accept the roots of every recorded update immediately, without
+/// waiting for the optimistic timeout.
+#[derive(Debug)]
+pub struct ArchiveContract {
+    name: String,
+    domain: u32,
+    writes: Option<PathBuf>,
+    state: Mutex<ArchiveState>,
+}
+
+impl ArchiveContract {
+    /// Load a contract's archive, and the transactions previously written to
+    /// it
+    pub fn from_conf(name: &str, domain: u32, conf: &ArchiveConf) -> Result<Self> {
+        let mut records = ArchiveRecord::read_all(&conf.path)?;
+        records.sort_by_key(ArchiveRecord::block_number);
+
+        let mut state = ArchiveState::default();
+        for record in records.iter() {
+            state.apply(record)?;
+        }
+
+        let writes = conf.writes.as_ref().map(PathBuf::from);
+        if let Some(path) = writes.as_ref().filter(|path| path.exists()) {
+            for record in ArchiveRecord::read_all(path)? {
+                state.apply(&record)?;
+                state.txids.insert(txid(&serde_json::to_string(&record)?));
+            }
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            domain,
+            writes,
+            state: Mutex::new(state),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ArchiveState> {
+        self.state.lock().expect("archive state lock poisoned")
+    }
+
+    /// Record the transaction `build` makes from the current state. `None`
+    /// means the transaction reverts, and nothing is recorded.
+    fn submit(
+        &self,
+        build: impl FnOnce(&ArchiveState) -> Option<ArchiveRecord>,
+    ) -> Result<TxOutcome, ChainCommunicationError> {
+        let mut state = self.lock();
+        let record = match build(&state) {
+            Some(record) => record,
+            None => return Ok(TxOutcome::default()),
+        };
+
+        let line = serde_json::to_string(&record)
+            .map_err(|e| ChainCommunicationError::CustomError(Box::new(e)))?;
+        if let Some(path) = &self.writes {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", line))
+                .map_err(|e| ChainCommunicationError::CustomError(Box::new(e)))?;
+        }
+        state.apply(&record)?;
+
+        let txid = txid(&line);
+        state.txids.insert(txid);
+        Ok(TxOutcome {
+            txid,
+            executed: true,
+            ..Default::default()
+        })
+    }
+}
+
+#[async_trait]
+impl Common for ArchiveContract {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn status(&self, txid: H256) -> Result<Option<TxOutcome>, ChainCommunicationError> {
+        Ok(self.lock().txids.get(&txid).map(|txid| TxOutcome {
+            txid: *txid,
+            executed: true,
+            ..Default::default()
+        }))
+    }
+
+    async fn updater(&self) -> Result<H256, ChainCommunicationError> {
+        self.lock().updater.ok_or_else(|| {
+            ChainCommunicationError::CustomError(
+                format!("no updater recorded in the archive of {}", self.name).into(),
+            )
+        })
+    }
+
+    async fn state(&self) -> Result<State, ChainCommunicationError> {
+        Ok(if self.lock().failed {
+            State::Failed
+        } else {
+            State::Active
+        })
+    }
+
+    async fn committed_root(&self) -> Result<H256, ChainCommunicationError> {
+        Ok(self.lock().committed_root)
+    }
+
+    async fn update(&self, update: &SignedUpdate) -> Result<TxOutcome, ChainCommunicationError> {
+        self.submit(|state| {
+            Some(ArchiveRecord::Update {
+                block_number: state.tip,
+                timestamp: None,
+                update: update.clone(),
+            })
+        })
+    }
+
+    async fn double_update(
+        &self,
+        double: &DoubleUpdate,
+    ) -> Result<TxOutcome, ChainCommunicationError> {
+        self.submit(|state| {
+            Some(ArchiveRecord::DoubleUpdate {
+                block_number: state.tip,
+                first: double.0.clone(),
+                second: double.1.clone(),
+            })
+        })
+    }
+}
+
+#[async_trait]
+impl Home for ArchiveContract {
+    fn local_domain(&self) -> u32 {
+        self.domain
+    }
+
+    async fn nonces(&self, destination: u32) -> Result<u32, ChainCommunicationError> {
+        Ok(self
+            .state()
+            .nonces
+            .get(&destination)
+            .copied()
+            .unwrap_or_default())
+    }
+
+    async fn dispatch(&self, message: &Message) -> Result<TxOutcome, ChainCommunicationError> {
+        self.submit(|state| {
+            // the archive has no signer, so messages have no sender
+            let message = NomadMessage {
+                origin: self.domain,
+                sender: H256::zero(),
+                nonce: state
+                    .nonces
+                    .get(&message.destination)
+                    .copied()
+                    .unwrap_or_default(),
+                destination: message.destination,
+                recipient: message.recipient,
+                body: message.body.clone(),
+            };
+            Some(ArchiveRecord::Dispatch {
+                block_number: state.tip,
+                leaf_index: state.tree.count() as u32,
+                committed_root: state.committed_root,
+                message: message.to_vec().into(),
+            })
+        })
+    }
+
+    async fn queue_contains(&self, root: H256) -> Result<bool, ChainCommunicationError> {
+        Ok(self.lock().queue.contains(&root))
+    }
+
+    async fn improper_update(
+        &self,
+        update: &SignedUpdate,
+    ) -> Result<TxOutcome, ChainCommunicationError> {
+        self.submit(|state| {
+            Some(ArchiveRecord::ImproperUpdate {
+                block_number: state.tip,
+                update: update.clone(),
+            })
+        })
+    }
+
+    async fn produce_update(&self) -> Result<Option<Update>, ChainCommunicationError> {
+        let state = self.lock();
+        Ok(state.queue.back().map(|new_root| Update {
+            home_domain: self.domain,
+            previous_root: state.committed_root,
+            new_root: *new_root,
+        }))
+    }
+}
+
+#[async_trait]
+impl Replica for ArchiveContract {
+    fn local_domain(&self) -> u32 {
+        self.domain
+    }
+
+    async fn remote_domain(&self) -> Result<u32, ChainCommunicationError> {
+        self.lock().remote_domain.ok_or_else(|| {
+            ChainCommunicationError::CustomError(
+                format!("no update recorded in the archive of {}", self.name).into(),
+            )
+        })
+    }
+
+    async fn prove(&self, proof: &Proof) -> Result<TxOutcome, ChainCommunicationError> {
+        self.submit(|state| {
+            Some(ArchiveRecord::Prove {
+                block_number: state.tip,
+                leaf: proof.leaf,
+            })
+        })
+    }
+
+    async fn process(&self, message: &NomadMessage) -> Result<TxOutcome, ChainCommunicationError> {
+        self.submit(|state| {
+            Some(ArchiveRecord::Process {
+                block_number: state.tip,
+                message_hash: message.to_leaf(),
+            })
+        })
+    }
+
+    async fn message_status(&self, leaf: H256) -> Result<MessageStatus, ChainCommunicationError> {
+        Ok(self
+            .state()
+            .statuses
+            .get(&leaf)
+            .copied()
+            .unwrap_or(MessageStatus::None))
+    }
+
+    async fn acceptable_root(&self, root: H256) -> Result<bool, ChainCommunicationError> {
+        Ok(self.lock().accepted_roots.contains(&root))
+    }
+}
+
+#[async_trait]
+impl ConnectionManager for ArchiveContract {
+    fn local_domain(&self) -> u32 {
+        self.domain
+    }
+
+    async fn is_replica(&self, address: NomadIdentifier) -> Result<bool, ChainCommunicationError> {
+        let address: H256 = address.into();
+        Ok(self
+            .state()
+            .replicas
+            .values()
+            .any(|replica| *replica == address))
+    }
+
+    async fn domain_to_replica(
+        &self,
+        domain: u32,
+    ) -> Result<NomadIdentifier, ChainCommunicationError> {
+        Ok(self
+            .state()
+            .replicas
+            .get(&domain)
+            .map(|replica| (*replica).into())
+            .unwrap_or_default())
+    }
+
+    async fn watcher_permission(
+        &self,
+        address: NomadIdentifier,
+        domain: u32,
+    ) -> Result<bool, ChainCommunicationError> {
+        let address: H256 = address.into();
+        Ok(self.lock().permissions.contains(&(address, domain)))
+    }
+
+    async fn owner_enroll_replica(
+        &self,
+        replica: NomadIdentifier,
+        domain: u32,
+    ) -> Result<TxOutcome, ChainCommunicationError> {
+        self.submit(|state| {
+            Some(ArchiveRecord::ReplicaEnrolled {
+                block_number: state.tip,
+                domain,
+                replica,
+            })
+        })
+    }
+
+    async fn owner_unenroll_replica(
+        &self,
+        replica: NomadIdentifier,
+    ) -> Result<TxOutcome, ChainCommunicationError> {
+        let address: H256 = replica.into();
+        self.submit(|state| {
+            let (domain, _) = state
+                .replicas
+                .iter()
+                .find(|(_, enrolled)| **enrolled == address)?;
+            Some(ArchiveRecord::ReplicaUnenrolled {
+                block_number: state.tip,
+                domain: *domain,
+                replica,
+            })
+        })
+    }
+
+    async fn set_home(&self, home: NomadIdentifier) -> Result<TxOutcome, ChainCommunicationError> {
+        self.submit(|state| {
+            Some(ArchiveRecord::NewHome {
+                block_number: state.tip,
+                home,
+            })
+        })
+    }
+
+    async fn set_watcher_permission(
+        &self,
+        watcher: NomadIdentifier,
+        domain: u32,
+        access: bool,
+    ) -> Result<TxOutcome, ChainCommunicationError> {
+        self.submit(|state| {
+            Some(ArchiveRecord::WatcherPermissionSet {
+                block_number: state.tip,
+                domain,
+                watcher,
+                access,
+            })
+        })
+    }
+
+    async fn unenroll_replica(
+        &self,
+        signed_failure: &SignedFailureNotification,
+    ) -> Result<TxOutcome, ChainCommunicationError> {
+        let domain = signed_failure.notification.home_domain;
+        self.submit(|state| {
+            let replica = state.replicas.get(&domain)?;
+            Some(ArchiveRecord::ReplicaUnenrolled {
+                block_number: state.tip,
+                domain,
+                replica: (*replica).into(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ChainConf, ChainSetup, FileIndexer};
+    use ethers::signers::LocalWallet;
+    use nomad_core::{CommonIndexer, FailureNotification, HomeIndexer};
+    use nomad_ethereum::TxManagers;
+
+    #[tokio::test]
+    async fn it_answers_calls_from_the_archive_and_records_writes() {
+        let dir = std::env::temp_dir().join(format!("nomad-archive-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("home.jsonl");
+        let writes = dir.join("home.writes.jsonl");
+        let _ = std::fs::remove_file(&writes);
+
+        let signer: LocalWallet =
+            "1111111111111111111111111111111111111111111111111111111111111111"
+                .parse()
+                .unwrap();
+        let messages: Vec<_> = (0..2)
+            .map(|nonce| NomadMessage {
+                origin: 1000,
+                sender: H256::repeat_byte(1),
+                nonce,
+                destination: 2000,
+                recipient: H256::repeat_byte(2),
+                body: vec![nonce as u8],
+            })
+            .collect();
+        let mut tree = IncrementalMerkle::default();
+        let roots: Vec<_> = messages
+            .iter()
+            .map(|message| {
+                tree.ingest(message.to_leaf());
+                tree.root()
+            })
+            .collect();
+        let update = Update {
+            home_domain: 1000,
+            previous_root: H256::zero(),
+            new_root: roots[0],
+        }
+        .sign_with(&signer)
+        .await
+        .unwrap();
+
+        let records = vec![
+            ArchiveRecord::Dispatch {
+                block_number: 5,
+                leaf_index: 0,
+                committed_root: H256::zero(),
+                message: messages[0].to_vec().into(),
+            },
+            ArchiveRecord::Update {
+                block_number: 6,
+                timestamp: None,
+                update: update.clone(),
+            },
+            ArchiveRecord::Dispatch {
+                block_number: 7,
+                leaf_index: 1,
+                committed_root: roots[0],
+                message: messages[1].to_vec().into(),
+            },
+            ArchiveRecord::ReplicaEnrolled {
+                block_number: 7,
+                domain: 2000,
+                replica: H256::repeat_byte(9).into(),
+            },
+            ArchiveRecord::WatcherPermissionSet {
+                block_number: 7,
+                domain: 2000,
+                watcher: H256::repeat_byte(8).into(),
+                access: true,
+            },
+        ];
+        let lines: Vec<_> = records
+            .iter()
+            .map(|record| serde_json::to_string(record).unwrap())
+            .collect();
+        std::fs::write(&archive, lines.join("\n")).unwrap();
+
+        let setup: ChainSetup = serde_json::from_value(serde_json::json!({
+            "name": "home",
+            "domain": "1000",
+            "address": "0x0000000000000000000000000000000000000000",
+            "timelag": 0,
+            "rpcStyle": "archive",
+            "connection": {
+                "path": archive.to_str().unwrap(),
+                "writes": writes.to_str().unwrap(),
+            },
+        }))
+        .unwrap();
+        let conf = match &setup.chain {
+            ChainConf::Archive(conf) => conf.clone(),
+            _ => panic!("expected an archive chain"),
+        };
+        let tx_managers = TxManagers::default();
+
+        let home = setup.try_into_home(None, None, &tx_managers).await.unwrap();
+        let indexer = FileIndexer::from_path(&conf.path).unwrap();
+        assert_eq!(indexer.get_block_number().await.unwrap(), 7);
+        assert_eq!(indexer.fetch_sorted_messages(0, 7).await.unwrap().len(), 2);
+
+        assert_eq!(
+            home.updater().await.unwrap(),
+            H256::from(ethers::signers::Signer::address(&signer))
+        );
+        assert_eq!(home.state().await.unwrap(), State::Active);
+        assert_eq!(home.committed_root().await.unwrap(), roots[0]);
+        assert_eq!(home.nonces(2000).await.unwrap(), 2);
+        assert!(!home.queue_contains(roots[0]).await.unwrap());
+        assert!(home.queue_contains(roots[1]).await.unwrap());
+
+        // the updater signs the suggested update and submits it
+        let suggested = home.produce_update().await.unwrap().unwrap();
+        assert_eq!(suggested.previous_root, roots[0]);
+        assert_eq!(suggested.new_root, roots[1]);
+        let outcome = home
+            .update(&suggested.sign_with(&signer).await.unwrap())
+            .await
+            .unwrap();
+        assert!(outcome.executed);
+        assert!(home.status(outcome.txid).await.unwrap().is_some());
+        assert_eq!(home.committed_root().await.unwrap(), roots[1]);
+        assert_eq!(home.produce_update().await.unwrap(), None);
+
+        // the same archive read as a replica of the home
+        let replica = setup
+            .try_into_replica(None, None, &tx_managers)
+            .await
+            .unwrap();
+        assert_eq!(replica.remote_domain().await.unwrap(), 1000);
+        // written updates are replayed on load
+        assert!(replica.acceptable_root(roots[1]).await.unwrap());
+        let leaf = messages[1].to_leaf();
+        assert_eq!(
+            replica.message_status(leaf).await.unwrap(),
+            MessageStatus::None
+        );
+        let proof = Proof {
+            leaf,
+            index: 1,
+            path: Default::default(),
+        };
+        replica
+            .prove_and_process(&messages[1], &proof)
+            .await
+            .unwrap();
+        assert_eq!(
+            replica.message_status(leaf).await.unwrap(),
+            MessageStatus::Processed
+        );
+
+        // and as a connection manager
+        let manager = setup
+            .try_into_connection_manager(None, None, &tx_managers)
+            .await
+            .unwrap();
+        assert!(manager
+            .is_replica(H256::repeat_byte(9).into())
+            .await
+            .unwrap());
+        assert!(manager
+            .watcher_permission(H256::repeat_byte(8).into(), 2000)
+            .await
+            .unwrap());
+        let failure = FailureNotification {
+            home_domain: 2000,
+            updater: H256::zero().into(),
+        }
+        .sign_with(&signer)
+        .await
+        .unwrap();
+        assert!(manager.unenroll_replica(&failure).await.unwrap().executed);
+        assert_eq!(
+            manager.domain_to_replica(2000).await.unwrap(),
+            NomadIdentifier::default()
+        );
+        // nothing left to unenroll, the transaction reverts
+        assert!(!manager.unenroll_replica(&failure).await.unwrap().executed);
+
+        let written = ArchiveRecord::read_all(&writes).unwrap();
+        assert_eq!(written.len(), 4);
+        assert!(matches!(written[0], ArchiveRecord::Update { .. }));
+        assert!(matches!(written[1], ArchiveRecord::Prove { .. }));
+        assert!(matches!(
+            written[2],
+            ArchiveRecord::Process { message_hash, .. } if message_hash == leaf
+        ));
+        assert!(matches!(
+            written[3],
+            ArchiveRecord::ReplicaUnenrolled { domain: 2000, .. }
+        ));
+
+        // a double update fails the contract
+        let conflicting = Update {
+            home_domain: 1000,
+            previous_root: H256::zero(),
+            new_root: H256::repeat_byte(7),
+        }
+        .sign_with(&signer)
+        .await
+        .unwrap();
+        home.double_update(&DoubleUpdate(update, conflicting))
+            .await
+            .unwrap();
+        assert_eq!(home.state().await.unwrap(), State::Failed);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use crate::RetryConfig;
+
+/// Exponential backoff between retries of a failed operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// Delay (in seconds) before the first retry
+    base_delay: u64,
+    /// Upper bound (in seconds) on the delay between retries
+    max_delay: u64,
+}
+
+impl Backoff {
+    /// Instantiate a new backoff doubling from `base_delay` up to `max_delay`
+    /// seconds
+    pub const fn new(base_delay: u64, max_delay: u64) -> Self {
+        Self {
+            base_delay,
+            max_delay,
+        }
+    }
+
+    /// This backoff, with the delays set in `config` overriding its own
+    pub fn configured(self, config: Option<&RetryConfig>) -> Self {
+        let parse = |delay: &Option<String>| -> Option<u64> {
+            delay.as_ref().map(|d| d.parse().expect("invalid integer"))
+        };
+        match config {
+            Some(config) => Self {
+                base_delay: parse(&config.base_delay).unwrap_or(self.base_delay),
+                max_delay: parse(&config.max_delay).unwrap_or(self.max_delay),
+            },
+            None => self,
+        }
+    }
+
+    /// Delay to wait after the `failures`-th failure in a row
+    pub fn delay(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(63);
+        let delay = self
+            .base_delay
+            .saturating_mul(1u64 << exponent)
+            .min(self.max_delay);
+        Duration::from_secs(delay)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_caps_the_backoff() {
+        let backoff = Backoff::new(30, 600).configured(Some(&RetryConfig {
+            base_delay: Some("10".to_owned()),
+            max_delay: None,
+        }));
+        assert_eq!(backoff, Backoff::new(10, 600));
+
+        let backoff = Backoff::new(10, 60);
+        assert_eq!(backoff.delay(0), Duration::from_secs(10));
+        assert_eq!(backoff.delay(1), Duration::from_secs(10));
+        assert_eq!(backoff.delay(3), Duration::from_secs(40));
+        assert_eq!(backoff.delay(4), Duration::from_secs(60));
+        assert_eq!(backoff.delay(80), Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ethers::types::U256;
+use nomad_core::{
+    db::{DbError, DB},
+    TxOutcome,
+};
+use tracing::debug;
+
+use crate::{AgentCore, CoreMetrics, NomadDB};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// What an agent sent a transaction for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasOperation {
+    /// Submitting an update to a home
+    Update,
+    /// Relaying an update to a replica
+    Relay,
+    /// Proving a message on a replica
+    Prove,
+    /// Processing a message on a replica, proving it first if needed
+    Process,
+    /// Submitting a double update
+    DoubleUpdate,
+    /// Submitting an improper update to a home
+    ImproperUpdate,
+    /// Unenrolling a replica from a connection manager
+    Unenroll,
+}
+
+impl GasOperation {
+    /// Every operation
+    pub const ALL: [GasOperation; 7] = [
+        GasOperation::Update,
+        GasOperation::Relay,
+        GasOperation::Prove,
+        GasOperation::Process,
+        GasOperation::DoubleUpdate,
+        GasOperation::ImproperUpdate,
+        GasOperation::Unenroll,
+    ];
+
+    /// Name of the operation in metrics and the DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GasOperation::Update => "update",
+            GasOperation::Relay => "relay",
+            GasOperation::Prove => "prove",
+            GasOperation::Process => "process",
+            GasOperation::DoubleUpdate => "double_update",
+            GasOperation::ImproperUpdate => "improper_update",
+            GasOperation::Unenroll => "unenroll",
+        }
+    }
+}
+
+/// Days since the unix epoch, in UTC
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("!time")
+        .as_secs()
+        / SECONDS_PER_DAY
+}
+
+/// Where daily gas spend totals are kept. Shared by the agents serving each
+/// network of a process, so that a chain's cap holds whichever network
+/// spends on it.
+#[derive(Debug, Clone)]
+pub struct SpendStore {
+    db: DB,
+    // serializes the read-modify-write of daily totals
+    lock: Arc<Mutex<()>>,
+}
+
+impl SpendStore {
+    /// Keep daily totals in `db`
+    pub fn new(db: DB) -> Self {
+        Self {
+            db,
+            lock: Default::default(),
+        }
+    }
+}
+
+/// Accounts what an agent spends on gas (gas used times effective gas
+/// price) per chain, operation and UTC day, in its metrics and DB, and
+/// checks the daily total against the chain's cap in `budget.dailyCaps`.
+///
+/// Capping is up to the caller: agents subsidizing messages check
+/// [`GasBudget::is_exhausted`] before sending a transaction.
+#[derive(Debug)]
+pub struct GasBudget {
+    db: NomadDB,
+    metrics: Arc<CoreMetrics>,
+    caps: HashMap<String, U256>,
+    // serializes the read-modify-write of daily totals
+    lock: Arc<Mutex<()>>,
+}
+
+impl GasBudget {
+    /// Account the spend of the agent named `agent`, in the spend store and
+    /// metrics of `core`
+    pub fn new(agent: &str, core: &AgentCore) -> Self {
+        let store = core
+            .settings
+            .spend_store
+            .clone()
+            .unwrap_or_else(|| SpendStore::new(core.db.clone()));
+        Self {
+            db: NomadDB::new(agent, store.db),
+            metrics: core.metrics.clone(),
+            caps: core.settings.budget.daily_caps(),
+            lock: store.lock,
+        }
+    }
+
+    /// Account the cost of a transaction sent to `chain`
+    pub fn record(
+        &self,
+        chain: &str,
+        operation: GasOperation,
+        outcome: &TxOutcome,
+    ) -> Result<(), DbError> {
+        let cost = outcome.cost();
+        if cost.is_zero() {
+            return Ok(());
+        }
+        self.metrics
+            .transaction_cost(chain, operation.as_str(), outcome.gas_used, cost);
+
+        let day = today();
+        let _guard = self.lock.lock().expect("poisoned");
+        let spend = self.db.gas_spend(chain, day, operation.as_str())?;
+        self.db
+            .store_gas_spend(chain, day, operation.as_str(), spend.saturating_add(cost))?;
+
+        let total = self.spent_on(chain, day)?;
+        debug!(
+            chain = chain,
+            operation = operation.as_str(),
+            txid = ?outcome.txid,
+            cost = %cost,
+            total = %total,
+            "Accounted transaction cost"
+        );
+        self.metrics.daily_gas_spend_changed(chain, total);
+        Ok(())
+    }
+
+    /// Total spent on `chain` during the UTC `day`
+    fn spent_on(&self, chain: &str, day: u64) -> Result<U256, DbError> {
+        let mut total = U256::zero();
+        for operation in GasOperation::ALL {
+            total = total.saturating_add(self.db.gas_spend(chain, day, operation.as_str())?);
+        }
+        Ok(total)
+    }
+
+    /// Total spent on `chain` during the current UTC day
+    pub fn spent_today(&self, chain: &str) -> Result<U256, DbError> {
+        self.spent_on(chain, today())
+    }
+
+    /// True if the spend on `chain` during the current UTC day reached the
+    /// chain's daily cap. Never true for uncapped chains
+    pub fn is_exhausted(&self, chain: &str) -> Result<bool, DbError> {
+        match self.caps.get(chain) {
+            Some(cap) => Ok(self.spent_today(chain)? >= *cap),
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::H256;
+    use nomad_test::test_utils;
+
+    #[tokio::test]
+    async fn it_caps_daily_spend_per_chain() {
+        test_utils::run_test_db(|db| async move {
+            let metrics = CoreMetrics::new("test", None, Default::default()).unwrap();
+            let budget = GasBudget {
+                db: NomadDB::new("test", db),
+                metrics: Arc::new(metrics),
+                caps: [("ethereum".to_owned(), U256::from(1_000_000u64))]
+                    .into_iter()
+                    .collect(),
+                lock: Default::default(),
+            };
+            let outcome = TxOutcome {
+                txid: H256::repeat_byte(1),
+                executed: true,
+                gas_used: U256::from(21_000u64),
+                gas_price: U256::from(30u64),
+            };
+
+            budget
+                .record("ethereum", GasOperation::Relay, &outcome)
+                .unwrap();
+            budget
+                .record("ethereum", GasOperation::Process, &outcome)
+                .unwrap();
+            budget
+                .record("moonbeam", GasOperation::Process, &outcome)
+                .unwrap();
+            assert_eq!(
+                budget.spent_today("ethereum").unwrap(),
+                U256::from(1_260_000u64)
+            );
+            assert!(budget.is_exhausted("ethereum").unwrap());
+            assert!(!budget.is_exhausted("moonbeam").unwrap());
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn it_shares_daily_spend_between_networks() {
+        test_utils::run_test_db(|db| async move {
+            let store = SpendStore::new(db);
+            let budget = |network: &str| {
+                let metrics = CoreMetrics::for_network(
+                    "test",
+                    Some(network.to_owned()),
+                    None,
+                    Default::default(),
+                )
+                .unwrap();
+                GasBudget {
+                    db: NomadDB::new("test", store.db.clone()),
+                    metrics: Arc::new(metrics),
+                    caps: [("ethereum".to_owned(), U256::from(1_000_000u64))]
+                        .into_iter()
+                        .collect(),
+                    lock: store.lock.clone(),
+                }
+            };
+            let outcome = TxOutcome {
+                txid: H256::repeat_byte(1),
+                executed: true,
+                gas_used: U256::from(21_000u64),
+                gas_price: U256::from(30u64),
+            };
+
+            let (first, second) = (budget("first"), budget("second"));
+            first
+                .record("ethereum", GasOperation::Process, &outcome)
+                .unwrap();
+            assert!(!second.is_exhausted("ethereum").unwrap());
+            second
+                .record("ethereum", GasOperation::Process, &outcome)
+                .unwrap();
+            assert!(first.is_exhausted("ethereum").unwrap());
+        })
+        .await
+    }
+}
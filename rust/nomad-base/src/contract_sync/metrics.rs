@@ -1,5 +1,5 @@
 use crate::CoreMetrics;
-use prometheus::{HistogramVec, IntGaugeVec};
+use prometheus::{HistogramVec, IntCounterVec, IntGaugeVec};
 use std::sync::Arc;
 
 /// Struct encapsulating prometheus metrics used by the ContractSync.
@@ -15,6 +15,9 @@ pub struct ContractSyncMetrics {
     /// Unique occasions when agent missed an event (label values
     /// differentiate updates vs. messages)
     pub missed_events: IntGaugeVec,
+    /// Chain reorgs that caused indexed events to be rolled back (label
+    /// values differentiate updates vs. messages)
+    pub reorgs: IntCounterVec,
 }
 
 impl ContractSyncMetrics {
@@ -55,11 +58,20 @@ impl ContractSyncMetrics {
             )
             .expect("failed to register missed_events metric");
 
+        let reorgs = metrics
+            .new_int_counter(
+                "contract_sync_reorgs",
+                "Number of chain reorgs that caused indexed events to be rolled back",
+                &["data_type", "contract_name", "agent"],
+            )
+            .expect("failed to register reorgs metric");
+
         ContractSyncMetrics {
             indexed_height,
             store_event_latency,
             stored_events,
             missed_events,
+            reorgs,
         }
     }
 }
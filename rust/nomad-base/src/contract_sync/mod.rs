@@ -12,12 +12,13 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 mod last_message;
 mod last_update;
 mod metrics;
+mod reorg;
 mod schema;
 
 use last_message::OptLatestLeafIndex;
 use last_update::OptLatestNewRoot;
 pub use metrics::ContractSyncMetrics;
-use schema::{CommonContractSyncDB, HomeContractSyncDB};
+pub(crate) use schema::{CommonContractSyncDB, HomeContractSyncDB};
 
 const UPDATES_LABEL: &str = "updates";
 const MESSAGES_LABEL: &str = "messages";
@@ -95,6 +96,12 @@ where
             &self.agent_name,
         ]);
 
+        let update_reorgs = self.metrics.reorgs.clone().with_label_values(&[
+            UPDATES_LABEL,
+            &self.contract_name,
+            &self.agent_name,
+        ]);
+
         let config_from = self.from_height;
         let chunk_size = self.chunk_size;
 
@@ -108,6 +115,8 @@ where
             let mut realized_missing_end_block: u32 = Default::default();
             let mut exponential: u32 = Default::default();
 
+            let mut checkpoints = db.retrieve_update_checkpoints();
+
             info!(from = from, "[Updates]: resuming indexer from {}", from);
 
             loop {
@@ -130,6 +139,36 @@ where
                     realized_missing_end_block = 0;
                 }
 
+                // If the last indexed block range is no longer part of the
+                // canonical chain, roll back to the last block that still is
+                if let Some(keep) = checkpoints.find_reorg(indexer.as_ref()).await? {
+                    checkpoints.0.truncate(keep);
+                    let fork_height = checkpoints.latest().map(|c| c.height);
+
+                    let removed = db.rollback_updates(fork_height.map(u64::from))?;
+                    match fork_height {
+                        Some(height) => db.store_update_latest_block_end(height)?,
+                        None => db.remove_update_latest_block_end()?,
+                    }
+                    db.store_update_checkpoints(&checkpoints)?;
+                    from = fork_height.map_or(config_from, |h| h + 1);
+
+                    finding_missing = false;
+                    exponential = 0;
+                    realized_missing_start_block = 0;
+                    realized_missing_end_block = 0;
+
+                    update_reorgs.inc();
+                    warn!(
+                        fork_height = ?fork_height,
+                        removed = removed,
+                        "[Updates]: chain reorg detected. Rolled back {} update(s), resuming indexer from {}",
+                        removed,
+                        from,
+                    );
+                    continue;
+                }
+
                 let tip = indexer.get_block_number().await?;
                 if tip <= from {
                     // Sleep if we caught up to tip
@@ -148,12 +187,17 @@ where
                     to
                 );
 
+                // Read the block hash before the logs, so that a reorg in
+                // between is caught on the next iteration
+                let block_hash = indexer.get_block_hash(to).await?;
                 let sorted_updates = indexer.fetch_sorted_updates(from, to).await?;
 
                 // If no updates found, update last seen block and next height
                 // and continue
                 if sorted_updates.is_empty() {
                     db.store_update_latest_block_end(to)?;
+                    checkpoints.record(to, block_hash, None);
+                    db.store_update_checkpoints(&checkpoints)?;
                     from = to + 1;
                     continue;
                 }
@@ -192,6 +236,8 @@ where
 
                         // Move forward next height
                         db.store_update_latest_block_end(to)?;
+                        checkpoints.record(to, block_hash, None);
+                        db.store_update_checkpoints(&checkpoints)?;
                         from = to + 1;
                     }
                     ListValidity::Invalid => {
@@ -258,6 +304,12 @@ where
             &self.agent_name,
         ]);
 
+        let message_reorgs = self.metrics.reorgs.clone().with_label_values(&[
+            MESSAGES_LABEL,
+            &self.contract_name,
+            &self.agent_name,
+        ]);
+
         let config_from = self.from_height;
         let chunk_size = self.chunk_size;
 
@@ -271,6 +323,8 @@ where
             let mut realized_missing_end_block = 0;
             let mut exponential = 0;
 
+            let mut checkpoints = db.retrieve_message_checkpoints();
+
             info!(from = from, "[Messages]: resuming indexer from {}", from);
 
             loop {
@@ -293,6 +347,38 @@ where
                     realized_missing_end_block = 0;
                 }
 
+                // If the last indexed block range is no longer part of the
+                // canonical chain, roll back to the last block that still is
+                if let Some(keep) = checkpoints.find_reorg(indexer.as_ref()).await? {
+                    checkpoints.0.truncate(keep);
+                    let fork_height = checkpoints.latest().map(|c| c.height);
+                    let fork_leaf_index = checkpoints.latest().and_then(|c| c.latest_leaf_index);
+
+                    let removed = db.rollback_messages(fork_leaf_index)?;
+                    match fork_height {
+                        Some(height) => db.store_message_latest_block_end(height)?,
+                        None => db.remove_message_latest_block_end()?,
+                    }
+                    db.store_message_checkpoints(&checkpoints)?;
+                    from = fork_height.map_or(config_from, |h| h + 1);
+
+                    finding_missing = false;
+                    exponential = 0;
+                    realized_missing_start_block = 0;
+                    realized_missing_end_block = 0;
+
+                    message_reorgs.inc();
+                    warn!(
+                        fork_height = ?fork_height,
+                        fork_leaf_index = ?fork_leaf_index,
+                        removed = removed,
+                        "[Messages]: chain reorg detected. Rolled back {} message(s), resuming indexer from {}",
+                        removed,
+                        from,
+                    );
+                    continue;
+                }
+
                 let tip = indexer.get_block_number().await?;
                 if tip <= from {
                     // Sleep if caught up to tip
@@ -311,12 +397,17 @@ where
                     to
                 );
 
+                // Read the block hash before the logs, so that a reorg in
+                // between is caught on the next iteration
+                let block_hash = indexer.get_block_hash(to).await?;
                 let sorted_messages = indexer.fetch_sorted_messages(from, to).await?;
 
                 // If no messages found, update last seen block and next height
                 // and continue
                 if sorted_messages.is_empty() {
                     db.store_message_latest_block_end(to)?;
+                    checkpoints.record(to, block_hash, db.retrieve_latest_leaf_index()?);
+                    db.store_message_checkpoints(&checkpoints)?;
                     from = to + 1;
                     continue;
                 }
@@ -333,6 +424,8 @@ where
 
                         // Move forward next height
                         db.store_message_latest_block_end(to)?;
+                        checkpoints.record(to, block_hash, db.retrieve_latest_leaf_index()?);
+                        db.store_message_checkpoints(&checkpoints)?;
                         from = to + 1;
                     }
                     ListValidity::Invalid => {
@@ -436,6 +529,9 @@ mod test {
             .expect("!sign");
 
             let mut mock_indexer = MockIndexer::new();
+            mock_indexer
+                .expect__get_block_hash()
+                .returning(|height| Ok(Some(H256::from_low_u64_be(height as u64))));
             {
                 let mut seq = Sequence::new();
 
@@ -705,6 +801,9 @@ mod test {
             let fourth_message_clone_2 = fourth_message.clone();
 
             let mut mock_indexer = MockIndexer::new();
+            mock_indexer
+                .expect__get_block_hash()
+                .returning(|height| Ok(Some(H256::from_low_u64_be(height as u64))));
             {
                 let mut seq = Sequence::new();
 
@@ -0,0 +1,115 @@
+use color_eyre::Result;
+use ethers::core::types::H256;
+use nomad_core::{CommonIndexer, Decode, Encode, NomadError};
+
+/// Number of checkpoints kept per data type. Reorgs deeper than the oldest
+/// checkpoint cause a full re-index.
+const MAX_CHECKPOINTS: usize = 256;
+
+/// Hash of the last block of an indexed block range, along with the latest
+/// leaf index stored once the range was indexed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BlockCheckpoint {
+    pub(crate) height: u32,
+    pub(crate) block_hash: H256,
+    pub(crate) latest_leaf_index: Option<u32>,
+}
+
+/// Most recent checkpoints, oldest first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct BlockCheckpoints(pub(crate) Vec<BlockCheckpoint>);
+
+impl BlockCheckpoints {
+    /// Record a checkpoint, dropping the oldest one if needed. Does nothing
+    /// if the block hash is unknown.
+    pub(crate) fn record(
+        &mut self,
+        height: u32,
+        block_hash: Option<H256>,
+        latest_leaf_index: Option<u32>,
+    ) {
+        if let Some(block_hash) = block_hash {
+            self.0.push(BlockCheckpoint {
+                height,
+                block_hash,
+                latest_leaf_index,
+            });
+            if self.0.len() > MAX_CHECKPOINTS {
+                self.0.remove(0);
+            }
+        }
+    }
+
+    /// The most recent checkpoint
+    pub(crate) fn latest(&self) -> Option<&BlockCheckpoint> {
+        self.0.last()
+    }
+
+    /// Compare the checkpoints against the chain. Returns `None` if the latest
+    /// checkpoint is still canonical (or its block is unknown to the chain
+    /// yet), otherwise the number of leading checkpoints that still are.
+    pub(crate) async fn find_reorg<I>(&self, indexer: &I) -> Result<Option<usize>>
+    where
+        I: CommonIndexer,
+    {
+        let latest = match self.latest() {
+            Some(latest) => latest,
+            None => return Ok(None),
+        };
+        match indexer.get_block_hash(latest.height).await? {
+            Some(hash) if hash != latest.block_hash => {}
+            _ => return Ok(None),
+        }
+
+        for (i, checkpoint) in self.0.iter().enumerate().rev().skip(1) {
+            if indexer.get_block_hash(checkpoint.height).await? == Some(checkpoint.block_hash) {
+                return Ok(Some(i + 1));
+            }
+        }
+        Ok(Some(0))
+    }
+}
+
+impl Encode for BlockCheckpoints {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let mut written = (self.0.len() as u32).write_to(writer)?;
+        for checkpoint in self.0.iter() {
+            written += checkpoint.height.write_to(writer)?;
+            written += checkpoint.block_hash.write_to(writer)?;
+            // u32::MAX stands for no stored leaf
+            written += checkpoint
+                .latest_leaf_index
+                .unwrap_or(u32::MAX)
+                .write_to(writer)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for BlockCheckpoints {
+    fn read_from<R>(reader: &mut R) -> Result<Self, NomadError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        let len = u32::read_from(reader)?;
+        let mut checkpoints = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            let height = u32::read_from(reader)?;
+            let block_hash = H256::read_from(reader)?;
+            let latest_leaf_index = match u32::read_from(reader)? {
+                u32::MAX => None,
+                index => Some(index),
+            };
+            checkpoints.push(BlockCheckpoint {
+                height,
+                block_hash,
+                latest_leaf_index,
+            });
+        }
+        Ok(Self(checkpoints))
+    }
+}
@@ -1,18 +1,27 @@
+use super::reorg::BlockCheckpoints;
 use crate::NomadDB;
 use color_eyre::Result;
 use nomad_core::db::DbError;
 
 static UPDATES_LAST_BLOCK_END: &str = "updates_last_block";
 static MESSAGES_LAST_BLOCK_END: &str = "messages_last_block";
+static UPDATES_CHECKPOINTS: &str = "updates_block_checkpoints";
+static MESSAGES_CHECKPOINTS: &str = "messages_block_checkpoints";
 
 pub(crate) trait CommonContractSyncDB {
     fn store_update_latest_block_end(&self, latest_block: u32) -> Result<(), DbError>;
     fn retrieve_update_latest_block_end(&self) -> Option<u32>;
+    fn remove_update_latest_block_end(&self) -> Result<(), DbError>;
+    fn store_update_checkpoints(&self, checkpoints: &BlockCheckpoints) -> Result<(), DbError>;
+    fn retrieve_update_checkpoints(&self) -> BlockCheckpoints;
 }
 
 pub(crate) trait HomeContractSyncDB {
     fn store_message_latest_block_end(&self, latest_block: u32) -> Result<(), DbError>;
     fn retrieve_message_latest_block_end(&self) -> Option<u32>;
+    fn remove_message_latest_block_end(&self) -> Result<(), DbError>;
+    fn store_message_checkpoints(&self, checkpoints: &BlockCheckpoints) -> Result<(), DbError>;
+    fn retrieve_message_checkpoints(&self) -> BlockCheckpoints;
 }
 
 impl CommonContractSyncDB for NomadDB {
@@ -24,6 +33,20 @@ impl CommonContractSyncDB for NomadDB {
         self.retrieve_decodable("", UPDATES_LAST_BLOCK_END)
             .expect("db failure")
     }
+
+    fn remove_update_latest_block_end(&self) -> Result<(), DbError> {
+        self.delete_value("", UPDATES_LAST_BLOCK_END)
+    }
+
+    fn store_update_checkpoints(&self, checkpoints: &BlockCheckpoints) -> Result<(), DbError> {
+        self.store_encodable("", UPDATES_CHECKPOINTS, checkpoints)
+    }
+
+    fn retrieve_update_checkpoints(&self) -> BlockCheckpoints {
+        self.retrieve_decodable("", UPDATES_CHECKPOINTS)
+            .expect("db failure")
+            .unwrap_or_default()
+    }
 }
 
 impl HomeContractSyncDB for NomadDB {
@@ -35,4 +58,18 @@ impl HomeContractSyncDB for NomadDB {
         self.retrieve_decodable("", MESSAGES_LAST_BLOCK_END)
             .expect("db failure")
     }
+
+    fn remove_message_latest_block_end(&self) -> Result<(), DbError> {
+        self.delete_value("", MESSAGES_LAST_BLOCK_END)
+    }
+
+    fn store_message_checkpoints(&self, checkpoints: &BlockCheckpoints) -> Result<(), DbError> {
+        self.store_encodable("", MESSAGES_CHECKPOINTS, checkpoints)
+    }
+
+    fn retrieve_message_checkpoints(&self) -> BlockCheckpoints {
+        self.retrieve_decodable("", MESSAGES_CHECKPOINTS)
+            .expect("db failure")
+            .unwrap_or_default()
+    }
 }
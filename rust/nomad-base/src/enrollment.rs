@@ -0,0 +1,427 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    Result,
+};
+use futures_util::future::FutureExt;
+use nomad_core::{db::DB, ConnectionManager, NomadIdentifier};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::RwLock,
+    task::{JoinError, JoinHandle},
+    time::sleep,
+};
+use tracing::{info, info_span, instrument::Instrumented, warn, Instrument};
+
+use crate::{AgentCore, CachingReplica, ChainSetup, ConnectionManagers, Settings};
+
+/// Tasks run per replica, by replica name
+pub type ReplicaTasks = RwLock<HashMap<String, JoinHandle<Result<()>>>>;
+
+/// Replicas served, by replica name. Shared with the API so it reports on
+/// replicas enrolled at runtime.
+pub type ServedReplicas = std::sync::RwLock<HashMap<String, Arc<CachingReplica>>>;
+
+/// How often replica tasks are checked for having exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Re-reads the agent's settings from the config files and/or env
+pub type SettingsLoader = Box<dyn Fn() -> Result<Settings> + Send + Sync>;
+
+/// Keeps an agent's replica tasks in line with the replicas it should serve:
+/// the replicas configured and not disabled which, if their chain has a
+/// connection manager under `enrollment.managers`, are enrolled in it.
+///
+/// The settings are re-read on SIGHUP and enrollment is checked every
+/// `enrollment.interval` seconds. Replicas whose settings changed on reload
+/// are rebuilt and their tasks restarted.
+pub struct ReplicaEnrollment {
+    settings: Settings,
+    load: SettingsLoader,
+    db: DB,
+    replicas: Arc<ServedReplicas>,
+    managers: HashMap<String, Arc<ConnectionManagers>>,
+}
+
+impl std::fmt::Debug for ReplicaEnrollment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplicaEnrollment")
+            .field("settings", &self.settings)
+            .field("replicas", &self.replicas)
+            .field("managers", &self.managers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReplicaEnrollment {
+    /// Track the replicas of `core`, reloading its settings with `load`.
+    /// The replicas served are kept in `replicas`.
+    pub async fn new(
+        core: &AgentCore,
+        replicas: Arc<ServedReplicas>,
+        load: SettingsLoader,
+    ) -> Result<Self> {
+        let managers = Self::try_managers(&core.settings).await?;
+        Ok(Self {
+            settings: core.settings.clone(),
+            load,
+            db: core.db.clone(),
+            replicas,
+            managers,
+        })
+    }
+
+    /// The replica served under `name`, if it was instantiated
+    fn replica(&self, name: &str) -> Option<Arc<CachingReplica>> {
+        self.replicas
+            .read()
+            .expect("served replicas lock poisoned")
+            .get(name)
+            .cloned()
+    }
+
+    /// Build the connection managers replicas must be enrolled in
+    async fn try_managers(settings: &Settings) -> Result<HashMap<String, Arc<ConnectionManagers>>> {
+        let mut managers = HashMap::new();
+        for (name, setup) in settings.enrollment.managers.iter() {
+            let timelag = if settings.use_timelag {
+                Some(setup.timelag)
+            } else {
+                None
+            };
+            let manager = setup
+                .try_into_connection_manager(None, timelag, &settings.tx_managers())
+                .await?;
+            managers.insert(name.to_owned(), Arc::new(manager));
+        }
+        Ok(managers)
+    }
+
+    /// Re-read the settings of the network served. Keeps the current settings
+    /// if they can't be read. Replicas whose settings changed are dropped, to
+    /// be rebuilt by the next `update_tasks`.
+    async fn reload(&mut self) {
+        let reloaded = async {
+            let network = self.settings.network.clone();
+            let mut settings = (self.load)()?
+                .split_networks()
+                .into_iter()
+                .find(|settings| settings.network == network)
+                .ok_or_else(|| eyre!("Network {:?} is no longer configured", network))?;
+            // Contracts built from the reloaded settings keep sharing nonces
+            // with the ones already running
+            settings.tx_managers = self.settings.tx_managers.clone();
+            let managers = Self::try_managers(&settings).await?;
+            Ok::<_, color_eyre::Report>((settings, managers))
+        };
+
+        match reloaded.await {
+            Ok((settings, managers)) => {
+                info!(
+                    replicas = settings.replicas.len(),
+                    managers = managers.len(),
+                    "Reloaded replica settings"
+                );
+                let mut replicas = self
+                    .replicas
+                    .write()
+                    .expect("served replicas lock poisoned");
+                for (name, setup) in settings.replicas.iter() {
+                    let changed = self
+                        .settings
+                        .replicas
+                        .get(name)
+                        .map_or(false, |current| current != setup);
+                    if changed && replicas.remove(name).is_some() {
+                        info!(replica = name.as_str(), "Replica settings changed");
+                    }
+                }
+                drop(replicas);
+                self.settings = settings;
+                self.managers = managers;
+            }
+            Err(e) => warn!(error = %e, "Could not reload settings, keeping the current ones"),
+        }
+    }
+
+    /// Check whether the replica is enrolled in its chain's connection
+    /// manager. True if the chain has none configured.
+    async fn is_enrolled(&self, setup: &ChainSetup) -> Result<bool> {
+        let manager = match self.managers.get(&setup.name) {
+            Some(manager) => manager,
+            None => return Ok(true),
+        };
+        let address: NomadIdentifier = setup.address.parse::<ethers::types::Address>()?.into();
+        Ok(manager.is_replica(address).await?)
+    }
+
+    /// Names of the replicas to serve. A replica whose enrollment can't be
+    /// checked keeps being served, or not, as it currently is.
+    async fn served_replicas(&self, running: &[String]) -> Vec<String> {
+        let mut served = vec![];
+        for (name, setup) in self
+            .settings
+            .replicas
+            .iter()
+            .filter(|(_, setup)| setup.disabled.is_none())
+        {
+            match self.is_enrolled(setup).await {
+                Ok(true) => served.push(name.to_owned()),
+                Ok(false) => {}
+                Err(e) => {
+                    warn!(
+                        replica = name.as_str(),
+                        error = %e,
+                        "Could not check replica enrollment"
+                    );
+                    if running.contains(name) {
+                        served.push(name.to_owned());
+                    }
+                }
+            }
+        }
+        served
+    }
+
+    /// Cancel the tasks of replicas no longer served and start tasks for
+    /// replicas newly served or rebuilt
+    async fn update_tasks<F>(&mut self, tasks: &ReplicaTasks, start: &F)
+    where
+        F: Fn(Arc<CachingReplica>) -> Instrumented<JoinHandle<Result<()>>>,
+    {
+        let running: Vec<String> = tasks.read().await.keys().cloned().collect();
+        let served = self.served_replicas(&running).await;
+
+        let mut started = vec![];
+        for name in served.iter() {
+            if let Some(replica) = self.replica(name) {
+                if !running.contains(name) {
+                    started.push((name.to_owned(), replica));
+                }
+                continue;
+            }
+
+            match self
+                .settings
+                .try_caching_replica(name, self.db.clone())
+                .await
+            {
+                Ok(replica) => {
+                    let replica = Arc::new(replica);
+                    self.replicas
+                        .write()
+                        .expect("served replicas lock poisoned")
+                        .insert(name.to_owned(), replica.clone());
+                    started.push((name.to_owned(), replica));
+                }
+                Err(e) => {
+                    warn!(replica = name.as_str(), error = %e, "Could not instantiate replica");
+                }
+            }
+        }
+        self.replicas
+            .write()
+            .expect("served replicas lock poisoned")
+            .retain(|name, _| served.contains(name));
+
+        let mut tasks = tasks.write().await;
+        let stopped = running.iter().filter(|name| {
+            !served.contains(name) || started.iter().any(|(started, _)| started == *name)
+        });
+        for name in stopped {
+            info!(replica = name.as_str(), "Stopping replica task");
+            if let Some(task) = tasks.remove(name) {
+                task.abort();
+            }
+        }
+        for (name, replica) in started {
+            info!(replica = name.as_str(), "Starting replica task");
+            tasks.insert(name, start(replica).into_inner());
+        }
+    }
+
+    /// Wait for the first replica task to exit. Only locks `tasks` while
+    /// checking them.
+    async fn exited(tasks: &ReplicaTasks) -> (String, Result<Result<()>, JoinError>) {
+        loop {
+            {
+                let mut tasks = tasks.write().await;
+                let exited = tasks
+                    .iter_mut()
+                    .find_map(|(name, task)| task.now_or_never().map(|res| (name.to_owned(), res)));
+                if let Some((name, res)) = exited {
+                    tasks.remove(&name);
+                    return (name, res);
+                }
+            }
+            sleep(EXIT_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Spawn a task serving replicas as they are configured and enrolled,
+    /// starting each replica's task with `start`. The replica tasks are kept
+    /// in `tasks`. Resolves when the first replica task exits.
+    pub fn spawn<F>(
+        mut self,
+        tasks: Arc<ReplicaTasks>,
+        start: F,
+    ) -> Instrumented<JoinHandle<Result<()>>>
+    where
+        F: Fn(Arc<CachingReplica>) -> Instrumented<JoinHandle<Result<()>>> + Send + Sync + 'static,
+    {
+        let span = info_span!("ReplicaEnrollment");
+        tokio::spawn(async move {
+            let mut hangup = signal(SignalKind::hangup())?;
+            loop {
+                self.update_tasks(&tasks, &start).await;
+
+                let check_enrollment = !self.managers.is_empty();
+                let interval = Duration::from_secs(self.settings.enrollment.interval());
+                let reload = tokio::select! {
+                    _ = hangup.recv() => true,
+                    _ = sleep(interval), if check_enrollment => false,
+                    (name, res) = Self::exited(&tasks) => {
+                        return res?.wrap_err(format!("Task for replica named {} failed", name));
+                    }
+                };
+
+                if reload {
+                    self.reload().await;
+                }
+            }
+        })
+        .instrument(span)
+    }
+}
+
+/// Cancel every task in `tasks`
+pub async fn abort_replica_tasks(tasks: &ReplicaTasks) {
+    for (_, task) in tasks.write().await.drain() {
+        task.abort();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::NomadDB;
+    use nomad_test::{
+        mocks::{MockConnectionManagerContract, MockIndexer, MockReplicaContract},
+        test_utils,
+    };
+
+    #[tokio::test]
+    async fn it_only_serves_enrolled_replicas() {
+        test_utils::run_test_db(|db| async move {
+            let replica = |name: &str, address: &str| ChainSetup {
+                name: name.to_owned(),
+                domain: "2000".to_owned(),
+                address: address.to_owned(),
+                ..Default::default()
+            };
+            let enrolled = "0x0000000000000000000000000000000000000001";
+            let unenrolled = "0x0000000000000000000000000000000000000002";
+            let settings = Settings {
+                replicas: [
+                    ("moonbeam".to_owned(), replica("moonbeam", enrolled)),
+                    ("evmos".to_owned(), replica("evmos", unenrolled)),
+                    ("celo".to_owned(), replica("celo", unenrolled)),
+                ]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            };
+
+            // moonbeam and evmos have a connection manager, celo doesn't
+            let mut managers = HashMap::new();
+            for name in ["moonbeam", "evmos"] {
+                let mut mock = MockConnectionManagerContract::new();
+                mock.expect__is_replica().returning(move |address| {
+                    Ok(address
+                        == NomadIdentifier::from(
+                            enrolled.parse::<ethers::types::Address>().unwrap(),
+                        ))
+                });
+                managers.insert(
+                    name.to_owned(),
+                    Arc::new(ConnectionManagers::Mock(Box::new(mock))),
+                );
+            }
+
+            let enrollment = ReplicaEnrollment {
+                settings,
+                load: Box::new(|| Ok(Default::default())),
+                db,
+                replicas: Default::default(),
+                managers,
+            };
+
+            let mut served = enrollment.served_replicas(&[]).await;
+            served.sort();
+            assert_eq!(served, vec!["celo".to_owned(), "moonbeam".to_owned()]);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn it_spawns_and_aborts_replica_tasks() {
+        test_utils::run_test_db(|db| async move {
+            let settings = Settings {
+                replicas: [(
+                    "moonbeam".to_owned(),
+                    ChainSetup {
+                        name: "moonbeam".to_owned(),
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            };
+
+            let mut mock_replica = MockReplicaContract::new();
+            mock_replica
+                .expect__name()
+                .return_const("moonbeam".to_owned());
+            let replica = Arc::new(CachingReplica::new(
+                mock_replica.into(),
+                NomadDB::new("moonbeam", db.clone()),
+                Arc::new(MockIndexer::new().into()),
+            ));
+            let replicas: Arc<ServedReplicas> = Arc::new(std::sync::RwLock::new(
+                [("moonbeam".to_owned(), replica)].into_iter().collect(),
+            ));
+
+            let enrollment = ReplicaEnrollment {
+                settings,
+                load: Box::new(|| Ok(Default::default())),
+                db,
+                replicas: replicas.clone(),
+                managers: Default::default(),
+            };
+            let tasks: Arc<ReplicaTasks> = Default::default();
+            let enrollment_task = enrollment.spawn(tasks.clone(), |_| {
+                tokio::spawn(async {
+                    sleep(Duration::from_secs(3600)).await;
+                    Ok(())
+                })
+                .in_current_span()
+            });
+
+            // Tasks can be inspected while enrollment is running
+            while !tasks.read().await.contains_key("moonbeam") {
+                sleep(Duration::from_millis(10)).await;
+            }
+            assert!(replicas.read().unwrap().contains_key("moonbeam"));
+
+            // Enrollment resolves once a replica task exits
+            tasks.read().await["moonbeam"].abort();
+            assert!(enrollment_task.await.unwrap().is_err());
+
+            abort_replica_tasks(&tasks).await;
+            assert!(tasks.read().await.is_empty());
+        })
+        .await
+    }
+}
@@ -0,0 +1,373 @@
+use async_trait::async_trait;
+use color_eyre::{eyre::WrapErr, Result};
+use ethers::core::types::{Bytes, H256};
+use nomad_core::{
+    CommonIndexer, HomeIndexer, NomadIdentifier, RawCommittedMessage, SignedUpdate,
+    SignedUpdateWithMeta, UpdateMeta,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+/// A single line of a recorded log archive.
+///
+/// ```json
+/// {"type": "block", "number": 5, "hash": "0x..."}
+/// {"type": "update", "blockNumber": 5, "timestamp": 1637000000, "update": {...}}
+/// {"type": "dispatch", "blockNumber": 5, "leafIndex": 0, "committedRoot": "0x...", "message": "0x..."}
+/// {"type": "process", "blockNumber": 6, "messageHash": "0x..."}
+/// ```
+///
+/// Records other than blocks, updates and dispatches are only read by
+/// [`ArchiveContract`](crate::ArchiveContract).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ArchiveRecord {
+    /// A block header. Optional, used to answer block hash queries
+    #[serde(rename_all = "camelCase")]
+    Block {
+        /// Block number
+        number: u32,
+        /// Block hash
+        hash: H256,
+    },
+    /// An `Update` event
+    #[serde(rename_all = "camelCase")]
+    Update {
+        /// Block the event was emitted in
+        block_number: u32,
+        /// Block timestamp
+        timestamp: Option<u64>,
+        /// The signed update
+        update: SignedUpdate,
+    },
+    /// A `Dispatch` event
+    #[serde(rename_all = "camelCase")]
+    Dispatch {
+        /// Block the event was emitted in
+        block_number: u32,
+        /// Leaf index of the message
+        leaf_index: u32,
+        /// Home's committed root when the message was dispatched
+        committed_root: H256,
+        /// Serialized message
+        message: Bytes,
+    },
+    /// A `NewUpdater` event
+    #[serde(rename_all = "camelCase")]
+    NewUpdater {
+        /// Block the event was emitted in
+        block_number: u32,
+        /// The new updater
+        updater: H256,
+    },
+    /// A `DoubleUpdate` event
+    #[serde(rename_all = "camelCase")]
+    DoubleUpdate {
+        /// Block the event was emitted in
+        block_number: u32,
+        /// The first conflicting update
+        first: SignedUpdate,
+        /// The second conflicting update
+        second: SignedUpdate,
+    },
+    /// An `ImproperUpdate` event
+    #[serde(rename_all = "camelCase")]
+    ImproperUpdate {
+        /// Block the event was emitted in
+        block_number: u32,
+        /// The improper update
+        update: SignedUpdate,
+    },
+    /// A message proven on a replica. Replicas emit no event for proofs, so
+    /// these are only recorded for `prove` calls
+    #[serde(rename_all = "camelCase")]
+    Prove {
+        /// Block the message was proven in
+        block_number: u32,
+        /// Leaf of the proven message
+        leaf: H256,
+    },
+    /// A `Process` event
+    #[serde(rename_all = "camelCase")]
+    Process {
+        /// Block the event was emitted in
+        block_number: u32,
+        /// Leaf of the processed message
+        message_hash: H256,
+    },
+    /// A `ReplicaEnrolled` event
+    #[serde(rename_all = "camelCase")]
+    ReplicaEnrolled {
+        /// Block the event was emitted in
+        block_number: u32,
+        /// Remote domain of the replica
+        domain: u32,
+        /// Address of the replica
+        replica: NomadIdentifier,
+    },
+    /// A `ReplicaUnenrolled` event
+    #[serde(rename_all = "camelCase")]
+    ReplicaUnenrolled {
+        /// Block the event was emitted in
+        block_number: u32,
+        /// Remote domain of the replica
+        domain: u32,
+        /// Address of the replica
+        replica: NomadIdentifier,
+    },
+    /// A `WatcherPermissionSet` event
+    #[serde(rename_all = "camelCase")]
+    WatcherPermissionSet {
+        /// Block the event was emitted in
+        block_number: u32,
+        /// Domain the permission applies to
+        domain: u32,
+        /// Address of the watcher
+        watcher: NomadIdentifier,
+        /// Whether the watcher may unenroll the domain's replica
+        access: bool,
+    },
+    /// A `NewHome` event
+    #[serde(rename_all = "camelCase")]
+    NewHome {
+        /// Block the event was emitted in
+        block_number: u32,
+        /// Address of the home
+        home: NomadIdentifier,
+    },
+}
+
+impl ArchiveRecord {
+    /// Block the record belongs to
+    pub fn block_number(&self) -> u32 {
+        match self {
+            ArchiveRecord::Block { number, .. } => *number,
+            ArchiveRecord::Update { block_number, .. }
+            | ArchiveRecord::Dispatch { block_number, .. }
+            | ArchiveRecord::NewUpdater { block_number, .. }
+            | ArchiveRecord::DoubleUpdate { block_number, .. }
+            | ArchiveRecord::ImproperUpdate { block_number, .. }
+            | ArchiveRecord::Prove { block_number, .. }
+            | ArchiveRecord::Process { block_number, .. }
+            | ArchiveRecord::ReplicaEnrolled { block_number, .. }
+            | ArchiveRecord::ReplicaUnenrolled { block_number, .. }
+            | ArchiveRecord::WatcherPermissionSet { block_number, .. }
+            | ArchiveRecord::NewHome { block_number, .. } => *block_number,
+        }
+    }
+
+    /// Read the records of a JSON lines archive
+    pub fn read_all(path: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .wrap_err_with(|| format!("unable to open log archive {}", path.display()))?;
+
+        let mut records = vec![];
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str(&line).wrap_err_with(|| {
+                format!("invalid record on line {} of {}", i + 1, path.display())
+            })?;
+            records.push(record);
+        }
+
+        Ok(records)
+    }
+}
+
+/// Indexer replaying events from a recorded archive of JSON lines instead of
+/// querying a chain. Used to replay the updates and messages of a historical
+/// period deterministically.
+///
+/// Chains configured with the `archive` rpc style also answer contract calls
+/// from the archive (see [`ArchiveContract`](crate::ArchiveContract)). Setting
+/// only `index.archive` replays indexing, while contract calls still go to the
+/// configured connection.
+///
+/// The chain tip is the highest block number found in the archive.
+#[derive(Debug, Default)]
+pub struct FileIndexer {
+    tip: u32,
+    block_hashes: HashMap<u32, H256>,
+    updates: Vec<SignedUpdateWithMeta>,
+    messages: Vec<(u32, RawCommittedMessage)>,
+}
+
+impl FileIndexer {
+    /// Load an archive from a JSON lines file
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::from_records(ArchiveRecord::read_all(path)?))
+    }
+
+    /// Build an indexer from archive records
+    pub fn from_records(records: impl IntoIterator<Item = ArchiveRecord>) -> Self {
+        let mut indexer = Self::default();
+
+        for record in records {
+            match record {
+                ArchiveRecord::Block { number, hash } => {
+                    indexer.tip = indexer.tip.max(number);
+                    indexer.block_hashes.insert(number, hash);
+                }
+                ArchiveRecord::Update {
+                    block_number,
+                    timestamp,
+                    update,
+                } => {
+                    indexer.tip = indexer.tip.max(block_number);
+                    indexer.updates.push(SignedUpdateWithMeta {
+                        signed_update: update,
+                        metadata: UpdateMeta {
+                            block_number: block_number as u64,
+                            timestamp,
+                        },
+                    });
+                }
+                ArchiveRecord::Dispatch {
+                    block_number,
+                    leaf_index,
+                    committed_root,
+                    message,
+                } => {
+                    indexer.tip = indexer.tip.max(block_number);
+                    indexer.messages.push((
+                        block_number,
+                        RawCommittedMessage {
+                            leaf_index,
+                            committed_root,
+                            message: message.to_vec(),
+                        },
+                    ));
+                }
+                record => indexer.tip = indexer.tip.max(record.block_number()),
+            }
+        }
+
+        // Keep recorded order within a block
+        indexer
+            .updates
+            .sort_by_key(|update| update.metadata.block_number);
+        indexer
+            .messages
+            .sort_by_key(|(_, message)| message.leaf_index);
+
+        indexer
+    }
+}
+
+#[async_trait]
+impl CommonIndexer for FileIndexer {
+    async fn get_block_number(&self) -> Result<u32> {
+        Ok(self.tip)
+    }
+
+    async fn get_block_hash(&self, height: u32) -> Result<Option<H256>> {
+        Ok(self.block_hashes.get(&height).cloned())
+    }
+
+    async fn fetch_sorted_updates(&self, from: u32, to: u32) -> Result<Vec<SignedUpdateWithMeta>> {
+        let (from, to) = (from as u64, to as u64);
+        Ok(self
+            .updates
+            .iter()
+            .filter(|update| (from..=to).contains(&update.metadata.block_number))
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl HomeIndexer for FileIndexer {
+    async fn fetch_sorted_messages(&self, from: u32, to: u32) -> Result<Vec<RawCommittedMessage>> {
+        Ok(self
+            .messages
+            .iter()
+            .filter(|(block_number, _)| (from..=to).contains(block_number))
+            .map(|(_, message)| message.clone())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::signers::LocalWallet;
+    use nomad_core::{Encode, NomadMessage, Update};
+
+    #[tokio::test]
+    async fn it_replays_records() {
+        let signer: LocalWallet =
+            "1111111111111111111111111111111111111111111111111111111111111111"
+                .parse()
+                .unwrap();
+        let update = Update {
+            home_domain: 1,
+            previous_root: H256::zero(),
+            new_root: H256::repeat_byte(1),
+        }
+        .sign_with(&signer)
+        .await
+        .unwrap();
+        let message = NomadMessage {
+            origin: 1,
+            sender: H256::repeat_byte(2),
+            nonce: 0,
+            destination: 2,
+            recipient: H256::repeat_byte(3),
+            body: vec![1, 2, 3],
+        };
+
+        let records = vec![
+            ArchiveRecord::Dispatch {
+                block_number: 7,
+                leaf_index: 0,
+                committed_root: H256::zero(),
+                message: message.to_vec().into(),
+            },
+            ArchiveRecord::Update {
+                block_number: 12,
+                timestamp: None,
+                update: update.clone(),
+            },
+            ArchiveRecord::Block {
+                number: 20,
+                hash: H256::repeat_byte(4),
+            },
+        ];
+        let lines: Vec<_> = records
+            .iter()
+            .map(|record| serde_json::to_string(record).unwrap())
+            .collect();
+        let parsed = lines.iter().map(|line| serde_json::from_str(line).unwrap());
+        let indexer = FileIndexer::from_records(parsed);
+
+        assert_eq!(indexer.get_block_number().await.unwrap(), 20);
+        assert_eq!(
+            indexer.get_block_hash(20).await.unwrap(),
+            Some(H256::repeat_byte(4))
+        );
+        assert_eq!(indexer.get_block_hash(19).await.unwrap(), None);
+
+        let messages = indexer.fetch_sorted_messages(0, 10).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].message, message.to_vec());
+        assert!(indexer
+            .fetch_sorted_messages(8, 20)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let updates = indexer.fetch_sorted_updates(10, 12).await.unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].signed_update, update);
+        assert_eq!(updates[0].metadata.block_number, 12);
+    }
+}
@@ -1,5 +1,6 @@
 use async_trait::async_trait;
 use color_eyre::Result;
+use ethers::core::types::H256;
 use nomad_core::{CommonIndexer, HomeIndexer, RawCommittedMessage, SignedUpdateWithMeta};
 use nomad_test::mocks::MockIndexer;
 
@@ -30,6 +31,14 @@ impl CommonIndexer for CommonIndexers {
         }
     }
 
+    async fn get_block_hash(&self, height: u32) -> Result<Option<H256>> {
+        match self {
+            CommonIndexers::Ethereum(indexer) => indexer.get_block_hash(height).await,
+            CommonIndexers::Mock(indexer) => indexer.get_block_hash(height).await,
+            CommonIndexers::Other(indexer) => indexer.get_block_hash(height).await,
+        }
+    }
+
     async fn fetch_sorted_updates(&self, from: u32, to: u32) -> Result<Vec<SignedUpdateWithMeta>> {
         match self {
             CommonIndexers::Ethereum(indexer) => indexer.fetch_sorted_updates(from, to).await,
@@ -66,6 +75,14 @@ impl CommonIndexer for HomeIndexers {
         }
     }
 
+    async fn get_block_hash(&self, height: u32) -> Result<Option<H256>> {
+        match self {
+            HomeIndexers::Ethereum(indexer) => indexer.get_block_hash(height).await,
+            HomeIndexers::Mock(indexer) => indexer.get_block_hash(height).await,
+            HomeIndexers::Other(indexer) => indexer.get_block_hash(height).await,
+        }
+    }
+
     async fn fetch_sorted_updates(&self, from: u32, to: u32) -> Result<Vec<SignedUpdateWithMeta>> {
         match self {
             HomeIndexers::Ethereum(indexer) => indexer.fetch_sorted_updates(from, to).await,
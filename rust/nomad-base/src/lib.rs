@@ -41,11 +41,35 @@ pub use replica::*;
 mod xapp;
 pub use xapp::*;
 
+/// Updating the replicas served while an agent runs
+mod enrollment;
+pub use enrollment::*;
+
 mod metrics;
 pub use metrics::*;
 
+/// Accounting and capping what agents spend on gas
+mod budget;
+pub use budget::*;
+
+/// Exponential backoff between retries
+mod backoff;
+pub use backoff::*;
+
+/// Read-only HTTP API
+mod api;
+pub use api::*;
+
 mod contract_sync;
 pub use contract_sync::*;
 
 mod indexer;
 pub use indexer::*;
+
+/// Indexer replaying recorded logs
+mod file_indexer;
+pub use file_indexer::*;
+
+/// Contracts replayed from recorded logs
+mod archive;
+pub use archive::*;
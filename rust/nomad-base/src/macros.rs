@@ -87,6 +87,12 @@ macro_rules! decl_settings {
                 }
             }
 
+            impl AsMut<nomad_base::Settings> for [<$name Settings>] {
+                fn as_mut(&mut self) -> &mut nomad_base::Settings {
+                    &mut self.base
+                }
+            }
+
             impl [<$name Settings>] {
                 /// Read settings from the config files and/or env
                 /// The config will be located at `config/default` unless specified
@@ -138,6 +144,19 @@ macro_rules! decl_settings {
 
                     Ok(settings)
                 }
+
+                /// Settings for each network served. See
+                /// [`nomad_base::Settings::split_networks`].
+                pub fn split_networks(&self) -> Vec<Self> {
+                    self.base
+                        .split_networks()
+                        .into_iter()
+                        .map(|base| Self {
+                            base,
+                            $($prop: self.$prop.clone(),)*
+                        })
+                        .collect()
+                }
             }
         }
     }
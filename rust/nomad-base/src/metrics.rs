@@ -1,8 +1,10 @@
 //! Useful metrics that all agents should track.
 
 use color_eyre::Result;
+use ethers::types::U256;
 use prometheus::{
-    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry,
+    CounterVec, Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts,
+    Registry,
 };
 use std::sync::Arc;
 use tokio::task::JoinHandle;
@@ -11,8 +13,12 @@ use tokio::task::JoinHandle;
 /// Metrics for a particular domain
 pub struct CoreMetrics {
     agent_name: String,
+    network: Option<String>,
     transactions: Box<IntGaugeVec>,
     wallet_balance: Box<IntGaugeVec>,
+    gas_used: Box<IntCounterVec>,
+    gas_spent: Box<CounterVec>,
+    daily_gas_spend: Box<GaugeVec>,
     rpc_latencies: Box<HistogramVec>,
     span_durations: Box<HistogramVec>,
     listen_port: Option<u16>,
@@ -20,6 +26,18 @@ pub struct CoreMetrics {
     registry: Arc<Registry>,
 }
 
+/// Options shared by all metrics. Metrics of agents serving one of several
+/// networks are labelled with the network, so they can share a registry.
+fn metric_opts(metric_name: &str, help: &str, network: Option<&str>) -> Opts {
+    let opts = Opts::new(metric_name, help)
+        .namespace("nomad")
+        .const_label("VERSION", env!("CARGO_PKG_VERSION"));
+    match network {
+        Some(network) => opts.const_label("NETWORK", network),
+        None => opts,
+    }
+}
+
 impl CoreMetrics {
     /// Track metrics for a particular agent name.
     pub fn new<S: Into<String>>(
@@ -27,44 +45,79 @@ impl CoreMetrics {
         listen_port: Option<u16>,
         registry: Arc<Registry>,
     ) -> prometheus::Result<CoreMetrics> {
+        Self::for_network(for_agent, None, listen_port, registry)
+    }
+
+    /// Track metrics for a particular agent name, serving one of several
+    /// networks sharing `registry`
+    pub fn for_network<S: Into<String>>(
+        for_agent: S,
+        network: Option<String>,
+        listen_port: Option<u16>,
+        registry: Arc<Registry>,
+    ) -> prometheus::Result<CoreMetrics> {
+        let labels = network.as_deref();
         let metrics = CoreMetrics {
             agent_name: for_agent.into(),
             transactions: Box::new(IntGaugeVec::new(
-                Opts::new(
+                metric_opts(
                     "transactions_total",
                     "Number of transactions sent by this agent since boot",
-                )
-                .namespace("nomad")
-                .const_label("VERSION", env!("CARGO_PKG_VERSION")),
+                    labels,
+                ),
                 &["chain", "wallet", "agent"],
             )?),
             wallet_balance: Box::new(IntGaugeVec::new(
-                Opts::new(
+                metric_opts(
                     "wallet_balance_total",
                     "Balance of the smart contract wallet",
-                )
-                .namespace("nomad")
-                .const_label("VERSION", env!("CARGO_PKG_VERSION")),
+                    labels,
+                ),
                 &["chain", "wallet", "agent"],
             )?),
+            gas_used: Box::new(IntCounterVec::new(
+                metric_opts(
+                    "gas_used_total",
+                    "Gas used by the transactions sent by this agent since boot",
+                    labels,
+                ),
+                &["chain", "agent", "operation"],
+            )?),
+            gas_spent: Box::new(CounterVec::new(
+                metric_opts(
+                    "gas_spent_wei_total",
+                    "Gas used times effective gas price of the transactions sent by this agent since boot",
+                    labels,
+                ),
+                &["chain", "agent", "operation"],
+            )?),
+            daily_gas_spend: Box::new(GaugeVec::new(
+                metric_opts(
+                    "daily_gas_spend_wei",
+                    "Spent on gas by this agent during the current UTC day, as counted against its daily cap",
+                    labels,
+                ),
+                &["chain", "agent"],
+            )?),
             rpc_latencies: Box::new(HistogramVec::new(
-                HistogramOpts::new(
+                metric_opts(
                     "rpc_duration_ms",
                     "Duration from dispatch to receipt-of-response for RPC calls",
+                    labels,
                 )
-                .namespace("nomad")
-                .const_label("VERSION", env!("CARGO_PKG_VERSION")),
+                .into(),
                 &["chain", "method", "agent"],
             )?),
             span_durations: Box::new(HistogramVec::new(
-                HistogramOpts::new(
+                metric_opts(
                     "span_duration_sec",
                     "Duration from span creation to span destruction",
+                    labels,
                 )
-                .namespace("nomad")
-                .const_label("VERSION", env!("CARGO_PKG_VERSION")),
+                .into(),
                 &["span_name", "target"],
             )?),
+            network,
             registry,
             listen_port,
         };
@@ -73,12 +126,20 @@ impl CoreMetrics {
 
         metrics.registry.register(metrics.transactions.clone())?;
         metrics.registry.register(metrics.wallet_balance.clone())?;
+        metrics.registry.register(metrics.gas_used.clone())?;
+        metrics.registry.register(metrics.gas_spent.clone())?;
+        metrics.registry.register(metrics.daily_gas_spend.clone())?;
         metrics.registry.register(metrics.rpc_latencies.clone())?;
         metrics.registry.register(metrics.span_durations.clone())?;
 
         Ok(metrics)
     }
 
+    /// The registry the metrics are registered in
+    pub fn registry(&self) -> Arc<Registry> {
+        self.registry.clone()
+    }
+
     /// Register an int gauge.
     pub fn new_int_gauge(
         &self,
@@ -87,9 +148,23 @@ impl CoreMetrics {
         labels: &[&str],
     ) -> Result<prometheus::IntGaugeVec> {
         let gauge = IntGaugeVec::new(
-            Opts::new(metric_name, help)
-                .namespace("nomad")
-                .const_label("VERSION", env!("CARGO_PKG_VERSION")),
+            metric_opts(metric_name, help, self.network.as_deref()),
+            labels,
+        )?;
+        self.registry.register(Box::new(gauge.clone()))?;
+
+        Ok(gauge)
+    }
+
+    /// Register a gauge.
+    pub fn new_gauge(
+        &self,
+        metric_name: &str,
+        help: &str,
+        labels: &[&str],
+    ) -> Result<prometheus::GaugeVec> {
+        let gauge = GaugeVec::new(
+            metric_opts(metric_name, help, self.network.as_deref()),
             labels,
         )?;
         self.registry.register(Box::new(gauge.clone()))?;
@@ -105,9 +180,7 @@ impl CoreMetrics {
         labels: &[&str],
     ) -> Result<prometheus::IntCounterVec> {
         let counter = IntCounterVec::new(
-            Opts::new(metric_name, help)
-                .namespace("nomad")
-                .const_label("VERSION", env!("CARGO_PKG_VERSION")),
+            metric_opts(metric_name, help, self.network.as_deref()),
             labels,
         )?;
 
@@ -125,10 +198,8 @@ impl CoreMetrics {
         buckets: &[f64],
     ) -> Result<prometheus::HistogramVec> {
         let histogram = HistogramVec::new(
-            HistogramOpts::new(metric_name, help)
-                .namespace("nomad")
-                .buckets(buckets.to_owned())
-                .const_label("VERSION", env!("CARGO_PKG_VERSION")),
+            HistogramOpts::from(metric_opts(metric_name, help, self.network.as_deref()))
+                .buckets(buckets.to_owned()),
             labels,
         )?;
 
@@ -149,6 +220,25 @@ impl CoreMetrics {
             .set(current_balance.as_u64() as i64) // XXX: truncated data
     }
 
+    /// Call with the gas used and cost (gas used times effective gas price)
+    /// of each transaction the agent sends
+    pub fn transaction_cost(&self, chain: &str, operation: &str, gas_used: U256, cost: U256) {
+        let labels = [chain, &self.agent_name, operation];
+        self.gas_used
+            .with_label_values(&labels)
+            .inc_by(u64::try_from(gas_used).unwrap_or(u64::MAX));
+        self.gas_spent
+            .with_label_values(&labels)
+            .inc_by(u128::try_from(cost).unwrap_or(u128::MAX) as f64);
+    }
+
+    /// Call with the total spent on gas on `chain` during the current UTC day
+    pub fn daily_gas_spend_changed(&self, chain: &str, spend: U256) {
+        self.daily_gas_spend
+            .with_label_values(&[chain, &self.agent_name])
+            .set(u128::try_from(spend).unwrap_or(u128::MAX) as f64)
+    }
+
     /// Call with RPC duration after it is complete
     pub fn rpc_complete(&self, chain: &str, method: &str, duration_ms: f64) {
         self.rpc_latencies
@@ -1,9 +1,10 @@
 use color_eyre::Result;
-use ethers::core::types::H256;
+use ethers::core::types::{H256, U256};
 use nomad_core::db::{DbError, TypedDB, DB};
 use nomad_core::{
-    accumulator::merkle::Proof, utils, CommittedMessage, Decode, NomadMessage, RawCommittedMessage,
-    SignedUpdate, SignedUpdateWithMeta, UpdateMeta,
+    accumulator::merkle::Proof, utils, CommittedMessage, Decode, MessageRetry, NomadMessage,
+    RawCommittedMessage, SignedUpdate, SignedUpdateWithMeta, UpdateAttestation, UpdateMeta,
+    UpdateRelay,
 };
 use tokio::time::sleep;
 use tracing::{debug, info};
@@ -23,7 +24,16 @@ static UPDATE_META: &str = "update_metadata_";
 static LATEST_ROOT: &str = "update_latest_root_";
 static LATEST_LEAF_INDEX: &str = "latest_known_leaf_index_";
 static UPDATER_PRODUCED_UPDATE: &str = "updater_produced_update_";
+static UPDATER_ATTESTATION: &str = "updater_attestation_";
 static PROVER_LATEST_COMMITTED: &str = "prover_latest_committed_";
+static PROCESSOR_NONCE: &str = "current_nonce_";
+static PROCESSOR_RETRY: &str = "processor_retry_";
+static PROCESSOR_DEAD_LETTER: &str = "processor_dead_letter_";
+static PROCESSOR_DEFERRED: &str = "processor_deferred_";
+static GAS_SPEND: &str = "gas_spend_";
+static RELAYER_RELAY: &str = "relayer_relay_";
+static RELAYER_SETTLED_ROOT: &str = "relayer_settled_root_";
+static ROLLBACK_GENERATION: &str = "rollback_generation_";
 
 /// DB handle for storing data tied to a specific home.
 ///
@@ -313,6 +323,119 @@ impl NomadDB {
         }
     }
 
+    /// Number of rollbacks that removed updates or messages from the DB.
+    /// Components keeping state derived from indexed updates or messages
+    /// rebuild it when this changes.
+    pub fn rollback_generation(&self) -> Result<u64, DbError> {
+        Ok(self
+            .retrieve_decodable("", ROLLBACK_GENERATION)?
+            .unwrap_or_default())
+    }
+
+    fn bump_rollback_generation(&self) -> Result<(), DbError> {
+        let generation = self.rollback_generation()? + 1;
+        debug!(generation, "bumping rollback generation");
+        self.store_encodable("", ROLLBACK_GENERATION, &generation)
+    }
+
+    /// Remove updates included after block `after_block`, walking back from
+    /// the latest root, and reset the latest root accordingly. Removes all
+    /// updates and the latest root if `after_block` is `None`. The prover's
+    /// latest committed root is reset too if it was rolled back. Returns the
+    /// number of updates removed.
+    pub fn rollback_updates(&self, after_block: Option<u64>) -> Result<usize, DbError> {
+        let mut root = match self.retrieve_latest_root()? {
+            Some(root) => root,
+            None => return Ok(0),
+        };
+        let prover_root = self.retrieve_prover_latest_committed()?;
+
+        let mut removed = 0;
+        let mut prover_rolled_back = false;
+        while let Some(update) = self.update_by_new_root(root)? {
+            if let Some(after_block) = after_block {
+                match self.retrieve_update_metadata(root)? {
+                    Some(meta) if meta.block_number > after_block => {}
+                    _ => break,
+                }
+            }
+
+            debug!(
+                previous_root = ?update.update.previous_root,
+                new_root = ?root,
+                "rolling back update"
+            );
+            self.delete_keyed_value(UPDATE, &update.update.previous_root)?;
+            self.delete_keyed_value(PREV_ROOT, &root)?;
+            self.delete_keyed_value(UPDATE_META, &root)?;
+
+            prover_rolled_back |= prover_root == Some(root);
+            root = update.update.previous_root;
+            removed += 1;
+        }
+
+        if after_block.is_none() {
+            self.delete_value("", LATEST_ROOT)?;
+        } else if removed > 0 {
+            self.store_latest_root(root)?;
+        }
+
+        if prover_rolled_back {
+            if after_block.is_none() || self.update_by_new_root(root)?.is_none() {
+                self.delete_value("", PROVER_LATEST_COMMITTED)?;
+            } else {
+                self.store_prover_latest_committed(root)?;
+            }
+        }
+
+        if removed > 0 {
+            self.bump_rollback_generation()?;
+        }
+        Ok(removed)
+    }
+
+    /// Remove messages (and their proofs) with a leaf index above
+    /// `leaf_index`, and reset the latest leaf index accordingly. Removes all
+    /// messages if `leaf_index` is `None`. Returns the number of messages
+    /// removed.
+    pub fn rollback_messages(&self, leaf_index: Option<u32>) -> Result<usize, DbError> {
+        let latest_leaf_index = self.retrieve_latest_leaf_index()?;
+
+        let mut removed = 0;
+        let mut index = leaf_index.map_or(0, |i| i + 1);
+        loop {
+            let leaf = self.leaf_by_leaf_index(index)?;
+
+            // Messages may have been stored out of order, keep going until
+            // both the latest leaf index and any gap have been passed
+            if leaf.is_none() && latest_leaf_index.map_or(true, |latest| index > latest) {
+                break;
+            }
+
+            if let Some(leaf) = leaf {
+                if let Some(message) = self.message_by_leaf(leaf)? {
+                    let parsed = NomadMessage::read_from(&mut message.message.as_slice())?;
+                    self.delete_keyed_value(LEAF, &parsed.destination_and_nonce())?;
+                    self.delete_keyed_value(MESSAGE, &leaf)?;
+                }
+                debug!(leaf_index = index, leaf = ?leaf, "rolling back message");
+                self.delete_keyed_value(LEAF, &index)?;
+                self.delete_keyed_value(PROOF, &index)?;
+                removed += 1;
+            }
+            index += 1;
+        }
+
+        match leaf_index {
+            Some(leaf_index) => self.update_latest_leaf_index(leaf_index)?,
+            None => self.delete_value("", LATEST_LEAF_INDEX)?,
+        }
+        if removed > 0 {
+            self.bump_rollback_generation()?;
+        }
+        Ok(removed)
+    }
+
     /// Iterate over all leaves
     pub fn leaf_iterator(&self) -> PrefixIterator<H256> {
         PrefixIterator::new(self.0.as_ref().prefix_iterator(LEAF_IDX), LEAF_IDX.as_ref())
@@ -363,6 +486,24 @@ impl NomadDB {
         self.retrieve_keyed_decodable(UPDATER_PRODUCED_UPDATE, &previous_root)
     }
 
+    /// Store the committee attestation collected for an update
+    pub fn store_attestation(&self, attestation: &UpdateAttestation) -> Result<(), DbError> {
+        self.store_keyed_encodable(
+            UPDATER_ATTESTATION,
+            &attestation.update.previous_root,
+            attestation,
+        )
+    }
+
+    /// Retrieve the committee attestation collected for the update building
+    /// off `previous_root` (if one exists).
+    pub fn retrieve_attestation(
+        &self,
+        previous_root: H256,
+    ) -> Result<Option<UpdateAttestation>, DbError> {
+        self.retrieve_keyed_decodable(UPDATER_ATTESTATION, &previous_root)
+    }
+
     /// Store prover latest root for which db has all leaves/proofs under root
     pub fn store_prover_latest_committed(&self, root: H256) -> Result<(), DbError> {
         self.store_encodable("", PROVER_LATEST_COMMITTED, &root)
@@ -373,13 +514,248 @@ impl NomadDB {
     pub fn retrieve_prover_latest_committed(&self) -> Result<Option<H256>, DbError> {
         self.retrieve_decodable("", PROVER_LATEST_COMMITTED)
     }
+
+    /// Store the last nonce the processor handled for a destination
+    pub fn store_processor_nonce(&self, destination: u32, nonce: u32) -> Result<(), DbError> {
+        self.store_keyed_encodable(PROCESSOR_NONCE, &destination, &nonce)
+    }
+
+    /// Retrieve the last nonce the processor handled for a destination
+    pub fn retrieve_processor_nonce(&self, destination: u32) -> Result<Option<u32>, DbError> {
+        self.retrieve_keyed_decodable(PROCESSOR_NONCE, &destination)
+    }
+
+    /// Store (or overwrite) a message in the processor retry queue
+    ///
+    /// Keys --> Values:
+    /// - `destination_and_nonce` --> `retry`
+    pub fn store_retry(&self, retry: &MessageRetry) -> Result<(), DbError> {
+        debug!(
+            destination = retry.destination,
+            nonce = retry.nonce,
+            attempts = retry.attempts,
+            next_attempt_at = retry.next_attempt_at,
+            "storing message retry in DB"
+        );
+        let key = utils::destination_and_nonce(retry.destination, retry.nonce);
+        self.store_keyed_encodable(PROCESSOR_RETRY, &key, retry)
+    }
+
+    /// Retrieve a queued retry by destination and nonce
+    pub fn retry_by_nonce(
+        &self,
+        destination: u32,
+        nonce: u32,
+    ) -> Result<Option<MessageRetry>, DbError> {
+        let key = utils::destination_and_nonce(destination, nonce);
+        self.retrieve_keyed_decodable(PROCESSOR_RETRY, &key)
+    }
+
+    /// Remove a message from the processor retry queue
+    pub fn remove_retry(&self, destination: u32, nonce: u32) -> Result<(), DbError> {
+        let key = utils::destination_and_nonce(destination, nonce);
+        self.delete_keyed_value(PROCESSOR_RETRY, &key)
+    }
+
+    /// Iterate over the retry queue for `destination`, ordered by nonce
+    pub fn retries(&self, destination: u32) -> impl Iterator<Item = MessageRetry> + '_ {
+        let mut prefix = PROCESSOR_RETRY.as_bytes().to_vec();
+        prefix.extend(destination.to_be_bytes());
+        self.prefix_values(prefix)
+    }
+
+    /// Move a message from the retry queue into the dead-letter store
+    ///
+    /// Keys --> Values:
+    /// - `destination_and_nonce` --> `retry`
+    pub fn store_dead_letter(&self, retry: &MessageRetry) -> Result<(), DbError> {
+        debug!(
+            destination = retry.destination,
+            nonce = retry.nonce,
+            attempts = retry.attempts,
+            "moving message retry to dead-letter store in DB"
+        );
+        let key = utils::destination_and_nonce(retry.destination, retry.nonce);
+        self.store_keyed_encodable(PROCESSOR_DEAD_LETTER, &key, retry)?;
+        self.remove_retry(retry.destination, retry.nonce)
+    }
+
+    /// Retrieve a dead-lettered message by destination and nonce
+    pub fn dead_letter_by_nonce(
+        &self,
+        destination: u32,
+        nonce: u32,
+    ) -> Result<Option<MessageRetry>, DbError> {
+        let key = utils::destination_and_nonce(destination, nonce);
+        self.retrieve_keyed_decodable(PROCESSOR_DEAD_LETTER, &key)
+    }
+
+    /// Iterate over the dead-lettered messages for `destination`, ordered by
+    /// nonce
+    pub fn dead_letters(&self, destination: u32) -> impl Iterator<Item = MessageRetry> + '_ {
+        let mut prefix = PROCESSOR_DEAD_LETTER.as_bytes().to_vec();
+        prefix.extend(destination.to_be_bytes());
+        self.prefix_values(prefix)
+    }
+
+    /// Move a dead-lettered message back into the retry queue with a fresh
+    /// attempt count. Returns the requeued entry, or `None` if there was no
+    /// dead letter for this destination and nonce.
+    pub fn requeue_dead_letter(
+        &self,
+        destination: u32,
+        nonce: u32,
+    ) -> Result<Option<MessageRetry>, DbError> {
+        let retry = match self.dead_letter_by_nonce(destination, nonce)? {
+            Some(dead) => MessageRetry {
+                attempts: 0,
+                next_attempt_at: 0,
+                ..dead
+            },
+            None => return Ok(None),
+        };
+
+        self.store_retry(&retry)?;
+        let key = utils::destination_and_nonce(destination, nonce);
+        self.delete_keyed_value(PROCESSOR_DEAD_LETTER, &key)?;
+        Ok(Some(retry))
+    }
+
+    /// Store (or overwrite) the relay state of an update
+    ///
+    /// Keys --> Values:
+    /// - `previous_root_and_new_root` --> `relay`
+    pub fn store_relay(&self, relay: &UpdateRelay) -> Result<(), DbError> {
+        debug!(
+            previous_root = ?relay.previous_root,
+            new_root = ?relay.new_root,
+            status = relay.status.as_str(),
+            attempts = relay.attempts,
+            "storing update relay in DB"
+        );
+        let key = [relay.previous_root.as_bytes(), relay.new_root.as_bytes()].concat();
+        self.store_encodable(RELAYER_RELAY, key, relay)
+    }
+
+    /// Retrieve the relay state of the update from `previous_root` to
+    /// `new_root`
+    pub fn relay_by_roots(
+        &self,
+        previous_root: H256,
+        new_root: H256,
+    ) -> Result<Option<UpdateRelay>, DbError> {
+        let key = [previous_root.as_bytes(), new_root.as_bytes()].concat();
+        self.retrieve_decodable(RELAYER_RELAY, key)
+    }
+
+    /// Store the replica root up to which the relayer has settled relays
+    pub fn store_settled_root(&self, root: H256) -> Result<(), DbError> {
+        self.store_encodable("", RELAYER_SETTLED_ROOT, &root)
+    }
+
+    /// Retrieve the replica root up to which the relayer has settled relays
+    pub fn retrieve_settled_root(&self) -> Result<Option<H256>, DbError> {
+        self.retrieve_decodable("", RELAYER_SETTLED_ROOT)
+    }
+
+    /// Hold a message in the processor's manual queue until an operator
+    /// releases or discards it
+    ///
+    /// Keys --> Values:
+    /// - `destination_and_nonce` --> `deferred`
+    pub fn store_deferred(&self, deferred: &MessageRetry) -> Result<(), DbError> {
+        debug!(
+            destination = deferred.destination,
+            nonce = deferred.nonce,
+            "storing deferred message in DB"
+        );
+        let key = utils::destination_and_nonce(deferred.destination, deferred.nonce);
+        self.store_keyed_encodable(PROCESSOR_DEFERRED, &key, deferred)
+    }
+
+    /// Retrieve a deferred message by destination and nonce
+    pub fn deferred_by_nonce(
+        &self,
+        destination: u32,
+        nonce: u32,
+    ) -> Result<Option<MessageRetry>, DbError> {
+        let key = utils::destination_and_nonce(destination, nonce);
+        self.retrieve_keyed_decodable(PROCESSOR_DEFERRED, &key)
+    }
+
+    /// Iterate over the deferred messages for `destination`, ordered by nonce
+    pub fn deferred(&self, destination: u32) -> impl Iterator<Item = MessageRetry> + '_ {
+        let mut prefix = PROCESSOR_DEFERRED.as_bytes().to_vec();
+        prefix.extend(destination.to_be_bytes());
+        self.prefix_values(prefix)
+    }
+
+    /// Drop a message from the manual queue without processing it
+    pub fn discard_deferred(&self, destination: u32, nonce: u32) -> Result<(), DbError> {
+        let key = utils::destination_and_nonce(destination, nonce);
+        self.delete_keyed_value(PROCESSOR_DEFERRED, &key)
+    }
+
+    /// Move a deferred message into the retry queue, where the processor
+    /// picks it up regardless of its policy. Returns the released entry, or
+    /// `None` if there was no deferred message for this destination and
+    /// nonce.
+    pub fn release_deferred(
+        &self,
+        destination: u32,
+        nonce: u32,
+    ) -> Result<Option<MessageRetry>, DbError> {
+        let retry = match self.deferred_by_nonce(destination, nonce)? {
+            Some(deferred) => MessageRetry {
+                attempts: 0,
+                next_attempt_at: 0,
+                ..deferred
+            },
+            None => return Ok(None),
+        };
+
+        self.store_retry(&retry)?;
+        self.discard_deferred(destination, nonce)?;
+        Ok(Some(retry))
+    }
+
+    /// Store the total spent on gas on `chain` during the UTC `day` (days
+    /// since the unix epoch) by transactions of `operation`
+    ///
+    /// Keys --> Values:
+    /// - `chain_day_operation` --> `spend`
+    pub fn store_gas_spend(
+        &self,
+        chain: &str,
+        day: u64,
+        operation: &str,
+        spend: U256,
+    ) -> Result<(), DbError> {
+        self.store_encodable(
+            GAS_SPEND,
+            format!("{}_{}_{}", chain, day, operation),
+            &spend,
+        )
+    }
+
+    /// Retrieve the total spent on gas on `chain` during the UTC `day` by
+    /// transactions of `operation`
+    pub fn gas_spend(&self, chain: &str, day: u64, operation: &str) -> Result<U256, DbError> {
+        Ok(self
+            .retrieve_decodable(GAS_SPEND, format!("{}_{}_{}", chain, day, operation))?
+            .unwrap_or_default())
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use ethers::signers::LocalWallet;
     use ethers::types::H256;
-    use nomad_core::{accumulator::merkle::Proof, Encode, NomadMessage, RawCommittedMessage};
+    use nomad_core::{
+        accumulator::merkle::Proof, Encode, MessageRetry, NomadMessage, RawCommittedMessage,
+        SignedUpdateWithMeta, Update, UpdateMeta,
+    };
     use nomad_test::test_utils::run_test_db;
 
     #[tokio::test]
@@ -442,4 +818,132 @@ mod test {
         })
         .await;
     }
+
+    #[tokio::test]
+    async fn db_retries_and_dead_letters_messages() {
+        run_test_db(|db| async move {
+            let home_name = "home_1".to_owned();
+            let db = NomadDB::new(home_name, db);
+
+            let retry = |destination, nonce| MessageRetry {
+                destination,
+                nonce,
+                leaf_index: nonce + 100,
+                attempts: 1,
+                next_attempt_at: 1_000,
+                last_error: "reverted".to_owned(),
+            };
+
+            db.store_retry(&retry(12, 3)).unwrap();
+            db.store_retry(&retry(12, 1)).unwrap();
+            db.store_retry(&retry(13, 2)).unwrap();
+
+            let queued: Vec<_> = db.retries(12).map(|r| r.nonce).collect();
+            assert_eq!(queued, vec![1, 3]);
+            assert_eq!(db.retry_by_nonce(13, 2).unwrap().unwrap(), retry(13, 2));
+
+            db.store_dead_letter(&retry(12, 1)).unwrap();
+            assert!(db.retry_by_nonce(12, 1).unwrap().is_none());
+            let dead: Vec<_> = db.dead_letters(12).collect();
+            assert_eq!(dead, vec![retry(12, 1)]);
+
+            let requeued = db.requeue_dead_letter(12, 1).unwrap().unwrap();
+            assert_eq!(requeued.attempts, 0);
+            assert_eq!(requeued.next_attempt_at, 0);
+            assert_eq!(db.dead_letters(12).count(), 0);
+            assert_eq!(db.retries(12).count(), 2);
+            assert!(db.requeue_dead_letter(12, 1).unwrap().is_none());
+
+            db.remove_retry(12, 3).unwrap();
+            let queued: Vec<_> = db.retries(12).map(|r| r.nonce).collect();
+            assert_eq!(queued, vec![1]);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn db_rolls_back_messages_and_updates() {
+        run_test_db(|db| async move {
+            let home_name = "home_1".to_owned();
+            let db = NomadDB::new(home_name, db);
+
+            let messages: Vec<_> = (0..4)
+                .map(|i| RawCommittedMessage {
+                    leaf_index: i,
+                    committed_root: H256::from_low_u64_be(3),
+                    message: NomadMessage {
+                        origin: 10,
+                        sender: H256::from_low_u64_be(4),
+                        nonce: i,
+                        destination: 12,
+                        recipient: H256::from_low_u64_be(5),
+                        body: vec![1, 2, 3],
+                    }
+                    .to_vec(),
+                })
+                .collect();
+            db.store_messages(&messages).unwrap();
+
+            assert_eq!(db.rollback_generation().unwrap(), 0);
+            assert_eq!(db.rollback_messages(Some(1)).unwrap(), 2);
+            assert_eq!(db.rollback_generation().unwrap(), 1);
+            assert_eq!(db.retrieve_latest_leaf_index().unwrap(), Some(1));
+            assert!(db.message_by_leaf_index(1).unwrap().is_some());
+            assert!(db.message_by_leaf_index(2).unwrap().is_none());
+            assert!(db.message_by_nonce(12, 3).unwrap().is_none());
+
+            assert_eq!(db.rollback_messages(None).unwrap(), 2);
+            assert_eq!(db.retrieve_latest_leaf_index().unwrap(), None);
+
+            let signer: LocalWallet =
+                "1111111111111111111111111111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap();
+            let mut updates = vec![];
+            for i in 0..3u64 {
+                let signed_update = Update {
+                    home_domain: 1,
+                    previous_root: H256::from_low_u64_be(i),
+                    new_root: H256::from_low_u64_be(i + 1),
+                }
+                .sign_with(&signer)
+                .await
+                .unwrap();
+                updates.push(SignedUpdateWithMeta {
+                    signed_update,
+                    metadata: UpdateMeta {
+                        block_number: 10 * (i + 1),
+                        timestamp: None,
+                    },
+                });
+            }
+            db.store_updates_and_meta(&updates).unwrap();
+            db.store_prover_latest_committed(H256::from_low_u64_be(3))
+                .unwrap();
+
+            assert_eq!(db.rollback_updates(Some(15)).unwrap(), 2);
+            assert_eq!(db.rollback_generation().unwrap(), 3);
+            assert_eq!(
+                db.retrieve_latest_root().unwrap(),
+                Some(H256::from_low_u64_be(1))
+            );
+            assert_eq!(
+                db.retrieve_prover_latest_committed().unwrap(),
+                Some(H256::from_low_u64_be(1))
+            );
+            assert!(db
+                .update_by_new_root(H256::from_low_u64_be(2))
+                .unwrap()
+                .is_none());
+            assert!(db
+                .update_by_new_root(H256::from_low_u64_be(1))
+                .unwrap()
+                .is_some());
+
+            assert_eq!(db.rollback_updates(None).unwrap(), 1);
+            assert_eq!(db.retrieve_latest_root().unwrap(), None);
+            assert_eq!(db.retrieve_prover_latest_committed().unwrap(), None);
+        })
+        .await;
+    }
 }
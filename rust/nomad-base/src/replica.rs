@@ -2,8 +2,9 @@ use async_trait::async_trait;
 use color_eyre::eyre::Result;
 use ethers::core::types::H256;
 use nomad_core::{
-    accumulator::merkle::Proof, db::DbError, ChainCommunicationError, Common, CommonEvents,
-    DoubleUpdate, MessageStatus, NomadMessage, Replica, SignedUpdate, State, TxOutcome,
+    accumulator::merkle::Proof, db::DbError, BatchOutcomes, ChainCommunicationError, Common,
+    CommonEvents, DoubleUpdate, MessageStatus, NomadMessage, Replica, SignedUpdate, State,
+    TxOutcome,
 };
 
 use crate::NomadDB;
@@ -96,6 +97,21 @@ impl Replica for CachingReplica {
         self.replica.process(message).await
     }
 
+    async fn prove_and_process_batch(
+        &self,
+        proofs: &[Proof],
+        messages: &[NomadMessage],
+    ) -> Result<BatchOutcomes, ChainCommunicationError> {
+        self.replica.prove_and_process_batch(proofs, messages).await
+    }
+
+    async fn update_chain(
+        &self,
+        updates: &[SignedUpdate],
+    ) -> Result<Vec<TxOutcome>, ChainCommunicationError> {
+        self.replica.update_chain(updates).await
+    }
+
     async fn message_status(&self, leaf: H256) -> Result<MessageStatus, ChainCommunicationError> {
         self.replica.message_status(leaf).await
     }
@@ -293,6 +309,35 @@ impl Replica for ReplicaVariants {
         }
     }
 
+    async fn prove_and_process_batch(
+        &self,
+        proofs: &[Proof],
+        messages: &[NomadMessage],
+    ) -> Result<BatchOutcomes, ChainCommunicationError> {
+        match self {
+            ReplicaVariants::Ethereum(replica) => {
+                replica.prove_and_process_batch(proofs, messages).await
+            }
+            ReplicaVariants::Mock(mock_replica) => {
+                mock_replica.prove_and_process_batch(proofs, messages).await
+            }
+            ReplicaVariants::Other(replica) => {
+                replica.prove_and_process_batch(proofs, messages).await
+            }
+        }
+    }
+
+    async fn update_chain(
+        &self,
+        updates: &[SignedUpdate],
+    ) -> Result<Vec<TxOutcome>, ChainCommunicationError> {
+        match self {
+            ReplicaVariants::Ethereum(replica) => replica.update_chain(updates).await,
+            ReplicaVariants::Mock(mock_replica) => mock_replica.update_chain(updates).await,
+            ReplicaVariants::Other(replica) => replica.update_chain(updates).await,
+        }
+    }
+
     async fn acceptable_root(&self, root: H256) -> Result<bool, ChainCommunicationError> {
         match self {
             ReplicaVariants::Ethereum(replica) => replica.acceptable_root(root).await,
@@ -2,21 +2,27 @@ use color_eyre::Report;
 use serde::Deserialize;
 
 use nomad_core::{ContractLocator, Signers};
-use nomad_ethereum::{make_conn_manager, make_home, make_replica, Connection};
+use nomad_ethereum::{
+    make_conn_manager, make_home, make_replica, Connection, GasConf, TxManagerConf, TxManagers,
+};
 
 use crate::{
-    home::Homes, replica::Replicas, xapp::ConnectionManagers, HomeVariants, ReplicaVariants,
+    home::Homes, replica::Replicas, xapp::ConnectionManagers, ArchiveConf, ArchiveContract,
+    HomeVariants, ReplicaVariants,
 };
 
 /// A connection to _some_ blockchain.
 ///
 /// Specify the chain name (enum variant) in toml under the `chain` key
 /// Specify the connection details as a toml object under the `connection` key.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 #[serde(tag = "rpcStyle", content = "connection", rename_all = "camelCase")]
 pub enum ChainConf {
     /// Ethereum configuration
     Ethereum(Connection),
+    /// Contracts and indexers replayed from a recorded log archive, without
+    /// a node
+    Archive(ArchiveConf),
 }
 
 impl Default for ChainConf {
@@ -27,7 +33,7 @@ impl Default for ChainConf {
 
 /// A chain setup is a domain ID, an address on that chain (where the home or
 /// replica is deployed) and details for connecting to the chain API.
-#[derive(Clone, Debug, Deserialize, Default)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Default)]
 pub struct ChainSetup {
     /// Chain name
     pub name: String,
@@ -43,6 +49,17 @@ pub struct ChainSetup {
     /// Set this key to disable the replica. Does nothing for homes.
     #[serde(default)]
     pub disabled: Option<String>,
+    /// Address of the multicall contract used to batch replica submissions.
+    /// Defaults to the well-known deployment for the chain. Does nothing for
+    /// homes.
+    #[serde(default)]
+    pub multicall: Option<String>,
+    /// Fee policy used when submitting transactions
+    #[serde(default)]
+    pub gas: GasConf,
+    /// Nonce management and stuck transaction replacement settings
+    #[serde(default, rename = "txManager")]
+    pub tx_manager: TxManagerConf,
 }
 
 impl ChainSetup {
@@ -51,6 +68,7 @@ impl ChainSetup {
         &self,
         signer: Option<Signers>,
         timelag: Option<u8>,
+        tx_managers: &TxManagers,
     ) -> Result<Homes, Report> {
         match &self.chain {
             ChainConf::Ethereum(conf) => Ok(HomeVariants::Ethereum(
@@ -63,10 +81,17 @@ impl ChainSetup {
                     },
                     signer,
                     timelag,
+                    self.gas.clone(),
+                    self.tx_manager.clone(),
+                    tx_managers,
                 )
                 .await?,
             )
             .into()),
+            ChainConf::Archive(conf) => {
+                let home: Box<dyn nomad_core::Home> = Box::new(self.try_into_archive(conf)?);
+                Ok(home.into())
+            }
         }
     }
 
@@ -75,6 +100,7 @@ impl ChainSetup {
         &self,
         signer: Option<Signers>,
         timelag: Option<u8>,
+        tx_managers: &TxManagers,
     ) -> Result<Replicas, Report> {
         match &self.chain {
             ChainConf::Ethereum(conf) => Ok(ReplicaVariants::Ethereum(
@@ -87,10 +113,21 @@ impl ChainSetup {
                     },
                     signer,
                     timelag,
+                    self.gas.clone(),
+                    self.tx_manager.clone(),
+                    tx_managers,
+                    self.multicall
+                        .as_ref()
+                        .map(|addr| addr.parse::<ethers::types::Address>())
+                        .transpose()?,
                 )
                 .await?,
             )
             .into()),
+            ChainConf::Archive(conf) => {
+                let replica: Box<dyn nomad_core::Replica> = Box::new(self.try_into_archive(conf)?);
+                Ok(replica.into())
+            }
         }
     }
 
@@ -99,6 +136,7 @@ impl ChainSetup {
         &self,
         signer: Option<Signers>,
         timelag: Option<u8>,
+        tx_managers: &TxManagers,
     ) -> Result<ConnectionManagers, Report> {
         match &self.chain {
             ChainConf::Ethereum(conf) => Ok(ConnectionManagers::Ethereum(
@@ -111,9 +149,19 @@ impl ChainSetup {
                     },
                     signer,
                     timelag,
+                    self.gas.clone(),
+                    self.tx_manager.clone(),
+                    tx_managers,
                 )
                 .await?,
             )),
+            ChainConf::Archive(conf) => Ok(ConnectionManagers::Other(Box::new(
+                self.try_into_archive(conf)?,
+            ))),
         }
     }
+
+    fn try_into_archive(&self, conf: &ArchiveConf) -> Result<ArchiveContract, Report> {
+        ArchiveContract::from_conf(&self.name, self.domain.parse().expect("invalid uint"), conf)
+    }
 }
@@ -35,17 +35,44 @@
 //! 4. Configuration env vars with the prefix `OPT_{agent name}`
 //!    intended to be used by a specific agent.
 //!    E.g. `export OPT_KATHY_CHAT_TYPE="static message"`
-
-use crate::{agent::AgentCore, CachingHome, CachingReplica, CommonIndexers, HomeIndexers, NomadDB};
-use color_eyre::{eyre::bail, Report};
+//!
+//! ### Multiple homes
+//!
+//! Instead of a single `home` and its `replicas`, the config may list several
+//! `networks`, each with its own home and replicas. One agent process then
+//! serves all of them, see [`Settings::split_networks`].
+//!
+//! ### Replica enrollment
+//!
+//! The processor and relayer re-read their config on SIGHUP and start or stop
+//! serving replicas as they are added, removed or disabled. Replicas on a
+//! chain listed under `enrollment.managers` are only served while enrolled in
+//! that chain's connection manager, checked every `enrollment.interval`
+//! seconds. See [`ReplicaEnrollment`](crate::ReplicaEnrollment).
+//!
+//! ### Gas budget
+//!
+//! Agents account what they spend on gas per chain and UTC day. Setting
+//! `budget.dailyCaps.{chain name}` (in wei) caps what the processor and
+//! relayer spend on that chain. Updates and fraud proofs are never held back.
+//! See [`GasBudget`](crate::GasBudget).
+
+use crate::{
+    agent::AgentCore, CachingHome, CachingReplica, CommonIndexers, FileIndexer, HomeIndexers,
+    NomadDB, SpendStore,
+};
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Report,
+};
 use config::{Config, ConfigError, Environment, File};
-use ethers::prelude::AwsSigner;
-use nomad_core::{db::DB, utils::HexString, Common, ContractLocator, Signers};
-use nomad_ethereum::{make_home_indexer, make_replica_indexer};
+use ethers::prelude::{Address, AwsSigner, Http, LocalWallet, U256};
+use nomad_core::{db::DB, utils::HexString, Common, ContractLocator, RemoteSigner, Signers};
+use nomad_ethereum::{make_home_indexer, make_replica_indexer, TxManagers};
 use rusoto_core::{credential::EnvironmentProvider, HttpClient};
 use rusoto_kms::KmsClient;
 use serde::Deserialize;
-use std::{collections::HashMap, env, sync::Arc};
+use std::{collections::HashMap, env, path::Path, sync::Arc};
 use tracing::instrument;
 
 /// Chain configuartion
@@ -93,6 +120,24 @@ pub enum SignerConf {
         /// The AWS region
         region: String,
     },
+    /// An Ethereum V3 JSON keystore, encrypted with a password read from the
+    /// env
+    Keystore {
+        /// Path to the keystore file
+        path: String,
+        /// Name of the env var holding the keystore's password
+        #[serde(rename = "passwordEnv")]
+        password_env: String,
+    },
+    /// A remote signing service speaking JSON-RPC (`eth_sign` and
+    /// `eth_signTransaction`), such as Web3Signer
+    Remote {
+        /// Url of the signing service
+        url: String,
+        /// Address of the key to sign with. Defaults to the first key the
+        /// service holds.
+        address: Option<String>,
+    },
     #[serde(other)]
     /// Assume node will sign on RPC calls
     Node,
@@ -124,6 +169,24 @@ impl SignerConf {
                 let signer = AwsSigner::new(client, id, 0).await?;
                 Ok(Signers::Aws(signer))
             }
+            SignerConf::Keystore { path, password_env } => {
+                let password = env::var(password_env).wrap_err_with(|| {
+                    format!("Missing keystore password env var {}", password_env)
+                })?;
+                Ok(Signers::Local(LocalWallet::decrypt_keystore(
+                    path, password,
+                )?))
+            }
+            SignerConf::Remote { url, address } => {
+                let client: Http = url.parse()?;
+                let address = address
+                    .as_ref()
+                    .map(|address| address.parse::<Address>())
+                    .transpose()?;
+                Ok(Signers::Remote(
+                    RemoteSigner::connect(client, address).await?,
+                ))
+            }
             SignerConf::Node => bail!("Node signer"),
         }
     }
@@ -137,6 +200,11 @@ pub struct IndexSettings {
     from: Option<String>,
     /// The number of blocks to query at once at which to start indexing the Home contract
     chunk: Option<String>,
+    /// Directory of recorded logs to index from instead of the chain. Logs of
+    /// each contract are read from `<archive>/<contract name>.jsonl`. Only
+    /// indexing reads the archive, contract calls still use the connection.
+    /// Use the `archive` rpc style to replay contract calls too.
+    archive: Option<String>,
 }
 
 impl IndexSettings {
@@ -155,6 +223,82 @@ impl IndexSettings {
             .and_then(|s| s.parse::<u32>().ok())
             .unwrap_or(1999)
     }
+
+    /// Get the path of the recorded log archive of a contract, if indexing
+    /// from an archive
+    pub fn archive_path(&self, contract_name: &str) -> Option<std::path::PathBuf> {
+        self.archive
+            .as_ref()
+            .map(|dir| std::path::Path::new(dir).join(format!("{}.jsonl", contract_name)))
+    }
+}
+
+/// Settings for updating the replicas served while the agent runs
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EnrollmentSettings {
+    /// Connection managers by chain name. A replica on a chain listed here is
+    /// only served while enrolled in the chain's connection manager
+    #[serde(default)]
+    pub managers: HashMap<String, ChainSetup>,
+    /// Interval (in seconds) between enrollment checks
+    interval: Option<String>,
+}
+
+impl EnrollmentSettings {
+    /// Get the `interval` setting
+    pub fn interval(&self) -> u64 {
+        self.interval
+            .as_ref()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(60)
+    }
+}
+
+/// Limits on what an agent spends on gas
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetSettings {
+    /// Most the agent may spend on gas per UTC day (in wei, as a decimal
+    /// string), by chain name. Uncapped on chains not listed
+    #[serde(default)]
+    daily_caps: HashMap<String, String>,
+}
+
+impl BudgetSettings {
+    /// Get the `dailyCaps` setting, parsed
+    pub fn daily_caps(&self) -> HashMap<String, U256> {
+        self.daily_caps
+            .iter()
+            .map(|(chain, cap)| {
+                let cap = U256::from_dec_str(cap).expect("invalid integer");
+                (chain.to_owned(), cap)
+            })
+            .collect()
+    }
+}
+
+/// Backoff between retries of a failed operation. See
+/// [`Backoff`](crate::Backoff).
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryConfig {
+    /// Delay (in seconds) before the first retry
+    pub base_delay: Option<String>,
+    /// Upper bound (in seconds) on the delay between retries
+    pub max_delay: Option<String>,
+}
+
+/// A home and its replicas, served alongside other networks by one agent
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkSetup {
+    /// The home configuration
+    pub home: ChainSetup,
+    /// The replica configurations
+    pub replicas: HashMap<String, ChainSetup>,
+    /// Port to serve the network's read-only HTTP API on. Not served if unset
+    pub api: Option<String>,
 }
 
 /// Settings. Usually this should be treated as a base config and used as
@@ -188,42 +332,139 @@ pub struct Settings {
     pub db: String,
     /// Port to listen for prometheus scrape requests
     pub metrics: Option<String>,
+    /// Port to serve the read-only HTTP API on. Not served if unset
+    pub api: Option<String>,
+    /// Address to bind the read-only HTTP API to. Defaults to 127.0.0.1
+    pub api_host: Option<String>,
     /// Settings for the home indexer
     #[serde(default)]
     pub index: IndexSettings,
     /// Whether or not agent should use timelag
     #[serde(default)]
     pub use_timelag: bool,
-    /// The home configuration
+    /// The home configuration. Unused if `networks` is set
+    #[serde(default)]
     pub home: ChainSetup,
-    /// The replica configurations
+    /// The replica configurations. Unused if `networks` is set
+    #[serde(default)]
     pub replicas: HashMap<String, ChainSetup>,
+    /// Several homes with their replicas, by network name, to serve from one
+    /// agent process instead of `home` and `replicas`
+    pub networks: Option<HashMap<String, NetworkSetup>>,
+    /// Connection managers to discover replica enrollment from
+    #[serde(default)]
+    pub enrollment: EnrollmentSettings,
+    /// Daily caps on gas spending
+    #[serde(default)]
+    pub budget: BudgetSettings,
     /// The tracing configuration
     pub tracing: TracingConfig,
     /// Transaction signers
     pub signers: HashMap<String, SignerConf>,
+    /// Name of the network served, if one of several
+    #[serde(skip)]
+    pub network: Option<String>,
+    /// Metrics registry shared by the agents serving each network
+    #[serde(skip)]
+    registry: Option<Arc<prometheus::Registry>>,
+    /// Tx managers shared by every contract the agent sends transactions to
+    #[serde(skip)]
+    pub(crate) tx_managers: Option<TxManagers>,
+    /// Daily gas spend totals shared by the agents serving each network
+    #[serde(skip)]
+    pub(crate) spend_store: Option<SpendStore>,
+    /// Signers built from `signers`, by chain name. Shared by clones of the
+    /// settings, so each signer is only built once
+    #[serde(skip)]
+    signer_cache: Arc<tokio::sync::Mutex<HashMap<String, Signers>>>,
 }
 
 impl Settings {
     /// Private to preserve linearity of AgentCore::from_settings -- creating an agent consumes the settings.
-    fn clone(&self) -> Self {
+    pub(crate) fn clone(&self) -> Self {
         Self {
             db: self.db.clone(),
             metrics: self.metrics.clone(),
+            api: self.api.clone(),
+            api_host: self.api_host.clone(),
             index: self.index.clone(),
             use_timelag: self.use_timelag,
             home: self.home.clone(),
             replicas: self.replicas.clone(),
+            networks: self.networks.clone(),
+            enrollment: self.enrollment.clone(),
+            budget: self.budget.clone(),
             tracing: self.tracing.clone(),
             signers: self.signers.clone(),
+            network: self.network.clone(),
+            registry: self.registry.clone(),
+            tx_managers: self.tx_managers.clone(),
+            spend_store: self.spend_store.clone(),
+            signer_cache: self.signer_cache.clone(),
         }
     }
+
+    /// Settings for each network served, ordered by network name.
+    ///
+    /// Without `networks`, these settings only. Otherwise one per network,
+    /// each with the network's home, replicas and API port and a DB in the
+    /// network's own subdirectory of `db`. Their metrics share one registry,
+    /// labelled by network, and are served on the `metrics` port.
+    pub fn split_networks(&self) -> Vec<Self> {
+        let networks = match &self.networks {
+            Some(networks) => networks,
+            None => return vec![self.clone()],
+        };
+
+        let registry = Arc::new(prometheus::Registry::new());
+        let mut names: Vec<&String> = networks.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| {
+                let setup = &networks[name];
+                let mut settings = self.clone();
+                settings.db = Path::new(&self.db)
+                    .join(name)
+                    .to_string_lossy()
+                    .into_owned();
+                settings.api = setup.api.clone();
+                settings.home = setup.home.clone();
+                settings.replicas = setup.replicas.clone();
+                settings.networks = None;
+                settings.network = Some(name.to_owned());
+                settings.registry = Some(registry.clone());
+                settings
+            })
+            .collect()
+    }
 }
 
 impl Settings {
-    /// Try to get a signer instance by name
-    pub async fn get_signer(&self, name: &str) -> Option<Signers> {
-        self.signers.get(name)?.try_into_signer().await.ok()
+    /// The tx managers shared by the contracts of the agent. Set up when the
+    /// agent core is built.
+    pub fn tx_managers(&self) -> TxManagers {
+        self.tx_managers.clone().unwrap_or_default()
+    }
+
+    /// Try to get a signer instance by name. Built the first time it is
+    /// requested. `None` if no signer is configured for `name`, or if the
+    /// node signs.
+    pub async fn get_signer(&self, name: &str) -> Result<Option<Signers>, Report> {
+        let mut cache = self.signer_cache.lock().await;
+        if let Some(signer) = cache.get(name) {
+            return Ok(Some(signer.clone()));
+        }
+        let conf = match self.signers.get(name) {
+            None | Some(SignerConf::Node) => return Ok(None),
+            Some(conf) => conf,
+        };
+        let signer = conf
+            .try_into_signer()
+            .await
+            .wrap_err_with(|| format!("Failed to build signer {}", name))?;
+        cache.insert(name.to_owned(), signer.clone());
+        Ok(Some(signer))
     }
 
     /// Set timelag on/off
@@ -257,33 +498,47 @@ impl Settings {
     ) -> Result<HashMap<String, Arc<CachingReplica>>, Report> {
         let mut result = HashMap::default();
         for (k, v) in self.replicas.iter().filter(|(_, v)| v.disabled.is_none()) {
-            if k != &v.name {
-                bail!(
-                    "Replica key does not match replica name:\n key: {}  name: {}",
-                    k,
-                    v.name
-                );
-            }
-            let signer = self.get_signer(&v.name).await;
-            let replica_timelag = self.replica_indexing_timelag(k);
-
-            let replica = v.try_into_replica(signer, replica_timelag).await?;
-            let indexer = Arc::new(self.try_replica_indexer(v, replica_timelag).await?);
-            let nomad_db = NomadDB::new(replica.name(), db.clone());
             result.insert(
                 v.name.clone(),
-                Arc::new(CachingReplica::new(replica, nomad_db, indexer)),
+                Arc::new(self.try_caching_replica(k, db.clone()).await?),
             );
         }
         Ok(result)
     }
 
+    /// Try to get the replica configured under `name`
+    pub async fn try_caching_replica(&self, name: &str, db: DB) -> Result<CachingReplica, Report> {
+        let setup = match self.replicas.get(name) {
+            Some(setup) => setup,
+            None => bail!("No replica named {}", name),
+        };
+        if name != setup.name {
+            bail!(
+                "Replica key does not match replica name:\n key: {}  name: {}",
+                name,
+                setup.name
+            );
+        }
+        let signer = self.get_signer(&setup.name).await?;
+        let replica_timelag = self.replica_indexing_timelag(name);
+
+        let replica = setup
+            .try_into_replica(signer, replica_timelag, &self.tx_managers())
+            .await?;
+        let indexer = Arc::new(self.try_replica_indexer(setup, replica_timelag).await?);
+        let nomad_db = NomadDB::new(replica.name(), db);
+        Ok(CachingReplica::new(replica, nomad_db, indexer))
+    }
+
     /// Try to get a home object
     pub async fn try_caching_home(&self, db: DB) -> Result<CachingHome, Report> {
-        let signer = self.get_signer(&self.home.name).await;
+        let signer = self.get_signer(&self.home.name).await?;
         let home_timelag = self.home_indexing_timelag();
 
-        let home = self.home.try_into_home(signer, home_timelag).await?;
+        let home = self
+            .home
+            .try_into_home(signer, home_timelag, &self.tx_managers())
+            .await?;
         let indexer = Arc::new(self.try_home_indexer(home_timelag).await?);
         let nomad_db = NomadDB::new(home.name(), db);
         Ok(CachingHome::new(home, nomad_db, indexer))
@@ -291,9 +546,16 @@ impl Settings {
 
     /// Try to get an indexer object for a home
     pub async fn try_home_indexer(&self, timelag: Option<u8>) -> Result<HomeIndexers, Report> {
-        let signer = self.get_signer(&self.home.name).await;
+        if let Some(path) = self.index.archive_path(&self.home.name) {
+            return Ok(HomeIndexers::Other(Box::new(FileIndexer::from_path(path)?)));
+        }
+
+        let signer = self.get_signer(&self.home.name).await?;
 
         match &self.home.chain {
+            ChainConf::Archive(conf) => Ok(HomeIndexers::Other(Box::new(FileIndexer::from_path(
+                &conf.path,
+            )?))),
             ChainConf::Ethereum(conn) => Ok(HomeIndexers::Ethereum(
                 make_home_indexer(
                     conn.clone(),
@@ -304,6 +566,9 @@ impl Settings {
                     },
                     signer,
                     timelag,
+                    Default::default(),
+                    Default::default(),
+                    &Default::default(),
                     self.index.from(),
                     self.index.chunk_size(),
                 )
@@ -318,9 +583,18 @@ impl Settings {
         setup: &ChainSetup,
         timelag: Option<u8>,
     ) -> Result<CommonIndexers, Report> {
-        let signer = self.get_signer(&setup.name).await;
+        if let Some(path) = self.index.archive_path(&setup.name) {
+            return Ok(CommonIndexers::Other(Box::new(FileIndexer::from_path(
+                path,
+            )?)));
+        }
+
+        let signer = self.get_signer(&setup.name).await?;
 
         match &setup.chain {
+            ChainConf::Archive(conf) => Ok(CommonIndexers::Other(Box::new(
+                FileIndexer::from_path(&conf.path)?,
+            ))),
             ChainConf::Ethereum(conn) => Ok(CommonIndexers::Ethereum(
                 make_replica_indexer(
                     conn.clone(),
@@ -331,6 +605,9 @@ impl Settings {
                     },
                     signer,
                     timelag,
+                    Default::default(),
+                    Default::default(),
+                    &Default::default(),
                     self.index.from(),
                     self.index.chunk_size(),
                 )
@@ -341,23 +618,31 @@ impl Settings {
 
     /// Try to generate an agent core for a named agent
     pub async fn try_into_core(&self, name: &str) -> Result<AgentCore, Report> {
-        let metrics = Arc::new(crate::metrics::CoreMetrics::new(
+        let metrics = Arc::new(crate::metrics::CoreMetrics::for_network(
             name,
+            self.network.clone(),
             self.metrics
                 .as_ref()
                 .map(|v| v.parse::<u16>().expect("metrics port must be u16")),
-            Arc::new(prometheus::Registry::new()),
+            self.registry.clone().unwrap_or_default(),
         )?);
 
         let db = DB::from_path(&self.db)?;
-        let home = Arc::new(self.try_caching_home(db.clone()).await?);
-        let replicas = self.try_caching_replicas(db.clone()).await?;
+        let mut settings = self.clone();
+        settings
+            .tx_managers
+            .get_or_insert_with(|| TxManagers::new(Some(db.clone())));
+        settings
+            .spend_store
+            .get_or_insert_with(|| SpendStore::new(db.clone()));
+        let home = Arc::new(settings.try_caching_home(db.clone()).await?);
+        let replicas = settings.try_caching_replicas(db.clone()).await?;
 
         Ok(AgentCore {
             home,
             replicas,
             db,
-            settings: self.clone(),
+            settings,
             metrics,
             indexer: self.index.clone(),
         })
@@ -379,3 +664,93 @@ impl Settings {
         s.try_into()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::CoreMetrics;
+    use ethers::signers::Signer;
+
+    #[test]
+    fn it_splits_networks() {
+        let network = |home: &str, replica: &str| NetworkSetup {
+            home: ChainSetup {
+                name: home.to_owned(),
+                ..Default::default()
+            },
+            replicas: [(
+                replica.to_owned(),
+                ChainSetup {
+                    name: replica.to_owned(),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            api: None,
+        };
+        let settings = Settings {
+            db: "db".to_owned(),
+            networks: Some(
+                [
+                    ("ethereum".to_owned(), network("ethereum", "moonbeam")),
+                    ("moonbeam".to_owned(), network("moonbeam", "ethereum")),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+            ..Default::default()
+        };
+
+        let split = settings.split_networks();
+        assert_eq!(split.len(), 2);
+        assert_eq!(split[0].network.as_deref(), Some("ethereum"));
+        assert_eq!(split[0].home.name, "ethereum");
+        assert!(split[0].replicas.contains_key("moonbeam"));
+        assert_eq!(Path::new(&split[1].db), Path::new("db").join("moonbeam"));
+        assert!(split[1].networks.is_none());
+
+        // Signers are only built once for both networks
+        assert!(Arc::ptr_eq(&split[0].signer_cache, &split[1].signer_cache));
+
+        // Metrics of both networks fit in the shared registry
+        let registry = split[0].registry.clone().unwrap();
+        assert!(Arc::ptr_eq(&registry, split[1].registry.as_ref().unwrap()));
+        for settings in split {
+            CoreMetrics::for_network("test", settings.network, None, registry.clone())
+                .expect("could not register metrics");
+        }
+    }
+
+    #[test]
+    fn it_keeps_single_network_settings() {
+        let settings = Settings {
+            db: "db".to_owned(),
+            ..Default::default()
+        };
+        let split = settings.split_networks();
+        assert_eq!(split.len(), 1);
+        assert_eq!(split[0].db, "db");
+        assert!(split[0].network.is_none());
+    }
+
+    #[tokio::test]
+    async fn it_loads_keystore_signers() {
+        let dir = env::temp_dir().join("nomad_keystore_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let (wallet, id) =
+            LocalWallet::new_keystore(&dir, &mut rand::thread_rng(), "correct horse").unwrap();
+        env::set_var("NOMAD_KEYSTORE_TEST_PASSWORD", "correct horse");
+
+        let conf: SignerConf = serde_json::from_value(serde_json::json!({
+            "type": "keystore",
+            "path": dir.join(&id),
+            "passwordEnv": "NOMAD_KEYSTORE_TEST_PASSWORD",
+        }))
+        .unwrap();
+        let signer = conf.try_into_signer().await.unwrap();
+        assert_eq!(signer.address(), wallet.address());
+
+        std::fs::remove_file(dir.join(&id)).unwrap();
+    }
+}
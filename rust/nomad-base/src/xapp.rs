@@ -76,6 +76,23 @@ impl ConnectionManager for ConnectionManagers {
         }
     }
 
+    async fn domain_to_replica(
+        &self,
+        domain: u32,
+    ) -> Result<NomadIdentifier, ChainCommunicationError> {
+        match self {
+            ConnectionManagers::Ethereum(connection_manager) => {
+                connection_manager.domain_to_replica(domain).await
+            }
+            ConnectionManagers::Mock(connection_manager) => {
+                connection_manager.domain_to_replica(domain).await
+            }
+            ConnectionManagers::Other(connection_manager) => {
+                connection_manager.domain_to_replica(domain).await
+            }
+        }
+    }
+
     async fn watcher_permission(
         &self,
         address: NomadIdentifier,
@@ -51,6 +51,11 @@ impl DB {
             false => info!("Creating db at {path}", path = path.to_str().unwrap()),
         }
 
+        // RocksDB only creates the last directory of the path
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
         let mut opts = Options::default();
         opts.create_if_missing(true);
 
@@ -72,6 +77,11 @@ impl DB {
         Ok(self.0.get(key)?)
     }
 
+    /// Delete a value from the DB
+    fn _delete(&self, key: impl AsRef<[u8]>) -> Result<()> {
+        Ok(self.0.delete(key)?)
+    }
+
     /// Prefix a key and store in the DB
     fn prefix_store(
         &self,
@@ -97,6 +107,14 @@ impl DB {
         self._retrieve(buf)
     }
 
+    /// Prefix the key and delete
+    fn prefix_delete(&self, prefix: impl AsRef<[u8]>, key: impl AsRef<[u8]>) -> Result<()> {
+        let mut buf = vec![];
+        buf.extend(prefix.as_ref());
+        buf.extend(key.as_ref());
+        self._delete(buf)
+    }
+
     /// Store any encodeable
     pub fn store_encodable<V: Encode>(
         &self,
@@ -138,6 +156,16 @@ impl DB {
         self.retrieve_decodable(prefix, key.to_vec())
     }
 
+    /// Delete the value stored under the prefixed key (if any)
+    pub fn delete_value(&self, prefix: impl AsRef<[u8]>, key: impl AsRef<[u8]>) -> Result<()> {
+        self.prefix_delete(prefix, key)
+    }
+
+    /// Delete the value stored under any encodable key (if any)
+    pub fn delete_keyed_value<K: Encode>(&self, prefix: impl AsRef<[u8]>, key: &K) -> Result<()> {
+        self.delete_value(prefix, key.to_vec())
+    }
+
     /// Get prefix db iterator for `prefix`
     pub fn prefix_iterator(&self, prefix: impl AsRef<[u8]>) -> DBIterator {
         self.0.prefix_iterator(prefix)
@@ -73,4 +73,35 @@ impl TypedDB {
         self.db
             .retrieve_keyed_decodable(self.full_prefix(prefix), key)
     }
+
+    /// Delete value stored under key
+    pub fn delete_value(
+        &self,
+        prefix: impl AsRef<[u8]>,
+        key: impl AsRef<[u8]>,
+    ) -> Result<(), DbError> {
+        self.db.delete_value(self.full_prefix(prefix), key)
+    }
+
+    /// Delete value stored under encodable key
+    pub fn delete_keyed_value<K: Encode>(
+        &self,
+        prefix: impl AsRef<[u8]>,
+        key: &K,
+    ) -> Result<(), DbError> {
+        self.db.delete_keyed_value(self.full_prefix(prefix), key)
+    }
+
+    /// Iterate (in key order) over all decodable values whose key starts
+    /// with `prefix`
+    pub fn prefix_values<V: Decode>(
+        &self,
+        prefix: impl AsRef<[u8]>,
+    ) -> impl Iterator<Item = V> + '_ {
+        let full_prefix = self.full_prefix(prefix);
+        self.db
+            .prefix_iterator(full_prefix.clone())
+            .take_while(move |(k, _)| k.starts_with(&full_prefix))
+            .map(|(_, v)| V::read_from(&mut &v[..]).expect("!corrupt"))
+    }
 }
@@ -40,6 +40,9 @@ pub mod test_utils;
 mod types;
 pub use types::*;
 
+/// Decoding of the message bodies of known xApps
+pub mod xapps;
+
 /// Test functions that output json files for Solidity tests
 #[cfg(feature = "output")]
 pub mod test_output;
@@ -47,6 +50,10 @@ pub mod test_output;
 mod chain;
 pub use chain::*;
 
+/// Signing with keys held by a remote signing service
+mod remote_signer;
+pub use remote_signer::{RemoteSigner, RemoteSignerError};
+
 use std::convert::Infallible;
 
 pub use identifiers::NomadIdentifier;
@@ -90,6 +97,25 @@ pub enum NomadError {
     /// improper update and is slashable
     #[error("Update has unknown new root: {0}")]
     UnknownNewRoot(H256),
+    /// Message body doesn't match the format it was decoded as
+    #[error("Invalid message body: {0}")]
+    InvalidMessageBody(String),
+    /// A committee member attested a different update
+    #[error("Attestation conflicts with update. Expected: {expected}. Got: {actual}.")]
+    ConflictingAttestation {
+        /// The attested update
+        expected: Update,
+        /// The conflicting update
+        actual: Update,
+    },
+    /// Too few committee members attested an update
+    #[error("Update attested by {signed} committee members, {threshold} required")]
+    InsufficientAttestation {
+        /// Number of committee members that attested
+        signed: usize,
+        /// Number of committee members required
+        threshold: usize,
+    },
     /// IO error from Read/Write usage
     #[error(transparent)]
     IoError(#[from] std::io::Error),
@@ -104,6 +130,9 @@ pub enum SignersError {
     /// Wallet Signer Error
     #[error("{0}")]
     WalletError(#[from] WalletError),
+    /// Remote Signer Error
+    #[error("{0}")]
+    RemoteSignerError(#[from] RemoteSignerError),
 }
 
 impl From<Infallible> for SignersError {
@@ -119,6 +148,8 @@ pub enum Signers {
     Local(LocalWallet),
     /// A signer using a key stored in aws kms
     Aws(AwsSigner<'static>),
+    /// A signer using a key held by a remote signing service
+    Remote(RemoteSigner),
 }
 
 impl From<LocalWallet> for Signers {
@@ -133,6 +164,12 @@ impl From<AwsSigner<'static>> for Signers {
     }
 }
 
+impl From<RemoteSigner> for Signers {
+    fn from(s: RemoteSigner) -> Self {
+        Signers::Remote(s)
+    }
+}
+
 #[async_trait]
 impl Signer for Signers {
     type Error = SignersError;
@@ -141,6 +178,7 @@ impl Signer for Signers {
         match self {
             Signers::Local(signer) => signer.with_chain_id(chain_id).into(),
             Signers::Aws(signer) => signer.with_chain_id(chain_id).into(),
+            Signers::Remote(signer) => signer.with_chain_id(chain_id).into(),
         }
     }
 
@@ -151,6 +189,7 @@ impl Signer for Signers {
         match self {
             Signers::Local(signer) => Ok(signer.sign_message(message).await?),
             Signers::Aws(signer) => Ok(signer.sign_message(message).await?),
+            Signers::Remote(signer) => Ok(signer.sign_message(message).await?),
         }
     }
 
@@ -159,6 +198,7 @@ impl Signer for Signers {
             Signers::Local(signer) => Ok(signer.sign_transaction(message).await?),
 
             Signers::Aws(signer) => Ok(signer.sign_transaction(message).await?),
+            Signers::Remote(signer) => Ok(signer.sign_transaction(message).await?),
         }
     }
 
@@ -166,6 +206,7 @@ impl Signer for Signers {
         match self {
             Signers::Local(signer) => signer.address(),
             Signers::Aws(signer) => signer.address(),
+            Signers::Remote(signer) => signer.address(),
         }
     }
 
@@ -173,6 +214,7 @@ impl Signer for Signers {
         match self {
             Signers::Local(signer) => signer.chain_id(),
             Signers::Aws(signer) => signer.chain_id(),
+            Signers::Remote(signer) => signer.chain_id(),
         }
     }
 
@@ -183,6 +225,7 @@ impl Signer for Signers {
         match self {
             Signers::Local(signer) => Ok(signer.sign_typed_data(payload).await?),
             Signers::Aws(signer) => Ok(signer.sign_typed_data(payload).await?),
+            Signers::Remote(signer) => Ok(signer.sign_typed_data(payload).await?),
         }
     }
 }
@@ -0,0 +1,233 @@
+use async_trait::async_trait;
+use ethers::{
+    core::types::{
+        transaction::{eip2718::TypedTransaction, eip712::Eip712},
+        Address, Bytes, Signature, SignatureError, U256,
+    },
+    providers::{Http, HttpClientError, JsonRpcClient},
+    signers::Signer,
+    utils::rlp::{DecoderError, Rlp},
+};
+use std::sync::Arc;
+
+/// Error types for the remote signer
+#[derive(Debug, thiserror::Error)]
+pub enum RemoteSignerError {
+    /// The signing service could not be reached, or returned an error
+    #[error(transparent)]
+    ClientError(#[from] HttpClientError),
+    /// The signing service returned a malformed signature
+    #[error(transparent)]
+    SignatureError(#[from] SignatureError),
+    /// The signing service returned a malformed signed transaction
+    #[error("Malformed signed transaction: {0}")]
+    RlpError(#[from] DecoderError),
+    /// The signing service holds no key to sign with
+    #[error("Remote signer has no accounts")]
+    NoAccounts,
+    /// The signing service can't sign this payload
+    #[error("Remote signer does not support {0}")]
+    Unsupported(&'static str),
+}
+
+/// A signer delegating to a remote signing service over JSON-RPC, such as
+/// Web3Signer. Messages are signed with `eth_sign` and transactions with
+/// `eth_signTransaction`, so the key never leaves the service.
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    client: Arc<Http>,
+    address: Address,
+    chain_id: u64,
+}
+
+impl RemoteSigner {
+    /// Sign with the key of `address` held by the service at `client`, or
+    /// with the service's first key if `address` is `None`
+    pub async fn connect(
+        client: Http,
+        address: Option<Address>,
+    ) -> Result<Self, RemoteSignerError> {
+        let address = match address {
+            Some(address) => address,
+            None => client
+                .request::<_, Vec<Address>>("eth_accounts", ())
+                .await?
+                .first()
+                .copied()
+                .ok_or(RemoteSignerError::NoAccounts)?,
+        };
+
+        Ok(Self {
+            client: Arc::new(client),
+            address,
+            chain_id: 1,
+        })
+    }
+}
+
+/// Extract the signature of a signed transaction, RLP-encoded as returned by
+/// `eth_signTransaction`. The signature of a typed transaction carries the
+/// y parity only, which is turned into an EIP-155 `v` for `chain_id`.
+fn signature_from_rlp(raw: &[u8], chain_id: u64) -> Result<Signature, RemoteSignerError> {
+    let (typed, body) = match raw.first() {
+        Some(&kind) if kind <= 0x7f => (true, &raw[1..]),
+        _ => (false, raw),
+    };
+
+    let rlp = Rlp::new(body);
+    let items = rlp.item_count()?;
+    if items < 3 {
+        return Err(DecoderError::RlpIncorrectListLen.into());
+    }
+    let v: u64 = rlp.val_at(items - 3)?;
+    let r: U256 = rlp.val_at(items - 2)?;
+    let s: U256 = rlp.val_at(items - 1)?;
+
+    let v = if typed { v + 35 + chain_id * 2 } else { v };
+    Ok(Signature { r, s, v })
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    type Error = RemoteSignerError;
+
+    fn with_chain_id<T: Into<u64>>(self, chain_id: T) -> Self {
+        Self {
+            chain_id: chain_id.into(),
+            ..self
+        }
+    }
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        let data = Bytes::from(message.as_ref().to_vec());
+        let signature: Bytes = self
+            .client
+            .request("eth_sign", (self.address, data))
+            .await?;
+        Ok(Signature::try_from(signature.as_ref())?)
+    }
+
+    async fn sign_transaction(&self, message: &TypedTransaction) -> Result<Signature, Self::Error> {
+        let mut tx = match message {
+            TypedTransaction::Legacy(tx) => serde_json::to_value(tx),
+            TypedTransaction::Eip2930(tx) => serde_json::to_value(tx),
+            TypedTransaction::Eip1559(tx) => serde_json::to_value(tx),
+        }
+        .expect("transaction requests serialize");
+        tx["from"] = serde_json::to_value(self.address).expect("addresses serialize");
+
+        let signed: Bytes = self.client.request("eth_signTransaction", [tx]).await?;
+        signature_from_rlp(signed.as_ref(), self.chain_id)
+    }
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        _payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        Err(RemoteSignerError::Unsupported("eth_signTypedData"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::{
+        core::types::{TransactionRequest, H256},
+        signers::LocalWallet,
+        utils::rlp::RlpStream,
+    };
+    use serde_json::{json, Value};
+    use warp::Filter;
+
+    use super::*;
+    use crate::Update;
+
+    /// Answer `eth_accounts`, `eth_sign` and `eth_signTransaction` like
+    /// Web3Signer would, with the key of `wallet`
+    async fn mock_signer(wallet: LocalWallet) -> Http {
+        let route = warp::post()
+            .and(warp::body::json())
+            .and_then(move |request: Value| {
+                let wallet = wallet.clone();
+                async move {
+                    let params = &request["params"];
+                    let result = match request["method"].as_str() {
+                        Some("eth_accounts") => json!([wallet.address()]),
+                        Some("eth_sign") => {
+                            let data: Bytes = serde_json::from_value(params[1].clone()).unwrap();
+                            let signature = wallet.sign_message(data.as_ref()).await.unwrap();
+                            json!(Bytes::from(signature.to_vec()))
+                        }
+                        Some("eth_signTransaction") => {
+                            let tx: TransactionRequest =
+                                serde_json::from_value(params[0].clone()).unwrap();
+                            let signature = wallet.sign_transaction(&tx.into()).await.unwrap();
+                            // only the signature matters to the client
+                            let mut stream = RlpStream::new_list(9);
+                            for _ in 0..6 {
+                                stream.append_empty_data();
+                            }
+                            stream.append(&signature.v);
+                            stream.append(&signature.r);
+                            stream.append(&signature.s);
+                            json!(Bytes::from(stream.out().to_vec()))
+                        }
+                        _ => Value::Null,
+                    };
+                    Ok::<_, warp::Rejection>(warp::reply::json(&json!({
+                        "jsonrpc": "2.0",
+                        "id": request["id"],
+                        "result": result,
+                    })))
+                }
+            });
+
+        let (address, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        format!("http://{}", address).parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_signs_with_a_remote_key() {
+        let wallet: LocalWallet =
+            "1111111111111111111111111111111111111111111111111111111111111111"
+                .parse()
+                .unwrap();
+        let signer = RemoteSigner::connect(mock_signer(wallet.clone()).await, None)
+            .await
+            .unwrap();
+        assert_eq!(signer.address(), wallet.address());
+
+        let signed = Update {
+            home_domain: 5,
+            new_root: H256::repeat_byte(1),
+            previous_root: H256::repeat_byte(2),
+        }
+        .sign_with(&signer)
+        .await
+        .unwrap();
+        signed.verify(wallet.address()).unwrap();
+
+        let tx: TypedTransaction = TransactionRequest::new()
+            .to(Address::repeat_byte(3))
+            .value(1)
+            .nonce(0)
+            .gas(21000)
+            .gas_price(1)
+            .into();
+        assert_eq!(
+            signer.sign_transaction(&tx).await.unwrap(),
+            wallet.sign_transaction(&tx).await.unwrap()
+        );
+    }
+}
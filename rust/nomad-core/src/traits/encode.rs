@@ -1,5 +1,5 @@
 use crate::NomadError;
-use ethers::prelude::{Signature, SignatureError, H256};
+use ethers::prelude::{Signature, SignatureError, H256, U256};
 use std::convert::TryFrom;
 
 /// Simple trait for types with a canonical encoding
@@ -73,6 +73,30 @@ impl Decode for H256 {
     }
 }
 
+impl Encode for U256 {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let mut buf = [0; 32];
+        self.to_big_endian(&mut buf);
+        writer.write_all(&buf)?;
+        Ok(32)
+    }
+}
+
+impl Decode for U256 {
+    fn read_from<R>(reader: &mut R) -> Result<Self, NomadError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        let mut buf = [0; 32];
+        reader.read_exact(&mut buf)?;
+        Ok(U256::from_big_endian(&buf))
+    }
+}
+
 impl Encode for u32 {
     fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
     where
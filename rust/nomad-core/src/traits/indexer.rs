@@ -9,6 +9,7 @@
 
 use async_trait::async_trait;
 use color_eyre::Result;
+use ethers::core::types::H256;
 
 use crate::{RawCommittedMessage, SignedUpdateWithMeta};
 
@@ -19,6 +20,10 @@ pub trait CommonIndexer: Send + Sync + std::fmt::Debug {
     /// Get chain's latest block number
     async fn get_block_number(&self) -> Result<u32>;
 
+    /// Get the hash of the canonical block at `height`, if the chain knows
+    /// about it
+    async fn get_block_hash(&self, height: u32) -> Result<Option<H256>>;
+
     /// Fetch sequentially sorted list of updates between blocks `from` and `to`
     async fn fetch_sorted_updates(&self, from: u32, to: u32) -> Result<Vec<SignedUpdateWithMeta>>;
 }
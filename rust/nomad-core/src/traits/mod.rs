@@ -8,7 +8,7 @@ use async_trait::async_trait;
 use color_eyre::Result;
 use ethers::{
     contract::ContractError,
-    core::types::{TransactionReceipt, H256},
+    core::types::{TransactionReceipt, H256, U256},
     providers::{Middleware, ProviderError},
 };
 use std::{error::Error as StdError, fmt::Display};
@@ -46,20 +46,33 @@ impl Display for DoubleUpdate {
 }
 
 /// The result of a transaction
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct TxOutcome {
     /// The txid
     pub txid: H256,
     /// True if executed, false otherwise (reverted, etc.)
     pub executed: bool,
+    /// Gas used by the transaction. Zero if it wasn't sent by the agent
+    pub gas_used: U256,
+    /// Price paid per unit of gas, after any EIP-1559 fee adjustment
+    pub gas_price: U256,
     // TODO: more? What can be abstracted across all chains?
 }
 
+impl TxOutcome {
+    /// What the transaction cost its sender, in the chain's native token
+    pub fn cost(&self) -> U256 {
+        self.gas_used.saturating_mul(self.gas_price)
+    }
+}
+
 impl From<TransactionReceipt> for TxOutcome {
     fn from(t: TransactionReceipt) -> Self {
         Self {
             txid: t.transaction_hash,
             executed: t.status.unwrap().low_u32() == 1,
+            gas_used: t.gas_used.unwrap_or_default(),
+            gas_price: t.effective_gas_price.unwrap_or_default(),
         }
     }
 }
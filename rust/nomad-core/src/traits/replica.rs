@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
 use color_eyre::Result;
 use ethers::core::types::H256;
@@ -5,10 +7,11 @@ use ethers::core::types::H256;
 use crate::{
     accumulator::merkle::Proof,
     traits::{ChainCommunicationError, Common, TxOutcome},
-    NomadMessage,
+    NomadMessage, SignedUpdate,
 };
 
 /// The status of a message in the replica
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum MessageStatus {
     /// Message is unknown
@@ -19,6 +22,15 @@ pub enum MessageStatus {
     Processed = 2,
 }
 
+/// Outcomes of submitting a batch of proofs and messages
+#[derive(Debug, Default)]
+pub struct BatchOutcomes {
+    /// Transactions that carried proofs but processed no message
+    pub proofs: Vec<TxOutcome>,
+    /// One result per message, in the order of the messages
+    pub messages: Vec<Result<TxOutcome, ChainCommunicationError>>,
+}
+
 /// Interface for on-chain replicas
 #[async_trait]
 pub trait Replica: Common + Send + Sync + std::fmt::Debug {
@@ -45,6 +57,78 @@ pub trait Replica: Common + Send + Sync + std::fmt::Debug {
         Ok(self.process(message).await?)
     }
 
+    /// Prove several leaves (all under the same root) and then process
+    /// several messages, batching the submissions where the chain supports
+    /// it. Messages that are already proven should have no entry in
+    /// `proofs`.
+    ///
+    /// The outer error is reserved for failures that prevent the batch from
+    /// being attempted at all. Otherwise one result is returned per message,
+    /// in the order of `messages`, so that one bad message does not hide the
+    /// outcome of the others. The outcomes of transactions that only carried
+    /// proofs are returned separately.
+    ///
+    /// The default implementation submits each proof and message in its own
+    /// transaction.
+    async fn prove_and_process_batch(
+        &self,
+        proofs: &[Proof],
+        messages: &[NomadMessage],
+    ) -> Result<BatchOutcomes, ChainCommunicationError> {
+        let mut outcomes = BatchOutcomes::default();
+        let mut failed_proofs = HashMap::new();
+        for proof in proofs {
+            match self.prove(proof).await {
+                Ok(outcome) => outcomes.proofs.push(outcome),
+                Err(e) => {
+                    failed_proofs.insert(proof.leaf, e);
+                }
+            }
+        }
+
+        for message in messages {
+            match failed_proofs.remove(&message.to_leaf()) {
+                Some(e) => outcomes.messages.push(Err(e)),
+                None => outcomes.messages.push(self.process(message).await),
+            }
+        }
+        Ok(outcomes)
+    }
+
+    /// Submit a chain of signed updates, each building off the previous
+    /// update's new root, in as few transactions as the chain supports.
+    ///
+    /// One outcome is returned per update submitted, in order. A transaction
+    /// carrying several updates is accounted to its first update, the others
+    /// reporting no gas used. Submission stops at the first transaction that
+    /// reverts or fails, so updates after it have no outcome. The error is
+    /// only returned if no update was submitted at all.
+    ///
+    /// The default implementation submits each update in its own
+    /// transaction.
+    async fn update_chain(
+        &self,
+        updates: &[SignedUpdate],
+    ) -> Result<Vec<TxOutcome>, ChainCommunicationError> {
+        let mut outcomes = Vec::with_capacity(updates.len());
+        for update in updates {
+            match self.update(update).await {
+                Ok(outcome) => {
+                    outcomes.push(outcome);
+                    if !outcome.executed {
+                        break;
+                    }
+                }
+                Err(e) if outcomes.is_empty() => return Err(e),
+                Err(e) => {
+                    tracing::warn!(error = %e, "Update submission failed, stopping early");
+                    break;
+                }
+            }
+        }
+        Ok(outcomes)
+    }
+
     /// Fetch the status of a message
     async fn message_status(&self, leaf: H256) -> Result<MessageStatus, ChainCommunicationError>;
 
@@ -13,6 +13,13 @@ pub trait ConnectionManager: Send + Sync + std::fmt::Debug {
     /// Returns true if provided address is enrolled replica
     async fn is_replica(&self, address: NomadIdentifier) -> Result<bool, ChainCommunicationError>;
 
+    /// Returns the replica enrolled for the given remote domain. Zero if no
+    /// replica is enrolled.
+    async fn domain_to_replica(
+        &self,
+        domain: u32,
+    ) -> Result<NomadIdentifier, ChainCommunicationError>;
+
     /// Returns permission for address at given domain
     async fn watcher_permission(
         &self,
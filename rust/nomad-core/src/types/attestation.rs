@@ -0,0 +1,158 @@
+use ethers::prelude::{Address, Signature};
+use serde::{Deserialize, Serialize};
+
+use crate::{Decode, Encode, NomadError, SignedUpdate, Update};
+
+/// An update attested by a committee of updaters, each signing it with its
+/// own key. The home only checks the signature of its updater, the other
+/// signatures are checked off-chain.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct UpdateAttestation {
+    /// The update
+    pub update: Update,
+    /// Signatures of the committee members that attested the update
+    pub signatures: Vec<Signature>,
+}
+
+impl UpdateAttestation {
+    /// An attestation of `update` with no signatures yet
+    pub fn new(update: Update) -> Self {
+        Self {
+            update,
+            signatures: vec![],
+        }
+    }
+
+    /// Add the signature of `signed`. Returns false if its signer already
+    /// attested the update.
+    pub fn add(&mut self, signed: &SignedUpdate) -> Result<bool, NomadError> {
+        if signed.update != self.update {
+            return Err(NomadError::ConflictingAttestation {
+                expected: self.update,
+                actual: signed.update,
+            });
+        }
+
+        let signer = signed.recover()?;
+        if self.signers()?.contains(&signer) {
+            return Ok(false);
+        }
+        self.signatures.push(signed.signature);
+        Ok(true)
+    }
+
+    /// The signed update of each signature
+    pub fn signed_updates(&self) -> impl Iterator<Item = SignedUpdate> + '_ {
+        self.signatures.iter().map(move |signature| SignedUpdate {
+            update: self.update,
+            signature: *signature,
+        })
+    }
+
+    /// Recover the Ethereum addresses of the signers
+    pub fn signers(&self) -> Result<Vec<Address>, NomadError> {
+        self.signed_updates()
+            .map(|signed| signed.recover())
+            .collect()
+    }
+
+    /// Number of distinct members of `committee` that attested the update
+    pub fn count(&self, committee: &[Address]) -> Result<usize, NomadError> {
+        let mut signers = self.signers()?;
+        signers.sort();
+        signers.dedup();
+        Ok(signers
+            .iter()
+            .filter(|signer| committee.contains(signer))
+            .count())
+    }
+
+    /// Check that at least `threshold` members of `committee` attested the
+    /// update
+    pub fn verify(&self, committee: &[Address], threshold: usize) -> Result<(), NomadError> {
+        let signed = self.count(committee)?;
+        if signed < threshold {
+            return Err(NomadError::InsufficientAttestation { signed, threshold });
+        }
+        Ok(())
+    }
+}
+
+impl Encode for UpdateAttestation {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let mut written = 0;
+        written += self.update.write_to(writer)?;
+        written += (self.signatures.len() as u32).write_to(writer)?;
+        for signature in self.signatures.iter() {
+            written += signature.write_to(writer)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for UpdateAttestation {
+    fn read_from<R>(reader: &mut R) -> Result<Self, NomadError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        let update = Update::read_from(reader)?;
+        let count = u32::read_from(reader)?;
+        let signatures = (0..count)
+            .map(|_| Signature::read_from(reader))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { update, signatures })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::{core::types::H256, signers::LocalWallet};
+    use ethers_signers::Signer;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_verifies_committee_attestations() {
+        let committee: Vec<LocalWallet> = (1u8..=3)
+            .map(|i| hex::encode([i; 32]).parse().unwrap())
+            .collect();
+        let members: Vec<Address> = committee.iter().map(Signer::address).collect();
+        let outsider: LocalWallet = hex::encode([9u8; 32]).parse().unwrap();
+
+        let update = Update {
+            home_domain: 1000,
+            previous_root: H256::repeat_byte(1),
+            new_root: H256::repeat_byte(2),
+        };
+        let mut attestation = UpdateAttestation::new(update);
+
+        let first = update.sign_with(&committee[0]).await.unwrap();
+        assert!(attestation.add(&first).unwrap());
+        assert!(!attestation.add(&first).unwrap());
+        assert!(attestation
+            .add(&update.sign_with(&outsider).await.unwrap())
+            .unwrap());
+        assert!(attestation.verify(&members, 2).is_err());
+
+        let conflicting = Update {
+            new_root: H256::repeat_byte(3),
+            ..update
+        };
+        assert!(attestation
+            .add(&conflicting.sign_with(&committee[1]).await.unwrap())
+            .is_err());
+
+        attestation
+            .add(&update.sign_with(&committee[2]).await.unwrap())
+            .unwrap();
+        attestation.verify(&members, 2).unwrap();
+        assert!(attestation.verify(&members, 3).is_err());
+
+        let decoded = UpdateAttestation::read_from(&mut attestation.to_vec().as_slice()).unwrap();
+        assert_eq!(decoded, attestation);
+    }
+}
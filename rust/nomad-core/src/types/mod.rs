@@ -1,11 +1,17 @@
+mod attestation;
 mod failure;
 mod messages;
+mod relay;
+mod retry;
 mod update;
 
 /// Unified 32-byte identifier with convenience tooling for handling
 /// 20-byte ids (e.g ethereum addresses)
 pub mod identifiers;
 
+pub use attestation::*;
 pub use failure::*;
 pub use messages::*;
+pub use relay::*;
+pub use retry::*;
 pub use update::*;
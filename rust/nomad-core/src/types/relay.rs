@@ -0,0 +1,152 @@
+use ethers::core::types::H256;
+
+use crate::{Decode, Encode, NomadError, SignedUpdate};
+
+/// Where the relay of an update to a replica stands
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum RelayStatus {
+    /// Submitted, outcome not known yet
+    Pending = 0,
+    /// Accepted by the replica
+    Confirmed = 1,
+    /// The transaction carrying the update reverted
+    Reverted = 2,
+    /// The transaction carrying the update never made it into a block
+    Dropped = 3,
+}
+
+impl RelayStatus {
+    /// Name of the status in logs
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RelayStatus::Pending => "pending",
+            RelayStatus::Confirmed => "confirmed",
+            RelayStatus::Reverted => "reverted",
+            RelayStatus::Dropped => "dropped",
+        }
+    }
+}
+
+impl TryFrom<u8> for RelayStatus {
+    type Error = NomadError;
+
+    fn try_from(status: u8) -> Result<Self, Self::Error> {
+        match status {
+            0 => Ok(RelayStatus::Pending),
+            1 => Ok(RelayStatus::Confirmed),
+            2 => Ok(RelayStatus::Reverted),
+            3 => Ok(RelayStatus::Dropped),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unknown relay status {}", status),
+            )
+            .into()),
+        }
+    }
+}
+
+/// Relay bookkeeping for a signed update on one replica
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UpdateRelay {
+    /// Root the update builds off
+    pub previous_root: H256,
+    /// Root the update moves the replica to
+    pub new_root: H256,
+    /// Where the relay stands
+    pub status: RelayStatus,
+    /// Hash of the last transaction carrying the update, zero if none landed
+    pub txid: H256,
+    /// Number of failed relays so far
+    pub attempts: u32,
+    /// Unix timestamp (seconds) the update appeared on the home
+    pub seen_at: u64,
+    /// Unix timestamp (seconds) the replica accepted the update, zero until
+    /// confirmed
+    pub confirmed_at: u64,
+    /// Unix timestamp (seconds) before which the update must not be relayed
+    /// again
+    pub next_attempt_at: u64,
+}
+
+impl UpdateRelay {
+    /// Bookkeeping for an update not relayed yet, which appeared on the home
+    /// at `seen_at`
+    pub fn new(update: &SignedUpdate, seen_at: u64) -> Self {
+        Self {
+            previous_root: update.update.previous_root,
+            new_root: update.update.new_root,
+            status: RelayStatus::Pending,
+            txid: H256::zero(),
+            attempts: 0,
+            seen_at,
+            confirmed_at: 0,
+            next_attempt_at: 0,
+        }
+    }
+
+    /// True if the update may be relayed at unix timestamp `now`
+    pub fn is_due(&self, now: u64) -> bool {
+        self.next_attempt_at <= now
+    }
+}
+
+impl std::fmt::Display for UpdateRelay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "UpdateRelay {{ previous_root: {:?}, new_root: {:?}, status: {}, txid: {:?}, attempts: {}, seen_at: {}, confirmed_at: {}, next_attempt_at: {} }}",
+            self.previous_root,
+            self.new_root,
+            self.status.as_str(),
+            self.txid,
+            self.attempts,
+            self.seen_at,
+            self.confirmed_at,
+            self.next_attempt_at,
+        )
+    }
+}
+
+impl Encode for UpdateRelay {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let mut written = 0;
+        written += self.previous_root.write_to(writer)?;
+        written += self.new_root.write_to(writer)?;
+        writer.write_all(&[self.status as u8])?;
+        written += 1;
+        written += self.txid.write_to(writer)?;
+        written += self.attempts.write_to(writer)?;
+        written += self.seen_at.write_to(writer)?;
+        written += self.confirmed_at.write_to(writer)?;
+        written += self.next_attempt_at.write_to(writer)?;
+        Ok(written)
+    }
+}
+
+impl Decode for UpdateRelay {
+    fn read_from<R>(reader: &mut R) -> Result<Self, NomadError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        let previous_root = H256::read_from(reader)?;
+        let new_root = H256::read_from(reader)?;
+        let mut status = [0u8; 1];
+        reader.read_exact(&mut status)?;
+
+        Ok(Self {
+            previous_root,
+            new_root,
+            status: status[0].try_into()?,
+            txid: H256::read_from(reader)?,
+            attempts: u32::read_from(reader)?,
+            seen_at: u64::read_from(reader)?,
+            confirmed_at: u64::read_from(reader)?,
+            next_attempt_at: u64::read_from(reader)?,
+        })
+    }
+}
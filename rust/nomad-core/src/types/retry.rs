@@ -0,0 +1,82 @@
+use crate::{Decode, Encode, NomadError};
+
+/// Retry bookkeeping for a message whose processing failed
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MessageRetry {
+    /// Destination domain of the message
+    pub destination: u32,
+    /// Destination-specific nonce of the message
+    pub nonce: u32,
+    /// Index of the message leaf in the home's tree
+    pub leaf_index: u32,
+    /// Number of failed processing attempts so far
+    pub attempts: u32,
+    /// Unix timestamp (seconds) before which the message must not be retried
+    pub next_attempt_at: u64,
+    /// Description of the most recent failure
+    pub last_error: String,
+}
+
+impl MessageRetry {
+    /// True if the retry is due at unix timestamp `now`
+    pub fn is_due(&self, now: u64) -> bool {
+        self.next_attempt_at <= now
+    }
+}
+
+impl std::fmt::Display for MessageRetry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "MessageRetry {{ destination: {}, nonce: {}, leaf_index: {}, attempts: {}, next_attempt_at: {}, last_error: {} }}",
+            self.destination,
+            self.nonce,
+            self.leaf_index,
+            self.attempts,
+            self.next_attempt_at,
+            self.last_error,
+        )
+    }
+}
+
+impl Encode for MessageRetry {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let mut written = 0;
+        written += self.destination.write_to(writer)?;
+        written += self.nonce.write_to(writer)?;
+        written += self.leaf_index.write_to(writer)?;
+        written += self.attempts.write_to(writer)?;
+        written += self.next_attempt_at.write_to(writer)?;
+        writer.write_all(self.last_error.as_bytes())?;
+        Ok(written + self.last_error.len())
+    }
+}
+
+impl Decode for MessageRetry {
+    fn read_from<R>(reader: &mut R) -> Result<Self, NomadError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        let destination = u32::read_from(reader)?;
+        let nonce = u32::read_from(reader)?;
+        let leaf_index = u32::read_from(reader)?;
+        let attempts = u32::read_from(reader)?;
+        let next_attempt_at = u64::read_from(reader)?;
+
+        let mut last_error = vec![];
+        reader.read_to_end(&mut last_error)?;
+
+        Ok(Self {
+            destination,
+            nonce,
+            leaf_index,
+            attempts,
+            next_attempt_at,
+            last_error: String::from_utf8_lossy(&last_error).into_owned(),
+        })
+    }
+}
@@ -0,0 +1,373 @@
+use ethers::{
+    types::{H256, U256},
+    utils::keccak256,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{Decode, Encode, NomadError};
+
+const TOKEN_ID_LEN: usize = 4 + 32;
+const TRANSFER_LEN: usize = 1 + 32 + 32 + 32;
+const BATCH_LEN: usize = 1 + 32;
+const TRANSFER_GOVERNOR_LEN: usize = 1 + 4 + 32;
+
+const TRANSFER_TYPE: u8 = 3;
+const FAST_TRANSFER_TYPE: u8 = 4;
+const BATCH_TYPE: u8 = 1;
+const TRANSFER_GOVERNOR_TYPE: u8 = 2;
+
+fn invalid_body(reason: String) -> NomadError {
+    NomadError::InvalidMessageBody(reason)
+}
+
+/// A token, identified by its domain of origin and its address there
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenId {
+    /// 4   Domain the token originates from
+    pub domain: u32,
+    /// 32  Address of the token on its domain of origin
+    pub id: H256,
+}
+
+/// A transfer of bridged tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transfer {
+    /// 32  Address of the recipient on the destination
+    pub recipient: H256,
+    /// 32  Amount transferred
+    pub amount: U256,
+    /// 32  Hash of the token's name, symbol and decimals
+    pub details_hash: H256,
+}
+
+/// Action of a token bridge message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BridgeAction {
+    /// A transfer
+    Transfer(Transfer),
+    /// A transfer that liquidity providers may fill ahead of the message
+    FastTransfer(Transfer),
+}
+
+impl BridgeAction {
+    fn type_byte(&self) -> u8 {
+        match self {
+            BridgeAction::Transfer(_) => TRANSFER_TYPE,
+            BridgeAction::FastTransfer(_) => FAST_TRANSFER_TYPE,
+        }
+    }
+
+    /// The transfer, fast or not
+    pub fn transfer(&self) -> &Transfer {
+        match self {
+            BridgeAction::Transfer(transfer) | BridgeAction::FastTransfer(transfer) => transfer,
+        }
+    }
+}
+
+/// A BridgeRouter message: a token ID followed by an action on the token
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BridgeMessage {
+    /// 36  The token acted on
+    pub token: TokenId,
+    /// 97  The action
+    pub action: BridgeAction,
+}
+
+impl Encode for BridgeMessage {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let transfer = self.action.transfer();
+        let mut amount = [0u8; 32];
+        transfer.amount.to_big_endian(&mut amount);
+
+        writer.write_all(&self.token.domain.to_be_bytes())?;
+        writer.write_all(self.token.id.as_ref())?;
+        writer.write_all(&[self.action.type_byte()])?;
+        writer.write_all(transfer.recipient.as_ref())?;
+        writer.write_all(&amount)?;
+        writer.write_all(transfer.details_hash.as_ref())?;
+        Ok(TOKEN_ID_LEN + TRANSFER_LEN)
+    }
+}
+
+impl Decode for BridgeMessage {
+    fn read_from<R>(reader: &mut R) -> Result<Self, NomadError>
+    where
+        R: std::io::Read,
+    {
+        let mut buf = vec![];
+        reader.read_to_end(&mut buf)?;
+        if buf.len() != TOKEN_ID_LEN + TRANSFER_LEN {
+            return Err(invalid_body(format!(
+                "bridge message of length {}, expected {}",
+                buf.len(),
+                TOKEN_ID_LEN + TRANSFER_LEN
+            )));
+        }
+
+        let token = TokenId {
+            domain: u32::from_be_bytes(buf[0..4].try_into().unwrap()),
+            id: H256::from_slice(&buf[4..36]),
+        };
+        let transfer = Transfer {
+            recipient: H256::from_slice(&buf[37..69]),
+            amount: U256::from_big_endian(&buf[69..101]),
+            details_hash: H256::from_slice(&buf[101..133]),
+        };
+        let action = match buf[TOKEN_ID_LEN] {
+            TRANSFER_TYPE => BridgeAction::Transfer(transfer),
+            FAST_TRANSFER_TYPE => BridgeAction::FastTransfer(transfer),
+            other => return Err(invalid_body(format!("unknown bridge action {}", other))),
+        };
+
+        Ok(Self { token, action })
+    }
+}
+
+/// A GovernanceRouter message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GovernanceMessage {
+    /// Commit to a batch of calls, executed once the calls are submitted
+    Batch {
+        /// 32  Hash of the serialized calls, see [`batch_hash`]
+        batch_hash: H256,
+    },
+    /// Hand governance over to another governor
+    TransferGovernor {
+        /// 4   Domain of the new governor
+        domain: u32,
+        /// 32  Address of the new governor
+        governor: H256,
+    },
+}
+
+impl Encode for GovernanceMessage {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        match self {
+            GovernanceMessage::Batch { batch_hash } => {
+                writer.write_all(&[BATCH_TYPE])?;
+                writer.write_all(batch_hash.as_ref())?;
+                Ok(BATCH_LEN)
+            }
+            GovernanceMessage::TransferGovernor { domain, governor } => {
+                writer.write_all(&[TRANSFER_GOVERNOR_TYPE])?;
+                writer.write_all(&domain.to_be_bytes())?;
+                writer.write_all(governor.as_ref())?;
+                Ok(TRANSFER_GOVERNOR_LEN)
+            }
+        }
+    }
+}
+
+impl Decode for GovernanceMessage {
+    fn read_from<R>(reader: &mut R) -> Result<Self, NomadError>
+    where
+        R: std::io::Read,
+    {
+        let mut buf = vec![];
+        reader.read_to_end(&mut buf)?;
+        match (buf.first(), buf.len()) {
+            (Some(&BATCH_TYPE), BATCH_LEN) => Ok(GovernanceMessage::Batch {
+                batch_hash: H256::from_slice(&buf[1..33]),
+            }),
+            (Some(&TRANSFER_GOVERNOR_TYPE), TRANSFER_GOVERNOR_LEN) => {
+                Ok(GovernanceMessage::TransferGovernor {
+                    domain: u32::from_be_bytes(buf[1..5].try_into().unwrap()),
+                    governor: H256::from_slice(&buf[5..37]),
+                })
+            }
+            (Some(identifier), len) => Err(invalid_body(format!(
+                "governance message of type {} and length {}",
+                identifier, len
+            ))),
+            (None, _) => Err(invalid_body("empty governance message".to_owned())),
+        }
+    }
+}
+
+/// A call in a governance batch
+#[derive(Debug, Clone, PartialEq)]
+pub struct GovernanceCall {
+    /// 32  Address to call
+    pub to: H256,
+    /// 0+  Call data
+    pub data: Vec<u8>,
+}
+
+/// Hash a batch of calls is committed to in a governance message
+pub fn batch_hash(calls: &[GovernanceCall]) -> H256 {
+    let mut buf = vec![calls.len() as u8];
+    for call in calls {
+        buf.extend_from_slice(call.to.as_ref());
+        buf.extend_from_slice(&(call.data.len() as u32).to_be_bytes());
+        buf.extend_from_slice(&call.data);
+    }
+    keccak256(buf).into()
+}
+
+/// Type of action a message body carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MessageAction {
+    /// Bridge transfer
+    Transfer,
+    /// Bridge fast transfer
+    FastTransfer,
+    /// Governance batch
+    Batch,
+    /// Governance transfer
+    TransferGovernor,
+    /// Body of an unknown xApp
+    Unknown,
+}
+
+impl MessageAction {
+    /// Name of the action in logs and config
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageAction::Transfer => "transfer",
+            MessageAction::FastTransfer => "fastTransfer",
+            MessageAction::Batch => "batch",
+            MessageAction::TransferGovernor => "transferGovernor",
+            MessageAction::Unknown => "unknown",
+        }
+    }
+}
+
+/// A message body in the format of a known xApp
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum XAppMessage {
+    /// A BridgeRouter message
+    Bridge(BridgeMessage),
+    /// A GovernanceRouter message
+    Governance(GovernanceMessage),
+}
+
+impl XAppMessage {
+    /// Decode a message body in any known xApp format. The formats differ in
+    /// length, so at most one matches.
+    pub fn decode(body: &[u8]) -> Option<Self> {
+        if let Ok(message) = BridgeMessage::read_from(&mut &body[..]) {
+            return Some(XAppMessage::Bridge(message));
+        }
+        GovernanceMessage::read_from(&mut &body[..])
+            .ok()
+            .map(XAppMessage::Governance)
+    }
+
+    /// Type of action the message carries
+    pub fn action(&self) -> MessageAction {
+        match self {
+            XAppMessage::Bridge(BridgeMessage {
+                action: BridgeAction::Transfer(_),
+                ..
+            }) => MessageAction::Transfer,
+            XAppMessage::Bridge(BridgeMessage {
+                action: BridgeAction::FastTransfer(_),
+                ..
+            }) => MessageAction::FastTransfer,
+            XAppMessage::Governance(GovernanceMessage::Batch { .. }) => MessageAction::Batch,
+            XAppMessage::Governance(GovernanceMessage::TransferGovernor { .. }) => {
+                MessageAction::TransferGovernor
+            }
+        }
+    }
+
+    /// Type of action `body` carries, `Unknown` if it isn't in a known xApp
+    /// format
+    pub fn action_of(body: &[u8]) -> MessageAction {
+        Self::decode(body)
+            .map(|message| message.action())
+            .unwrap_or(MessageAction::Unknown)
+    }
+
+    /// Human readable summary of `body`, decoded if in a known xApp format
+    pub fn describe(body: &[u8]) -> String {
+        match Self::decode(body) {
+            Some(message) => message.to_string(),
+            None => format!("{} bytes of unknown format", body.len()),
+        }
+    }
+}
+
+impl std::fmt::Display for XAppMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XAppMessage::Bridge(BridgeMessage { token, action }) => {
+                let transfer = action.transfer();
+                write!(
+                    f,
+                    "{} of {} of token {:?} from domain {} to {:?}",
+                    self.action().as_str(),
+                    transfer.amount,
+                    token.id,
+                    token.domain,
+                    transfer.recipient,
+                )
+            }
+            XAppMessage::Governance(GovernanceMessage::Batch { batch_hash }) => {
+                write!(f, "batch {:?}", batch_hash)
+            }
+            XAppMessage::Governance(GovernanceMessage::TransferGovernor { domain, governor }) => {
+                write!(f, "transferGovernor to {:?} on domain {}", governor, domain)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_decodes_known_xapp_messages() {
+        let transfer = BridgeMessage {
+            token: TokenId {
+                domain: 6648936,
+                id: H256::repeat_byte(1),
+            },
+            action: BridgeAction::FastTransfer(Transfer {
+                recipient: H256::repeat_byte(2),
+                amount: U256::from(1_000_000u64),
+                details_hash: H256::repeat_byte(3),
+            }),
+        };
+        let body = transfer.to_vec();
+        assert_eq!(body.len(), 133);
+        assert_eq!(body[36], FAST_TRANSFER_TYPE);
+        assert_eq!(
+            XAppMessage::decode(&body),
+            Some(XAppMessage::Bridge(transfer))
+        );
+        assert_eq!(XAppMessage::action_of(&body), MessageAction::FastTransfer);
+
+        let calls = vec![GovernanceCall {
+            to: H256::repeat_byte(4),
+            data: vec![0xde, 0xad],
+        }];
+        let batch = GovernanceMessage::Batch {
+            batch_hash: batch_hash(&calls),
+        };
+        assert_eq!(
+            XAppMessage::decode(&batch.to_vec()),
+            Some(XAppMessage::Governance(batch))
+        );
+
+        let governor = GovernanceMessage::TransferGovernor {
+            domain: 1650811245,
+            governor: H256::repeat_byte(5),
+        };
+        assert_eq!(
+            XAppMessage::action_of(&governor.to_vec()),
+            MessageAction::TransferGovernor
+        );
+
+        assert_eq!(XAppMessage::action_of(b"hello"), MessageAction::Unknown);
+    }
+}
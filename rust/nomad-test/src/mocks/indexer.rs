@@ -2,6 +2,7 @@
 
 use async_trait::async_trait;
 use color_eyre::Result;
+use ethers::core::types::H256;
 use mockall::*;
 
 use nomad_core::*;
@@ -10,6 +11,8 @@ mock! {
     pub Indexer {
         pub fn _get_block_number(&self) -> Result<u32> {}
 
+        pub fn _get_block_hash(&self, height: u32) -> Result<Option<H256>> {}
+
         pub fn _fetch_sorted_updates(&self, from: u32, to: u32) -> Result<Vec<SignedUpdateWithMeta>> {}
 
         pub fn _fetch_sorted_messages(&self, from: u32, to: u32) -> Result<Vec<RawCommittedMessage>> {}
@@ -28,6 +31,10 @@ impl CommonIndexer for MockIndexer {
         self._get_block_number()
     }
 
+    async fn get_block_hash(&self, height: u32) -> Result<Option<H256>> {
+        self._get_block_hash(height)
+    }
+
     async fn fetch_sorted_updates(&self, from: u32, to: u32) -> Result<Vec<SignedUpdateWithMeta>> {
         self._fetch_sorted_updates(from, to)
     }
@@ -24,6 +24,17 @@ mock! {
             proof: &Proof,
         ) -> Result<TxOutcome, ChainCommunicationError> {}
 
+        pub fn _prove_and_process_batch(
+            &self,
+            proofs: &[Proof],
+            messages: &[NomadMessage],
+        ) -> Result<BatchOutcomes, ChainCommunicationError> {}
+
+        pub fn _update_chain(
+            &self,
+            updates: &[SignedUpdate],
+        ) -> Result<Vec<TxOutcome>, ChainCommunicationError> {}
+
         // Common
         pub fn _name(&self) -> &str {}
 
@@ -79,6 +90,21 @@ impl Replica for MockReplicaContract {
         self._prove_and_process(message, proof)
     }
 
+    async fn prove_and_process_batch(
+        &self,
+        proofs: &[Proof],
+        messages: &[NomadMessage],
+    ) -> Result<BatchOutcomes, ChainCommunicationError> {
+        self._prove_and_process_batch(proofs, messages)
+    }
+
+    async fn update_chain(
+        &self,
+        updates: &[SignedUpdate],
+    ) -> Result<Vec<TxOutcome>, ChainCommunicationError> {
+        self._update_chain(updates)
+    }
+
     async fn message_status(&self, leaf: H256) -> Result<MessageStatus, ChainCommunicationError> {
         self._message_status(leaf)
     }
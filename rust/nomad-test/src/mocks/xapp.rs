@@ -11,6 +11,11 @@ mock! {
 
         pub fn _is_replica(&self, address: NomadIdentifier) -> Result<bool, ChainCommunicationError> {}
 
+        pub fn _domain_to_replica(
+            &self,
+            domain: u32,
+        ) -> Result<NomadIdentifier, ChainCommunicationError> {}
+
         pub fn _watcher_permission(
             &self,
             address: NomadIdentifier,
@@ -60,6 +65,13 @@ impl ConnectionManager for MockConnectionManagerContract {
         self._is_replica(address)
     }
 
+    async fn domain_to_replica(
+        &self,
+        domain: u32,
+    ) -> Result<NomadIdentifier, ChainCommunicationError> {
+        self._domain_to_replica(domain)
+    }
+
     async fn watcher_permission(
         &self,
         address: NomadIdentifier,
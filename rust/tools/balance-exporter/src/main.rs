@@ -1,160 +1,336 @@
-use std::{path::PathBuf, time::Duration};
+//! The balance exporter polls the native balance of the agents' wallets on
+//! the chains they are configured for, and exports them as Prometheus gauges
+//! along with whether they dropped below their low-balance threshold.
+//!
+//! It reads the agents' config directory: the chains are those of the base
+//! config's home and replicas (or `networks`), and the wallets are those of
+//! the `signers` each agent is configured with. The address of a wallet can
+//! be set under `addresses.{agent}.{chain}` instead, e.g. for AWS signers,
+//! which otherwise have to be reached at startup. Low-balance thresholds are
+//! set per wallet and chain under `lowBalance.{agent or address}.{chain}`.
+//! Both are set in `balanceexporter-partial.json` or `OPT_BALANCEEXPORTER_*`
+//! env vars.
 
-use clap::Arg;
-use color_eyre::eyre::anyhow;
-use futures::StreamExt;
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+#![warn(unused_extern_crates)]
+
+mod settings;
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    Result,
+};
+use futures::future::join_all;
 use human_panic::setup_panic;
-use nomad_base::ChainSetup;
+use nomad_base::{chains::ChainConf, ChainSetup, CoreMetrics};
+use nomad_core::{Balance, Chain};
+use num::{BigInt, ToPrimitive};
+use prometheus::{GaugeVec, IntCounterVec, IntGaugeVec};
 use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::settings::{BalanceExporterSettings as Settings, WalletConf};
 
-#[derive(serde::Deserialize, Debug)]
-struct Input {
-    contracts: Vec<ChainSetup>,
+const NAME: &str = "balance-exporter";
+
+/// A wallet and a connection to its chain
+struct Wallet {
+    conf: WalletConf,
+    low_balance: Option<BigInt>,
+    chain: Arc<dyn Chain + Send + Sync>,
 }
 
-struct Sample {
-    balances: Vec<color_eyre::Result<String>>,
+impl Wallet {
+    fn labels(&self) -> [String; 3] {
+        [
+            self.conf.chain.clone(),
+            self.conf.name.clone(),
+            format!("{:?}", self.conf.address),
+        ]
+    }
 }
 
-async fn poll_once(input: &Input, timeout: Duration) -> Sample {
-    // does this open a new ws connection for each query? probably.
-    let (send, mut recv) = tokio::sync::mpsc::unbounded_channel();
-
-    // moves `send` into the subtask executor. by the time it is complete,
-    // `recv` will complete to exhaustion, due to `send` clones being dropped
-    futures::stream::iter(
-        input
-            .contracts
-            .iter()
-            .enumerate()
-            .zip(std::iter::repeat(send)),
-    )
-    .for_each_concurrent(None, |((ix, _cs), send)| async move {
-        let sub_result =
-            tokio::time::timeout(timeout, std::future::ready(Err(anyhow!("WIP")))).await;
-        send.send((ix, sub_result))
-            .expect("channel closed before we're done?");
-    })
-    .await;
+/// True if `balance` is below the `low_balance` threshold, if any
+fn is_low(balance: &Balance, low_balance: Option<&BigInt>) -> bool {
+    low_balance
+        .map(|threshold| balance.0 < *threshold)
+        .unwrap_or(false)
+}
 
-    let mut balances: Vec<Option<color_eyre::Result<String>>> =
-        input.contracts.iter().map(|_| None).collect();
+/// Gauges the balances are exported as
+struct Gauges {
+    balance: GaugeVec,
+    threshold: GaugeVec,
+    low: IntGaugeVec,
+    errors: IntCounterVec,
+}
 
-    while let Some((ix, sub_result)) = recv.recv().await {
-        match sub_result {
-            Ok(Ok(v)) => balances[ix] = Some(Ok(v)),
-            Ok(Err(e)) => balances[ix] = Some(Err(e)),
-            Err(_) => balances[ix] = Some(Err(anyhow!("timeout expired"))),
-        }
+impl Gauges {
+    fn new(metrics: &CoreMetrics) -> Result<Self> {
+        let labels = &["chain", "wallet", "address"];
+        Ok(Self {
+            balance: metrics.new_gauge(
+                "wallet_balance_wei",
+                "Native token balance of the wallet",
+                labels,
+            )?,
+            threshold: metrics.new_gauge(
+                "wallet_low_balance_threshold_wei",
+                "Balance below which the wallet is flagged as low",
+                labels,
+            )?,
+            low: metrics.new_int_gauge(
+                "wallet_balance_low",
+                "1 if the wallet's balance is below its low-balance threshold",
+                labels,
+            )?,
+            errors: metrics.new_int_counter(
+                "wallet_balance_query_errors",
+                "Number of failed or timed out balance queries",
+                labels,
+            )?,
+        })
     }
+}
 
-    assert!(balances.iter().all(Option::is_some));
-
-    Sample {
-        balances: balances.into_iter().map(Option::unwrap).collect(),
-    }
+/// Every chain the agents are configured for, by name
+fn chain_setups(settings: &nomad_base::Settings) -> HashMap<String, ChainSetup> {
+    settings
+        .split_networks()
+        .into_iter()
+        .flat_map(|settings| {
+            let mut setups = vec![settings.home.clone()];
+            setups.extend(settings.replicas.values().cloned());
+            setups
+        })
+        .map(|setup| (setup.name.clone(), setup))
+        .collect()
 }
 
-#[tokio::main]
-async fn main() -> color_eyre::Result<()> {
-    setup_panic!();
-    color_eyre::install()?;
+/// Connect to the chains of `wallets`, once per chain. Wallets on chains the
+/// agents are not configured for are skipped.
+async fn connect(
+    setups: &HashMap<String, ChainSetup>,
+    wallets: &[WalletConf],
+    low_balance: impl Fn(&WalletConf) -> Option<BigInt>,
+) -> Result<Vec<Wallet>> {
+    let mut chains: HashMap<String, Arc<dyn Chain + Send + Sync>> = HashMap::new();
+    let mut connected = Vec::with_capacity(wallets.len());
 
-    let args = clap::app_from_crate!()
-        .arg(
-            Arg::new("polling-interval")
-                .validator(|s| {
-                    str::parse::<u64>(s).map_err(|_| anyhow!("polling interval must be u64!"))
-                })
-                .about("Minimum number of seconds to wait between poll attempts")
-                .default_value("120"),
-        )
-        .arg(
-            Arg::new("stdin")
-                .about("Read configuration JSON from stdin")
-                .required_unless_present("file"),
-        )
-        .arg(
-            Arg::new("file")
-                .takes_value(true)
-                .about("Path to configuration JSON file"),
-        )
-        .get_matches();
+    for conf in wallets {
+        let chain = match chains.get(&conf.chain) {
+            Some(chain) => chain.clone(),
+            None => {
+                let setup = match setups.get(&conf.chain) {
+                    Some(setup) => setup,
+                    None => {
+                        warn!(
+                            chain = conf.chain.as_str(),
+                            wallet = conf.name.as_str(),
+                            "Skipping wallet {} on unknown chain {}",
+                            conf.name,
+                            conf.chain
+                        );
+                        continue;
+                    }
+                };
+                let chain: Arc<dyn Chain + Send + Sync> = match &setup.chain {
+                    ChainConf::Ethereum(conn) => nomad_ethereum::make_chain(conn.clone())
+                        .await
+                        .wrap_err(format!("Could not connect to {}", setup.name))?
+                        .into(),
+                    ChainConf::Archive(_) => {
+                        warn!(
+                            chain = conf.chain.as_str(),
+                            wallet = conf.name.as_str(),
+                            "Skipping wallet {} on archived chain {}, it has no balances",
+                            conf.name,
+                            conf.chain
+                        );
+                        continue;
+                    }
+                };
+                chains.insert(conf.chain.clone(), chain.clone());
+                chain
+            }
+        };
 
-    eprintln!("Hello, world!");
+        connected.push(Wallet {
+            conf: conf.clone(),
+            low_balance: low_balance(conf),
+            chain,
+        });
+    }
 
-    eprintln!("You asked me to do this: {:?}", args);
+    Ok(connected)
+}
 
-    eprintln!("Loading up the input...");
+/// Balances of the wallets, in order
+struct Sample {
+    balances: Vec<Result<Balance>>,
+}
 
-    let setup: Input = if !args.is_present("stdin") {
-        serde_json::from_reader(std::fs::File::open(PathBuf::from(
-            args.value_of_os("file").expect("malformed --file"),
-        ))?)?
-    } else {
-        serde_json::from_reader(std::io::stdin())?
-    };
+async fn poll_once(wallets: &[Wallet], timeout: Duration) -> Sample {
+    let queries = wallets.iter().map(|wallet| async move {
+        tokio::time::timeout(
+            timeout,
+            wallet.chain.query_balance(wallet.conf.address.into()),
+        )
+        .await
+        .unwrap_or_else(|_| Err(eyre!("timeout expired")))
+    });
 
-    let interval = Duration::from_secs(
-        args.value_of_t("polling-interval")
-            .expect("malformed --polling-interval"),
-    );
+    Sample {
+        balances: join_all(queries).await,
+    }
+}
 
-    println!("Going to start exporting these:");
-    setup.contracts.iter().for_each(|s| println!("\t {:?}", s));
+impl Sample {
+    /// Export the balances and flag low ones
+    fn record(self, wallets: &[Wallet], gauges: &Gauges) {
+        for (wallet, result) in wallets.iter().zip(self.balances) {
+            let labels = wallet.labels();
+            let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
 
-    loop {
-        let start = Instant::now();
-        let results = poll_once(&setup, interval).await;
-
-        for (ix, res) in results.balances.into_iter().enumerate() {
-            let ChainSetup {
-                name: network,
-                address,
-                timelag: _,
-                ..
-            } = &setup.contracts[ix];
-            match res {
-                Ok(s) => {
-                    // TODO: export metric
-                    println!("{} {} = {}", network, address, s);
+            match result {
+                Ok(balance) => {
+                    let low = is_low(&balance, wallet.low_balance.as_ref());
+                    if low {
+                        warn!(
+                            chain = wallet.conf.chain.as_str(),
+                            wallet = wallet.conf.name.as_str(),
+                            balance = %balance.0,
+                            "Balance of wallet {} on {} is low",
+                            wallet.conf.name,
+                            wallet.conf.chain
+                        );
+                    }
+                    gauges
+                        .balance
+                        .with_label_values(&labels)
+                        .set(balance.0.to_f64().unwrap_or(f64::MAX));
+                    gauges.low.with_label_values(&labels).set(low as i64);
                 }
                 Err(e) => {
-                    eprintln!("Error while querying {:?}: {}", setup.contracts[ix], e);
+                    warn!(
+                        chain = wallet.conf.chain.as_str(),
+                        wallet = wallet.conf.name.as_str(),
+                        error = %e,
+                        "Could not query balance of wallet {} on {}",
+                        wallet.conf.name,
+                        wallet.conf.chain
+                    );
+                    gauges.errors.with_label_values(&labels).inc();
                 }
             }
         }
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    setup_panic!();
+    color_eyre::install()?;
+
+    let settings = Settings::new()?;
+    let metrics = Arc::new(CoreMetrics::new(
+        NAME,
+        settings
+            .base
+            .metrics
+            .as_ref()
+            .map(|v| v.parse::<u16>().expect("metrics port must be u16")),
+        Default::default(),
+    )?);
+    settings
+        .base
+        .tracing
+        .start_tracing(metrics.span_duration())?;
+    let gauges = Gauges::new(&metrics)?;
+    let _ = metrics.clone().run_http_server();
 
+    let wallets = settings::wallets(&settings::agent_signers()?, &settings.addresses).await;
+    let wallets = connect(&chain_setups(&settings.base), &wallets, |wallet| {
+        settings.low_balance(wallet)
+    })
+    .await?;
+    for wallet in wallets.iter() {
+        let labels = wallet.labels();
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        if let Some(threshold) = &wallet.low_balance {
+            gauges
+                .threshold
+                .with_label_values(&labels)
+                .set(threshold.to_f64().unwrap_or(f64::MAX));
+        }
+        info!(
+            chain = wallet.conf.chain.as_str(),
+            wallet = wallet.conf.name.as_str(),
+            address = ?wallet.conf.address,
+            "Exporting balance of wallet {} on {}",
+            wallet.conf.name,
+            wallet.conf.chain
+        );
+    }
+
+    let interval = Duration::from_secs(settings.interval.parse().expect("invalid integer"));
+    loop {
+        let start = Instant::now();
+        poll_once(&wallets, interval)
+            .await
+            .record(&wallets, &gauges);
         tokio::time::sleep_until(start + interval).await;
     }
 }
 
-impl Sample {
-    #[allow(dead_code)]
-    fn record(_m: impl metrics::Recorder) {}
-}
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_flags_low_balances() {
+        let threshold = BigInt::from(1_000_000u64);
+        assert!(is_low(&Balance(BigInt::from(999_999u64)), Some(&threshold)));
+        assert!(!is_low(
+            &Balance(BigInt::from(1_000_000u64)),
+            Some(&threshold)
+        ));
+        assert!(!is_low(&Balance(BigInt::from(0u64)), None));
+    }
 
-#[tokio::test]
-#[should_panic]
-async fn mainnet_works() {
-    // query ethereum instance of XAppConnectionManager and asserts the balance is nonzero.
-    let sample = poll_once(
-        &Input {
-            contracts: vec![ChainSetup {
-                name: "ethereum".into(),
-                domain: "6648936".into(),
-                timelag: 5,
-                // i would love for this to just be ChainConf::ethereum()
-                chain: nomad_base::chains::ChainConf::Ethereum(nomad_ethereum::Connection::Ws {
-                    url: "wss://main-light.eth.linkpool.io/ws".into(),
-                }),
-                address: "0xcEc158A719d11005Bd9339865965bed938BEafA3".into(),
-                disabled: None,
+    #[tokio::test]
+    #[ignore = "queries mainnet"]
+    async fn mainnet_works() {
+        // query the balance of the ethereum XAppConnectionManager and assert it is nonzero
+        let setup = ChainSetup {
+            name: "ethereum".into(),
+            domain: "6648936".into(),
+            // i would love for this to just be ChainConf::ethereum()
+            chain: ChainConf::Ethereum(nomad_ethereum::Connection::Ws {
+                url: "wss://main-light.eth.linkpool.io/ws".into(),
+            }),
+            ..Default::default()
+        };
+        let wallets = connect(
+            &[(setup.name.clone(), setup)].into_iter().collect(),
+            &[WalletConf {
+                name: "xapp_connection_manager".into(),
+                agents: vec![],
+                chain: "ethereum".into(),
+                address: "0xcEc158A719d11005Bd9339865965bed938BEafA3"
+                    .parse()
+                    .unwrap(),
             }],
-        },
-        Duration::from_secs(120),
-    )
-    .await;
-    let only_balance = sample.balances[0].as_ref();
-    assert!(only_balance.expect("failed to query chain!") != "0");
+            |_| None,
+        )
+        .await
+        .expect("failed to connect to chain!");
+
+        let sample = poll_once(&wallets, Duration::from_secs(120)).await;
+        let only_balance = sample.balances[0].as_ref();
+        assert!(only_balance.expect("failed to query chain!").0 != BigInt::from(0u64));
+    }
 }
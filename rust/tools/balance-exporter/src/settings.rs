@@ -0,0 +1,346 @@
+//! Configuration
+
+use color_eyre::{eyre::eyre, Result};
+use ethers::{
+    signers::{LocalWallet, Signer},
+    types::Address,
+};
+use nomad_base::SignerConf;
+use num::BigInt;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    str::FromStr,
+};
+use tracing::warn;
+
+/// Agents whose transaction signers are monitored
+pub const AGENTS: [&str; 5] = ["updater", "relayer", "processor", "watcher", "kathy"];
+
+/// Load the config of `name` from the config files and/or env, in the same
+/// precedence order as the agents' settings:
+///
+/// 1. The file specified by the `RUN_ENV` and `BASE_CONFIG` env vars.
+/// 2. `RUN_ENV/{name}-partial.json`
+/// 3. Configuration env vars with the prefix `OPT_BASE`
+/// 4. Configuration env vars with the prefix `OPT_{NAME}`
+fn load(name: &str) -> Result<config::Config, config::ConfigError> {
+    let mut s = config::Config::new();
+
+    let env = std::env::var("RUN_ENV").unwrap_or_else(|_| "default".into());
+    let fname = std::env::var("BASE_CONFIG").unwrap_or_else(|_| "base".into());
+
+    s.merge(config::File::with_name(&format!(
+        "./config/{}/{}",
+        env, fname
+    )))?;
+    s.merge(
+        config::File::with_name(&format!("./config/{}/{}-partial", env, name)).required(false),
+    )?;
+    s.merge(config::Environment::with_prefix("OPT_BASE").separator("_"))?;
+    let prefix = format!("OPT_{}", name.to_ascii_uppercase());
+    s.merge(config::Environment::with_prefix(&prefix).separator("_"))?;
+
+    Ok(s)
+}
+
+/// Settings for the balance exporter
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceExporterSettings {
+    #[serde(flatten)]
+    pub(crate) base: nomad_base::Settings,
+    /// The polling interval (in seconds)
+    pub(crate) interval: String,
+    /// Balance (in wei) below which a wallet is flagged as low, by wallet and
+    /// chain name. Wallets are keyed by the name of an agent signing with
+    /// them, or by address
+    #[serde(default)]
+    pub(crate) low_balance: HashMap<String, HashMap<String, String>>,
+    /// Addresses of the agents' wallets, by agent and chain name. Take
+    /// precedence over the address of the agents' signers
+    #[serde(default)]
+    pub(crate) addresses: HashMap<String, HashMap<String, Address>>,
+}
+
+impl BalanceExporterSettings {
+    /// Read settings from the config files and/or env. See [`load`] for the
+    /// precedence order, with the `balanceexporter` name.
+    pub fn new() -> Result<Self, config::ConfigError> {
+        load("balanceexporter")?.try_into()
+    }
+
+    /// Get the `lowBalance` setting of `wallet`, parsed. A wallet shared by
+    /// several agents, or also set by address, gets the highest of its
+    /// thresholds.
+    pub fn low_balance(&self, wallet: &WalletConf) -> Option<BigInt> {
+        self.low_balance
+            .iter()
+            .filter(|(key, _)| {
+                wallet.agents.contains(*key) || key.parse::<Address>().ok() == Some(wallet.address)
+            })
+            .filter_map(|(_, chains)| chains.get(&wallet.chain))
+            .map(|s| BigInt::from_str(s).expect("invalid integer"))
+            .max()
+    }
+}
+
+/// The transaction signers of an agent, by chain name
+#[derive(Debug, Default, Deserialize)]
+struct AgentSigners {
+    #[serde(default)]
+    signers: HashMap<String, SignerConf>,
+}
+
+/// Read the signers configured for each of [`AGENTS`]
+pub fn agent_signers() -> Result<Vec<(String, HashMap<String, SignerConf>)>> {
+    AGENTS
+        .iter()
+        .map(|agent| {
+            let signers: AgentSigners = load(agent)?.try_into()?;
+            Ok((agent.to_string(), signers.signers))
+        })
+        .collect()
+}
+
+/// A wallet whose balance is exported
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalletConf {
+    /// Name of the wallet in metrics: the agents signing with it
+    pub name: String,
+    /// The agents signing with the wallet
+    pub agents: Vec<String>,
+    /// Name of the chain the wallet is on, as configured for the agents'
+    /// home or replicas
+    pub chain: String,
+    /// Address of the wallet
+    pub address: Address,
+}
+
+/// Read the address of a V3 keystore, which it keeps unencrypted
+fn keystore_address(path: impl AsRef<Path>) -> Result<Address> {
+    #[derive(Deserialize)]
+    struct Keystore {
+        address: Option<String>,
+    }
+
+    let keystore: Keystore = serde_json::from_slice(&std::fs::read(path)?)?;
+    let address = keystore
+        .address
+        .ok_or_else(|| eyre!("Keystore does not include its address"))?;
+    Ok(address.trim_start_matches("0x").parse()?)
+}
+
+/// The address `conf` signs with, or `None` if the node signs. Read from the
+/// config where possible, so that keystores are not decrypted and remote
+/// signers not reached.
+async fn signer_address(conf: &SignerConf) -> Result<Option<Address>> {
+    Ok(match conf {
+        SignerConf::HexKey { key } => Some(key.as_ref().parse::<LocalWallet>()?.address()),
+        SignerConf::Keystore { path, .. } => Some(keystore_address(path)?),
+        SignerConf::Remote {
+            address: Some(address),
+            ..
+        } => Some(address.parse()?),
+        SignerConf::Node => None,
+        _ => Some(conf.try_into_signer().await?.address()),
+    })
+}
+
+/// Derive the wallets to export from the agents' signers, or from
+/// `addresses` where configured. Signers whose address can't be resolved are
+/// skipped. Agents sharing an address on a chain share a wallet, named after
+/// all of them.
+pub async fn wallets(
+    agents: &[(String, HashMap<String, SignerConf>)],
+    addresses: &HashMap<String, HashMap<String, Address>>,
+) -> Vec<WalletConf> {
+    let mut wallets: BTreeMap<(String, Address), Vec<String>> = BTreeMap::new();
+    let no_addresses = HashMap::new();
+
+    for (agent, signers) in agents {
+        let configured = addresses.get(agent).unwrap_or(&no_addresses);
+        for (chain, address) in configured {
+            wallets
+                .entry((chain.clone(), *address))
+                .or_default()
+                .push(agent.clone());
+        }
+
+        for (chain, conf) in signers {
+            if configured.contains_key(chain) {
+                continue;
+            }
+            match signer_address(conf).await {
+                Ok(Some(address)) => wallets
+                    .entry((chain.clone(), address))
+                    .or_default()
+                    .push(agent.clone()),
+                Ok(None) => {}
+                Err(e) => warn!(
+                    agent = agent.as_str(),
+                    chain = chain.as_str(),
+                    error = %e,
+                    "Skipping wallet of {} on {}: could not resolve its address. Set it under addresses.{}.{}",
+                    agent,
+                    chain,
+                    agent,
+                    chain
+                ),
+            }
+        }
+    }
+
+    wallets
+        .into_iter()
+        .map(|((chain, address), agents)| WalletConf {
+            name: agents.join(","),
+            agents,
+            chain,
+            address,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_merges_wallets_shared_by_agents() {
+        let key = |key: &str| SignerConf::HexKey {
+            key: key.parse().unwrap(),
+        };
+        let shared = "1111111111111111111111111111111111111111111111111111111111111111";
+        let own = "2222222222222222222222222222222222222222222222222222222222222222";
+
+        let agents = vec![
+            (
+                "relayer".to_owned(),
+                [("ethereum".to_owned(), key(shared))].into_iter().collect(),
+            ),
+            (
+                "processor".to_owned(),
+                [
+                    ("ethereum".to_owned(), key(shared)),
+                    ("celo".to_owned(), key(own)),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+        ];
+
+        let wallets = wallets(&agents, &HashMap::new()).await;
+        assert_eq!(wallets.len(), 2);
+
+        let ethereum = wallets.iter().find(|w| w.chain == "ethereum").unwrap();
+        assert_eq!(ethereum.name, "relayer,processor");
+
+        let celo = wallets.iter().find(|w| w.chain == "celo").unwrap();
+        assert_eq!(celo.name, "processor");
+        assert_ne!(celo.address, ethereum.address);
+    }
+
+    #[tokio::test]
+    async fn it_resolves_addresses_without_signing() {
+        let address = Address::repeat_byte(1);
+        let keystore = std::env::temp_dir().join(format!(
+            "nomad-balance-exporter-keystore-{}.json",
+            std::process::id()
+        ));
+        std::fs::write(&keystore, format!(r#"{{"address":"{:x}"}}"#, address)).unwrap();
+
+        let signers: HashMap<String, SignerConf> = [
+            (
+                "ethereum".to_owned(),
+                SignerConf::Keystore {
+                    path: keystore.to_string_lossy().into_owned(),
+                    password_env: "NOMAD_UNSET_KEYSTORE_PASSWORD".to_owned(),
+                },
+            ),
+            (
+                "celo".to_owned(),
+                SignerConf::Keystore {
+                    path: "/nonexistent/keystore.json".to_owned(),
+                    password_env: "NOMAD_UNSET_KEYSTORE_PASSWORD".to_owned(),
+                },
+            ),
+            ("polygon".to_owned(), SignerConf::Node),
+            (
+                "moonbeam".to_owned(),
+                SignerConf::Remote {
+                    url: "http://127.0.0.1:1".to_owned(),
+                    address: None,
+                },
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let addresses = [(
+            "updater".to_owned(),
+            [("moonbeam".to_owned(), Address::repeat_byte(2))]
+                .into_iter()
+                .collect(),
+        )]
+        .into_iter()
+        .collect();
+
+        let wallets = wallets(&[("updater".to_owned(), signers)], &addresses).await;
+        std::fs::remove_file(&keystore).unwrap();
+
+        // the unreadable keystore and the node signer are skipped
+        assert_eq!(wallets.len(), 2);
+        let ethereum = wallets.iter().find(|w| w.chain == "ethereum").unwrap();
+        assert_eq!(ethereum.address, address);
+        // the configured address is used without reaching the remote signer
+        let moonbeam = wallets.iter().find(|w| w.chain == "moonbeam").unwrap();
+        assert_eq!(moonbeam.address, Address::repeat_byte(2));
+    }
+
+    #[test]
+    fn it_sets_low_balance_by_wallet_and_chain() {
+        let thresholds = |thresholds: &[(&str, &str)]| {
+            thresholds
+                .iter()
+                .map(|(chain, threshold)| (chain.to_string(), threshold.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        let address = Address::repeat_byte(1);
+        let settings = BalanceExporterSettings {
+            base: Default::default(),
+            interval: "120".to_owned(),
+            addresses: HashMap::new(),
+            low_balance: [
+                ("updater".to_owned(), thresholds(&[("ethereum", "100")])),
+                (
+                    "processor".to_owned(),
+                    thresholds(&[("ethereum", "300"), ("celo", "5")]),
+                ),
+                ("relayer".to_owned(), thresholds(&[("ethereum", "200")])),
+                (format!("{:?}", address), thresholds(&[("celo", "50")])),
+            ]
+            .into_iter()
+            .collect(),
+        };
+
+        let low_balance = |agents: &[&str], chain: &str| {
+            settings.low_balance(&WalletConf {
+                name: agents.join(","),
+                agents: agents.iter().map(|agent| agent.to_string()).collect(),
+                chain: chain.to_owned(),
+                address,
+            })
+        };
+        assert_eq!(low_balance(&["updater"], "ethereum"), Some(100.into()));
+        assert_eq!(low_balance(&["kathy"], "ethereum"), None);
+        // shared wallets get the highest threshold of their agents
+        assert_eq!(
+            low_balance(&["relayer", "processor"], "ethereum"),
+            Some(300.into())
+        );
+        // thresholds set by address apply whichever agents sign
+        assert_eq!(low_balance(&["updater"], "celo"), Some(50.into()));
+        assert_eq!(low_balance(&["processor"], "celo"), Some(50.into()));
+    }
+}
@@ -1,6 +1,9 @@
 use structopt::StructOpt;
 
-use crate::subcommands::{db_state::DbStateCommand, prove::ProveCommand};
+use crate::subcommands::{
+    db_state::DbStateCommand, dead_letters::DeadLettersCommand, deferred::DeferredCommand,
+    keystore::KeystoreCommand, prove::ProveCommand, trace::TraceCommand,
+};
 
 #[derive(StructOpt)]
 pub enum Commands {
@@ -8,4 +11,12 @@ pub enum Commands {
     Prove(ProveCommand),
     /// Print the processor's db state
     DbState(DbStateCommand),
+    /// List or requeue messages the processor gave up on
+    DeadLetters(DeadLettersCommand),
+    /// List, release or discard messages deferred by the processor's policy
+    Deferred(DeferredCommand),
+    /// Trace a message from dispatch on its home to processing on its replica
+    TraceMessage(TraceCommand),
+    /// Create an encrypted keystore for a new or existing private key
+    Keystore(KeystoreCommand),
 }
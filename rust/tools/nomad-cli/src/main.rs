@@ -15,5 +15,9 @@ async fn main() -> Result<()> {
     match command {
         Commands::Prove(prove) => prove.run().await,
         Commands::DbState(db_state) => db_state.run().await,
+        Commands::DeadLetters(dead_letters) => dead_letters.run().await,
+        Commands::Deferred(deferred) => deferred.run().await,
+        Commands::TraceMessage(trace) => trace.run().await,
+        Commands::Keystore(keystore) => keystore.run().await,
     }
 }
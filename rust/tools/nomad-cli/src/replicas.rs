@@ -1,3 +1,4 @@
+use color_eyre::Result;
 use ethers::prelude::H160;
 
 const CELO: u32 = 1667591279;
@@ -16,3 +17,15 @@ pub(crate) fn address_by_domain_pair(origin: u32, destination: u32) -> Option<H1
     };
     Some(addr.parse().unwrap())
 }
+
+/// Tries the passed-in address first, then defaults to lookup by domain pair
+pub(crate) fn address_or_lookup(
+    address: Option<&str>,
+    origin: u32,
+    destination: u32,
+) -> Result<Option<H160>> {
+    match address {
+        Some(address) => Ok(Some(address.parse()?)),
+        None => Ok(address_by_domain_pair(origin, destination)),
+    }
+}
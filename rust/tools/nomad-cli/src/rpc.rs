@@ -1,5 +1,6 @@
 use std::convert::TryFrom;
 
+use color_eyre::Result;
 use ethers::prelude::{Http, Provider};
 
 fn domain_to_env(domain: u32) -> Option<&'static str> {
@@ -16,3 +17,11 @@ pub(crate) fn fetch_rpc_connection(domain: u32) -> Option<Provider<Http>> {
         .map(|rpc| TryFrom::try_from(rpc).expect("Invalid RPC url"))
         .ok()
 }
+
+/// Tries the passed-in rpc first, then defaults to lookup by domain
+pub(crate) fn rpc_or_lookup(rpc: Option<&str>, domain: u32) -> Result<Option<Provider<Http>>> {
+    match rpc {
+        Some(rpc) => Ok(Some(Provider::<Http>::try_from(rpc)?)),
+        None => Ok(fetch_rpc_connection(domain)),
+    }
+}
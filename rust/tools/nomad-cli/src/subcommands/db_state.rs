@@ -4,7 +4,7 @@ use std::{collections::HashMap, convert::TryInto, fs::OpenOptions, io::Write};
 use structopt::StructOpt;
 
 use nomad_base::NomadDB;
-use nomad_core::{db::DB, CommittedMessage};
+use nomad_core::{db::DB, xapps::XAppMessage, CommittedMessage};
 
 use ethers::types::H256;
 
@@ -21,6 +21,10 @@ pub struct DbStateCommand {
     /// Save output to json file
     #[structopt(long)]
     json: bool,
+
+    /// Show the decoded body of each message
+    #[structopt(long)]
+    decode: bool,
 }
 
 type OutputVec = Vec<((H256, u64), Vec<CommittedMessage>)>;
@@ -34,9 +38,9 @@ impl DbStateCommand {
         let output_vec = self.create_output_vec(&db, messages_by_committed_roots)?;
 
         if self.json {
-            DbStateCommand::save_to_json(output_vec)?;
+            DbStateCommand::save_to_json(output_vec, self.decode)?;
         } else {
-            DbStateCommand::print_output(output_vec);
+            DbStateCommand::print_output(output_vec, self.decode);
         }
 
         Ok(())
@@ -112,12 +116,28 @@ impl DbStateCommand {
         Ok(output_vec)
     }
 
-    fn print_output(output_vec: OutputVec) {
+    fn print_output(output_vec: OutputVec, decode: bool) {
         for ((update_root, block_number), mut bucket) in output_vec {
             println!("Update root: {:?}", update_root);
             println!("Block number: {}", block_number);
 
             bucket.sort_by(|x, y| x.leaf_index.cmp(&y.leaf_index));
+            if decode {
+                println!("Leaves:");
+                for message in bucket {
+                    println!(
+                        "  {} ({}->{}:{}): {}",
+                        message.leaf_index,
+                        message.message.origin,
+                        message.message.destination,
+                        message.message.nonce,
+                        XAppMessage::describe(&message.message.body)
+                    );
+                }
+                println!();
+                continue;
+            }
+
             print!("Leaves:");
             for message in bucket {
                 print!(" {} ", message.leaf_index);
@@ -127,17 +147,31 @@ impl DbStateCommand {
         }
     }
 
-    fn save_to_json(output_vec: OutputVec) -> Result<()> {
+    fn save_to_json(output_vec: OutputVec, decode: bool) -> Result<()> {
         let mut json_entries: Vec<Value> = Vec::new();
         for ((update_root, block_number), mut bucket) in output_vec {
             bucket.sort_by(|x, y| x.leaf_index.cmp(&y.leaf_index));
             let leaf_indexes: Vec<_> = bucket.iter().map(|leaf| leaf.leaf_index).collect();
 
-            json_entries.push(json!({
+            let mut entry = json!({
                 "updateRoot": update_root,
                 "blockNumber": block_number,
                 "leaves": leaf_indexes,
-            }));
+            });
+            if decode {
+                let bodies: Vec<_> = bucket
+                    .iter()
+                    .map(|leaf| {
+                        json!({
+                            "leafIndex": leaf.leaf_index,
+                            "action": XAppMessage::action_of(&leaf.message.body),
+                            "body": XAppMessage::describe(&leaf.message.body),
+                        })
+                    })
+                    .collect();
+                entry["bodies"] = json!(bodies);
+            }
+            json_entries.push(entry);
         }
 
         let json = json!(json_entries).to_string();
@@ -0,0 +1,60 @@
+use color_eyre::{eyre::bail, Result};
+use structopt::StructOpt;
+
+use nomad_base::NomadDB;
+use nomad_core::db::DB;
+
+#[derive(StructOpt, Debug)]
+pub struct DeadLettersCommand {
+    /// Path to processor db
+    #[structopt(long)]
+    db_path: String,
+
+    /// Name of associated home
+    #[structopt(long)]
+    home_name: String,
+
+    /// Destination domain of the dead-lettered messages
+    #[structopt(long)]
+    destination: u32,
+
+    /// Move the dead-lettered message with this nonce back into the
+    /// processor's retry queue
+    #[structopt(long, conflicts_with = "requeue_all")]
+    requeue: Option<u32>,
+
+    /// Move every dead-lettered message for the destination back into the
+    /// processor's retry queue
+    #[structopt(long)]
+    requeue_all: bool,
+}
+
+impl DeadLettersCommand {
+    pub async fn run(&self) -> Result<()> {
+        let db = NomadDB::new(&self.home_name, DB::from_path(&self.db_path)?);
+
+        let nonces: Vec<u32> = match (self.requeue, self.requeue_all) {
+            (Some(nonce), _) => vec![nonce],
+            (None, true) => db.dead_letters(self.destination).map(|d| d.nonce).collect(),
+            (None, false) => {
+                for dead in db.dead_letters(self.destination) {
+                    println!("{}", dead);
+                }
+                return Ok(());
+            }
+        };
+
+        for nonce in nonces {
+            match db.requeue_dead_letter(self.destination, nonce)? {
+                Some(retry) => println!("Requeued {}", retry),
+                None => bail!(
+                    "No dead-lettered message for destination {} at nonce {}",
+                    self.destination,
+                    nonce
+                ),
+            }
+        }
+
+        Ok(())
+    }
+}
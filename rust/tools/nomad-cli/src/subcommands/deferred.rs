@@ -0,0 +1,77 @@
+use color_eyre::{eyre::bail, Result};
+use structopt::StructOpt;
+
+use nomad_base::NomadDB;
+use nomad_core::db::DB;
+
+#[derive(StructOpt, Debug)]
+pub struct DeferredCommand {
+    /// Path to processor db
+    #[structopt(long)]
+    db_path: String,
+
+    /// Name of associated home
+    #[structopt(long)]
+    home_name: String,
+
+    /// Destination domain of the deferred messages
+    #[structopt(long)]
+    destination: u32,
+
+    /// Release the deferred message with this nonce into the processor's
+    /// retry queue
+    #[structopt(long, conflicts_with_all = &["release_all", "discard"])]
+    release: Option<u32>,
+
+    /// Release every deferred message for the destination into the
+    /// processor's retry queue
+    #[structopt(long, conflicts_with = "discard")]
+    release_all: bool,
+
+    /// Drop the deferred message with this nonce without processing it
+    #[structopt(long)]
+    discard: Option<u32>,
+}
+
+impl DeferredCommand {
+    pub async fn run(&self) -> Result<()> {
+        let db = NomadDB::new(&self.home_name, DB::from_path(&self.db_path)?);
+
+        if let Some(nonce) = self.discard {
+            if db.deferred_by_nonce(self.destination, nonce)?.is_none() {
+                bail!(
+                    "No deferred message for destination {} at nonce {}",
+                    self.destination,
+                    nonce
+                );
+            }
+            db.discard_deferred(self.destination, nonce)?;
+            println!("Discarded deferred message at nonce {}", nonce);
+            return Ok(());
+        }
+
+        let nonces: Vec<u32> = match (self.release, self.release_all) {
+            (Some(nonce), _) => vec![nonce],
+            (None, true) => db.deferred(self.destination).map(|d| d.nonce).collect(),
+            (None, false) => {
+                for deferred in db.deferred(self.destination) {
+                    println!("{}", deferred);
+                }
+                return Ok(());
+            }
+        };
+
+        for nonce in nonces {
+            match db.release_deferred(self.destination, nonce)? {
+                Some(retry) => println!("Released {}", retry),
+                None => bail!(
+                    "No deferred message for destination {} at nonce {}",
+                    self.destination,
+                    nonce
+                ),
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+use std::{env, path::PathBuf};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use ethers::signers::{LocalWallet, Signer};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+pub struct KeystoreCommand {
+    /// Directory to write the keystore file to
+    #[structopt(long)]
+    dir: PathBuf,
+
+    /// Env var holding the password to encrypt the keystore with. Agents
+    /// read it from the env var named by the signer's `passwordEnv`.
+    #[structopt(long)]
+    password_env: String,
+
+    /// Env var holding a hex private key to convert into a keystore. A new
+    /// key is generated if omitted.
+    #[structopt(long)]
+    key_env: Option<String>,
+}
+
+impl KeystoreCommand {
+    pub async fn run(&self) -> Result<()> {
+        let password = env::var(&self.password_env)
+            .wrap_err_with(|| format!("Missing password env var {}", self.password_env))?;
+        if password.is_empty() {
+            bail!("Empty password in env var {}", self.password_env);
+        }
+
+        let mut rng = rand::thread_rng();
+        let (address, id) = match &self.key_env {
+            Some(key_env) => {
+                let key = env::var(key_env)
+                    .wrap_err_with(|| format!("Missing private key env var {}", key_env))?;
+                let key = key.trim().trim_start_matches("0x");
+                let wallet: LocalWallet = key.parse().wrap_err("Invalid private key")?;
+                let key = hex::decode(key)?;
+                let id = eth_keystore::encrypt_key(&self.dir, &mut rng, &key, &password)?;
+                (wallet.address(), id)
+            }
+            None => {
+                let (wallet, id) = LocalWallet::new_keystore(&self.dir, &mut rng, &password)?;
+                (wallet.address(), id)
+            }
+        };
+
+        // make sure the agents will be able to read it back
+        let path = self.dir.join(&id);
+        let wallet = LocalWallet::decrypt_keystore(&path, &password)?;
+        if wallet.address() != address {
+            bail!(
+                "Keystore at {} does not decrypt to {:?}",
+                path.display(),
+                address
+            );
+        }
+
+        println!("Wrote keystore for {:?} to {}", address, path.display());
+        println!(
+            "Signer config: {{ \"type\": \"keystore\", \"path\": \"{}\", \"passwordEnv\": \"{}\" }}",
+            path.display(),
+            self.password_env
+        );
+        Ok(())
+    }
+}
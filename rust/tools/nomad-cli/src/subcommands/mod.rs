@@ -1,5 +1,13 @@
 pub mod db_state;
+pub mod dead_letters;
+pub mod deferred;
+pub mod keystore;
 pub mod prove;
+pub mod trace;
 
 pub use db_state::*;
+pub use dead_letters::*;
+pub use deferred::*;
+pub use keystore::*;
 pub use prove::*;
+pub use trace::*;
@@ -1,4 +1,4 @@
-use std::{convert::TryFrom, sync::Arc};
+use std::sync::Arc;
 use structopt::StructOpt;
 
 use crate::{replicas, rpc};
@@ -12,11 +12,14 @@ use nomad_base::NomadDB;
 use nomad_ethereum::EthereumReplica;
 
 use ethers::{
-    prelude::{Http, Middleware, Provider, SignerMiddleware, H160},
+    prelude::{Http, Middleware, Provider, SignerMiddleware},
     types::H256,
 };
 
-use color_eyre::{eyre::bail, Result};
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
 use ethers_signers::{AwsSigner, Signer};
 
 use once_cell::sync::OnceCell;
@@ -129,27 +132,15 @@ impl ProveCommand {
     }
 
     async fn replica(&self, origin: u32, destination: u32) -> Result<ConcreteReplica> {
-        // bit ugly. Tries passed-in rpc first, then defaults to lookup by
-        // domain
-        let provider = self
-            .rpc
-            .as_ref()
-            .map(|rpc| Provider::<Http>::try_from(rpc.as_ref()))
-            .transpose()?
-            .unwrap_or_else(|| rpc::fetch_rpc_connection(destination).unwrap());
+        let provider = rpc::rpc_or_lookup(self.rpc.as_deref(), destination)?
+            .ok_or_else(|| eyre!("no rpc connection for domain {}", destination))?;
 
         let chain_id = provider.get_chainid().await?;
         let signer = self.signer().await?.with_chain_id(chain_id.low_u64());
         let middleware = SignerMiddleware::new(provider, signer);
 
-        // bit ugly. Tries passed-in address first, then defaults to lookup by
-        // domain
-        let address = self
-            .address
-            .as_ref()
-            .map(|addr| addr.parse::<H160>())
-            .transpose()?
-            .unwrap_or_else(|| replicas::address_by_domain_pair(origin, destination).unwrap());
+        let address = replicas::address_or_lookup(self.address.as_deref(), origin, destination)?
+            .ok_or_else(|| eyre!("no replica of {} on {}", origin, destination))?;
 
         Ok(EthereumReplica::new(
             Arc::new(middleware),
@@ -158,6 +149,7 @@ impl ProveCommand {
                 domain: 0,
                 address: address.into(),
             },
+            None,
         ))
     }
 }
@@ -0,0 +1,200 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use structopt::StructOpt;
+
+use crate::{replicas, rpc};
+
+use nomad_core::{
+    accumulator::incremental::IncrementalMerkle, db::DB, CommittedMessage, ContractLocator,
+    Replica, SignedUpdate,
+};
+
+use nomad_base::NomadDB;
+use nomad_ethereum::EthereumReplica;
+
+use ethers::{
+    prelude::{Http, Provider},
+    types::H256,
+};
+
+use color_eyre::{eyre::bail, Result};
+
+#[derive(StructOpt, Debug)]
+pub struct TraceCommand {
+    /// Leaf hash of the message
+    #[structopt(long, required_unless = "nonce")]
+    leaf: Option<H256>,
+
+    /// Destination domain of the message
+    #[structopt(long, required_unless = "leaf")]
+    destination: Option<u32>,
+
+    /// Nonce of the message
+    #[structopt(long, requires = "destination")]
+    nonce: Option<u32>,
+
+    /// The name of the home chain, used to lookup keys in the db
+    #[structopt(long)]
+    home_name: String,
+
+    /// Path to db of the processor or relayer of the home
+    #[structopt(long)]
+    db_path: String,
+
+    /// replica contract address
+    #[structopt(long)]
+    address: Option<String>,
+
+    /// RPC connection details
+    #[structopt(long)]
+    rpc: Option<String>,
+}
+
+impl TraceCommand {
+    pub async fn run(&self) -> Result<()> {
+        let db = NomadDB::new(&self.home_name, DB::from_path(&self.db_path)?);
+
+        let raw = match (self.leaf, self.destination, self.nonce) {
+            (Some(leaf), _, _) => db.message_by_leaf(leaf)?,
+            (None, Some(destination), Some(nonce)) => db.message_by_nonce(destination, nonce)?,
+            _ => bail!("Must provide leaf hash or destination and nonce"),
+        };
+        let message = match raw {
+            Some(raw) => CommittedMessage::try_from(raw)?,
+            None => {
+                println!("Dispatched: not found in db");
+                return Ok(());
+            }
+        };
+        let leaf = message.to_leaf();
+        let origin = message.message.origin;
+        let destination = message.message.destination;
+
+        println!("Message: {}", message.message);
+        println!("Leaf: {:?}", leaf);
+        println!("Dispatched: leaf index {}", message.leaf_index);
+        println!("Committed root at dispatch: {:?}", message.committed_root);
+        println!(
+            "Proof: {}",
+            if db.proof_by_leaf_index(message.leaf_index)?.is_some() {
+                "stored"
+            } else {
+                "not stored"
+            }
+        );
+
+        let update = including_update(&db, message.committed_root, message.leaf_index)?;
+        match &update {
+            Some(update) => {
+                let new_root = update.update.new_root;
+                let block = db
+                    .retrieve_update_metadata(new_root)?
+                    .map_or("unknown".to_owned(), |meta| meta.block_number.to_string());
+                println!("Included under root: {:?}", new_root);
+                println!(
+                    "Signed update: {:?} -> {:?} by {:?} at block {}",
+                    update.update.previous_root,
+                    new_root,
+                    update.recover()?,
+                    block
+                );
+            }
+            None => println!("Signed update: none yet"),
+        }
+
+        match db.retrieve_processor_nonce(destination)? {
+            Some(last) => println!("Processor nonce: {} (next to process: {})", last, last + 1),
+            None => println!("Processor nonce: none processed yet"),
+        }
+
+        let replica = match self.replica(origin, destination)? {
+            Some(replica) => replica,
+            None => {
+                println!("Replica: unknown, provide --rpc and --address to query it");
+                return Ok(());
+            }
+        };
+
+        if let Some(update) = &update {
+            let new_root = update.update.new_root;
+            match replica.confirm_at(new_root).await? {
+                Some(confirm_at) => {
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+                    let acceptable = replica.acceptable_root(new_root).await?;
+                    println!(
+                        "Replica: has root, acceptable at {} ({})",
+                        confirm_at,
+                        match (acceptable, confirm_at > now) {
+                            (true, _) => "acceptable now".to_owned(),
+                            (false, true) => format!("in {}s", confirm_at - now),
+                            (false, false) => "not acceptable".to_owned(),
+                        }
+                    );
+                }
+                None => println!("Replica: root not relayed yet"),
+            }
+        }
+
+        println!("Status: {:?}", replica.message_status(leaf).await?);
+
+        Ok(())
+    }
+
+    fn replica(
+        &self,
+        origin: u32,
+        destination: u32,
+    ) -> Result<Option<EthereumReplica<Provider<Http>>>> {
+        let provider = match rpc::rpc_or_lookup(self.rpc.as_deref(), destination)? {
+            Some(provider) => provider,
+            None => return Ok(None),
+        };
+        let address =
+            match replicas::address_or_lookup(self.address.as_deref(), origin, destination)? {
+                Some(address) => address,
+                None => return Ok(None),
+            };
+
+        Ok(Some(EthereumReplica::new(
+            Arc::new(provider),
+            &ContractLocator {
+                name: "".into(),
+                domain: 0,
+                address: address.into(),
+            },
+            None,
+        )))
+    }
+}
+
+/// The first update including the leaf at `leaf_index`, walking the updates
+/// from `committed_root`. Leaves are ingested from the db to find the number
+/// of leaves under each update's new root.
+fn including_update(
+    db: &NomadDB,
+    committed_root: H256,
+    leaf_index: u32,
+) -> Result<Option<SignedUpdate>> {
+    let mut tree = IncrementalMerkle::default();
+    let mut root = committed_root;
+    while let Some(update) = db.update_by_previous_root(root)? {
+        let new_root = update.update.new_root;
+        while tree.root() != new_root {
+            match db.leaf_by_leaf_index(tree.count() as u32)? {
+                Some(leaf) => tree.ingest(leaf),
+                None => bail!(
+                    "Leaf {} under root {:?} not found in db",
+                    tree.count(),
+                    new_root
+                ),
+            }
+        }
+        if tree.count() > leaf_index as usize {
+            return Ok(Some(update));
+        }
+        root = new_root;
+    }
+    Ok(None)
+}